@@ -0,0 +1,4 @@
+// `sqlx::migrate!` embebe `migrations/postgres/`: recompilar al agregar una migración
+fn main() {
+    println!("cargo:rerun-if-changed=migrations/postgres");
+}
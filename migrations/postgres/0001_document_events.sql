@@ -0,0 +1,13 @@
+-- Eventos del ciclo de vida de los documentos (EVENTS_DATABASE_URL)
+CREATE TABLE IF NOT EXISTS document_events (
+    id BIGSERIAL PRIMARY KEY,
+    event_id UUID NOT NULL UNIQUE,
+    event_type TEXT NOT NULL,
+    schema_version INTEGER NOT NULL,
+    document_id UUID NOT NULL,
+    tenant_id BIGINT NOT NULL,
+    organization_id TEXT NOT NULL,
+    template_id TEXT NOT NULL,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL
+);
@@ -0,0 +1,21 @@
+-- Secuencias de numeración por tenant (NUMBERING_DATABASE_URL)
+CREATE TABLE IF NOT EXISTS numbering_sequences (
+    tenant_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    format TEXT NOT NULL,
+    start_value BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (tenant_id, name)
+);
+CREATE TABLE IF NOT EXISTS numbering_issued (
+    tenant_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    seq BIGINT NOT NULL,
+    number TEXT NOT NULL,
+    document_id UUID,
+    reference TEXT,
+    issued_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (tenant_id, name, seq)
+);
+CREATE UNIQUE INDEX IF NOT EXISTS numbering_issued_document
+    ON numbering_issued (tenant_id, name, document_id) WHERE document_id IS NOT NULL;
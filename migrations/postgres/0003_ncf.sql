@@ -0,0 +1,20 @@
+-- Rangos de NCF autorizados por la DGII (NUMBERING_DATABASE_URL)
+CREATE TABLE IF NOT EXISTS ncf_series (
+    tenant_id BIGINT NOT NULL,
+    series TEXT NOT NULL,
+    next_value BIGINT NOT NULL,
+    range_end BIGINT NOT NULL,
+    expires_on DATE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (tenant_id, series)
+);
+CREATE TABLE IF NOT EXISTS ncf_issued (
+    tenant_id BIGINT NOT NULL,
+    series TEXT NOT NULL,
+    ncf TEXT NOT NULL,
+    document_id UUID,
+    issued_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (tenant_id, ncf)
+);
+CREATE UNIQUE INDEX IF NOT EXISTS ncf_issued_document
+    ON ncf_issued (tenant_id, series, document_id) WHERE document_id IS NOT NULL;
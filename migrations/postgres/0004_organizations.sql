@@ -0,0 +1,8 @@
+-- Organizaciones de cada tenant (NUMBERING_DATABASE_URL)
+CREATE TABLE IF NOT EXISTS organizations (
+    tenant_id BIGINT NOT NULL,
+    id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (tenant_id, id)
+);
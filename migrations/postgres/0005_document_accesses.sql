@@ -0,0 +1,11 @@
+-- Bitácora de accesos (presign/descarga) a documentos (EVENTS_DATABASE_URL)
+CREATE TABLE IF NOT EXISTS document_accesses (
+    id BIGSERIAL PRIMARY KEY,
+    document_id UUID NOT NULL,
+    tenant_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    ip TEXT,
+    user_agent TEXT,
+    accessed_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX IF NOT EXISTS document_accesses_document ON document_accesses (document_id, tenant_id);
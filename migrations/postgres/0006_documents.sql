@@ -0,0 +1,16 @@
+-- Registro de documentos (EVENTS_DATABASE_URL); `record` guarda el registro completo
+CREATE TABLE IF NOT EXISTS documents (
+    id UUID PRIMARY KEY,
+    tenant_id BIGINT NOT NULL,
+    template_id TEXT NOT NULL,
+    external_ref TEXT,
+    status TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    deleted_at TIMESTAMPTZ,
+    version BIGINT NOT NULL DEFAULT 0,
+    record JSONB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS documents_tenant_created ON documents (tenant_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS documents_external_ref ON documents (tenant_id, external_ref) WHERE external_ref IS NOT NULL;
+CREATE INDEX IF NOT EXISTS documents_template ON documents (tenant_id, template_id, created_at);
+CREATE INDEX IF NOT EXISTS documents_deleted ON documents (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,14 @@
+-- Intentos de entrega de webhooks por documento o lote (DATABASE_URL)
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    document_id UUID NOT NULL,
+    tenant_id BIGINT NOT NULL,
+    url TEXT NOT NULL,
+    attempt INTEGER NOT NULL,
+    status_code INTEGER,
+    error TEXT,
+    duration_ms BIGINT NOT NULL,
+    delivered BOOLEAN NOT NULL,
+    attempted_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX IF NOT EXISTS webhook_deliveries_document ON webhook_deliveries (document_id, tenant_id);
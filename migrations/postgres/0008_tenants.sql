@@ -0,0 +1,7 @@
+-- Configuración por tenant (perfil de render)
+CREATE TABLE IF NOT EXISTS tenants (
+    tenant_id BIGINT PRIMARY KEY,
+    render_profile JSONB,
+    render_profile_updated_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
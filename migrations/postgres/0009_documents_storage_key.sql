@@ -0,0 +1,2 @@
+-- Búsqueda del documento por su archivo (job de retención)
+CREATE INDEX IF NOT EXISTS documents_storage_key ON documents ((record->>'storage_key'));
@@ -0,0 +1,232 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use uuid::Uuid;
+
+use super::state::ApiState;
+use super::error::{ApiError, ApiResult, ErrorCode};
+use crate::models::DocumentStatus;
+use crate::worker::diagnostics::{support_bundle, RECENT_LOGS};
+use crate::templates::template_access::TemplateRestriction;
+use super::template_handler::{warm_up_templates, warmup_template_ids};
+use super::handlers::extract_tenant_user;
+use super::middleware::auth::{extract_role, ADMIN_ROLE};
+
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+    pub message: Option<String>,
+}
+
+/// Estado actual del modo mantenimiento
+pub async fn get_maintenance(state: web::Data<ApiState>) -> ApiResult<HttpResponse> {
+    Ok(HttpResponse::Ok().json(maintenance_body(&state)))
+}
+
+/// Activa o desactiva el modo mantenimiento (drenado antes de un deploy)
+pub async fn set_maintenance(
+    body: web::Json<MaintenanceRequest>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let request = body.into_inner();
+    state.maintenance.set(request.enabled, request.message);
+
+    if request.enabled {
+        tracing::warn!("Maintenance mode enabled: {}", state.maintenance.message());
+    } else {
+        tracing::info!("Maintenance mode disabled");
+    }
+
+    Ok(HttpResponse::Ok().json(maintenance_body(&state)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct WarmupRequest {
+    pub templates: Option<Vec<String>>,
+}
+
+/// Precompila plantillas con datos de ejemplo (por defecto las de `WARMUP_TEMPLATES`)
+pub async fn warm_up(
+    body: Option<web::Json<WarmupRequest>>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let template_ids = body
+        .and_then(|b| b.into_inner().templates)
+        .unwrap_or_else(|| warmup_template_ids(&state.template_manager));
+
+    let results = warm_up_templates(&state.template_manager, &template_ids).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "warmed_up": results
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatisticsQuery {
+    pub tenant_id: i64,
+}
+
+/// Estadísticas de generación de un tenant (bytes, páginas, latencias por prioridad)
+pub async fn get_statistics(
+    query: web::Query<StatisticsQuery>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    Ok(HttpResponse::Ok().json(state.documents.statistics(query.tenant_id).await?))
+}
+
+/// Plantillas globales privadas con sus tenants y planes
+pub async fn list_template_access(state: web::Data<ApiState>) -> ApiResult<HttpResponse> {
+    let registry = state.template_manager.get_registry();
+    Ok(HttpResponse::Ok().json(json!({ "templates": registry.access().restrictions() })))
+}
+
+/// Hace privada una plantilla global: solo la usan los tenants y planes dados
+pub async fn set_template_access(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<TemplateRestriction>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    if let Some(response) = admin_guard(&req) {
+        return Ok(response);
+    }
+
+    let template_id = path.into_inner();
+    let registry = state.template_manager.get_registry();
+    if !registry.exists(&template_id) {
+        return Err(ApiError::not_found(format!("Template {} not found", template_id)));
+    }
+
+    let restriction = body.into_inner();
+    registry.access().set_restriction(&template_id, restriction.clone());
+    tracing::info!(
+        "Template {} restricted to tenants {:?} and plans {:?}",
+        template_id,
+        restriction.tenants,
+        restriction.plans
+    );
+
+    Ok(HttpResponse::Ok().json(json!({ "template_id": template_id, "access": restriction })))
+}
+
+/// Vuelve pública una plantilla global
+pub async fn remove_template_access(
+    req: HttpRequest,
+    path: web::Path<String>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    if let Some(response) = admin_guard(&req) {
+        return Ok(response);
+    }
+
+    let template_id = path.into_inner();
+    if !state.template_manager.get_registry().access().remove_restriction(&template_id) {
+        return Err(ApiError::not_found(format!("Template {} has no access restriction", template_id)));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TenantPlanRequest {
+    pub plan: Option<String>,
+}
+
+/// Asigna (o quita, con `plan: null`) el plan de un tenant; un tenant no
+/// puede cambiarse el plan a sí mismo
+pub async fn set_tenant_plan(
+    req: HttpRequest,
+    path: web::Path<i64>,
+    body: web::Json<TenantPlanRequest>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    if let Some(response) = admin_guard(&req) {
+        return Ok(response);
+    }
+
+    let tenant_id = path.into_inner();
+    let registry = state.template_manager.get_registry();
+    registry.access().set_plan(tenant_id, body.into_inner().plan);
+
+    Ok(HttpResponse::Ok().json(json!({ "tenant_id": tenant_id, "plan": registry.access().plan_of(tenant_id) })))
+}
+
+/// Bundle de soporte (zip) de un documento fallido, de cualquier tenant,
+/// para escalar incidentes sin pedir datos al cliente. Lleva el request y el
+/// fuente del documento: solo para el rol `admin`, aunque la ruta salga del scope
+pub async fn get_support_bundle(
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    if let Some(response) = admin_guard(&req) {
+        return Ok(response);
+    }
+
+    let document_id = path.into_inner();
+    let (_, user_id) = extract_tenant_user(&req);
+    let record = state.documents.find(&document_id).await?
+        .ok_or_else(|| ApiError::not_found(format!("Document {} not found", document_id)))?;
+
+    if record.status != DocumentStatus::Failed {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "Support bundles are only available for failed documents",
+            "code": ErrorCode::DocumentNotFailed,
+            "status": record.status
+        })));
+    }
+
+    let diagnostics = state.diagnostics.get(&document_id);
+    let log_lines = RECENT_LOGS.matching(&document_id.to_string());
+    let deliveries = state.webhooks.deliveries(&document_id, record.tenant_id).await?;
+    let archive = support_bundle(&record, diagnostics.as_ref(), &log_lines, &deliveries)?;
+
+    tracing::info!("Support bundle exported for document {} by admin user {}", document_id, user_id);
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .append_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"support-{}.zip\"", document_id),
+        ))
+        .body(archive))
+}
+
+/// Respuesta 403 si el usuario no tiene el rol `admin`, para los handlers que
+/// exponen datos de otros tenants o cambian planes y acceso a plantillas
+pub fn admin_guard(req: &HttpRequest) -> Option<HttpResponse> {
+    let role = extract_role(req);
+    if role == ADMIN_ROLE {
+        return None;
+    }
+
+    Some(HttpResponse::Forbidden().json(json!({
+        "error": "This operation requires the admin role",
+        "code": ErrorCode::Forbidden,
+        "role": role
+    })))
+}
+
+fn maintenance_body(state: &ApiState) -> serde_json::Value {
+    let enabled = state.maintenance.is_enabled();
+    json!({
+        "maintenance": enabled,
+        "message": if enabled { Some(state.maintenance.message()) } else { None }
+    })
+}
+
+/// Respuesta 503 para endpoints de generación mientras dura el mantenimiento
+pub fn maintenance_guard(state: &ApiState) -> Option<HttpResponse> {
+    if !state.maintenance.is_enabled() {
+        return None;
+    }
+
+    Some(HttpResponse::ServiceUnavailable()
+        .append_header(("Retry-After", "120"))
+        .json(json!({
+            "error": "Service unavailable",
+            "code": ErrorCode::MaintenanceMode,
+            "details": state.maintenance.message(),
+            "maintenance": true
+        })))
+}
@@ -1,10 +1,178 @@
 use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
+use serde::Serialize;
 use std::fmt;
 
+/// Código estable (`code`) de las respuestas de error; el mensaje puede
+/// cambiar, el código no. El catálogo se publica en `GET /api/v1/errors`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidRequest,
+    NotFound,
+    InvalidDownloadUrl,
+    Forbidden,
+    PayloadTooLarge,
+    RateLimited,
+    DocumentNotReady,
+    DocumentInProgress,
+    DocumentNotQueued,
+    DocumentNotFailed,
+    DocumentArchived,
+    DocumentRestoring,
+    NcfUnavailable,
+    UnsupportedFormat,
+    TemplateExists,
+    TemplateInUse,
+    GenerationFailed,
+    GenerationTimeout,
+    MaintenanceMode,
+    FeatureDisabled,
+    NotImplemented,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 22] = [
+        ErrorCode::InvalidRequest,
+        ErrorCode::NotFound,
+        ErrorCode::InvalidDownloadUrl,
+        ErrorCode::Forbidden,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::RateLimited,
+        ErrorCode::DocumentNotReady,
+        ErrorCode::DocumentInProgress,
+        ErrorCode::DocumentNotQueued,
+        ErrorCode::DocumentNotFailed,
+        ErrorCode::DocumentArchived,
+        ErrorCode::DocumentRestoring,
+        ErrorCode::NcfUnavailable,
+        ErrorCode::UnsupportedFormat,
+        ErrorCode::TemplateExists,
+        ErrorCode::TemplateInUse,
+        ErrorCode::GenerationFailed,
+        ErrorCode::GenerationTimeout,
+        ErrorCode::MaintenanceMode,
+        ErrorCode::FeatureDisabled,
+        ErrorCode::NotImplemented,
+        ErrorCode::InternalError,
+    ];
+
+    /// Código por defecto de un status HTTP
+    pub fn for_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::BAD_REQUEST => ErrorCode::InvalidRequest,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+            StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+            StatusCode::TOO_MANY_REQUESTS => ErrorCode::RateLimited,
+            StatusCode::GATEWAY_TIMEOUT => ErrorCode::GenerationTimeout,
+            StatusCode::SERVICE_UNAVAILABLE => ErrorCode::MaintenanceMode,
+            StatusCode::NOT_IMPLEMENTED => ErrorCode::NotImplemented,
+            _ => ErrorCode::InternalError,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::InvalidDownloadUrl | ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::UnsupportedFormat => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::DocumentNotReady
+            | ErrorCode::DocumentInProgress
+            | ErrorCode::DocumentNotQueued
+            | ErrorCode::DocumentNotFailed
+            | ErrorCode::DocumentArchived
+            | ErrorCode::DocumentRestoring
+            | ErrorCode::NcfUnavailable
+            | ErrorCode::TemplateExists
+            | ErrorCode::TemplateInUse => StatusCode::CONFLICT,
+            ErrorCode::GenerationFailed | ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::GenerationTimeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::MaintenanceMode | ErrorCode::FeatureDisabled => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidRequest => "The request body, query or headers failed validation",
+            ErrorCode::NotFound => "The document, template or resource does not exist for this tenant",
+            ErrorCode::InvalidDownloadUrl => "The signed download URL is invalid or has expired",
+            ErrorCode::Forbidden => "The user's role does not allow this operation",
+            ErrorCode::PayloadTooLarge => "The payload exceeds the configured size limit",
+            ErrorCode::RateLimited => "Too many requests for this tenant and user",
+            ErrorCode::DocumentNotReady => "The document has not finished generating",
+            ErrorCode::DocumentInProgress => "The document is queued or being generated",
+            ErrorCode::DocumentNotQueued => "The document already left the queue",
+            ErrorCode::DocumentNotFailed => "The operation only applies to failed documents",
+            ErrorCode::DocumentArchived => "The document was moved to cold storage by the retention policy",
+            ErrorCode::DocumentRestoring => "The document is being restored from cold storage",
+            ErrorCode::NcfUnavailable => "The NCF series has no numbers left in its authorized range or has expired",
+            ErrorCode::UnsupportedFormat => "The output format is not generated for this document type, or the template does not exist",
+            ErrorCode::TemplateExists => "A template with that id already exists for this tenant",
+            ErrorCode::TemplateInUse => "The template has queued jobs or was used to generate documents recently",
+            ErrorCode::GenerationFailed => "Document generation failed",
+            ErrorCode::GenerationTimeout => "Document generation exceeded the synchronous time limit",
+            ErrorCode::MaintenanceMode => "The service is draining for maintenance and does not accept new generations",
+            ErrorCode::FeatureDisabled => "The feature is not enabled on this deployment",
+            ErrorCode::NotImplemented => "The endpoint is not implemented yet",
+            ErrorCode::InternalError => "Unexpected server error",
+        }
+    }
+
+    /// Qué debe hacer el cliente ante el error
+    pub fn action(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidRequest => "Fix the request using the message in `error`; do not retry unchanged",
+            ErrorCode::NotFound => "Check the id and the tenant; do not retry",
+            ErrorCode::InvalidDownloadUrl => "Request a new URL from /documents/{id}/download",
+            ErrorCode::Forbidden => "Use a token with the required role; do not retry",
+            ErrorCode::PayloadTooLarge => "Reduce the payload, upload it first or use async generation",
+            ErrorCode::RateLimited => "Retry after `retry_after` seconds",
+            ErrorCode::DocumentNotReady => "Poll /documents/{id}/status or wait for the callback",
+            ErrorCode::DocumentInProgress => "Retry once the document reaches a final status",
+            ErrorCode::DocumentNotQueued => "No action needed; the document is already being processed",
+            ErrorCode::DocumentNotFailed => "Check the document status; do not retry",
+            ErrorCode::DocumentArchived => "Request a restore with POST /documents/{id}/archive/restore, then retry",
+            ErrorCode::DocumentRestoring => "Retry later; restores from cold storage take hours",
+            ErrorCode::NcfUnavailable => "Register the new authorized range with PUT /ncf/series/{series}",
+            ErrorCode::UnsupportedFormat => "Use one of `details.allowed_formats` or an existing template; do not retry unchanged",
+            ErrorCode::TemplateExists => "Replace it with PUT /templates/{id} or choose another id",
+            ErrorCode::TemplateInUse => "Wait for the queued jobs to finish; repeat with ?force=true to delete despite recent documents",
+            ErrorCode::GenerationFailed => "Check `details`; retry if the cause was transient",
+            ErrorCode::GenerationTimeout => "Use /documents/generate/async for this document",
+            ErrorCode::MaintenanceMode => "Retry after the Retry-After header",
+            ErrorCode::FeatureDisabled => "Ask the operator to enable it; do not retry",
+            ErrorCode::NotImplemented => "Do not use this endpoint",
+            ErrorCode::InternalError => "Retry with exponential backoff; report it if it persists",
+        }
+    }
+
+    /// Si reintentar el mismo request puede funcionar
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::RateLimited
+                | ErrorCode::DocumentNotReady
+                | ErrorCode::DocumentInProgress
+                | ErrorCode::DocumentRestoring
+                | ErrorCode::GenerationFailed
+                | ErrorCode::MaintenanceMode
+                | ErrorCode::InternalError
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct ApiError {
     message: String,
     status_code: StatusCode,
+    code: ErrorCode,
+    /// Datos para que el cliente corrija el request (p. ej. formatos permitidos)
+    details: Option<serde_json::Value>,
 }
 
 impl ApiError {
@@ -12,9 +180,24 @@ impl ApiError {
         ApiError {
             message: message.into(),
             status_code,
+            code: ErrorCode::for_status(status_code),
+            details: None,
         }
     }
 
+    /// Formato de salida o plantilla que no se puede generar para el tipo (422)
+    pub fn unsupported_format(message: impl Into<String>, details: serde_json::Value) -> Self {
+        let mut error = Self::new(message, StatusCode::UNPROCESSABLE_ENTITY).with_code(ErrorCode::UnsupportedFormat);
+        error.details = Some(details);
+        error
+    }
+
+    /// Reemplaza el código derivado del status
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = code;
+        self
+    }
+
     pub fn internal_server_error(message: impl Into<String>) -> Self {
         Self::new(message, StatusCode::INTERNAL_SERVER_ERROR)
     }
@@ -36,11 +219,15 @@ impl fmt::Display for ApiError {
 
 impl ResponseError for ApiError {
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code)
-            .json(serde_json::json!({
-                "error": self.message,
-                "status": self.status_code.as_u16()
-            }))
+        let mut body = serde_json::json!({
+            "error": super::redaction::redact_text(&self.message),
+            "code": self.code,
+            "status": self.status_code.as_u16()
+        });
+        if let Some(details) = &self.details {
+            body["details"] = details.clone();
+        }
+        HttpResponse::build(self.status_code).json(body)
     }
 
     fn status_code(&self) -> StatusCode {
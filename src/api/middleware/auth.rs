@@ -1,4 +1,7 @@
-use actix_web::{dev::ServiceRequest, Error, HttpMessage};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage, HttpResponse};
 use actix_web_httpauth::extractors::bearer::{BearerAuth, Config};
 use actix_web_httpauth::extractors::AuthenticationError;
 use actix_web_httpauth::middleware::HttpAuthentication;
@@ -24,8 +27,8 @@ fn validator(
     // Validate token (simplified for demo)
     // In production, decode JWT and extract tenant_id and user_id
     if token.starts_with("valid_") {
-        // Extract tenant and user from token
-        // Example: valid_tenant123_user456
+        // Extract tenant, user and optional role from token
+        // Example: valid_tenant123_user456 or valid_tenant123_user456_roleadmin
         let parts: Vec<&str> = token.split('_').collect();
         let tenant_id = parts.get(1)
             .and_then(|s| s.strip_prefix("tenant"))
@@ -35,12 +38,18 @@ fn validator(
             .and_then(|s| s.strip_prefix("user"))
             .and_then(|s| s.parse::<i64>().ok())
             .unwrap_or(1);
+        let role = parts.get(3)
+            .and_then(|s| s.strip_prefix("role"))
+            .filter(|s| !s.is_empty())
+            .unwrap_or(DEFAULT_ROLE)
+            .to_string();
 
         // Add to request extensions
         req.extensions_mut().insert(UserInfo {
             tenant_id,
             user_id,
             organization_id: None,
+            role,
         });
 
         // Also add AuthInfo for handlers
@@ -56,15 +65,48 @@ fn validator(
     }
 }
 
+/// Rol asignado cuando el token no indica uno
+pub const DEFAULT_ROLE: &str = "user";
+
+/// Rol que puede crear, editar y borrar plantillas del tenant
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Middleware del scope `/admin`: sin el rol `admin` responde 403 `forbidden`
+/// (mantenimiento, planes, acceso a plantillas, bundles de soporte)
+pub async fn require_admin(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let role = req.extensions().get::<UserInfo>().map(|info| info.role.clone());
+    if role.as_deref() == Some(ADMIN_ROLE) {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+
+    let response = HttpResponse::Forbidden().json(serde_json::json!({
+        "error": "Admin endpoints require the admin role",
+        "code": crate::api::error::ErrorCode::Forbidden,
+        "role": role.unwrap_or_else(|| DEFAULT_ROLE.to_string())
+    }));
+    Ok(req.into_response(response).map_into_right_body())
+}
+
 #[derive(Clone)]
 pub struct UserInfo {
     pub tenant_id: i64,
     pub user_id: i64,
     pub organization_id: Option<String>,
+    pub role: String,
 }
 
 // Helper function to extract tenant and user info from request
 pub fn extract_tenant_user(req: &actix_web::HttpRequest) -> Option<(i64, i64)> {
     req.extensions().get::<UserInfo>()
         .map(|info| (info.tenant_id, info.user_id))
-}
\ No newline at end of file
+}
+
+/// Rol del usuario autenticado (por defecto `user`)
+pub fn extract_role(req: &actix_web::HttpRequest) -> String {
+    req.extensions().get::<UserInfo>()
+        .map(|info| info.role.clone())
+        .unwrap_or_else(|| DEFAULT_ROLE.to_string())
+}
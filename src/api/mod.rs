@@ -3,6 +3,13 @@ pub mod middleware;
 pub mod state;
 pub mod routes;
 pub mod template_handler;
+pub mod admin_handler;
+pub mod redaction;
+pub mod organization_handler;
+pub mod render_profile_handler;
+pub mod signing_handler;
+pub mod numbering_handler;
+pub mod ncf_handler;
 pub mod error;
 
 pub use state::ApiState;
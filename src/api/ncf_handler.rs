@@ -0,0 +1,126 @@
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use super::state::ApiState;
+use super::error::{ApiError, ApiResult, ErrorCode};
+use super::handlers::extract_tenant_user;
+use crate::storage::ncf::{validate_range, validate_series, NcfError};
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterSeriesRequest {
+    /// Primer secuencial autorizado
+    pub range_start: i64,
+    /// Último secuencial autorizado
+    pub range_end: i64,
+    /// Fecha de vencimiento de la secuencia (va en el XML e-CF)
+    pub expires_on: Option<NaiveDate>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AllocateNcfRequest {
+    /// Documento al que se asigna; repetir el pedido retorna el mismo NCF
+    pub document_id: Option<Uuid>,
+}
+
+/// Registra el rango autorizado de una serie o lo amplía
+pub async fn register_series(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<RegisterSeriesRequest>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let series = path.into_inner().to_uppercase();
+    let (tenant_id, user_id) = extract_tenant_user(&req);
+
+    validate_range(&series, body.range_start, body.range_end).map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    let registered = state.ncf
+        .register(tenant_id, &series, body.range_start, body.range_end, body.expires_on)
+        .await?;
+    tracing::info!(
+        "Tenant {} registered NCF series {} {}..={} (user {})",
+        tenant_id, series, body.range_start, body.range_end, user_id
+    );
+
+    Ok(HttpResponse::Ok().json(registered))
+}
+
+/// Series del tenant con los números restantes
+pub async fn list_series(
+    req: HttpRequest,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let (tenant_id, _user_id) = extract_tenant_user(&req);
+    let series = state.ncf.list(tenant_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({ "series": series })))
+}
+
+/// Asigna el siguiente NCF de la serie (para comprobantes emitidos fuera del servicio)
+pub async fn allocate_ncf(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: Option<web::Json<AllocateNcfRequest>>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let series = path.into_inner().to_uppercase();
+    let (tenant_id, _user_id) = extract_tenant_user(&req);
+    let body = body.map(web::Json::into_inner).unwrap_or_default();
+
+    validate_series(&series).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let allocated = state.ncf
+        .allocate(tenant_id, &series, body.document_id)
+        .await
+        .map_err(ncf_api_error)?;
+
+    Ok(HttpResponse::Ok().json(allocated))
+}
+
+/// Serie inexistente → 404; agotada o vencida → 409 `ncf_unavailable`
+pub fn ncf_api_error(error: anyhow::Error) -> ApiError {
+    match error.downcast_ref::<NcfError>() {
+        Some(NcfError::SeriesNotFound(_)) => ApiError::not_found(error.to_string()),
+        Some(_) => ApiError::new(error.to_string(), StatusCode::CONFLICT).with_code(ErrorCode::NcfUnavailable),
+        None => error.into(),
+    }
+}
+
+/// Serie de la que hay que tomar el e-NCF: `Some` si la factura trae
+/// `fiscalInfo` sin `eNcf`; falla si en ese caso falta `series` o es inválida
+pub fn pending_series(data: &Value) -> Result<Option<String>, String> {
+    let Some(fiscal) = data.get("fiscalInfo").and_then(Value::as_object) else {
+        return Ok(None);
+    };
+    if fiscal.get("eNcf").and_then(Value::as_str).is_some_and(|ncf| !ncf.trim().is_empty()) {
+        return Ok(None);
+    }
+
+    let series = fiscal.get("series")
+        .and_then(Value::as_str)
+        .map(str::to_uppercase)
+        .ok_or("fiscalInfo.series is required when fiscalInfo.eNcf is not provided")?;
+    validate_series(&series).map_err(|e| e.to_string())?;
+    Ok(Some(series))
+}
+
+/// Asigna a la factura el siguiente e-NCF de `fiscalInfo.series` si no trae
+/// uno (el reintento del mismo documento recibe el mismo) y completa el
+/// vencimiento de la secuencia si falta
+pub async fn assign_ncf(state: &ApiState, tenant_id: i64, document_id: Uuid, data: &mut Value) -> anyhow::Result<()> {
+    let Some(series) = pending_series(data).map_err(anyhow::Error::msg)? else {
+        return Ok(());
+    };
+    let allocated = state.ncf.allocate(tenant_id, &series, Some(document_id)).await?;
+
+    if let Some(fiscal) = data.get_mut("fiscalInfo").and_then(Value::as_object_mut) {
+        fiscal.insert("eNcf".to_string(), json!(allocated.ncf));
+        let has_expiration = fiscal.get("expirationDate").is_some_and(|v| !v.is_null());
+        if let (Some(expires_on), false) = (allocated.expires_on, has_expiration) {
+            fiscal.insert("expirationDate".to_string(), json!(expires_on.format("%Y-%m-%d").to_string()));
+        }
+    }
+    Ok(())
+}
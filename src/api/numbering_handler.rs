@@ -0,0 +1,103 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::state::ApiState;
+use super::error::{ApiError, ApiResult};
+use super::handlers::extract_tenant_user;
+use crate::storage::numbering::{format_number, validate_sequence_name, SequenceNotFound};
+
+#[derive(Debug, Deserialize)]
+pub struct DefineSequenceRequest {
+    /// Formato, p. ej. `INV-{year}-{seq:06}` o `E31{seq:010}`
+    pub format: String,
+    /// Primer valor (solo al crear la secuencia)
+    #[serde(default = "default_start")]
+    pub start: i64,
+}
+
+fn default_start() -> i64 {
+    1
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NextNumberRequest {
+    /// Documento al que se asigna; repetir el pedido retorna el mismo número
+    pub document_id: Option<Uuid>,
+    /// Referencia libre para la auditoría (p. ej. id en el ERP)
+    pub reference: Option<String>,
+}
+
+/// Crea una secuencia del tenant o cambia su formato
+pub async fn define_sequence(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<DefineSequenceRequest>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let name = path.into_inner();
+    let (tenant_id, user_id) = extract_tenant_user(&req);
+
+    validate_sequence_name(&name).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    format_number(&body.format, body.start, Utc::now()).map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    let sequence = state.numbering.define(tenant_id, &name, &body.format, body.start).await?;
+    tracing::info!("Tenant {} defined numbering sequence {} as {} (user {})", tenant_id, name, body.format, user_id);
+
+    Ok(HttpResponse::Ok().json(sequence))
+}
+
+/// Secuencias del tenant
+pub async fn list_sequences(
+    req: HttpRequest,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let (tenant_id, _user_id) = extract_tenant_user(&req);
+    let sequences = state.numbering.list(tenant_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({ "sequences": sequences })))
+}
+
+/// Emite el siguiente número de la secuencia
+pub async fn next_number(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: Option<web::Json<NextNumberRequest>>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let name = path.into_inner();
+    let (tenant_id, _user_id) = extract_tenant_user(&req);
+    let body = body.map(web::Json::into_inner).unwrap_or_default();
+
+    validate_sequence_name(&name).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let issued = state.numbering
+        .next(tenant_id, &name, body.document_id, body.reference.as_deref())
+        .await
+        .map_err(not_found_or_internal)?;
+
+    Ok(HttpResponse::Ok().json(issued))
+}
+
+/// Auditoría de huecos: valores consumidos de la secuencia sin número emitido
+pub async fn sequence_gaps(
+    req: HttpRequest,
+    path: web::Path<String>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let name = path.into_inner();
+    let (tenant_id, _user_id) = extract_tenant_user(&req);
+
+    validate_sequence_name(&name).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let report = state.numbering.gaps(tenant_id, &name).await.map_err(not_found_or_internal)?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+fn not_found_or_internal(error: anyhow::Error) -> ApiError {
+    match error.downcast_ref::<SequenceNotFound>() {
+        Some(not_found) => ApiError::not_found(not_found.to_string()),
+        None => error.into(),
+    }
+}
@@ -0,0 +1,101 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::models::{default_organization_id, InvalidOrganization, OrganizationRequest};
+use super::state::ApiState;
+use super::error::{ApiError, ApiResult};
+use super::handlers::extract_tenant_user;
+
+/// Lista las organizaciones registradas del tenant
+pub async fn list_organizations(
+    req: HttpRequest,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let (tenant_id, _user_id) = extract_tenant_user(&req);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "tenant_id": tenant_id,
+        "default_organization_id": default_organization_id(tenant_id),
+        "organizations": state.organizations.list(tenant_id).await?
+    })))
+}
+
+/// Registra (o renombra) una organización del tenant
+pub async fn register_organization(
+    req: HttpRequest,
+    body: web::Json<OrganizationRequest>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let (tenant_id, _user_id) = extract_tenant_user(&req);
+
+    let organization = state.organizations
+        .register(tenant_id, body.into_inner())
+        .await
+        .map_err(organization_error)?;
+
+    Ok(HttpResponse::Created().json(organization))
+}
+
+/// 400 si la organización es inválida o desconocida; 500 si falló el backend
+pub fn organization_error(error: anyhow::Error) -> ApiError {
+    match error.downcast_ref::<InvalidOrganization>() {
+        Some(invalid) => ApiError::bad_request(invalid.to_string()),
+        None => ApiError::from(error),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MigrationRequest {
+    #[serde(default = "default_dry_run")]
+    pub dry_run: bool,
+}
+
+fn default_dry_run() -> bool {
+    true
+}
+
+/// Migra documentos guardados con la organización legada "default"
+/// (`default/{tenant}/...`) a la organización por defecto del tenant
+/// (`tenant_{id}/{tenant}/...`)
+pub async fn migrate_legacy_paths(
+    body: web::Json<MigrationRequest>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let bucket = &state.config.s3_bucket_documents;
+    let keys = state.storage.list(bucket, Some("default/")).await?;
+
+    let mut moves = Vec::new();
+    for object in keys {
+        let Some(rest) = object.key.strip_prefix("default/") else { continue };
+        let Some(tenant_id) = rest.split('/').next().and_then(|t| t.parse::<i64>().ok()) else {
+            continue;
+        };
+
+        let new_key = format!("{}/{}", default_organization_id(tenant_id), rest);
+        moves.push((object.key, new_key));
+    }
+
+    let mut migrated = 0;
+    let mut failed = Vec::new();
+
+    if !body.dry_run {
+        for (from, to) in &moves {
+            match state.storage.move_object(bucket, from, to).await {
+                Ok(()) => migrated += 1,
+                Err(e) => {
+                    tracing::warn!("Failed to migrate {} -> {}: {}", from, to, e);
+                    failed.push(from.clone());
+                }
+            }
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "dry_run": body.dry_run,
+        "found": moves.len(),
+        "migrated": migrated,
+        "failed": failed,
+        "moves": moves.iter().map(|(from, to)| json!({ "from": from, "to": to })).collect::<Vec<_>>()
+    })))
+}
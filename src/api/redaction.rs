@@ -0,0 +1,168 @@
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::FormatFields;
+
+/// Campos que se pueden registrar sin enmascarar (logs y payloads JSON)
+pub const SAFE_FIELDS: &[&str] = &[
+    "id",
+    "document_id",
+    "template_id",
+    "template_type",
+    "document_type",
+    "format",
+    "priority",
+    "status",
+    "currency",
+    "tenant_id",
+    "user_id",
+    "bucket",
+    "key",
+    "duration_ms",
+];
+
+const REDACTED: &str = "[REDACTED]";
+
+static PII_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(concat!(
+        // UUIDs y similares se conservan (se listan primero para ganar el match)
+        r"(?P<uuid>\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b)",
+        r"|(?P<email>[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,})",
+        // Montos: 1,234.56 o 1234.56
+        r"|(?P<amount>\b\d{1,3}(?:,\d{3})+(?:\.\d+)?\b|\b\d+\.\d{2}\b)",
+        // RNC (9 dígitos) y cédula (11 dígitos), con o sin guiones
+        r"|(?P<tax_id>\b\d(?:-?\d){8,10}\b)",
+    ))
+    .expect("patrón de redacción inválido")
+});
+
+/// Enmascara identificadores fiscales, emails y montos en un texto libre
+pub fn redact_text(text: &str) -> String {
+    PII_PATTERN
+        .replace_all(text, |caps: &Captures| {
+            if let Some(uuid) = caps.name("uuid") {
+                uuid.as_str().to_string()
+            } else if let Some(email) = caps.name("email") {
+                let email = email.as_str();
+                let domain = email.split_once('@').map(|(_, d)| d).unwrap_or("");
+                format!("{}***@{}", &email[..1], domain)
+            } else if caps.name("amount").is_some() {
+                "[AMOUNT]".to_string()
+            } else if let Some(tax_id) = caps.name("tax_id") {
+                let digits: String = tax_id.as_str().chars().filter(|c| c.is_ascii_digit()).collect();
+                format!("***{}", &digits[digits.len() - 3..])
+            } else {
+                REDACTED.to_string()
+            }
+        })
+        .into_owned()
+}
+
+/// Redacta un payload JSON conservando solo los valores de campos permitidos
+pub fn redact_json(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| {
+                    let redacted = match v {
+                        Value::Object(_) | Value::Array(_) => redact_json(v),
+                        _ if SAFE_FIELDS.contains(&k.as_str()) => v.clone(),
+                        Value::Null => Value::Null,
+                        _ => Value::String(REDACTED.to_string()),
+                    };
+                    (k.clone(), redacted)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_json).collect()),
+        Value::Null => Value::Null,
+        _ => Value::String(REDACTED.to_string()),
+    }
+}
+
+/// Valor de un campo de log: si es un documento JSON (payload del request o
+/// de la respuesta) se redacta campo a campo; si no, como texto libre
+fn redact_logged(raw: &str) -> String {
+    let json = match serde_json::from_str::<Value>(raw) {
+        // Los campos `&str` llegan entre comillas por el formato Debug
+        Ok(Value::String(inner)) => serde_json::from_str(&inner).ok(),
+        parsed => parsed.ok(),
+    };
+
+    match json {
+        Some(json @ (Value::Object(_) | Value::Array(_))) => redact_json(&json).to_string(),
+        _ => redact_text(raw),
+    }
+}
+
+/// Formateador de campos para `tracing_subscriber` que aplica la redacción
+/// a cada evento; los campos en `SAFE_FIELDS` se escriben tal cual
+pub struct RedactedFields;
+
+impl<'writer> FormatFields<'writer> for RedactedFields {
+    fn format_fields<R: RecordFields>(&self, mut writer: Writer<'writer>, fields: R) -> fmt::Result {
+        let mut visitor = RedactingVisitor {
+            writer: &mut writer,
+            result: Ok(()),
+            first: true,
+        };
+        fields.record(&mut visitor);
+        visitor.result
+    }
+}
+
+struct RedactingVisitor<'a, 'writer> {
+    writer: &'a mut Writer<'writer>,
+    result: fmt::Result,
+    first: bool,
+}
+
+impl Visit for RedactingVisitor<'_, '_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if self.result.is_err() {
+            return;
+        }
+
+        let raw = format!("{:?}", value);
+        let value = if SAFE_FIELDS.contains(&field.name()) {
+            raw
+        } else {
+            redact_logged(&raw)
+        };
+
+        let separator = if self.first { "" } else { " " };
+        self.first = false;
+
+        self.result = if field.name() == "message" {
+            write!(self.writer, "{}{}", separator, value)
+        } else {
+            write!(self.writer, "{}{}={}", separator, field.name(), value)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logged_json_payloads_keep_only_safe_fields() {
+        let raw = format!("{:?}", r#"{"id":"doc-1","customer":{"rnc":"131234567","email":"ana@example.com"}}"#);
+        let redacted: Value = serde_json::from_str(&redact_logged(&raw)).unwrap();
+
+        assert_eq!(redacted["id"], "doc-1");
+        assert_eq!(redacted["customer"]["rnc"], REDACTED);
+        assert_eq!(redacted["customer"]["email"], REDACTED);
+    }
+
+    #[test]
+    fn logged_text_masks_tax_ids_emails_and_amounts() {
+        let redacted = redact_logged("RNC 131234567 of ana@example.com owes 1,250.00");
+
+        assert_eq!(redacted, "RNC ***567 of a***@example.com owes [AMOUNT]");
+    }
+}
@@ -0,0 +1,59 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde_json::Value;
+
+use crate::models::InvalidRenderProfile;
+use super::state::ApiState;
+use super::error::{ApiError, ApiResult};
+use super::handlers::extract_tenant_user;
+
+/// Perfil de render del tenant
+pub async fn get_render_profile(
+    req: HttpRequest,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let (tenant_id, _user_id) = extract_tenant_user(&req);
+
+    let profile = state.render_profiles
+        .get(tenant_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Tenant {} has no render profile", tenant_id)))?;
+
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+/// Reemplaza el perfil de render del tenant con las opciones del cuerpo
+pub async fn put_render_profile(
+    req: HttpRequest,
+    body: web::Json<Value>,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let (tenant_id, _user_id) = extract_tenant_user(&req);
+
+    let profile = state.render_profiles
+        .set(tenant_id, body.into_inner())
+        .await
+        .map_err(render_profile_error)?;
+
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+/// Elimina el perfil de render del tenant; los documentos vuelven a los valores por defecto
+pub async fn delete_render_profile(
+    req: HttpRequest,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let (tenant_id, _user_id) = extract_tenant_user(&req);
+
+    if !state.render_profiles.remove(tenant_id).await? {
+        return Err(ApiError::not_found(format!("Tenant {} has no render profile", tenant_id)));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// 400 si el perfil es inválido; 500 si falló el backend
+fn render_profile_error(error: anyhow::Error) -> ApiError {
+    match error.downcast_ref::<InvalidRenderProfile>() {
+        Some(invalid) => ApiError::bad_request(invalid.to_string()),
+        None => ApiError::from(error),
+    }
+}
@@ -4,7 +4,15 @@ use actix_cors::Cors;
 
 use super::handlers;
 use super::template_handler;
-use super::middleware::{auth::create_auth_middleware, compression::create_compression_middleware};
+use super::admin_handler;
+use super::organization_handler;
+use super::render_profile_handler;
+use super::signing_handler;
+use super::numbering_handler;
+use super::ncf_handler;
+use actix_web::middleware::from_fn;
+use super::middleware::auth::{create_auth_middleware, require_admin};
+use super::middleware::compression::create_compression_middleware;
 
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
     cfg
@@ -13,6 +21,9 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
         .route("/ready", web::get().to(readiness_check))
         .route("/metrics", web::get().to(metrics_endpoint))
 
+        // Descargas firmadas del backend de almacenamiento local
+        .route("/files/{bucket}/{key:.*}", web::get().to(handlers::serve_file))
+
         // API v1
         .service(
             web::scope("/api/v1")
@@ -30,26 +41,116 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
                         .max_age(3600)
                 )
 
+                // Catálogo de códigos de error
+                .route("/errors", web::get().to(handlers::list_error_codes))
+
                 // Document generation
                 .service(
                     web::scope("/documents")
+                        .route("", web::get().to(handlers::list_documents))
                         .route("/generate/sync", web::post().to(handlers::generate_sync))
                         .route("/generate/async", web::post().to(handlers::generate_async))
+                        .route("/generate/batch", web::post().to(handlers::generate_batch))
                         .route("/upload", web::post().to(handlers::upload_data))
+                        .route("/upload/presign", web::post().to(handlers::presign_upload))
+                        .route("/upload/convert", web::post().to(handlers::convert_upload))
+                        .route("/preflight", web::post().to(handlers::preflight))
+                        .route("/merge", web::post().to(handlers::merge_documents))
+                        .route("/delete", web::post().to(handlers::delete_documents))
+                        .route("/restore", web::post().to(handlers::restore_documents))
+                        .route("/trash", web::get().to(handlers::list_trash))
+                        .route("/by-ref/{ref}", web::get().to(handlers::get_by_external_ref))
+                        .route("/{id}", web::delete().to(handlers::delete_document))
+                        .route("/{id}/restore", web::post().to(handlers::restore_document))
                         .route("/{id}/status", web::get().to(handlers::get_status))
                         .route("/{id}/download", web::get().to(handlers::download_document))
+                        .route("/{id}/archive/restore", web::post().to(handlers::restore_archived_document))
+                        .route("/{id}/preview.png", web::get().to(handlers::get_preview))
+                        .route("/{id}/access-log", web::get().to(handlers::get_access_log))
+                        .route("/{id}/webhooks", web::get().to(handlers::get_webhook_deliveries))
+                        .route("/{id}/priority", web::post().to(handlers::boost_priority))
                 )
 
+                // Eventos del ciclo de vida de los documentos
+                .route("/events", web::get().to(handlers::list_events))
+
+                // Lotes de generación asíncrona
+                .route("/batches/{id}", web::get().to(handlers::get_batch))
+
                 // Template management (admin only)
                 .service(
                     web::scope("/templates")
                         .route("", web::get().to(list_templates))
+                        .route("", web::post().to(template_handler::create_template))
                         .route("/list", web::get().to(template_handler::list_templates))
                         .route("/generate", web::post().to(template_handler::generate_pdf_from_template))
                         .route("/preview/{id}", web::get().to(template_handler::preview_template))
-                        .route("/{id}", web::get().to(get_template))
-                        .route("/{id}", web::put().to(update_template))
-                        .route("/{id}/reload", web::post().to(reload_template))
+                        .route("/{id}", web::get().to(template_handler::get_template))
+                        .route("/{id}", web::put().to(template_handler::upload_template_override))
+                        .route("/{id}", web::delete().to(template_handler::delete_template_override))
+                        .route("/{id}/reload", web::post().to(template_handler::reload_template))
+                        .route("/{id}/stats", web::get().to(template_handler::template_stats))
+                        .route("/{id}/validate", web::post().to(template_handler::validate_template))
+                        .route("/{id}/fields", web::post().to(template_handler::template_field_usage))
+                        .route("/{id}/assets", web::get().to(template_handler::list_template_assets))
+                        .route("/{id}/assets/{name:.*}", web::put().to(template_handler::upload_template_asset))
+                        .route("/{id}/assets/{name:.*}", web::delete().to(template_handler::delete_template_asset))
+                )
+
+                // Certificado de firma PAdES del tenant
+                .service(
+                    web::scope("/signing")
+                        .route("/certificate", web::get().to(signing_handler::get_certificate))
+                        .route("/certificate", web::put().to(signing_handler::upload_certificate))
+                        .route("/certificate", web::delete().to(signing_handler::delete_certificate))
+                )
+
+                // Secuencias de numeración del tenant (NCF, facturas, ...)
+                .service(
+                    web::scope("/numbering")
+                        .route("", web::get().to(numbering_handler::list_sequences))
+                        .route("/{name}", web::put().to(numbering_handler::define_sequence))
+                        .route("/{name}/next", web::post().to(numbering_handler::next_number))
+                        .route("/{name}/gaps", web::get().to(numbering_handler::sequence_gaps))
+                )
+
+                // Rangos de NCF autorizados por la DGII
+                .service(
+                    web::scope("/ncf")
+                        .route("/series", web::get().to(ncf_handler::list_series))
+                        .route("/series/{series}", web::put().to(ncf_handler::register_series))
+                        .route("/series/{series}/next", web::post().to(ncf_handler::allocate_ncf))
+                )
+
+                // Organizaciones del tenant
+                .service(
+                    web::scope("/organizations")
+                        .route("", web::get().to(organization_handler::list_organizations))
+                        .route("", web::post().to(organization_handler::register_organization))
+                )
+
+                // Opciones de render por defecto del tenant
+                .service(
+                    web::scope("/render-profile")
+                        .route("", web::get().to(render_profile_handler::get_render_profile))
+                        .route("", web::put().to(render_profile_handler::put_render_profile))
+                        .route("", web::delete().to(render_profile_handler::delete_render_profile))
+                )
+
+                // Administración
+                .service(
+                    web::scope("/admin")
+                        .wrap(from_fn(require_admin))
+                        .route("/maintenance", web::get().to(admin_handler::get_maintenance))
+                        .route("/maintenance", web::post().to(admin_handler::set_maintenance))
+                        .route("/warmup", web::post().to(admin_handler::warm_up))
+                        .route("/statistics", web::get().to(admin_handler::get_statistics))
+                        .route("/documents/{id}/support-bundle", web::get().to(admin_handler::get_support_bundle))
+                        .route("/organizations/migrate", web::post().to(organization_handler::migrate_legacy_paths))
+                        .route("/template-access", web::get().to(admin_handler::list_template_access))
+                        .route("/templates/{template_id}/access", web::put().to(admin_handler::set_template_access))
+                        .route("/templates/{template_id}/access", web::delete().to(admin_handler::remove_template_access))
+                        .route("/tenants/{tenant_id}/plan", web::put().to(admin_handler::set_tenant_plan))
                 )
         );
 }
@@ -70,6 +171,7 @@ async fn readiness_check(state: web::Data<crate::api::ApiState>) -> HttpResponse
     if s3_healthy && templates_loaded {
         HttpResponse::Ok().json(serde_json::json!({
             "status": "ready",
+            "maintenance": state.maintenance.is_enabled(),
             "checks": {
                 "s3": "ok",
                 "templates": if templates_loaded { "ok" } else { "no templates loaded" }
@@ -112,43 +214,3 @@ async fn list_templates(
         "templates": templates
     }))
 }
-
-async fn get_template(
-    path: web::Path<String>,
-    _state: web::Data<crate::api::ApiState>,
-) -> HttpResponse {
-    let template_id = path.into_inner();
-
-    // TODO: Implementar get_template en TemplateManager
-    HttpResponse::NotImplemented().json(serde_json::json!({
-        "error": "Template retrieval not implemented",
-        "template_id": template_id
-    }))
-}
-
-async fn update_template(
-    path: web::Path<String>,
-    _body: String,
-    _state: web::Data<crate::api::ApiState>,
-) -> HttpResponse {
-    let template_id = path.into_inner();
-
-    // TODO: Implementar update_template en TemplateManager
-    HttpResponse::NotImplemented().json(serde_json::json!({
-        "error": "Template update not implemented",
-        "template_id": template_id
-    }))
-}
-
-async fn reload_template(
-    path: web::Path<String>,
-    _state: web::Data<crate::api::ApiState>,
-) -> HttpResponse {
-    let template_id = path.into_inner();
-
-    // TODO: Implementar reload_template en TemplateManager
-    HttpResponse::NotImplemented().json(serde_json::json!({
-        "error": "Template reload not implemented",
-        "template_id": template_id
-    }))
-}
\ No newline at end of file
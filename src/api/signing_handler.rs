@@ -0,0 +1,83 @@
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use serde_json::json;
+
+use super::state::ApiState;
+use super::error::{ApiError, ApiResult, ErrorCode};
+use super::handlers::extract_tenant_user;
+
+/// Registra (o reemplaza) el certificado PKCS#12 con el que se firman los
+/// PDFs del tenant. El cuerpo es el `.p12`; la contraseña va en `X-Certificate-Password`
+pub async fn upload_certificate(
+    req: HttpRequest,
+    body: web::Bytes,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let (tenant_id, user_id) = extract_tenant_user(&req);
+    ensure_enabled(&state)?;
+
+    if body.is_empty() {
+        return Err(ApiError::bad_request("Request body must be a PKCS#12 (.p12) file"));
+    }
+    let password = req.headers()
+        .get("X-Certificate-Password")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("");
+
+    let identity = state.certificates.put(tenant_id, &body, password).await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let info = identity.info();
+
+    tracing::info!(
+        "Tenant {} registered signing certificate {} (user {})",
+        tenant_id, info.fingerprint_sha256, user_id
+    );
+
+    Ok(HttpResponse::Ok().json(json!({
+        "tenant_id": tenant_id,
+        "certificate": info
+    })))
+}
+
+/// Datos públicos del certificado de firma del tenant
+pub async fn get_certificate(
+    req: HttpRequest,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let (tenant_id, _user_id) = extract_tenant_user(&req);
+    ensure_enabled(&state)?;
+
+    let identity = state.certificates.identity(tenant_id).await?
+        .ok_or_else(|| ApiError::not_found("No signing certificate registered"))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "tenant_id": tenant_id,
+        "certificate": identity.info()
+    })))
+}
+
+/// Elimina el certificado: los PDFs siguientes se entregan sin firma
+pub async fn delete_certificate(
+    req: HttpRequest,
+    state: web::Data<ApiState>,
+) -> ApiResult<HttpResponse> {
+    let (tenant_id, user_id) = extract_tenant_user(&req);
+    ensure_enabled(&state)?;
+
+    if state.certificates.identity(tenant_id).await?.is_none() {
+        return Err(ApiError::not_found("No signing certificate registered"));
+    }
+    state.certificates.delete(tenant_id).await?;
+
+    tracing::info!("Tenant {} removed its signing certificate (user {})", tenant_id, user_id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn ensure_enabled(state: &ApiState) -> ApiResult<()> {
+    if state.certificates.is_enabled() {
+        Ok(())
+    } else {
+        Err(ApiError::new("PDF signing is not enabled", StatusCode::SERVICE_UNAVAILABLE)
+            .with_code(ErrorCode::FeatureDisabled))
+    }
+}
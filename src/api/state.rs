@@ -1,18 +1,67 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
 use governor::{Quota, RateLimiter, clock::DefaultClock, state::keyed::DashMapStateStore};
 
 use crate::templates::TemplateManager;
-use crate::storage::s3::S3Client;
+use crate::storage::storage_trait::{storage_from_env, Storage};
+use crate::storage::retention::{RetentionConfig, RetentionJob};
+use crate::storage::presign::PresignPolicy;
+use crate::storage::multipart_janitor::MultipartJanitor;
+use crate::storage::trash::TrashPurgeJob;
+use crate::storage::access_log::AccessLog;
+use crate::storage::document_store::DocumentStore;
+use crate::storage::certificates::CertificateStore;
+use crate::storage::numbering::{numbering_backend, NumberingBackend};
+use crate::storage::ncf::{ncf_allocator, NcfAllocator};
+use crate::storage::organizations::organizations_backend;
+use crate::storage::render_profiles::render_profiles_backend;
+use crate::storage::database::Database;
+use crate::storage::redis_pool::{redis_from_env, RedisPool};
+use crate::templates::template_assets::TemplateAssetStore;
+use crate::templates::template_logos::LogoCache;
+use crate::templates::template_access::TemplateAccessConfig;
+use crate::models::{OrganizationRegistry, RenderProfiles};
+use crate::worker::retry::RetryPolicy;
+use crate::worker::webhook::WebhookSender;
+use crate::worker::health::WorkerHealth;
+use crate::worker::queue::JobQueue;
+use crate::worker::diagnostics::DiagnosticsStore;
+use crate::worker::batch::BatchStore;
+use crate::worker::events::EventLog;
+use crate::generators::post_process::PostProcessor;
+use crate::generators::EngineRegistry;
 
 // Key format: "tenant_id:user_id"
 pub type KeyedRateLimiter = Arc<RateLimiter<String, DashMapStateStore<String>, DefaultClock>>;
 
 #[derive(Clone)]
 pub struct ApiState {
-    pub s3_client: Arc<S3Client>,
+    pub storage: Arc<dyn Storage>,
     pub template_manager: Arc<TemplateManager>,
     pub rate_limiter: KeyedRateLimiter,
     pub config: Arc<AppConfig>,
+    pub maintenance: Arc<MaintenanceState>,
+    pub access_log: Arc<AccessLog>,
+    pub documents: Arc<DocumentStore>,
+    pub webhooks: Arc<WebhookSender>,
+    pub organizations: Arc<OrganizationRegistry>,
+    /// Opciones de render por defecto de cada tenant
+    pub render_profiles: Arc<RenderProfiles>,
+    pub post_processor: Arc<PostProcessor>,
+    pub worker_health: Arc<WorkerHealth>,
+    pub jobs: Arc<JobQueue>,
+    pub certificates: Arc<CertificateStore>,
+    pub diagnostics: Arc<DiagnosticsStore>,
+    pub batches: Arc<BatchStore>,
+    pub events: Arc<EventLog>,
+    pub numbering: Arc<dyn NumberingBackend>,
+    pub ncf: Arc<dyn NcfAllocator>,
+    /// Pool de Postgres (`DATABASE_URL`); sin él, registros y secuencias quedan en memoria
+    pub database: Option<Database>,
+    /// Pool de Redis (`REDIS_URL`); sin él, el estado compartido queda en memoria
+    pub redis: Option<Arc<RedisPool>>,
+    /// Engines de generación por formato de salida
+    pub engines: Arc<EngineRegistry>,
 }
 
 #[derive(Clone)]
@@ -22,9 +71,20 @@ pub struct AppConfig {
     pub rate_limit_per_minute: u32,
     pub rate_limit_burst: u32,
     pub sync_timeout_ms: u64,
+    pub generation_timeout_ms: u64,
     pub s3_bucket_documents: String,
     pub s3_bucket_temp: String,
     pub enable_compression: bool,
+    pub maintenance_mode: bool,
+    pub organizations_strict: bool,
+    pub retry_max_attempts: u32,
+    pub retry_base_delay_ms: u64,
+    /// Horas que un documento borrado puede restaurarse antes de purgarlo
+    pub trash_retention_hours: i64,
+    /// Duración de las URLs firmadas por tenant
+    pub presign: PresignPolicy,
+    /// Filas desde las que un reporte PDF se genera por partes (0 lo desactiva)
+    pub pdf_chunk_rows: usize,
 }
 
 impl Default for AppConfig {
@@ -35,34 +95,217 @@ impl Default for AppConfig {
             rate_limit_per_minute: 100,
             rate_limit_burst: 20,
             sync_timeout_ms: 5000,
+            generation_timeout_ms: 120_000,
             s3_bucket_documents: "documents".to_string(),
             s3_bucket_temp: "temp-uploads".to_string(),
             enable_compression: true,
+            maintenance_mode: false,
+            organizations_strict: false,
+            retry_max_attempts: 3,
+            retry_base_delay_ms: 1000,
+            trash_retention_hours: 72,
+            presign: PresignPolicy::default(),
+            pdf_chunk_rows: 20_000,
         }
     }
 }
 
+impl AppConfig {
+    /// Política de reintentos de la generación asíncrona
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retry_max_attempts.max(1),
+            base_delay_ms: self.retry_base_delay_ms,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Ventana de restauración de la papelera
+    pub fn trash_retention(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.trash_retention_hours.max(0))
+    }
+}
+
 impl ApiState {
-    pub async fn new(config: AppConfig) -> anyhow::Result<Self> {
-        // Initialize S3
-        let s3_client = Arc::new(S3Client::new().await?);
-
-        // Initialize template manager
-        let template_manager = Arc::new(TemplateManager::new(
-            "templates".to_string(),
-            "output".to_string()
+    pub async fn new(config: AppConfig, database: Option<Database>) -> anyhow::Result<Self> {
+        // Initialize storage backend (STORAGE_BACKEND=s3|local)
+        let storage = storage_from_env().await?;
+
+        // Initialize template manager (PREVIEW_PPI=0 desactiva las miniaturas)
+        let preview_ppi = std::env::var("PREVIEW_PPI")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(36);
+        let template_assets = Arc::new(TemplateAssetStore::new(
+            storage.clone(),
+            config.s3_bucket_documents.clone(),
         ));
+        // Plantillas en disco (TEMPLATES_DIR); TEMPLATES_HOT_RELOAD=false desactiva la recarga al editarlas
+        let templates_dir = std::env::var("TEMPLATES_DIR").unwrap_or_else(|_| "templates".to_string());
+        let hot_reload = std::env::var("TEMPLATES_HOT_RELOAD")
+            .map(|v| v != "false" && v != "0")
+            .unwrap_or(true);
+        let render_profiles = Arc::new(RenderProfiles::new(render_profiles_backend(database.clone())));
+        let mut template_manager = TemplateManager::new(templates_dir, "output".to_string())
+            .with_assets(template_assets)
+            .with_previews(preview_ppi)
+            .with_logos(Arc::new(LogoCache::from_env()))
+            .with_render_profiles(render_profiles.clone())
+            .with_template_access(TemplateAccessConfig::from_env()?);
+        if hot_reload {
+            template_manager = template_manager.with_hot_reload();
+        }
+        let template_manager = Arc::new(template_manager);
+        let engines = Arc::new(EngineRegistry::with_defaults(template_manager.clone()));
 
         // Initialize rate limiter
         let quota = Quota::per_minute(std::num::NonZeroU32::new(config.rate_limit_per_minute).unwrap())
             .allow_burst(std::num::NonZeroU32::new(config.rate_limit_burst).unwrap());
         let rate_limiter = Arc::new(RateLimiter::dashmap_with_clock(quota, &DefaultClock::default()));
+        let redis = redis_from_env()?;
+
+        let maintenance = Arc::new(MaintenanceState::new(config.maintenance_mode));
+        let webhooks = Arc::new(WebhookSender::from_env(config.retry_policy(), database.clone())?);
+        let post_processor = Arc::new(PostProcessor::from_env()?);
+        let certificates = Arc::new(CertificateStore::from_env(storage.clone(), config.s3_bucket_documents.clone())?);
+        let organizations_strict = config.organizations_strict;
+
+        // Latido del runtime y sondeo del storage para el listener de salud
+        let worker_health = Arc::new(WorkerHealth::from_env());
+        let probe_interval = std::env::var("WORKER_HEALTH_PROBE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+        worker_health.spawn_monitor(storage.clone(), config.s3_bucket_documents.clone(), probe_interval);
+        if let Some(redis) = &redis {
+            worker_health.spawn_redis_probe(redis.clone(), probe_interval);
+        }
+
+        // Limpieza de multipart uploads abandonados en los buckets del servicio
+        let janitor_interval = std::env::var("MULTIPART_JANITOR_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3_600);
+        MultipartJanitor::from_env(
+            storage.clone(),
+            vec![config.s3_bucket_documents.clone(), config.s3_bucket_temp.clone()],
+        )
+        .spawn(janitor_interval);
+
+        // Registro de documentos; con Redis, las lecturas por id se cachean
+        // DOCUMENT_CACHE_TTL_SECS (0 desactiva)
+        let documents = DocumentStore::new(database.clone());
+        let document_cache_ttl = std::env::var("DOCUMENT_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+        let documents = Arc::new(match &redis {
+            Some(redis) if document_cache_ttl > 0 => {
+                documents.with_cache(redis.clone(), std::time::Duration::from_secs(document_cache_ttl))
+            },
+            _ => documents,
+        });
+
+        // Job de retención (archivado en frío y borrado) si hay políticas configuradas
+        if let Some(retention) = RetentionConfig::from_env()? {
+            let interval = std::env::var("RETENTION_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(86_400);
+            RetentionJob::new(storage.clone(), documents.clone(), config.s3_bucket_documents.clone(), retention)
+                .spawn(interval);
+        }
+
+        // Purga de la papelera: borra del storage los documentos con la ventana vencida
+        let purge_interval = std::env::var("TRASH_PURGE_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3_600);
+        TrashPurgeJob::new(storage.clone(), documents.clone(), config.trash_retention())
+            .spawn(purge_interval);
 
         Ok(ApiState {
-            s3_client,
+            storage,
             template_manager,
             rate_limiter,
             config: Arc::new(config),
+            maintenance,
+            access_log: Arc::new(AccessLog::new(database.clone())),
+            documents,
+            webhooks,
+            organizations: Arc::new(OrganizationRegistry::new(organizations_backend(database.clone()), organizations_strict)),
+            render_profiles,
+            post_processor,
+            worker_health,
+            jobs: Arc::new(JobQueue::from_env()?),
+            certificates,
+            diagnostics: Arc::new(DiagnosticsStore::new()),
+            batches: Arc::new(BatchStore::new()),
+            events: Arc::new(match &redis {
+                Some(redis) => EventLog::new(database.clone()).with_publisher(redis.clone()),
+                None => EventLog::new(database.clone()),
+            }),
+            numbering: numbering_backend(database.clone()),
+            ncf: ncf_allocator(database.clone()),
+            database,
+            redis,
+            engines,
         })
     }
-}
\ No newline at end of file
+
+    /// Rate limit por clave (`tenant_id:user_id`). Con Redis la ventana es por
+    /// minuto y compartida entre réplicas; si Redis no responde se usa el
+    /// limitador local para no bloquear la API
+    pub async fn check_rate_limit(&self, key: &str) -> bool {
+        if let Some(redis) = &self.redis {
+            let window = chrono::Utc::now().timestamp() / 60;
+            let redis_key = format!("ratelimit:{}:{}", key, window);
+            let pipe = deadpool_redis::redis::pipe()
+                .atomic()
+                .incr(&redis_key, 1)
+                .expire(&redis_key, 120)
+                .ignore()
+                .to_owned();
+            match redis.query_pipeline::<(u32,)>(&pipe).await {
+                Ok((count,)) => {
+                    return count <= self.config.rate_limit_per_minute;
+                },
+                Err(e) => tracing::warn!("Redis rate limit unavailable, using local limiter: {:#}", e),
+            }
+        }
+        self.rate_limiter.check_key(&key.to_string()).is_ok()
+    }
+}
+
+/// Modo mantenimiento: la API deja de aceptar nuevas generaciones
+/// pero sigue sirviendo status y descargas (drenado para deploys)
+pub struct MaintenanceState {
+    enabled: AtomicBool,
+    message: RwLock<Option<String>>,
+}
+
+impl MaintenanceState {
+    pub fn new(enabled: bool) -> Self {
+        MaintenanceState {
+            enabled: AtomicBool::new(enabled),
+            message: RwLock::new(None),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    pub fn set(&self, enabled: bool, message: Option<String>) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+        *self.message.write().unwrap() = if enabled { message } else { None };
+    }
+
+    pub fn message(&self) -> String {
+        self.message
+            .read()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| "Service is in maintenance mode, please retry later".to_string())
+    }
+}
@@ -1,38 +1,112 @@
-use actix_web::{web, HttpResponse, HttpRequest, Result, HttpMessage};
+use actix_web::{http::StatusCode, web, HttpResponse, HttpRequest, Result, HttpMessage};
+use serde::Deserialize;
 use serde_json::json;
 use uuid::Uuid;
-use crate::templates::{TemplateEngine, TemplateData, InvoiceData};
+use chrono::Utc;
+use crate::models::{validate_external_ref, DocumentNumbering, DocumentStatus, Priority};
+use crate::storage::numbering::{validate_sequence_name, SequenceNotFound};
+use crate::generators::pdf::{page_count, protection_step};
+use crate::generators::pdf_chunks::render_report;
+use crate::storage::document_store::{DocumentRecord, StageTimings};
+use crate::storage::keys::{document_key, parse_template_override_key, template_archive_key, template_override_key, TEMPLATES_PREFIX};
+use crate::templates::template_overrides::UploadedTemplate;
+use crate::templates::template_fields::analyze_field_usage;
+use crate::templates::template_assets::{validate_asset_name, TemplateAssetStore};
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::templates::template_trait::utils;
+use crate::templates::{parse_diagnostics, CompileDiagnostic, CompileError, RenderPipeline, RenderRequest, TemplateData, InvoiceData, TypstTemplate};
 use super::state::ApiState;
-use super::handlers::AuthInfo;
+use super::error::ErrorCode;
+use super::organization_handler::organization_error;
+use super::handlers::{completed_event_data, pades_sign, reject_protected_signing, store_preview, AuthInfo};
+use crate::worker::events::EventType;
+use super::redaction::redact_text;
+use super::admin_handler::maintenance_guard;
+use super::ncf_handler::{assign_ncf, ncf_api_error, pending_series};
+use super::middleware::auth::{extract_role, ADMIN_ROLE};
+use crate::generators::report_processor::mask_report_payload;
 
 pub async fn generate_pdf_from_template(
     req: HttpRequest,
     data: web::Json<serde_json::Value>,
     state: web::Data<ApiState>,
 ) -> Result<HttpResponse> {
+    if let Some(response) = maintenance_guard(&state) {
+        return Ok(response);
+    }
+
+    let mut data = data.into_inner();
     let (tenant_id, user_id) = extract_tenant_user_helper(&req);
 
+    let org_id = state.organizations
+        .resolve(tenant_id, data.get("organization_id").and_then(|v| v.as_str()))
+        .await
+        .map_err(organization_error)?;
+
+    let external_ref = data.get("external_ref").and_then(|v| v.as_str()).map(str::to_string);
+    if let Some(external_ref) = &external_ref {
+        validate_external_ref(external_ref).map_err(actix_web::error::ErrorBadRequest)?;
+    }
+
+    let document_id = Uuid::new_v4();
+    let numbering: Option<DocumentNumbering> = data.get("numbering")
+        .cloned()
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid numbering: {}", e)))?;
+    if let Some(numbering) = numbering {
+        validate_sequence_name(&numbering.sequence).map_err(actix_web::error::ErrorBadRequest)?;
+        let issued = state.numbering
+            .next(tenant_id, &numbering.sequence, Some(document_id), external_ref.as_deref())
+            .await
+            .map_err(|e| match e.downcast_ref::<SequenceNotFound>() {
+                Some(not_found) => actix_web::error::ErrorNotFound(not_found.to_string()),
+                None => actix_web::error::ErrorInternalServerError(format!("Failed to assign document number: {}", e)),
+            })?;
+        if let Some(fields) = data.get_mut("data").and_then(|v| v.as_object_mut()) {
+            fields.insert(numbering.field, json!(issued.number));
+        }
+    }
+
     let template_id = data.get("template_id")
         .and_then(|v| v.as_str())
-        .unwrap_or("fiscal_electronic");
+        .unwrap_or("fiscal_electronic")
+        .to_owned();
 
     let template_data = match data.get("template_type").and_then(|v| v.as_str()) {
         Some("invoice") => {
+            if let Some(fields) = data.get_mut("data") {
+                pending_series(fields).map_err(actix_web::error::ErrorBadRequest)?;
+                assign_ncf(&state, tenant_id, document_id, fields).await.map_err(ncf_api_error)?;
+            }
             let invoice_data: InvoiceData = serde_json::from_value(
                 data.get("data").cloned().unwrap_or(json!({}))
-            ).map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid invoice data: {}", e)))?;
+            ).map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid invoice data: {}", redact_text(&e.to_string()))))?;
             TemplateData::Invoice(invoice_data)
         },
         Some("report") => {
-            let report_data = serde_json::from_value(
-                data.get("data").cloned().unwrap_or(json!({}))
-            ).map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid report data: {}", e)))?;
+            let mut raw = data.get("data").cloned().unwrap_or(json!({}));
+            mask_report_payload(&mut raw, &extract_role(&req));
+            let report_data = serde_json::from_value(raw).map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid report data: {}", redact_text(&e.to_string()))))?;
             TemplateData::Report(report_data)
         },
+        Some("statement") => {
+            let statement_data = serde_json::from_value(
+                data.get("data").cloned().unwrap_or(json!({}))
+            ).map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid statement data: {}", redact_text(&e.to_string()))))?;
+            TemplateData::Statement(statement_data)
+        },
+        Some("certificate") => {
+            let certificate_data = serde_json::from_value(
+                data.get("data").cloned().unwrap_or(json!({}))
+            ).map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid certificate data: {}", redact_text(&e.to_string()))))?;
+            TemplateData::Certificate(certificate_data)
+        },
         Some("receipt") => {
             let receipt_data = serde_json::from_value(
                 data.get("data").cloned().unwrap_or(json!({}))
-            ).map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid receipt data: {}", e)))?;
+            ).map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid receipt data: {}", redact_text(&e.to_string()))))?;
             TemplateData::Receipt(receipt_data)
         },
         _ => {
@@ -48,46 +122,128 @@ pub async fn generate_pdf_from_template(
         }
     };
 
-    let engine = TemplateEngine::new(
-        "templates".to_string(),
-        "facturas".to_string(),
-    );
+    let engine = &state.template_manager;
 
     let output_filename = data.get("output_filename")
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
 
-    match engine.generate_pdf(template_id, template_data, output_filename).await {
-        Ok(pdf_path) => {
-            let document_id = Uuid::new_v4();
+    let start = std::time::Instant::now();
 
-            let org_id = format!("tenant_{}", tenant_id);
-            let key = format!("documents/{}/{}.pdf", org_id, document_id);
+    let mut json_data = serde_json::to_value(&template_data)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let tags: Option<HashMap<String, String>> = data.get("tags").cloned().map(serde_json::from_value).transpose()
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid tags: {}", e)))?;
+    let custom_fields: Option<HashMap<String, serde_json::Value>> = data.get("custom_fields").cloned().map(serde_json::from_value).transpose()
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid custom_fields: {}", e)))?;
+    utils::insert_document_metadata(&mut json_data, tags.as_ref(), custom_fields.as_ref());
+    let protection = protection_step(&json_data)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    if protection.is_some() {
+        reject_protected_signing(&state, tenant_id).await?;
+    }
 
-            let pdf_bytes = tokio::fs::read(&pdf_path).await
-                .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to read PDF: {}", e)))?;
+    let mut render = RenderRequest::new(Some(tenant_id), &template_id, json_data);
+    render.output_filename = output_filename;
 
-            let url = state.s3_client.put_object(
+    // Reportes grandes: por partes compiladas en paralelo
+    let rendered = match &template_data {
+        TemplateData::Report(_) => render_report(engine.as_ref(), render, state.config.pdf_chunk_rows).await,
+        _ => engine.render(render).await,
+    };
+
+    match rendered {
+        Ok(rendered) => {
+            let (timings, warnings) = (rendered.timings, rendered.warnings);
+            let now = Utc::now();
+            let document_type = data.get("template_type")
+                .and_then(|v| v.as_str())
+                .unwrap_or("document");
+
+            let key = document_key(tenant_id, &org_id, document_type, document_id, "pdf", now);
+
+            let pdf_bytes = match protection {
+                Some(encrypt) => state.post_processor.run(rendered.pdf, vec![encrypt]).await
+                    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to protect PDF: {}", e)))?,
+                None => rendered.pdf,
+            };
+            let pdf_bytes = pades_sign(&state, tenant_id, pdf_bytes).await
+                .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to sign PDF: {}", e)))?;
+            let size_bytes = pdf_bytes.len() as u64;
+            let page_count = page_count(&pdf_bytes);
+
+            let upload_start = std::time::Instant::now();
+            let stored = state.storage.put(
                 &state.config.s3_bucket_documents,
                 &key,
                 pdf_bytes,
                 "application/pdf",
             ).await.map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to upload to S3: {}", e)))?;
+            let stages = StageTimings {
+                queue_wait_ms: Some(0),
+                data_fetch_ms: Some(timings.assets_ms),
+                render_ms: Some(timings.render_ms),
+                compile_ms: Some(timings.compile_ms),
+                upload_ms: Some(upload_start.elapsed().as_millis() as u64),
+                ..StageTimings::default()
+            };
 
-            let _ = tokio::fs::remove_file(&pdf_path).await;
+            let preview = match rendered.preview_png {
+                Some(png) => store_preview(&state, &state.config.s3_bucket_documents, &key, png).await,
+                None => None,
+            };
+
+            let record = DocumentRecord {
+                id: document_id,
+                tenant_id,
+                user_id,
+                organization_id: org_id,
+                document_type: document_type.to_string(),
+                template_id: template_id.clone(),
+                priority: data.get("priority")
+                    .and_then(|p| serde_json::from_value(p.clone()).ok())
+                    .unwrap_or(Priority::Normal),
+                status: DocumentStatus::Completed,
+                progress: Some(100.0),
+                error: None,
+                attempts: 1,
+                bucket: state.config.s3_bucket_documents.clone(),
+                storage_key: Some(key),
+                preview_key: preview,
+                xml_key: None,
+                companion_key: None,
+                content_type: Some("application/pdf".to_string()),
+                checksum_sha256: Some(stored.checksum_sha256.clone()),
+                size_bytes,
+                page_count,
+                processing_time_ms: start.elapsed().as_millis() as u64,
+                stages,
+                compile_warnings: warnings.clone(),
+                storage_class: None,
+                deleted_at: None,
+                external_ref,
+                created_at: now,
+                updated_at: now,
+            };
+            state.events.emit(EventType::Created, &record, json!({ "mode": "template" }));
+            state.events.emit(EventType::Completed, &record, completed_event_data(&record));
+            state.documents.upsert(record).await
+                .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to save document: {}", e)))?;
 
             Ok(HttpResponse::Ok().json(json!({
                 "status": "success",
                 "document_id": document_id,
-                "url": url,
-                "local_path": pdf_path
+                "url": stored.url,
+                "checksum_sha256": stored.checksum_sha256,
+                "warnings": warnings
             })))
         },
         Err(e) => {
             tracing::error!("Failed to generate PDF from template: {:?}", e);
             Ok(HttpResponse::InternalServerError().json(json!({
                 "error": "Failed to generate PDF",
-                "details": e.to_string()
+                "code": ErrorCode::GenerationFailed,
+                "details": redact_text(&e.to_string())
             })))
         }
     }
@@ -144,111 +300,787 @@ pub async fn preview_template(
 
     let sample_data = get_sample_data_for_template(&template_id);
 
-    let engine = TemplateEngine::new(
-        "templates".to_string(),
-        "temp".to_string(),
-    );
+    let engine = &state.template_manager;
 
-    match engine.generate_pdf(&template_id, sample_data, Some(format!("preview_{}", template_id))).await {
-        Ok(pdf_path) => {
-            let pdf_bytes = tokio::fs::read(&pdf_path).await
-                .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to read PDF: {}", e)))?;
+    let (tenant_id, _) = extract_tenant_user_helper(&req);
 
-            let _ = tokio::fs::remove_file(&pdf_path).await;
+    let json_data = serde_json::to_value(&sample_data)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
 
+    let render = RenderRequest::new(Some(tenant_id), template_id.as_str(), json_data)
+        .with_output_filename(format!("preview_{}", template_id));
+
+    match engine.render(render).await {
+        Ok(rendered) => {
+            // El detalle de las advertencias está en POST /templates/{id}/validate
             Ok(HttpResponse::Ok()
                 .content_type("application/pdf")
-                .body(pdf_bytes))
+                .append_header(("X-Typst-Warnings", rendered.warnings.len().to_string()))
+                .body(rendered.pdf))
         },
         Err(e) => {
             Ok(HttpResponse::InternalServerError().json(json!({
                 "error": "Failed to generate preview",
-                "details": e.to_string()
+                "code": ErrorCode::GenerationFailed,
+                "details": redact_text(&e.to_string())
             })))
         }
     }
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct ValidateTemplateRequest {
+    /// Datos con los que compilar; por defecto los de ejemplo de la plantilla
+    pub data: Option<serde_json::Value>,
+}
+
+/// Compila la plantilla sin guardar nada y devuelve las advertencias y
+/// errores de Typst (mensaje, ubicación y sugerencias)
+pub async fn validate_template(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: Option<web::Json<ValidateTemplateRequest>>,
+    state: web::Data<ApiState>,
+) -> Result<HttpResponse> {
+    let template_id = path.into_inner();
+    let (tenant_id, _) = extract_tenant_user_helper(&req);
+
+    if state.template_manager.get_registry().resolve(Some(tenant_id), &template_id).is_none() {
+        return Ok(HttpResponse::NotFound().json(json!({
+            "error": "Template not found",
+            "code": ErrorCode::NotFound,
+            "template_id": template_id
+        })));
+    }
+
+    let data = match body.and_then(|b| b.into_inner().data) {
+        Some(data) => data,
+        None => serde_json::to_value(get_sample_data_for_template(&template_id))
+            .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?,
+    };
+    let output_filename = format!("validate_{}_{}", template_id, Uuid::new_v4());
+
+    let render = RenderRequest::new(Some(tenant_id), template_id.as_str(), data).with_output_filename(output_filename);
+
+    let (warnings, errors) = match state.template_manager.render(render).await {
+        Ok(rendered) => (rendered.warnings, Vec::new()),
+        Err(e) => failure_diagnostics(&e),
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "template_id": template_id,
+        "valid": errors.is_empty(),
+        "warnings": warnings,
+        "errors": errors
+    })))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FieldUsageQuery {
+    /// Plantilla incorporada cuyo modelo de datos se usa; por defecto la del mismo id
+    pub schema: Option<String>,
+}
+
+/// Campos que lee la plantilla frente a los del modelo de datos: marca los
+/// declarados sin uso y los leídos que el modelo no tiene. Analiza el fuente
+/// enviado en el body o, sin body, la versión subida por el tenant
+pub async fn template_field_usage(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<FieldUsageQuery>,
+    body: String,
+    state: web::Data<ApiState>,
+) -> Result<HttpResponse> {
+    let template_id = path.into_inner();
+    let (tenant_id, _) = extract_tenant_user_helper(&req);
+
+    let referenced = if body.trim().is_empty() {
+        let Some(template) = state.template_manager.get_registry().resolve(Some(tenant_id), &template_id) else {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "error": "Template not found",
+                "code": ErrorCode::NotFound,
+                "template_id": template_id
+            })));
+        };
+        template.referenced_fields().ok_or_else(|| actix_web::error::ErrorBadRequest(
+            format!("Template {} is built in; send a template source to analyze", template_id)
+        ))?
+    } else {
+        UploadedTemplate::new(tenant_id, &template_id, body, String::new())
+            .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?
+            .referenced_fields()
+            .unwrap_or_default()
+    };
+
+    let schema_id = query.into_inner().schema.unwrap_or_else(|| template_id.clone());
+    let schema = match get_sample_data_for_template(&schema_id) {
+        TemplateData::Custom(_) => {
+            return Err(actix_web::error::ErrorBadRequest(
+                format!("No data model for {}; pass ?schema= with a built-in template id", schema_id)
+            ));
+        },
+        sample => serde_json::to_value(sample)
+            .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?,
+    };
+
+    let usage = analyze_field_usage(&referenced, &schema);
+    Ok(HttpResponse::Ok().json(json!({
+        "template_id": template_id,
+        "schema": schema_id,
+        "referenced": usage.referenced,
+        "declared": usage.declared,
+        "unused": usage.unused,
+        "missing": usage.missing
+    })))
+}
+
+/// Respuesta 403 para los cambios de plantillas si el usuario no es `admin`
+fn admin_guard(req: &HttpRequest) -> Option<HttpResponse> {
+    let role = extract_role(req);
+    if role == ADMIN_ROLE {
+        return None;
+    }
+
+    Some(HttpResponse::Forbidden().json(json!({
+        "error": "Template changes require the admin role",
+        "code": ErrorCode::Forbidden,
+        "role": role
+    })))
+}
+
+/// Plantilla que usa el tenant para el id: la suya (con el fuente, editable
+/// con PUT), la global en disco (con el fuente) o la incorporada (sin fuente)
+pub async fn get_template(
+    req: HttpRequest,
+    path: web::Path<String>,
+    state: web::Data<ApiState>,
+) -> Result<HttpResponse> {
+    let template_id = path.into_inner();
+    let (tenant_id, _) = extract_tenant_user_helper(&req);
+
+    let Some(template) = state.template_manager.get_template(Some(tenant_id), &template_id) else {
+        return Ok(HttpResponse::NotFound().json(json!({
+            "error": "Template not found",
+            "code": ErrorCode::NotFound,
+            "template_id": template_id
+        })));
+    };
+
+    let is_override = state.template_manager.get_registry().get_override(tenant_id, &template_id).is_some();
+    let origin = match (is_override, template.source()) {
+        (true, _) => "tenant",
+        (false, Some(_)) => "file",
+        (false, None) => "builtin",
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "template_id": template_id,
+        "description": template.description(),
+        "version": template.version(),
+        "origin": origin,
+        "overrides_builtin": is_override && state.template_manager.template_exists(&template_id),
+        "source": template.source()
+    })))
+}
+
+/// Vuelve a leer del storage la versión del tenant de una plantilla (editada
+/// por otra instancia o directamente en el bucket); si ya no está, la quita
+pub async fn reload_template(
+    req: HttpRequest,
+    path: web::Path<String>,
+    state: web::Data<ApiState>,
+) -> Result<HttpResponse> {
+    if let Some(response) = admin_guard(&req) {
+        return Ok(response);
+    }
+    let template_id = path.into_inner();
+    let (tenant_id, _) = extract_tenant_user_helper(&req);
+    validate_template_id(&template_id)?;
+
+    let bucket = &state.config.s3_bucket_documents;
+    let key = template_override_key(tenant_id, &template_id);
+    let stored = state.storage.list(bucket, Some(&key)).await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to list stored templates: {}", e)))?
+        .into_iter()
+        .find(|object| object.key == key);
+
+    let source = match &stored {
+        Some(object) => {
+            let bytes = state.storage.get(bucket, &key).await
+                .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to read stored template: {}", e)))?;
+            let version = object.last_modified
+                .map(|t| format!("tenant-{}", t.format("%Y%m%d%H%M%S")))
+                .unwrap_or_else(|| "tenant".to_string());
+            Some((String::from_utf8_lossy(&bytes).to_string(), version))
+        },
+        None => None,
+    };
+
+    let had_override = state.template_manager
+        .reload_template(tenant_id, &template_id, source)
+        .map_err(|e| actix_web::error::ErrorUnprocessableEntity(format!("Stored template is invalid: {}", e)))?;
+
+    if stored.is_none() && !had_override {
+        return Ok(HttpResponse::NotFound().json(json!({
+            "error": "Template override not found",
+            "code": ErrorCode::NotFound,
+            "template_id": template_id
+        })));
+    }
+
+    let template = state.template_manager.get_template(Some(tenant_id), &template_id);
+    tracing::info!("Tenant {} reloaded template {} (stored: {})", tenant_id, template_id, stored.is_some());
+
+    Ok(HttpResponse::Ok().json(json!({
+        "template_id": template_id,
+        "reloaded": stored.is_some(),
+        "removed": stored.is_none(),
+        "version": template.as_ref().map(|t| t.version().to_string())
+    })))
+}
+
+/// Advertencias y errores de una compilación fallida; si falló antes de
+/// compilar (datos inválidos, error de render) el error es el mensaje
+fn failure_diagnostics(error: &anyhow::Error) -> (Vec<CompileDiagnostic>, Vec<CompileDiagnostic>) {
+    match error.downcast_ref::<CompileError>() {
+        Some(compile) => (
+            parse_diagnostics(&compile.stderr, "warning"),
+            parse_diagnostics(&compile.stderr, "error"),
+        ),
+        None => (Vec::new(), vec![CompileDiagnostic {
+            message: redact_text(&error.to_string()),
+            location: None,
+            hints: Vec::new(),
+        }]),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTemplateRequest {
+    pub template_id: String,
+    /// Fuente Typst con marcadores minijinja
+    pub source: String,
+    /// Datos de la compilación de prueba; por defecto `{}`
+    pub sample_data: Option<serde_json::Value>,
+}
+
+/// Registra una plantilla propia del tenant. Antes de guardarla se revisa el
+/// fuente (tamaño, sin paquetes, plugins ni rutas fuera de sus assets) y se
+/// compila aislada con `sample_data`; si falla responde 400 con los errores
+pub async fn create_template(
+    req: HttpRequest,
+    body: web::Json<CreateTemplateRequest>,
+    state: web::Data<ApiState>,
+) -> Result<HttpResponse> {
+    if let Some(response) = admin_guard(&req) {
+        return Ok(response);
+    }
+    let CreateTemplateRequest { template_id, source, sample_data } = body.into_inner();
+    let (tenant_id, _) = extract_tenant_user_helper(&req);
+
+    validate_template_id(&template_id)?;
+
+    let registry = state.template_manager.get_registry();
+    if state.template_manager.template_exists(&template_id) || registry.overrides_for(tenant_id).contains(&template_id) {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "Template already exists",
+            "code": ErrorCode::TemplateExists,
+            "template_id": template_id
+        })));
+    }
+
+    let sample_data = sample_data.unwrap_or_else(|| json!({}));
+    store_tenant_template(&state, tenant_id, template_id, source, sample_data, StatusCode::CREATED).await
+}
+
+/// Sube la versión del tenant de una plantilla (Typst con marcadores minijinja).
+/// Reemplaza la incorporada con el mismo id solo para ese tenant; se valida
+/// igual que en `create_template`, con los datos de ejemplo de la incorporada
+pub async fn upload_template_override(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: String,
+    state: web::Data<ApiState>,
+) -> Result<HttpResponse> {
+    if let Some(response) = admin_guard(&req) {
+        return Ok(response);
+    }
+    let template_id = path.into_inner();
+    let (tenant_id, _) = extract_tenant_user_helper(&req);
+
+    validate_template_id(&template_id)?;
+
+    let sample_data = match get_sample_data_for_template(&template_id) {
+        TemplateData::Custom(_) => json!({}),
+        sample => serde_json::to_value(sample)
+            .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?,
+    };
+    store_tenant_template(&state, tenant_id, template_id, body, sample_data, StatusCode::OK).await
+}
+
+/// Valida el fuente en el sandbox y, si compila, lo guarda en el storage y lo
+/// registra para el tenant
+async fn store_tenant_template(
+    state: &ApiState,
+    tenant_id: i64,
+    template_id: String,
+    source: String,
+    sample_data: serde_json::Value,
+    status: StatusCode,
+) -> Result<HttpResponse> {
+    let version = format!("tenant-{}", Utc::now().format("%Y%m%d%H%M%S"));
+    let stored_source = source.as_bytes().to_vec();
+    let template = UploadedTemplate::new(tenant_id, &template_id, source, version.clone())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+
+    let warnings = match state.template_manager.compile_check(tenant_id, &template, &sample_data).await {
+        Ok(warnings) => warnings,
+        Err(e) => {
+            let (warnings, errors) = failure_diagnostics(&e);
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Template failed sandbox validation",
+                "code": ErrorCode::InvalidRequest,
+                "template_id": template_id,
+                "warnings": warnings,
+                "errors": errors
+            })));
+        },
+    };
+
+    let key = template_override_key(tenant_id, &template_id);
+    state.storage.put(
+        &state.config.s3_bucket_documents,
+        &key,
+        stored_source,
+        "text/plain; charset=utf-8",
+    ).await.map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to store template: {}", e)))?;
+
+    let overrides_builtin = state.template_manager.template_exists(&template_id);
+    state.template_manager.update_template(tenant_id, Arc::new(template));
+
+    tracing::info!("Tenant {} uploaded template {} ({})", tenant_id, template_id, version);
+
+    Ok(HttpResponse::build(status).json(json!({
+        "template_id": template_id,
+        "tenant_id": tenant_id,
+        "version": version,
+        "overrides_builtin": overrides_builtin,
+        "warnings": warnings
+    })))
+}
+
+/// Días en que un documento generado con la plantilla impide borrarla sin `force`
+const TEMPLATE_RECENT_USE_DAYS: i64 = 7;
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteTemplateQuery {
+    /// Borra aunque haya documentos recientes con la plantilla
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Elimina la versión del tenant; vuelve a usarse la plantilla global. Se niega
+/// si hay trabajos en cola con ella y, salvo `force`, si generó documentos en
+/// los últimos días. El fuente no se destruye: se archiva con su versión
+pub async fn delete_template_override(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<DeleteTemplateQuery>,
+    state: web::Data<ApiState>,
+) -> Result<HttpResponse> {
+    if let Some(response) = admin_guard(&req) {
+        return Ok(response);
+    }
+    let template_id = path.into_inner();
+    let (tenant_id, _) = extract_tenant_user_helper(&req);
+    let registry = state.template_manager.get_registry();
+
+    let Some(template) = registry.get_override(tenant_id, &template_id) else {
+        return Ok(HttpResponse::NotFound().json(json!({
+            "error": "Template override not found",
+            "code": ErrorCode::NotFound,
+            "template_id": template_id
+        })));
+    };
+
+    let since = Utc::now() - chrono::Duration::days(TEMPLATE_RECENT_USE_DAYS);
+    let dependents = state.documents.using_template(tenant_id, &template_id, since).await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+    let pending = dependents
+        .iter()
+        .filter(|r| matches!(r.status, DocumentStatus::Queued | DocumentStatus::Processing))
+        .count();
+    if pending > 0 || (!dependents.is_empty() && !query.force) {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "Template is in use",
+            "code": ErrorCode::TemplateInUse,
+            "template_id": template_id,
+            "pending_jobs": pending,
+            "recent_documents": dependents.len() - pending,
+            "last_used_at": dependents.iter().map(|r| r.created_at).max(),
+            "force_allowed": pending == 0
+        })));
+    }
+
+    let bucket = &state.config.s3_bucket_documents;
+    let key = template_override_key(tenant_id, &template_id);
+    let archive_key = template_archive_key(tenant_id, &template_id, template.version());
+    let source = state.storage.get(bucket, &key).await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to read stored template: {}", e)))?;
+    state.storage.put(bucket, &archive_key, source, "text/plain; charset=utf-8").await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to archive template: {}", e)))?;
+
+    registry.remove_override(tenant_id, &template_id);
+    if let Err(e) = state.storage.delete(bucket, &key).await {
+        tracing::warn!("Failed to delete stored template {}: {}", key, e);
+    }
+
+    tracing::info!(
+        "Tenant {} deleted template {} ({} archived at {}, forced: {})",
+        tenant_id, template_id, template.version(), archive_key, query.force && !dependents.is_empty()
+    );
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Sube un asset de la plantilla (imagen, fuente, include); queda disponible
+/// al compilar con la ruta relativa `name` (p.ej. `image("img/logo.png")`)
+pub async fn upload_template_asset(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    mut payload: web::Payload,
+    state: web::Data<ApiState>,
+) -> Result<HttpResponse> {
+    if let Some(response) = admin_guard(&req) {
+        return Ok(response);
+    }
+    use futures::StreamExt;
+
+    let (template_id, name) = path.into_inner();
+    let (tenant_id, _) = extract_tenant_user_helper(&req);
+    validate_template_id(&template_id)?;
+    validate_asset_name(&name).map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+
+    let assets = asset_store(&state)?;
+
+    let mut body = web::BytesMut::new();
+    let max_size = state.config.max_upload_size_bytes;
+
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if (body.len() + chunk.len()) > max_size {
+            return Ok(HttpResponse::PayloadTooLarge().json(json!({
+                "error": "Asset too large",
+                "code": ErrorCode::PayloadTooLarge,
+                "max_size_mb": max_size / 1_048_576
+            })));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    let content_type = req.headers()
+        .get("Content-Type")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let size = body.len();
+
+    assets.put(tenant_id, &template_id, &name, body.to_vec(), &content_type).await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to store asset: {}", e)))?;
+
+    tracing::info!("Tenant {} uploaded asset {} for template {}", tenant_id, name, template_id);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "template_id": template_id,
+        "name": name,
+        "size": size,
+        "content_type": content_type
+    })))
+}
+
+/// Lista los assets de la plantilla del tenant
+pub async fn list_template_assets(
+    req: HttpRequest,
+    path: web::Path<String>,
+    state: web::Data<ApiState>,
+) -> Result<HttpResponse> {
+    let template_id = path.into_inner();
+    let (tenant_id, _) = extract_tenant_user_helper(&req);
+    validate_template_id(&template_id)?;
+
+    let assets = asset_store(&state)?.list(tenant_id, &template_id).await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to list assets: {}", e)))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "template_id": template_id,
+        "assets": assets
+    })))
+}
+
+pub async fn delete_template_asset(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    state: web::Data<ApiState>,
+) -> Result<HttpResponse> {
+    if let Some(response) = admin_guard(&req) {
+        return Ok(response);
+    }
+    let (template_id, name) = path.into_inner();
+    let (tenant_id, _) = extract_tenant_user_helper(&req);
+    validate_template_id(&template_id)?;
+    validate_asset_name(&name).map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+
+    asset_store(&state)?.delete(tenant_id, &template_id, &name).await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to delete asset: {}", e)))?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn asset_store(state: &ApiState) -> Result<Arc<TemplateAssetStore>> {
+    state.template_manager.assets()
+        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Template assets are not enabled"))
+}
+
+fn validate_template_id(template_id: &str) -> Result<()> {
+    if template_id.is_empty()
+        || !template_id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+    {
+        return Err(actix_web::error::ErrorBadRequest("Template id must match [a-z0-9_-]+"));
+    }
+    Ok(())
+}
+
+/// Carga al arrancar las plantillas de tenants guardadas en el storage
+pub async fn restore_template_overrides(state: &ApiState) -> usize {
+    let bucket = &state.config.s3_bucket_documents;
+    let objects = match state.storage.list(bucket, Some(TEMPLATES_PREFIX)).await {
+        Ok(objects) => objects,
+        Err(e) => {
+            tracing::warn!("Failed to list tenant templates: {}", e);
+            return 0;
+        }
+    };
+
+    let registry = state.template_manager.get_registry();
+    let mut restored = 0;
+
+    for object in objects {
+        let Some((tenant_id, template_id)) = parse_template_override_key(&object.key) else { continue };
+
+        let source = match state.storage.get(bucket, &object.key).await {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+            Err(e) => {
+                tracing::warn!("Failed to read tenant template {}: {}", object.key, e);
+                continue;
+            }
+        };
+
+        let version = object.last_modified
+            .map(|t| format!("tenant-{}", t.format("%Y%m%d%H%M%S")))
+            .unwrap_or_else(|| "tenant".to_string());
+
+        match UploadedTemplate::new(tenant_id, &template_id, source, version) {
+            Ok(template) => {
+                registry.set_override(tenant_id, Arc::new(template));
+                restored += 1;
+            },
+            Err(e) => tracing::warn!("Skipping invalid tenant template {}: {}", object.key, e),
+        }
+    }
+
+    restored
+}
+
+/// Estadísticas de uso de una plantilla (renders, fallos, tiempos de compilación
+/// por versión y las versiones cuyo p95 empeoró tras actualizarlas)
+pub async fn template_stats(
+    path: web::Path<String>,
+    state: web::Data<ApiState>,
+) -> Result<HttpResponse> {
+    let template_id = path.into_inner();
+
+    if !state.template_manager.template_exists(&template_id) {
+        return Ok(HttpResponse::NotFound().json(json!({
+            "error": "Template not found",
+            "code": ErrorCode::NotFound,
+            "template_id": template_id
+        })));
+    }
+
+    let versions = state.template_manager.template_stats(&template_id);
+    let renders: u64 = versions.iter().map(|v| v.renders).sum();
+    let failures: u64 = versions.iter().map(|v| v.failures).sum();
+    let total_compile_ms: u64 = versions.iter().map(|v| v.total_compile_ms).sum();
+    let regressed_versions: Vec<&str> = versions
+        .iter()
+        .filter(|v| v.regressed)
+        .map(|v| v.version.as_str())
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "template_id": template_id,
+        "renders": renders,
+        "failures": failures,
+        "avg_compile_ms": if renders > 0 { total_compile_ms as f64 / renders as f64 } else { 0.0 },
+        "regressed_versions": regressed_versions,
+        "versions": versions
+    })))
+}
+
 fn get_sample_data_for_template(template_id: &str) -> TemplateData {
     use crate::templates::*;
 
     match template_id {
-        "fiscal_electronic" => {
-            TemplateData::Invoice(InvoiceData {
-                invoice_number: "INV-2024-001".to_string(),
-                issue_date: "2024-01-15".to_string(),
-                due_date: "2024-02-15".to_string(),
-                company_info: CompanyInfo {
-                    name: "COMERCIAL ZYL".to_string(),
-                    legal_name: Some("ZYL, SRL".to_string()),
-                    tax_id: "101000001".to_string(),
-                    address: Address {
-                        street: "Calle Segunda #01, Gascue".to_string(),
-                        city: "Santo Domingo".to_string(),
-                        state: Some("Distrito Nacional".to_string()),
-                        postal_code: Some("10210".to_string()),
-                        country: "República Dominicana".to_string(),
-                    },
-                    phone: Some("809-555-0100".to_string()),
-                    email: Some("ventas@zyl.com.do".to_string()),
-                    website: Some("www.zyl.com.do".to_string()),
-                    logo_path: None,
-                },
-                client_info: ClientInfo {
-                    name: "COMERCIO, SRL".to_string(),
-                    legal_name: Some("COMERCIO, SRL".to_string()),
-                    tax_id: "130000001".to_string(),
-                    address: None,
-                    phone: Some("809-555-0200".to_string()),
-                    email: Some("compras@comercio.com.do".to_string()),
-                },
+        "fiscal_electronic" | "fiscal_invoice" => TemplateData::Invoice(sample_invoice_data()),
+        "simple_invoice" => TemplateData::Invoice(InvoiceData {
+            fiscal_info: None,
+            ..sample_invoice_data()
+        }),
+        "receipt" => {
+            let invoice = sample_invoice_data();
+            TemplateData::Receipt(ReceiptData {
+                receipt_number: "REC-2024-001".to_string(),
+                date: "2024-01-15".to_string(),
+                vendor: invoice.company_info,
+                items: vec![ReceiptItem {
+                    description: "Abono a factura INV-2024-001".to_string(),
+                    quantity: 1.0,
+                    unit_price: 50000.00,
+                    total: 50000.00,
+                }],
+                total: 50000.00,
+                payment_method: "Transferencia".to_string(),
+                currency: "RD$".to_string(),
+            })
+        },
+        "quote" => {
+            let invoice = sample_invoice_data();
+            TemplateData::Quote(QuoteData {
+                quote_number: "COT-2024-001".to_string(),
+                issue_date: "2024-01-10".to_string(),
+                valid_until: "2024-02-09".to_string(),
+                company_info: invoice.company_info,
+                client_info: invoice.client_info,
                 items: vec![
-                    InvoiceItem {
-                        quantity: 150.0,
+                    QuoteItem {
                         description: "Zapatos".to_string(),
+                        quantity: 150.0,
                         unit_price: 550.00,
-                        unit: Some("CAJ".to_string()),
-                        tax_rate: Some(0.18),
-                        tax_amount: Some(14880.00),
-                        discount: None,
-                        subtotal: 82500.00,
-                        total: 97380.00,
+                        total: 82500.00,
+                        optional: false,
                     },
-                    InvoiceItem {
-                        quantity: 200.0,
-                        description: "Vestidos".to_string(),
-                        unit_price: 800.00,
-                        unit: Some("PZA".to_string()),
-                        tax_rate: Some(0.18),
-                        tax_amount: Some(28800.00),
-                        discount: None,
-                        subtotal: 160000.00,
-                        total: 188800.00,
+                    QuoteItem {
+                        description: "Empaque de regalo".to_string(),
+                        quantity: 150.0,
+                        unit_price: 35.00,
+                        total: 5250.00,
+                        optional: true,
                     },
                 ],
                 totals: InvoiceTotals {
-                    subtotal: 242500.00,
-                    tax_amount: 43650.00,
+                    subtotal: 82500.00,
+                    tax_amount: 14850.00,
                     discount_amount: None,
-                    total: 286150.00,
+                    total: 97350.00,
                     currency: "RD$".to_string(),
                 },
-                fiscal_info: Some(FiscalInfo {
-                    e_ncf: "E310000000001".to_string(),
-                    security_code: "S7DQdu".to_string(),
-                    signature_date: "2024-01-15 10:30:00".to_string(),
-                    qr_data: "https://fc.dgii.gov.do/eCF/consultatimbrefc?rncemisor=101000001&encf=E310000000001".to_string(),
-                    expiration_date: Some("2025-12-31".to_string()),
+                terms: Some("50% de anticipo, saldo contra entrega.".to_string()),
+                notes: None,
+                acceptance: None,
+            })
+        },
+        "statement" => {
+            let invoice = sample_invoice_data();
+            let open_invoice = |number: &str, issued: &str, due: &str, amount: f64, balance: f64| StatementInvoice {
+                invoice_number: number.to_string(),
+                issue_date: issued.to_string(),
+                due_date: due.to_string(),
+                amount,
+                balance,
+            };
+            TemplateData::Statement(StatementData {
+                statement_number: "EC-2024-001".to_string(),
+                statement_date: "2024-03-31".to_string(),
+                period: Some(ReportPeriod {
+                    start_date: "2024-03-01".to_string(),
+                    end_date: "2024-03-31".to_string(),
                 }),
-                payment_info: Some(PaymentInfo {
-                    method: "Crédito".to_string(),
-                    terms: Some("30 días".to_string()),
-                    bank_info: None,
-                    paid: false,
-                    paid_date: None,
+                company_info: invoice.company_info,
+                client_info: invoice.client_info,
+                currency: "RD$".to_string(),
+                opening_balance: 283530.00,
+                invoices: vec![
+                    open_invoice("INV-2023-118", "2023-11-20", "2023-12-20", 97380.00, 47380.00),
+                    open_invoice("INV-2024-001", "2024-01-15", "2024-02-15", 286150.00, 136150.00),
+                    open_invoice("INV-2024-044", "2024-03-05", "2024-04-04", 52500.00, 52500.00),
+                ],
+                payments: vec![StatementPayment {
+                    date: "2024-03-12".to_string(),
+                    reference: "Transferencia 88213".to_string(),
+                    method: Some("Transferencia".to_string()),
+                    amount: 100000.00,
+                }],
+                aging: None,
+                notes: Some("Favor remitir el comprobante de pago a cobros@zyl.com.do".to_string()),
+            })
+        },
+        "certificate" => TemplateData::Certificate(CertificateData {
+            certificate_number: "CERT-2024-0042".to_string(),
+            title: "Certificado de Participación".to_string(),
+            recipient_name: "María Fernández".to_string(),
+            description: "Por completar el taller de Facturación Electrónica (e-CF) con una duración de 16 horas.".to_string(),
+            issue_date: "2024-01-20".to_string(),
+            issuer_name: "COMERCIAL ZYL".to_string(),
+            expiration_date: None,
+            signatories: vec![
+                CertificateSignatory { name: "Juan Pérez".to_string(), title: Some("Director Académico".to_string()) },
+                CertificateSignatory { name: "Ana Gómez".to_string(), title: Some("Instructora".to_string()) },
+            ],
+            verification_url: Some("https://zyl.com.do/certificados/CERT-2024-0042".to_string()),
+        }),
+        "dgii" => {
+            let sample = json!({
+                "format": "606",
+                "rnc": "101000001",
+                "period": "202401",
+                "rows": [{
+                    "rnc_cedula": "130000001",
+                    "tipo_bienes_servicios": "09",
+                    "ncf": "B0100000123",
+                    "fecha_comprobante": "2024-01-15",
+                    "fecha_pago": "2024-01-31",
+                    "monto_bienes": 82500.00,
+                    "total_monto_facturado": 82500.00,
+                    "itbis_facturado": 14850.00,
+                    "forma_pago": "02"
+                }]
+            });
+            TemplateData::Custom(serde_json::from_value(sample).unwrap_or_default())
+        },
+        "report" => {
+            let row = |cliente: &str, total: &str| {
+                std::collections::HashMap::from([
+                    ("Cliente".to_string(), cliente.to_string()),
+                    ("Total".to_string(), total.to_string()),
+                ])
+            };
+            TemplateData::Report(ReportData {
+                title: "Ventas del Mes".to_string(),
+                generated_date: "2024-01-31".to_string(),
+                period: ReportPeriod {
+                    start_date: "2024-01-01".to_string(),
+                    end_date: "2024-01-31".to_string(),
+                },
+                data: vec![row("COMERCIO, SRL", "286,150.00"), row("ZYL, SRL", "97,380.00")],
+                summary: Some(ReportSummary {
+                    metrics: std::collections::HashMap::from([("Total ventas".to_string(), 383530.00)]),
+                    highlights: vec!["Crecimiento de 12% vs mes anterior".to_string()],
                 }),
-                notes: Some("Gracias por su compra.".to_string()),
-                custom_fields: None,
+                charts: None,
+                schema: None,
+                footnotes: None,
+                conditional_formatting: None,
+                chunk: None,
             })
         },
         _ => {
@@ -257,6 +1089,140 @@ fn get_sample_data_for_template(template_id: &str) -> TemplateData {
     }
 }
 
+fn sample_invoice_data() -> crate::templates::InvoiceData {
+    use crate::templates::*;
+
+    InvoiceData {
+        invoice_number: "INV-2024-001".to_string(),
+        issue_date: "2024-01-15".to_string(),
+        due_date: "2024-02-15".to_string(),
+        company_info: CompanyInfo {
+            name: "COMERCIAL ZYL".to_string(),
+            legal_name: Some("ZYL, SRL".to_string()),
+            tax_id: "101000001".to_string(),
+            address: Address {
+                street: "Calle Segunda #01, Gascue".to_string(),
+                city: "Santo Domingo".to_string(),
+                state: Some("Distrito Nacional".to_string()),
+                postal_code: Some("10210".to_string()),
+                country: "República Dominicana".to_string(),
+            },
+            phone: Some("809-555-0100".to_string()),
+            email: Some("ventas@zyl.com.do".to_string()),
+            website: Some("www.zyl.com.do".to_string()),
+            logo_path: None,
+            logo_url: None,
+        },
+        client_info: ClientInfo {
+            name: "COMERCIO, SRL".to_string(),
+            legal_name: Some("COMERCIO, SRL".to_string()),
+            tax_id: "130000001".to_string(),
+            address: None,
+            phone: Some("809-555-0200".to_string()),
+            email: Some("compras@comercio.com.do".to_string()),
+        },
+        items: vec![
+            InvoiceItem {
+                quantity: 150.0,
+                description: "Zapatos".to_string(),
+                unit_price: 550.00,
+                unit: Some("CAJ".to_string()),
+                tax_rate: Some(0.18),
+                tax_amount: Some(14880.00),
+                discount: None,
+                subtotal: 82500.00,
+                total: 97380.00,
+            },
+            InvoiceItem {
+                quantity: 200.0,
+                description: "Vestidos".to_string(),
+                unit_price: 800.00,
+                unit: Some("PZA".to_string()),
+                tax_rate: Some(0.18),
+                tax_amount: Some(28800.00),
+                discount: None,
+                subtotal: 160000.00,
+                total: 188800.00,
+            },
+        ],
+        totals: InvoiceTotals {
+            subtotal: 242500.00,
+            tax_amount: 43650.00,
+            discount_amount: None,
+            total: 286150.00,
+            currency: "RD$".to_string(),
+        },
+        fiscal_info: Some(FiscalInfo {
+            e_ncf: "E310000000001".to_string(),
+            series: None,
+            security_code: "S7DQdu".to_string(),
+            signature_date: "2024-01-15 10:30:00".to_string(),
+            qr_data: "https://fc.dgii.gov.do/eCF/consultatimbrefc?rncemisor=101000001&encf=E310000000001".to_string(),
+            expiration_date: Some("2025-12-31".to_string()),
+        }),
+        payment_info: Some(PaymentInfo {
+            method: "Crédito".to_string(),
+            terms: Some("30 días".to_string()),
+            bank_info: None,
+            paid: false,
+            paid_date: None,
+        }),
+        notes: Some("Gracias por su compra.".to_string()),
+        custom_fields: None,
+    }
+}
+
+/// Compila las plantillas indicadas con datos de ejemplo para eliminar la
+/// latencia del primer request tras un deploy (caches de fuentes de Typst, disco)
+pub async fn warm_up_templates(
+    engine: &crate::templates::TemplateManager,
+    template_ids: &[String],
+) -> Vec<serde_json::Value> {
+    let mut results = Vec::new();
+
+    for template_id in template_ids {
+        let start = std::time::Instant::now();
+        let result = match serde_json::to_value(get_sample_data_for_template(template_id)) {
+            Ok(sample_data) => {
+                let render = RenderRequest::new(None, template_id.as_str(), sample_data)
+                    .with_output_filename(format!("warmup_{}_{}", template_id, Uuid::new_v4()));
+                engine.render(render).await
+            },
+            Err(e) => Err(e.into()),
+        };
+        let elapsed_ms = start.elapsed().as_millis() as u64;
+
+        match result {
+            Ok(_) => {
+                tracing::info!("Template {} warmed up in {}ms", template_id, elapsed_ms);
+                results.push(json!({ "template_id": template_id, "status": "ok", "duration_ms": elapsed_ms }));
+            },
+            Err(e) => {
+                tracing::warn!("Warm-up failed for template {}: {}", template_id, e);
+                results.push(json!({ "template_id": template_id, "status": "failed", "error": redact_text(&e.to_string()) }));
+            }
+        }
+    }
+
+    results
+}
+
+/// Plantillas a precalentar: `WARMUP_TEMPLATES` (lista separada por comas) o todas las registradas
+pub fn warmup_template_ids(engine: &crate::templates::TemplateManager) -> Vec<String> {
+    match std::env::var("WARMUP_TEMPLATES") {
+        Ok(list) if !list.trim().is_empty() => list
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
+        _ => {
+            let mut ids: Vec<String> = engine.list_templates().into_iter().map(|(id, _)| id).collect();
+            ids.sort();
+            ids
+        }
+    }
+}
+
 fn extract_tenant_user_helper(req: &HttpRequest) -> (i64, i64) {
     let tenant_id = req.headers()
         .get("X-Tenant-Id")
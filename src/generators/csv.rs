@@ -0,0 +1,142 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+use super::report_processor::filter_and_sort;
+use crate::models::{ColumnDefinition, CsvOptions, DataType, ReportSchema};
+
+/// Generador de CSV para reportes
+pub struct CsvGenerator;
+
+impl CsvGenerator {
+    pub fn new() -> Self {
+        CsvGenerator
+    }
+
+    /// Genera un CSV desde datos JSON genéricos: con `schema` las columnas
+    /// visibles en su orden; sin él `headers` + `rows` (arreglos) o las
+    /// claves de la primera fila. `csv.delimiter` cambia el separador
+    pub async fn generate(&self, data: Value) -> Result<Vec<u8>> {
+        tokio::task::spawn_blocking(move || Self::generate_csv_from_json(&data)).await?
+    }
+
+    /// Genera un CSV a partir del esquema del reporte (filas como objetos JSON)
+    pub async fn generate_report(&self, schema: ReportSchema, rows: Vec<Value>, options: CsvOptions) -> Result<Vec<u8>> {
+        tokio::task::spawn_blocking(move || Self::generate_csv_from_schema(&schema, &rows, &options)).await?
+    }
+
+    fn generate_csv_from_json(data: &Value) -> Result<Vec<u8>> {
+        let options: CsvOptions = match data.get("csv") {
+            Some(options) => serde_json::from_value(options.clone())?,
+            None => CsvOptions::default(),
+        };
+        let rows = data["rows"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+
+        if let Some(schema) = data.get("schema") {
+            let schema: ReportSchema = serde_json::from_value(schema.clone())?;
+            return Self::generate_csv_from_schema(&schema, rows, &options);
+        }
+
+        let mut writer = Self::writer(&options)?;
+
+        match data["headers"].as_array() {
+            Some(headers) => {
+                if options.has_header.unwrap_or(true) {
+                    writer.write_record(headers.iter().map(Self::value_text))?;
+                }
+                for row in rows {
+                    let cells = row.as_array().map(Vec::as_slice).unwrap_or(&[]);
+                    writer.write_record(cells.iter().map(Self::value_text))?;
+                }
+            },
+            None => {
+                // Filas como objetos: columnas según las claves de la primera fila
+                let fields: Vec<String> = rows
+                    .first()
+                    .and_then(Value::as_object)
+                    .map(|row| row.keys().cloned().collect())
+                    .unwrap_or_default();
+
+                if options.has_header.unwrap_or(true) {
+                    writer.write_record(&fields)?;
+                }
+                for row in rows {
+                    writer.write_record(fields.iter().map(|f| Self::value_text(row.get(f).unwrap_or(&Value::Null))))?;
+                }
+            },
+        }
+
+        Ok(writer.into_inner().map_err(|e| e.into_error())?)
+    }
+
+    fn generate_csv_from_schema(schema: &ReportSchema, rows: &[Value], options: &CsvOptions) -> Result<Vec<u8>> {
+        let rows = filter_and_sort(schema, rows)?;
+        let columns = schema.visible_columns();
+        let mut writer = Self::writer(options)?;
+
+        if options.has_header.unwrap_or(true) {
+            writer.write_record(columns.iter().map(|c| c.header.as_str()))?;
+        }
+
+        for row in rows.iter() {
+            // Las columnas calculadas salen con el valor precalculado que traiga la fila
+            writer.write_record(
+                columns
+                    .iter()
+                    .map(|c| Self::format_cell(row.get(&c.field).unwrap_or(&Value::Null), c)),
+            )?;
+        }
+
+        Ok(writer.into_inner().map_err(|e| e.into_error())?)
+    }
+
+    fn writer(options: &CsvOptions) -> Result<::csv::Writer<Vec<u8>>> {
+        let delimiter = options.delimiter.unwrap_or(',');
+        if !delimiter.is_ascii() {
+            bail!("CSV delimiter must be an ASCII character");
+        }
+
+        Ok(::csv::WriterBuilder::new()
+            .delimiter(delimiter as u8)
+            .flexible(true)
+            .from_writer(Vec::new()))
+    }
+
+    /// Texto de la celda según el tipo de la columna: moneda con dos
+    /// decimales, porcentaje como `12.50%` (0.125) y números sin formato
+    pub(crate) fn format_cell(value: &Value, column: &ColumnDefinition) -> String {
+        let number = match value {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => s.trim().parse::<f64>().ok(),
+            _ => None,
+        };
+
+        match (&column.data_type, number) {
+            (DataType::Currency, Some(n)) => format!("{:.2}", n),
+            (DataType::Percentage, Some(n)) => format!("{:.2}%", n * 100.0),
+            (DataType::Number, Some(n)) => Self::number_text(n),
+            _ => Self::value_text(value),
+        }
+    }
+
+    fn number_text(n: f64) -> String {
+        if n.fract() == 0.0 && n.abs() < 1e15 {
+            format!("{}", n as i64)
+        } else {
+            n.to_string()
+        }
+    }
+
+    pub(crate) fn value_text(value: &Value) -> String {
+        match value {
+            Value::Null => String::new(),
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl Default for CsvGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::models::{CsvOptions, ExcelOptions, FileFormat};
+use super::data_source::read_file;
+
+/// Filas por row group al escribir Parquet
+const ROW_GROUP_SIZE: usize = 10_000;
+
+/// Archivo de datos convertido
+pub struct ConvertedFile {
+    pub bytes: Vec<u8>,
+    pub row_count: usize,
+}
+
+/// Convierte un archivo de datos de `from` a `to` (JSON, JSONL, CSV o
+/// Parquet); se lee con el mismo parser que usa el pipeline de reportes.
+/// Excel solo se acepta como origen
+pub fn convert_file(
+    bytes: Vec<u8>,
+    from: &FileFormat,
+    to: &FileFormat,
+    csv: CsvOptions,
+    excel: ExcelOptions,
+) -> Result<ConvertedFile> {
+    if matches!(to, FileFormat::Excel) {
+        bail!("Excel is only supported as a source format");
+    }
+
+    let rows = read_file(bytes, from, csv, excel)?;
+    let bytes = match to {
+        FileFormat::Json => serde_json::to_vec(&rows)?,
+        FileFormat::Jsonl => write_jsonl(&rows)?,
+        FileFormat::Csv => write_csv(&rows)?,
+        FileFormat::Parquet => write_parquet(&rows)?,
+        FileFormat::Excel => unreachable!(),
+    };
+
+    Ok(ConvertedFile { bytes, row_count: rows.len() })
+}
+
+pub fn extension(format: &FileFormat) -> &'static str {
+    match format {
+        FileFormat::Csv => "csv",
+        FileFormat::Json => "json",
+        FileFormat::Jsonl => "jsonl",
+        FileFormat::Parquet => "parquet",
+        FileFormat::Excel => "xlsx",
+    }
+}
+
+pub fn content_type(format: &FileFormat) -> &'static str {
+    match format {
+        FileFormat::Csv => "text/csv; charset=utf-8",
+        FileFormat::Json => "application/json",
+        FileFormat::Jsonl => "application/x-ndjson",
+        FileFormat::Parquet => "application/vnd.apache.parquet",
+        FileFormat::Excel => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    }
+}
+
+fn write_jsonl(rows: &[Value]) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    for row in rows {
+        serde_json::to_writer(&mut output, row)?;
+        output.push(b'\n');
+    }
+    Ok(output)
+}
+
+/// Campos de las filas en orden de aparición
+fn field_names(rows: &[Value]) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+    for row in rows.iter().filter_map(Value::as_object) {
+        for key in row.keys() {
+            if !fields.contains(key) {
+                fields.push(key.clone());
+            }
+        }
+    }
+    fields
+}
+
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn write_csv(rows: &[Value]) -> Result<Vec<u8>> {
+    let fields = field_names(rows);
+    let mut writer = ::csv::Writer::from_writer(Vec::new());
+    writer.write_record(&fields)?;
+    for row in rows {
+        writer.write_record(fields.iter().map(|f| text_of(row.get(f).unwrap_or(&Value::Null))))?;
+    }
+    Ok(writer.into_inner().map_err(|e| e.into_error())?)
+}
+
+/// Tipo de una columna Parquet, inferido de sus valores no nulos
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnKind {
+    Boolean,
+    Int64,
+    Double,
+    Text,
+}
+
+impl ColumnKind {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Bool(_) => ColumnKind::Boolean,
+            Value::Number(n) if n.is_i64() => ColumnKind::Int64,
+            Value::Number(_) => ColumnKind::Double,
+            _ => ColumnKind::Text,
+        }
+    }
+
+    /// Enteros y decimales mezclados quedan como decimales; cualquier otra
+    /// mezcla, como texto
+    fn infer<'a>(values: impl Iterator<Item = &'a Value>) -> Self {
+        values
+            .filter(|v| !v.is_null())
+            .map(ColumnKind::of)
+            .reduce(|a, b| match (a, b) {
+                _ if a == b => a,
+                (ColumnKind::Int64, ColumnKind::Double) | (ColumnKind::Double, ColumnKind::Int64) => ColumnKind::Double,
+                _ => ColumnKind::Text,
+            })
+            .unwrap_or(ColumnKind::Text)
+    }
+}
+
+/// Parquet con una columna opcional por campo (booleano, entero, decimal o
+/// texto UTF-8; objetos y arreglos van como JSON en texto)
+fn write_parquet(rows: &[Value]) -> Result<Vec<u8>> {
+    use parquet::basic::{Compression, LogicalType, Repetition, Type as PhysicalType};
+    use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::types::Type;
+
+    let fields = field_names(rows);
+    if fields.is_empty() {
+        bail!("Cannot write a parquet file without columns");
+    }
+    let kinds: Vec<ColumnKind> = fields
+        .iter()
+        .map(|field| ColumnKind::infer(rows.iter().filter_map(|row| row.get(field))))
+        .collect();
+
+    let columns = fields
+        .iter()
+        .zip(&kinds)
+        .map(|(field, kind)| {
+            let physical = match kind {
+                ColumnKind::Boolean => PhysicalType::BOOLEAN,
+                ColumnKind::Int64 => PhysicalType::INT64,
+                ColumnKind::Double => PhysicalType::DOUBLE,
+                ColumnKind::Text => PhysicalType::BYTE_ARRAY,
+            };
+            let mut builder = Type::primitive_type_builder(field, physical).with_repetition(Repetition::OPTIONAL);
+            if *kind == ColumnKind::Text {
+                builder = builder.with_logical_type(Some(LogicalType::String));
+            }
+            Ok(Arc::new(builder.build()?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let schema = Arc::new(Type::group_type_builder("rows").with_fields(columns).build()?);
+    let properties = Arc::new(WriterProperties::builder().set_compression(Compression::SNAPPY).build());
+    let mut writer = SerializedFileWriter::new(Vec::new(), schema, properties)?;
+
+    for chunk in rows.chunks(ROW_GROUP_SIZE) {
+        let mut row_group = writer.next_row_group()?;
+        for (field, kind) in fields.iter().zip(&kinds) {
+            let Some(mut column) = row_group.next_column()? else {
+                bail!("Parquet writer has no column for {}", field);
+            };
+            let cells: Vec<Option<&Value>> = chunk
+                .iter()
+                .map(|row| row.get(field).filter(|v| !v.is_null()))
+                .collect();
+            let levels: Vec<i16> = cells.iter().map(|cell| cell.is_some() as i16).collect();
+            let present = cells.iter().flatten();
+
+            match kind {
+                ColumnKind::Boolean => {
+                    let values: Vec<bool> = present.filter_map(|v| v.as_bool()).collect();
+                    column.typed::<BoolType>().write_batch(&values, Some(&levels), None)?;
+                },
+                ColumnKind::Int64 => {
+                    let values: Vec<i64> = present.filter_map(|v| v.as_i64()).collect();
+                    column.typed::<Int64Type>().write_batch(&values, Some(&levels), None)?;
+                },
+                ColumnKind::Double => {
+                    let values: Vec<f64> = present.filter_map(|v| v.as_f64()).collect();
+                    column.typed::<DoubleType>().write_batch(&values, Some(&levels), None)?;
+                },
+                ColumnKind::Text => {
+                    let values: Vec<ByteArray> = present.map(|v| ByteArray::from(text_of(v).into_bytes())).collect();
+                    column.typed::<ByteArrayType>().write_batch(&values, Some(&levels), None)?;
+                },
+            }
+            column.close()?;
+        }
+        row_group.close()?;
+    }
+
+    Ok(writer.into_inner()?)
+}
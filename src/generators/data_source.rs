@@ -0,0 +1,542 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::io::Read;
+use std::time::Duration;
+
+use crate::models::{
+    AuthMethod, ColumnDefinition, CompressionFormat, CsvOptions, DataSource, DataType, ExcelOptions, FileFormat, PaginationConfig,
+    ReportSchema,
+};
+use crate::storage::storage_trait::Storage;
+
+/// Límite de páginas cuando el endpoint no informa `total_pages`
+const MAX_PAGES: usize = 10_000;
+
+/// Filas por lote al leer archivos en modo streaming
+const STREAM_BATCH_ROWS: usize = 1_000;
+
+/// Obtiene las filas de un origen de datos de reporte; el esquema (si lo hay)
+/// guía el mapeo y tipado de formatos sin tipos como CSV
+pub async fn load_rows(
+    source: &DataSource,
+    storage: &dyn Storage,
+    schema: Option<&ReportSchema>,
+) -> Result<Vec<Value>> {
+    match source {
+        DataSource::Inline { rows } => Ok(rows.clone()),
+        DataSource::Compressed { format, data } => {
+            let format = format.clone();
+            let data = data.clone();
+            tokio::task::spawn_blocking(move || {
+                let json = decompress(&format, &data)?;
+                extract_rows(serde_json::from_slice(&json).context("Invalid JSON in compressed data")?)
+            })
+            .await?
+        },
+        DataSource::R2Reference { bucket, key, format, csv, excel, .. } => {
+            let bytes = storage.get(bucket, key).await
+                .with_context(|| format!("Failed to read data source {}/{}", bucket, key))?;
+            let format = format.clone();
+            let options = FileOptions {
+                csv: csv.clone().unwrap_or_default(),
+                excel: excel.clone().unwrap_or_default(),
+            };
+            let schema = schema.cloned();
+            tokio::task::spawn_blocking(move || parse_file(bytes, &format, &options, schema.as_ref())).await?
+        },
+        DataSource::StreamingEndpoint { url, auth, pagination } => {
+            let mut rows = Vec::new();
+            fetch_pages(url, auth.as_ref(), pagination.as_ref(), |page| {
+                rows.extend(page);
+                Ok(())
+            })
+            .await?;
+            Ok(rows)
+        },
+        other => bail!("Unsupported data source: {}", source_name(other)),
+    }
+}
+
+/// Como `load_rows`, pero entrega las filas por lotes a `on_batch` a medida
+/// que se leen, sin juntarlas: páginas del endpoint y filas de archivos
+/// JSONL, CSV y Parquet. El resto de orígenes llega en un solo lote.
+/// Devuelve el total de filas
+pub async fn stream_rows<F>(
+    source: &DataSource,
+    storage: &dyn Storage,
+    schema: Option<&ReportSchema>,
+    mut on_batch: F,
+) -> Result<usize>
+where
+    F: FnMut(Vec<Value>) -> Result<()>,
+{
+    match source {
+        DataSource::StreamingEndpoint { url, auth, pagination } => {
+            fetch_pages(url, auth.as_ref(), pagination.as_ref(), on_batch).await
+        },
+        DataSource::R2Reference { bucket, key, format, csv, .. }
+            if matches!(format, FileFormat::Jsonl | FileFormat::Csv | FileFormat::Parquet) =>
+        {
+            let bytes = storage.get(bucket, key).await
+                .with_context(|| format!("Failed to read data source {}/{}", bucket, key))?;
+
+            let mut batch = Vec::with_capacity(STREAM_BATCH_ROWS);
+            let mut total = 0;
+            let mut push = |row: Value| -> Result<()> {
+                batch.push(row);
+                total += 1;
+                if batch.len() == STREAM_BATCH_ROWS {
+                    on_batch(std::mem::replace(&mut batch, Vec::with_capacity(STREAM_BATCH_ROWS)))?;
+                }
+                Ok(())
+            };
+            match format {
+                FileFormat::Jsonl => {
+                    let lines = bytes
+                        .split(|b| *b == b'\n')
+                        .filter(|line| !line.iter().all(u8::is_ascii_whitespace));
+                    for line in lines {
+                        push(serde_json::from_slice(line)?)?;
+                    }
+                },
+                FileFormat::Csv => each_csv_row(&bytes, &csv.clone().unwrap_or_default(), schema, &mut push)?,
+                _ => each_parquet_row(bytes, &mut push)?,
+            }
+
+            if !batch.is_empty() {
+                on_batch(batch)?;
+            }
+            Ok(total)
+        },
+        other => {
+            let rows = load_rows(other, storage, schema).await?;
+            let total = rows.len();
+            if total > 0 {
+                on_batch(rows)?;
+            }
+            Ok(total)
+        },
+    }
+}
+
+/// Si el payload trae `data_source`, lo resuelve y deja las filas en `rows`
+pub async fn resolve_payload_source(data: &mut Value, storage: &dyn Storage) -> Result<()> {
+    let Some(source) = data.get("data_source") else {
+        return Ok(());
+    };
+
+    let source: DataSource = serde_json::from_value(source.clone())
+        .context("Invalid data_source")?;
+    let schema: Option<ReportSchema> = match data.get("schema") {
+        Some(schema) => Some(serde_json::from_value(schema.clone()).context("Invalid schema")?),
+        None => None,
+    };
+    let rows = load_rows(&source, storage, schema.as_ref()).await?;
+
+    if let Some(object) = data.as_object_mut() {
+        object.remove("data_source");
+        object.insert("rows".to_string(), Value::Array(rows));
+    }
+
+    Ok(())
+}
+
+/// Recorre un endpoint paginado entregando cada página a `on_page` a medida
+/// que llega; sin paginación hace una única petición. Devuelve el total de filas
+pub async fn fetch_pages<F>(
+    url: &str,
+    auth: Option<&AuthMethod>,
+    pagination: Option<&PaginationConfig>,
+    mut on_page: F,
+) -> Result<usize>
+where
+    F: FnMut(Vec<Value>) -> Result<()>,
+{
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()?;
+
+    let Some(pagination) = pagination else {
+        let rows = fetch_page(&client, url, auth, &[]).await?;
+        let count = rows.len();
+        on_page(rows)?;
+        return Ok(count);
+    };
+
+    let last_page = pagination.total_pages.unwrap_or(MAX_PAGES);
+    let mut total = 0;
+
+    for page in 1..=last_page {
+        let query = [
+            (pagination.page_param.as_str(), page.to_string()),
+            (pagination.size_param.as_str(), pagination.page_size.to_string()),
+        ];
+        let rows = fetch_page(&client, url, auth, &query).await?;
+        let count = rows.len();
+
+        tracing::debug!("Fetched page {} from {} ({} rows)", page, url, count);
+
+        if count > 0 {
+            total += count;
+            on_page(rows)?;
+        }
+
+        // Página vacía o incompleta: no hay más datos
+        if count == 0 || count < pagination.page_size {
+            break;
+        }
+    }
+
+    Ok(total)
+}
+
+async fn fetch_page(
+    client: &reqwest::Client,
+    url: &str,
+    auth: Option<&AuthMethod>,
+    query: &[(&str, String)],
+) -> Result<Vec<Value>> {
+    let mut request = client.get(url).query(query);
+    if let Some(auth) = auth {
+        request = apply_auth(request, auth)?;
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("Data source endpoint returned {}", status);
+    }
+
+    let body: Value = response.json().await.context("Invalid JSON from data source endpoint")?;
+    extract_rows(body)
+}
+
+fn apply_auth(request: reqwest::RequestBuilder, auth: &AuthMethod) -> Result<reqwest::RequestBuilder> {
+    let credential = |name: &str| {
+        auth.credentials
+            .get(name)
+            .with_context(|| format!("Missing credential '{}' for {} auth", name, auth.auth_type))
+    };
+
+    match auth.auth_type.as_str() {
+        "bearer" => Ok(request.bearer_auth(credential("token")?)),
+        "basic" => Ok(request.basic_auth(credential("username")?, auth.credentials.get("password"))),
+        "api_key" => {
+            let header = auth.credentials.get("header").map(String::as_str).unwrap_or("X-API-Key");
+            Ok(request.header(header, credential("key")?))
+        },
+        other => bail!("Unsupported auth type: {}", other),
+    }
+}
+
+/// Descomprime un payload gzip, zstd o deflate. Deflate acepta el formato
+/// zlib (el de `Content-Encoding: deflate`) y también deflate crudo
+pub fn decompress(format: &CompressionFormat, data: &[u8]) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+
+    match format {
+        CompressionFormat::Gzip => {
+            flate2::read::GzDecoder::new(data).read_to_end(&mut output).context("Invalid gzip data")?;
+        },
+        CompressionFormat::Zstd => {
+            output = zstd::stream::decode_all(data).context("Invalid zstd data")?;
+        },
+        CompressionFormat::Deflate => {
+            if flate2::read::ZlibDecoder::new(data).read_to_end(&mut output).is_err() {
+                output.clear();
+                flate2::read::DeflateDecoder::new(data)
+                    .read_to_end(&mut output)
+                    .context("Invalid deflate data")?;
+            }
+        },
+    }
+
+    Ok(output)
+}
+
+/// Filas de una respuesta: un arreglo o un objeto con `data`, `rows`, `items` o `results`
+fn extract_rows(body: Value) -> Result<Vec<Value>> {
+    match body {
+        Value::Array(rows) => Ok(rows),
+        Value::Object(mut object) => ["data", "rows", "items", "results"]
+            .iter()
+            .find_map(|key| match object.remove(*key) {
+                Some(Value::Array(rows)) => Some(rows),
+                _ => None,
+            })
+            .context("Data source response has no rows array"),
+        _ => bail!("Data source response has no rows array"),
+    }
+}
+
+/// Filas de un archivo de datos sin esquema (conversión de formatos)
+pub fn read_file(bytes: Vec<u8>, format: &FileFormat, csv: CsvOptions, excel: ExcelOptions) -> Result<Vec<Value>> {
+    parse_file(bytes, format, &FileOptions { csv, excel }, None)
+}
+
+/// Opciones de lectura por formato de archivo
+struct FileOptions {
+    csv: CsvOptions,
+    excel: ExcelOptions,
+}
+
+/// Filas de un archivo almacenado según su formato
+fn parse_file(
+    bytes: Vec<u8>,
+    format: &FileFormat,
+    options: &FileOptions,
+    schema: Option<&ReportSchema>,
+) -> Result<Vec<Value>> {
+    match format {
+        FileFormat::Json => extract_rows(serde_json::from_slice(&bytes)?),
+        FileFormat::Jsonl => bytes
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+            .map(|line| Ok(serde_json::from_slice(line)?))
+            .collect(),
+        FileFormat::Parquet => parse_parquet(bytes),
+        FileFormat::Csv => parse_csv(&bytes, &options.csv, schema),
+        FileFormat::Excel => parse_excel(bytes, &options.excel, schema),
+    }
+}
+
+/// Lee un CSV; con encabezados cada celda va al campo de la columna cuyo
+/// `field` o `header` coincide, sin ellos se asignan en orden del esquema.
+/// Los valores se tipan según el `data_type` de la columna
+fn parse_csv(bytes: &[u8], options: &CsvOptions, schema: Option<&ReportSchema>) -> Result<Vec<Value>> {
+    let mut rows = Vec::new();
+    each_csv_row(bytes, options, schema, |row| {
+        rows.push(row);
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+/// Recorre las filas de un CSV (ver `parse_csv`) sin juntarlas
+fn each_csv_row(
+    bytes: &[u8],
+    options: &CsvOptions,
+    schema: Option<&ReportSchema>,
+    mut on_row: impl FnMut(Value) -> Result<()>,
+) -> Result<()> {
+    let delimiter = options.delimiter.unwrap_or(',');
+    if !delimiter.is_ascii() {
+        bail!("CSV delimiter must be an ASCII character");
+    }
+    let has_header = options.has_header.unwrap_or(true);
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter as u8)
+        .has_headers(has_header)
+        .flexible(true)
+        .from_reader(bytes);
+
+    let columns = schema.map(|s| s.columns.as_slice()).unwrap_or(&[]);
+
+    let fields = if has_header {
+        let headers: Vec<String> = reader.headers()?.iter().map(str::to_string).collect();
+        header_fields(&headers, columns)
+    } else {
+        positional_fields(columns)?
+    };
+
+    for record in reader.records() {
+        let record = record?;
+        let row: serde_json::Map<String, Value> = fields
+            .iter()
+            .zip(record.iter())
+            .map(|((field, data_type), cell)| (field.clone(), typed_cell(cell, *data_type)))
+            .collect();
+        on_row(Value::Object(row))?;
+    }
+
+    Ok(())
+}
+
+/// Campo y tipo de cada posición según los encabezados: la columna del
+/// esquema cuyo `field` o `header` coincide, o el encabezado tal cual
+fn header_fields<'a>(headers: &[String], columns: &'a [ColumnDefinition]) -> Vec<(String, Option<&'a DataType>)> {
+    headers
+        .iter()
+        .map(|header| {
+            let name = header.trim();
+            columns
+                .iter()
+                .find(|c| c.field.eq_ignore_ascii_case(name) || c.header.eq_ignore_ascii_case(name))
+                .map(|c| (c.field.clone(), Some(&c.data_type)))
+                .unwrap_or_else(|| (name.to_string(), None))
+        })
+        .collect()
+}
+
+/// Sin encabezados: las columnas del esquema en orden (sin las calculadas)
+fn positional_fields(columns: &[ColumnDefinition]) -> Result<Vec<(String, Option<&DataType>)>> {
+    if columns.is_empty() {
+        bail!("Data file without header row requires a report schema");
+    }
+    Ok(columns
+        .iter()
+        .filter(|c| c.formula.is_none())
+        .map(|c| (c.field.clone(), Some(&c.data_type)))
+        .collect())
+}
+
+/// Lee una hoja de cálculo (xlsx, xls, ods); toma la hoja y el rango pedidos
+/// y mapea las celdas a campos igual que el CSV
+fn parse_excel(bytes: Vec<u8>, options: &ExcelOptions, schema: Option<&ReportSchema>) -> Result<Vec<Value>> {
+    use calamine::Reader;
+
+    let mut workbook = calamine::open_workbook_auto_from_rs(std::io::Cursor::new(bytes))
+        .context("Invalid spreadsheet file")?;
+
+    let sheet = match &options.sheet {
+        Some(sheet) => sheet.clone(),
+        None => workbook.sheet_names().first().cloned().context("Spreadsheet has no sheets")?,
+    };
+    let mut range = workbook.worksheet_range(&sheet)
+        .with_context(|| format!("Sheet '{}' not found", sheet))?;
+
+    if let Some(selection) = &options.range {
+        let (start, end) = parse_a1_range(selection)?;
+        let end = end.or(range.end()).unwrap_or(start);
+        range = range.range(start, end);
+    }
+
+    let columns = schema.map(|s| s.columns.as_slice()).unwrap_or(&[]);
+    let mut cells = range.rows();
+
+    let fields = if options.has_header.unwrap_or(true) {
+        let Some(headers) = cells.next() else {
+            return Ok(Vec::new());
+        };
+        let headers: Vec<String> = headers.iter().map(|c| c.to_string()).collect();
+        header_fields(&headers, columns)
+    } else {
+        positional_fields(columns)?
+    };
+
+    let rows = cells
+        .filter(|row| row.iter().any(|c| !matches!(c, calamine::Data::Empty)))
+        .map(|row| {
+            let object: serde_json::Map<String, Value> = fields
+                .iter()
+                .zip(row.iter())
+                .filter(|((field, _), _)| !field.is_empty())
+                .map(|((field, data_type), cell)| (field.clone(), excel_cell(cell, *data_type)))
+                .collect();
+            Value::Object(object)
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+fn excel_cell(cell: &calamine::Data, data_type: Option<&DataType>) -> Value {
+    use calamine::{Data, DataType as _};
+
+    match cell {
+        Data::Empty | Data::Error(_) => Value::Null,
+        Data::Bool(b) => Value::Bool(*b),
+        Data::Int(i) => Value::from(*i),
+        Data::Float(f) => serde_json::Number::from_f64(*f).map(Value::Number).unwrap_or(Value::Null),
+        Data::String(s) => typed_cell(s, data_type),
+        Data::DateTime(_) | Data::DateTimeIso(_) => match (cell.as_datetime(), data_type) {
+            (Some(dt), Some(DataType::Date)) => Value::String(dt.date().to_string()),
+            (Some(dt), _) => Value::String(dt.format("%Y-%m-%dT%H:%M:%S").to_string()),
+            (None, _) => Value::String(cell.to_string()),
+        },
+        Data::DurationIso(s) => Value::String(s.clone()),
+    }
+}
+
+/// Celda (fila, columna) desde 0
+type Cell = (u32, u32);
+
+/// `B2:F100` → ((1, 1), Some((99, 5))); `B2` → ((1, 1), None). Filas y columnas desde 0
+fn parse_a1_range(range: &str) -> Result<(Cell, Option<Cell>)> {
+    let (start, end) = match range.split_once(':') {
+        Some((start, end)) => (start, Some(end)),
+        None => (range, None),
+    };
+    let start = parse_a1_cell(start)?;
+    let end = end.map(parse_a1_cell).transpose()?;
+
+    if let Some(end) = end {
+        if end.0 < start.0 || end.1 < start.1 {
+            bail!("Invalid cell range: {}", range);
+        }
+    }
+
+    Ok((start, end))
+}
+
+fn parse_a1_cell(cell: &str) -> Result<(u32, u32)> {
+    let cell = cell.trim().to_ascii_uppercase();
+    let split = cell.find(|c: char| c.is_ascii_digit()).unwrap_or(cell.len());
+    let (letters, digits) = cell.split_at(split);
+
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_uppercase()) {
+        bail!("Invalid cell reference: {}", cell);
+    }
+    let row: u32 = digits.parse().ok().filter(|r| *r > 0)
+        .with_context(|| format!("Invalid cell reference: {}", cell))?;
+    let col = letters.chars().fold(0u32, |acc, c| acc * 26 + (c as u32 - 'A' as u32 + 1));
+
+    Ok((row - 1, col - 1))
+}
+
+fn typed_cell(cell: &str, data_type: Option<&DataType>) -> Value {
+    let cell = cell.trim();
+    if cell.is_empty() {
+        return Value::Null;
+    }
+
+    match data_type {
+        Some(DataType::Number | DataType::Currency | DataType::Percentage) => cell
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .unwrap_or_else(|| Value::String(cell.to_string())),
+        Some(DataType::Boolean) => match cell.to_lowercase().as_str() {
+            "true" | "1" | "si" | "sí" | "yes" => Value::Bool(true),
+            "false" | "0" | "no" => Value::Bool(false),
+            _ => Value::String(cell.to_string()),
+        },
+        _ => Value::String(cell.to_string()),
+    }
+}
+
+/// Lee un Parquet fila a fila; cada fila queda como objeto JSON por columna
+fn parse_parquet(bytes: Vec<u8>) -> Result<Vec<Value>> {
+    let mut rows = Vec::new();
+    each_parquet_row(bytes, |row| {
+        rows.push(row);
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+/// Recorre las filas de un Parquet sin juntarlas
+fn each_parquet_row(bytes: Vec<u8>, mut on_row: impl FnMut(Value) -> Result<()>) -> Result<()> {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let reader = SerializedFileReader::new(bytes::Bytes::from(bytes))
+        .context("Invalid parquet file")?;
+    for row in reader.get_row_iter(None)? {
+        on_row(row?.to_json_value())?;
+    }
+
+    Ok(())
+}
+
+/// Tipo del origen tal como va en `type`
+pub fn source_name(source: &DataSource) -> &'static str {
+    match source {
+        DataSource::Inline { .. } => "inline",
+        DataSource::Compressed { .. } => "compressed",
+        DataSource::R2Reference { .. } => "r2_reference",
+        DataSource::StreamingEndpoint { .. } => "streaming_endpoint",
+        DataSource::DatabaseQuery { .. } => "database_query",
+    }
+}
@@ -0,0 +1,391 @@
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use rust_xlsxwriter::{Color, Format, FormatBorder, Workbook};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::report_processor::numeric_value;
+
+/// Errores de filas que se detallan como máximo en el mensaje
+const MAX_REPORTED_ERRORS: usize = 50;
+
+/// Formatos de envío de la DGII (Norma 07-2018)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DgiiFormat {
+    /// Compras de bienes y servicios
+    #[serde(rename = "606")]
+    F606,
+    /// Ventas de bienes y servicios
+    #[serde(rename = "607")]
+    F607,
+}
+
+impl DgiiFormat {
+    pub fn code(&self) -> &'static str {
+        match self {
+            DgiiFormat::F606 => "606",
+            DgiiFormat::F607 => "607",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            DgiiFormat::F606 => "Formato de Envío de Compras de Bienes y Servicios",
+            DgiiFormat::F607 => "Formato de Envío de Ventas de Bienes y Servicios",
+        }
+    }
+
+    /// Columnas en el orden exacto del formato
+    pub fn columns(&self) -> &'static [DgiiColumn] {
+        match self {
+            DgiiFormat::F606 => COLUMNS_606,
+            DgiiFormat::F607 => COLUMNS_607,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    /// RNC (9 dígitos) o cédula (11)
+    TaxId,
+    /// 1 = RNC, 2 = cédula; si falta se deduce del largo del RNC/cédula
+    TaxIdType,
+    /// NCF (11 caracteres, serie B) o e-CF (13, serie E)
+    Ncf,
+    /// Código de tabla DGII de 2 dígitos, del 01 al máximo indicado
+    Code(u8),
+    /// Fecha AAAAMMDD (acepta también AAAA-MM-DD)
+    Date,
+    /// Monto con 2 decimales
+    Amount,
+}
+
+/// Columna de un formato DGII; `key` es el campo de la fila de datos
+#[derive(Debug, Clone, Copy)]
+pub struct DgiiColumn {
+    pub key: &'static str,
+    pub header: &'static str,
+    pub kind: ColumnKind,
+    pub required: bool,
+}
+
+const fn column(key: &'static str, header: &'static str, kind: ColumnKind, required: bool) -> DgiiColumn {
+    DgiiColumn { key, header, kind, required }
+}
+
+const COLUMNS_606: &[DgiiColumn] = &[
+    column("rnc_cedula", "RNC o Cédula", ColumnKind::TaxId, true),
+    column("tipo_id", "Tipo Id", ColumnKind::TaxIdType, false),
+    column("tipo_bienes_servicios", "Tipo Bienes y Servicios Comprados", ColumnKind::Code(11), true),
+    column("ncf", "NCF", ColumnKind::Ncf, true),
+    column("ncf_modificado", "NCF o Documento Modificado", ColumnKind::Ncf, false),
+    column("fecha_comprobante", "Fecha Comprobante", ColumnKind::Date, true),
+    column("fecha_pago", "Fecha Pago", ColumnKind::Date, false),
+    column("monto_servicios", "Monto Facturado en Servicios", ColumnKind::Amount, false),
+    column("monto_bienes", "Monto Facturado en Bienes", ColumnKind::Amount, false),
+    column("total_monto_facturado", "Total Monto Facturado", ColumnKind::Amount, true),
+    column("itbis_facturado", "ITBIS Facturado", ColumnKind::Amount, false),
+    column("itbis_retenido", "ITBIS Retenido", ColumnKind::Amount, false),
+    column("itbis_proporcionalidad", "ITBIS sujeto a Proporcionalidad (Art. 349)", ColumnKind::Amount, false),
+    column("itbis_costo", "ITBIS llevado al Costo", ColumnKind::Amount, false),
+    column("itbis_adelantar", "ITBIS por Adelantar", ColumnKind::Amount, false),
+    column("itbis_percibido", "ITBIS percibido en compras", ColumnKind::Amount, false),
+    column("tipo_retencion_isr", "Tipo de Retención en ISR", ColumnKind::Code(8), false),
+    column("monto_retencion_renta", "Monto Retención Renta", ColumnKind::Amount, false),
+    column("isr_percibido", "ISR Percibido en compras", ColumnKind::Amount, false),
+    column("impuesto_selectivo", "Impuesto Selectivo al Consumo", ColumnKind::Amount, false),
+    column("otros_impuestos", "Otros Impuestos/Tasas", ColumnKind::Amount, false),
+    column("propina_legal", "Monto Propina Legal", ColumnKind::Amount, false),
+    column("forma_pago", "Forma de Pago", ColumnKind::Code(7), true),
+];
+
+const COLUMNS_607: &[DgiiColumn] = &[
+    column("rnc_cedula", "RNC/Cédula o Pasaporte", ColumnKind::TaxId, false),
+    column("tipo_id", "Tipo Identificación", ColumnKind::TaxIdType, false),
+    column("ncf", "Número Comprobante Fiscal", ColumnKind::Ncf, true),
+    column("ncf_modificado", "Número Comprobante Fiscal Modificado", ColumnKind::Ncf, false),
+    column("tipo_ingreso", "Tipo de Ingreso", ColumnKind::Code(6), true),
+    column("fecha_comprobante", "Fecha Comprobante", ColumnKind::Date, true),
+    column("fecha_retencion", "Fecha de Retención", ColumnKind::Date, false),
+    column("monto_facturado", "Monto Facturado", ColumnKind::Amount, true),
+    column("itbis_facturado", "ITBIS Facturado", ColumnKind::Amount, false),
+    column("itbis_retenido_terceros", "ITBIS Retenido por Terceros", ColumnKind::Amount, false),
+    column("itbis_percibido", "ITBIS Percibido", ColumnKind::Amount, false),
+    column("retencion_renta_terceros", "Retención Renta por Terceros", ColumnKind::Amount, false),
+    column("isr_percibido", "ISR Percibido", ColumnKind::Amount, false),
+    column("impuesto_selectivo", "Impuesto Selectivo al Consumo", ColumnKind::Amount, false),
+    column("otros_impuestos", "Otros Impuestos/Tasas", ColumnKind::Amount, false),
+    column("propina_legal", "Monto Propina Legal", ColumnKind::Amount, false),
+    column("efectivo", "Efectivo", ColumnKind::Amount, false),
+    column("cheque_transferencia", "Cheque/ Transferencia/ Depósito", ColumnKind::Amount, false),
+    column("tarjeta", "Tarjeta Débito/Crédito", ColumnKind::Amount, false),
+    column("venta_credito", "Venta a Crédito", ColumnKind::Amount, false),
+    column("bonos_regalo", "Bonos o Certificados de Regalo", ColumnKind::Amount, false),
+    column("permuta", "Permuta", ColumnKind::Amount, false),
+    column("otras_formas_venta", "Otras Formas de Ventas", ColumnKind::Amount, false),
+];
+
+/// Datos del documento: RNC del informante, período y filas (inline o
+/// resueltas desde `data_source`), con los campos `key` de cada columna
+#[derive(Debug, Clone, Deserialize)]
+pub struct DgiiReportData {
+    pub format: DgiiFormat,
+    pub rnc: String,
+    /// Período AAAAMM
+    pub period: String,
+    #[serde(default)]
+    pub rows: Vec<Value>,
+}
+
+/// Celda ya normalizada al formato de la DGII
+#[derive(Debug, Clone, PartialEq)]
+pub enum DgiiCell {
+    Text(String),
+    Amount(f64),
+    Empty,
+}
+
+impl DgiiCell {
+    fn txt(&self) -> String {
+        match self {
+            DgiiCell::Text(text) => text.clone(),
+            DgiiCell::Amount(amount) => format!("{:.2}", amount),
+            DgiiCell::Empty => String::new(),
+        }
+    }
+}
+
+/// Reporte validado, listo para escribir en TXT o Excel
+#[derive(Debug, Clone)]
+pub struct DgiiReport {
+    pub format: DgiiFormat,
+    pub rnc: String,
+    pub period: String,
+    pub rows: Vec<Vec<DgiiCell>>,
+}
+
+impl DgiiReport {
+    /// Valida y normaliza los datos; falla con el detalle de las filas inválidas
+    pub fn from_data(data: &Value) -> Result<Self> {
+        let data: DgiiReportData = serde_json::from_value(data.clone())
+            .context("Invalid DGII report data (expected format, rnc, period and rows)")?;
+
+        let rnc = normalize_tax_id(&Value::String(data.rnc.clone()))
+            .map_err(|e| anyhow::anyhow!("rnc: {}", e))?;
+        if data.period.len() != 6 || NaiveDate::parse_from_str(&format!("{}01", data.period), "%Y%m%d").is_err() {
+            bail!("period: '{}' is not a valid AAAAMM period", data.period);
+        }
+
+        let mut rows = Vec::with_capacity(data.rows.len());
+        let mut errors = Vec::new();
+        for (index, row) in data.rows.iter().enumerate() {
+            match normalize_row(data.format, row) {
+                Ok(cells) => rows.push(cells),
+                Err(e) => errors.push(format!("row {}: {}", index + 1, e)),
+            }
+        }
+
+        if !errors.is_empty() {
+            let total = errors.len();
+            errors.truncate(MAX_REPORTED_ERRORS);
+            bail!("{} invalid rows for format {}: {}", total, data.format.code(), errors.join("; "));
+        }
+
+        Ok(DgiiReport { format: data.format, rnc, period: data.period, rows })
+    }
+
+    /// Suma de una columna de montos
+    pub fn total(&self, key: &str) -> f64 {
+        let Some(index) = self.format.columns().iter().position(|c| c.key == key) else {
+            return 0.0;
+        };
+        self.rows
+            .iter()
+            .filter_map(|row| match row[index] {
+                DgiiCell::Amount(amount) => Some(amount),
+                _ => None,
+            })
+            .fold(0.0, |total, amount| total + amount)
+    }
+}
+
+/// Normaliza una fila; junta todos los errores de sus campos
+fn normalize_row(format: DgiiFormat, row: &Value) -> Result<Vec<DgiiCell>> {
+    let Some(fields) = row.as_object() else {
+        bail!("expected an object");
+    };
+
+    let mut cells = Vec::with_capacity(format.columns().len());
+    let mut errors = Vec::new();
+    for column in format.columns() {
+        let value = fields.get(column.key).filter(|v| !is_blank(v));
+        let cell = match (value, column.kind) {
+            (None, ColumnKind::TaxIdType) => match cells.first() {
+                Some(DgiiCell::Text(tax_id)) if tax_id.len() == 9 => Ok(DgiiCell::Text("1".to_string())),
+                Some(DgiiCell::Text(tax_id)) if tax_id.len() == 11 => Ok(DgiiCell::Text("2".to_string())),
+                _ => Ok(DgiiCell::Empty),
+            },
+            (None, _) if column.required => Err(anyhow::anyhow!("required")),
+            (None, _) => Ok(DgiiCell::Empty),
+            (Some(value), kind) => normalize_cell(value, kind),
+        };
+
+        match cell {
+            Ok(cell) => cells.push(cell),
+            Err(e) => {
+                errors.push(format!("{}: {}", column.key, e));
+                cells.push(DgiiCell::Empty);
+            },
+        }
+    }
+
+    if format == DgiiFormat::F606 {
+        let amount = |key: &str| match format.columns().iter().position(|c| c.key == key).map(|i| &cells[i]) {
+            Some(DgiiCell::Amount(amount)) => *amount,
+            _ => 0.0,
+        };
+        let billed = amount("monto_servicios") + amount("monto_bienes");
+        if errors.is_empty() && (billed - amount("total_monto_facturado")).abs() > 0.01 {
+            errors.push(format!(
+                "total_monto_facturado: {:.2} differs from monto_servicios + monto_bienes ({:.2})",
+                amount("total_monto_facturado"), billed
+            ));
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!("{}", errors.join(", "));
+    }
+    Ok(cells)
+}
+
+fn is_blank(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.trim().is_empty(),
+        _ => false,
+    }
+}
+
+fn normalize_cell(value: &Value, kind: ColumnKind) -> Result<DgiiCell> {
+    match kind {
+        ColumnKind::TaxId => normalize_tax_id(value).map(DgiiCell::Text),
+        ColumnKind::TaxIdType => match text(value).as_str() {
+            id @ ("1" | "2" | "3") => Ok(DgiiCell::Text(id.to_string())),
+            other => bail!("'{}' is not 1 (RNC), 2 (cédula) or 3 (pasaporte)", other),
+        },
+        ColumnKind::Ncf => {
+            let ncf = text(value).to_uppercase();
+            let valid = match ncf.chars().next() {
+                Some('B') => ncf.len() == 11,
+                Some('E') => ncf.len() == 13,
+                _ => false,
+            };
+            if !valid || !ncf.chars().skip(1).all(|c| c.is_ascii_digit()) {
+                bail!("'{}' is not a valid NCF (B + 10 digits) or e-CF (E + 12 digits)", ncf);
+            }
+            Ok(DgiiCell::Text(ncf))
+        },
+        ColumnKind::Code(max) => {
+            let code: u8 = text(value).parse().ok().filter(|c| (1..=max).contains(c))
+                .with_context(|| format!("'{}' is not a code from 01 to {:02}", text(value), max))?;
+            Ok(DgiiCell::Text(format!("{:02}", code)))
+        },
+        ColumnKind::Date => {
+            let raw = text(value);
+            let date = NaiveDate::parse_from_str(&raw, "%Y-%m-%d")
+                .or_else(|_| NaiveDate::parse_from_str(&raw, "%Y%m%d"))
+                .with_context(|| format!("'{}' is not a date (AAAAMMDD or AAAA-MM-DD)", raw))?;
+            Ok(DgiiCell::Text(date.format("%Y%m%d").to_string()))
+        },
+        ColumnKind::Amount => {
+            let amount = numeric_value(value).with_context(|| format!("'{}' is not an amount", text(value)))?;
+            if amount < 0.0 {
+                bail!("amounts cannot be negative ({})", amount);
+            }
+            Ok(DgiiCell::Amount((amount * 100.0).round() / 100.0))
+        },
+    }
+}
+
+fn normalize_tax_id(value: &Value) -> Result<String> {
+    let tax_id: String = text(value).chars().filter(|c| *c != '-').collect();
+    if !(tax_id.len() == 9 || tax_id.len() == 11) || !tax_id.chars().all(|c| c.is_ascii_digit()) {
+        bail!("'{}' is not an RNC (9 digits) or cédula (11 digits)", text(value));
+    }
+    Ok(tax_id)
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.trim().to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Generador de los formatos 606/607 en TXT (para la Oficina Virtual) y Excel
+#[derive(Default)]
+pub struct DgiiGenerator;
+
+impl DgiiGenerator {
+    pub fn new() -> Self {
+        DgiiGenerator
+    }
+
+    /// TXT delimitado por `|`: encabezado `formato|RNC|período|registros` y
+    /// una línea por registro con las columnas en el orden del formato
+    pub async fn generate_txt(&self, data: Value) -> Result<Vec<u8>> {
+        tokio::task::spawn_blocking(move || Ok(Self::txt(&DgiiReport::from_data(&data)?))).await?
+    }
+
+    /// Excel con los encabezados del formato; RNC, NCF y fechas como texto
+    /// para no perder ceros a la izquierda
+    pub async fn generate_excel(&self, data: Value) -> Result<Vec<u8>> {
+        tokio::task::spawn_blocking(move || Self::excel(&DgiiReport::from_data(&data)?)).await?
+    }
+
+    fn txt(report: &DgiiReport) -> Vec<u8> {
+        let mut output = format!("{}|{}|{}|{}\r\n", report.format.code(), report.rnc, report.period, report.rows.len());
+        for row in &report.rows {
+            output.push_str(&row.iter().map(DgiiCell::txt).collect::<Vec<_>>().join("|"));
+            output.push_str("\r\n");
+        }
+        output.into_bytes()
+    }
+
+    fn excel(report: &DgiiReport) -> Result<Vec<u8>> {
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(report.format.code())?;
+
+        let header_format = Format::new()
+            .set_bold()
+            .set_text_wrap()
+            .set_background_color(Color::RGB(0x1F3864))
+            .set_font_color(Color::White)
+            .set_border(FormatBorder::Thin);
+        let text_format = Format::new().set_num_format("@").set_border(FormatBorder::Thin);
+        let amount_format = Format::new().set_num_format("0.00").set_border(FormatBorder::Thin);
+
+        worksheet.write_string(0, 0, format!("{} - RNC {} - Período {}", report.format.title(), report.rnc, report.period))?;
+        for (col, column) in report.format.columns().iter().enumerate() {
+            worksheet.write_string_with_format(1, col as u16, column.header, &header_format)?;
+            worksheet.set_column_width(col as u16, if column.kind == ColumnKind::Amount { 16 } else { 18 })?;
+        }
+
+        for (index, row) in report.rows.iter().enumerate() {
+            let row_num = index as u32 + 2;
+            for (col, cell) in row.iter().enumerate() {
+                let col = col as u16;
+                match cell {
+                    DgiiCell::Text(text) => worksheet.write_string_with_format(row_num, col, text, &text_format)?,
+                    DgiiCell::Amount(amount) => worksheet.write_number_with_format(row_num, col, *amount, &amount_format)?,
+                    DgiiCell::Empty => worksheet.write_blank(row_num, col, &text_format)?,
+                };
+            }
+        }
+        worksheet.set_freeze_panes(2, 0)?;
+
+        Ok(workbook.save_to_buffer()?)
+    }
+}
@@ -0,0 +1,326 @@
+use anyhow::{bail, Context, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde_json::Value;
+
+use crate::templates::template_models::{FiscalInfo, InvoiceData, InvoiceItem};
+
+/// Tipos de e-CF soportados
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcfType {
+    /// 31: Factura de Crédito Fiscal Electrónica
+    CreditoFiscal,
+    /// 32: Factura de Consumo Electrónica
+    Consumo,
+}
+
+impl EcfType {
+    /// Tipo según el e-NCF (`E31...`, `E32...`); None para otros tipos
+    pub fn from_encf(encf: &str) -> Option<Self> {
+        match encf.get(..3).map(str::to_uppercase).as_deref() {
+            Some("E31") => Some(EcfType::CreditoFiscal),
+            Some("E32") => Some(EcfType::Consumo),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            EcfType::CreditoFiscal => "31",
+            EcfType::Consumo => "32",
+        }
+    }
+}
+
+/// Tipo de e-CF de los datos de una factura, si traen `fiscalInfo` con un
+/// e-NCF 31/32 (las facturas sin él no llevan XML)
+pub fn ecf_type_of(data: &Value) -> Option<EcfType> {
+    data.pointer("/fiscalInfo/eNcf")
+        .and_then(Value::as_str)
+        .and_then(EcfType::from_encf)
+}
+
+/// Indicador de facturación del ítem según su tasa de ITBIS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TaxBracket {
+    /// ITBIS1 (18%)
+    Itbis1,
+    /// ITBIS2 (16%)
+    Itbis2,
+    Exento,
+}
+
+impl TaxBracket {
+    /// Acepta la tasa como fracción (0.18) o porcentaje (18)
+    fn of(item: &InvoiceItem) -> Result<Self> {
+        let rate = item.tax_rate.unwrap_or(0.0);
+        let percent = if rate > 1.0 { rate } else { rate * 100.0 };
+        match percent.round() as i64 {
+            0 => Ok(TaxBracket::Exento),
+            16 => Ok(TaxBracket::Itbis2),
+            18 => Ok(TaxBracket::Itbis1),
+            _ => bail!("item '{}': ITBIS rate {} is not 18%, 16% or exempt", item.description, rate),
+        }
+    }
+
+    fn indicator(&self) -> &'static str {
+        match self {
+            TaxBracket::Itbis1 => "1",
+            TaxBracket::Itbis2 => "2",
+            TaxBracket::Exento => "4",
+        }
+    }
+}
+
+/// Generador del XML e-CF (tipos 31 y 32) de la DGII a partir de los datos
+/// de la factura fiscal. El XML sale sin firmar: la firma XMLDSig la agrega
+/// el emisor o su proveedor de facturación electrónica al enviarlo
+#[derive(Default)]
+pub struct XmlInvoiceGenerator;
+
+impl XmlInvoiceGenerator {
+    pub fn new() -> Self {
+        XmlInvoiceGenerator
+    }
+
+    /// XML e-CF de los datos de la factura (mismo `data` que la plantilla fiscal)
+    pub async fn generate(&self, data: Value) -> Result<Vec<u8>> {
+        tokio::task::spawn_blocking(move || {
+            let invoice: InvoiceData = serde_json::from_value(data)
+                .context("Invalid fiscal invoice data for e-CF XML")?;
+            Self::xml(&invoice)
+        })
+        .await?
+    }
+
+    fn xml(invoice: &InvoiceData) -> Result<Vec<u8>> {
+        let Some(fiscal) = &invoice.fiscal_info else {
+            bail!("e-CF XML requires fiscalInfo");
+        };
+        let encf = fiscal.e_ncf.trim().to_uppercase();
+        let ecf_type = EcfType::from_encf(&encf)
+            .with_context(|| format!("'{}' is not an e-NCF of type 31 or 32", encf))?;
+        if encf.len() != 13 || !encf.chars().skip(1).all(|c| c.is_ascii_digit()) {
+            bail!("'{}' is not a valid e-NCF (E + 12 digits)", encf);
+        }
+
+        let brackets = invoice.items.iter().map(TaxBracket::of).collect::<Result<Vec<_>>>()?;
+        let amount_in = |bracket: TaxBracket| -> f64 {
+            invoice.items.iter().zip(&brackets)
+                .filter(|(_, b)| **b == bracket)
+                .map(|(item, _)| item.subtotal)
+                .sum()
+        };
+        let tax_in = |bracket: TaxBracket| -> f64 {
+            invoice.items.iter().zip(&brackets)
+                .filter(|(_, b)| **b == bracket)
+                .map(|(item, _)| item.tax_amount.unwrap_or(item.total - item.subtotal))
+                .sum()
+        };
+
+        let mut xml = XmlWriter::new();
+        xml.open("ECF");
+        xml.open("Encabezado");
+        xml.leaf("Version", "1.0");
+
+        xml.open("IdDoc");
+        xml.leaf("TipoeCF", ecf_type.code());
+        xml.leaf("eNCF", &encf);
+        if let Some(expiration) = &fiscal.expiration_date {
+            xml.leaf("FechaVencimientoSecuencia", &dgii_date(expiration)?);
+        }
+        // Montos de los ítems sin ITBIS incluido
+        xml.leaf("IndicadorMontoGravado", "0");
+        xml.leaf("TipoIngresos", "01");
+        let paid = invoice.payment_info.as_ref().is_some_and(|p| p.paid);
+        xml.leaf("TipoPago", if paid { "1" } else { "2" });
+        if !paid {
+            xml.leaf("FechaLimitePago", &dgii_date(&invoice.due_date)?);
+        }
+        xml.close("IdDoc");
+
+        let company = &invoice.company_info;
+        xml.open("Emisor");
+        xml.leaf("RNCEmisor", &tax_id(&company.tax_id).context("RNCEmisor")?);
+        xml.leaf("RazonSocialEmisor", company.legal_name.as_deref().unwrap_or(&company.name));
+        xml.leaf("NombreComercial", &company.name);
+        xml.leaf("DireccionEmisor", &format!("{}, {}", company.address.street, company.address.city));
+        if let Some(phone) = &company.phone {
+            xml.open("TablaTelefonoEmisor");
+            xml.leaf("TelefonoEmisor", phone);
+            xml.close("TablaTelefonoEmisor");
+        }
+        if let Some(email) = &company.email {
+            xml.leaf("CorreoEmisor", email);
+        }
+        xml.leaf("FechaEmision", &dgii_date(&invoice.issue_date)?);
+        xml.close("Emisor");
+
+        // En consumo (32) el comprador es opcional
+        let client = &invoice.client_info;
+        let client_id = match ecf_type {
+            EcfType::CreditoFiscal => Some(tax_id(&client.tax_id).context("RNCComprador")?),
+            EcfType::Consumo => tax_id(&client.tax_id).ok(),
+        };
+        if let Some(client_id) = client_id {
+            xml.open("Comprador");
+            xml.leaf("RNCComprador", &client_id);
+            xml.leaf("RazonSocialComprador", client.legal_name.as_deref().unwrap_or(&client.name));
+            if let Some(email) = &client.email {
+                xml.leaf("CorreoComprador", email);
+            }
+            xml.close("Comprador");
+        }
+
+        let (taxed_1, taxed_2) = (amount_in(TaxBracket::Itbis1), amount_in(TaxBracket::Itbis2));
+        let (itbis_1, itbis_2) = (tax_in(TaxBracket::Itbis1), tax_in(TaxBracket::Itbis2));
+        let exempt = amount_in(TaxBracket::Exento);
+        xml.open("Totales");
+        if taxed_1 + taxed_2 > 0.0 {
+            xml.leaf("MontoGravadoTotal", &amount(taxed_1 + taxed_2));
+        }
+        if taxed_1 > 0.0 {
+            xml.leaf("MontoGravadoI1", &amount(taxed_1));
+        }
+        if taxed_2 > 0.0 {
+            xml.leaf("MontoGravadoI2", &amount(taxed_2));
+        }
+        if exempt > 0.0 {
+            xml.leaf("MontoExento", &amount(exempt));
+        }
+        if taxed_1 > 0.0 {
+            xml.leaf("ITBIS1", "18");
+        }
+        if taxed_2 > 0.0 {
+            xml.leaf("ITBIS2", "16");
+        }
+        if taxed_1 + taxed_2 > 0.0 {
+            xml.leaf("TotalITBIS", &amount(itbis_1 + itbis_2));
+        }
+        if taxed_1 > 0.0 {
+            xml.leaf("TotalITBIS1", &amount(itbis_1));
+        }
+        if taxed_2 > 0.0 {
+            xml.leaf("TotalITBIS2", &amount(itbis_2));
+        }
+        xml.leaf("MontoTotal", &amount(invoice.totals.total));
+        xml.close("Totales");
+        xml.close("Encabezado");
+
+        xml.open("DetallesItems");
+        for (index, (item, bracket)) in invoice.items.iter().zip(&brackets).enumerate() {
+            xml.open("Item");
+            xml.leaf("NumeroLinea", &(index + 1).to_string());
+            xml.leaf("IndicadorFacturacion", bracket.indicator());
+            xml.leaf("NombreItem", &item.description);
+            xml.leaf("IndicadorBienoServicio", "1");
+            xml.leaf("CantidadItem", &quantity(item.quantity));
+            xml.leaf("PrecioUnitarioItem", &amount(item.unit_price));
+            if let Some(discount) = item.discount.filter(|d| *d > 0.0) {
+                xml.leaf("DescuentoMonto", &amount(discount));
+            }
+            xml.leaf("MontoItem", &amount(item.subtotal));
+            xml.close("Item");
+        }
+        xml.close("DetallesItems");
+
+        xml.leaf("FechaHoraFirma", &signature_time(fiscal)?);
+        xml.close("ECF");
+
+        Ok(xml.finish())
+    }
+}
+
+/// Escritor mínimo de XML con sangría; escapa el texto de las hojas
+struct XmlWriter {
+    output: String,
+    depth: usize,
+}
+
+impl XmlWriter {
+    fn new() -> Self {
+        XmlWriter {
+            output: "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".to_string(),
+            depth: 0,
+        }
+    }
+
+    fn open(&mut self, tag: &str) {
+        self.indent();
+        self.output.push_str(&format!("<{}>\n", tag));
+        self.depth += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.indent();
+        self.output.push_str(&format!("</{}>\n", tag));
+    }
+
+    fn leaf(&mut self, tag: &str, value: &str) {
+        self.indent();
+        self.output.push_str(&format!("<{0}>{1}</{0}>\n", tag, escape_xml(value.trim())));
+    }
+
+    fn indent(&mut self) {
+        self.output.push_str(&"  ".repeat(self.depth));
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.output.into_bytes()
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// RNC (9 dígitos) o cédula (11) sin guiones
+fn tax_id(raw: &str) -> Result<String> {
+    let tax_id: String = raw.trim().chars().filter(|c| *c != '-').collect();
+    if !(tax_id.len() == 9 || tax_id.len() == 11) || !tax_id.chars().all(|c| c.is_ascii_digit()) {
+        bail!("'{}' is not an RNC (9 digits) or cédula (11 digits)", raw);
+    }
+    Ok(tax_id)
+}
+
+fn parse_date(raw: &str) -> Option<NaiveDate> {
+    let raw = raw.trim();
+    ["%Y-%m-%d", "%d-%m-%Y", "%d/%m/%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(raw, format).ok())
+}
+
+/// Fecha en el formato del e-CF (`dd-MM-AAAA`)
+fn dgii_date(raw: &str) -> Result<String> {
+    let date = parse_date(raw).with_context(|| format!("'{}' is not a date (AAAA-MM-DD)", raw))?;
+    Ok(date.format("%d-%m-%Y").to_string())
+}
+
+/// Fecha y hora de la firma (`dd-MM-AAAA HH:mm:ss`); una fecha sola se toma a medianoche
+fn signature_time(fiscal: &FiscalInfo) -> Result<String> {
+    let raw = fiscal.signature_date.trim();
+    let time = chrono::DateTime::parse_from_rfc3339(raw)
+        .map(|t| t.naive_local())
+        .or_else(|_| NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| NaiveDateTime::parse_from_str(raw, "%d-%m-%Y %H:%M:%S"))
+        .ok()
+        .or_else(|| parse_date(raw).and_then(|d| d.and_hms_opt(0, 0, 0)))
+        .with_context(|| format!("signatureDate '{}' is not a date or date-time", raw))?;
+    Ok(time.format("%d-%m-%Y %H:%M:%S").to_string())
+}
+
+fn amount(value: f64) -> String {
+    format!("{:.2}", value)
+}
+
+fn quantity(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
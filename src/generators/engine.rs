@@ -0,0 +1,226 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::generators::{CsvGenerator, DgiiGenerator, ExcelGenerator, PdfGenerator};
+use crate::models::{CompanionFormat, DocumentType, OutputFormat, RenderOptions};
+use crate::templates::{CompileDiagnostic, RenderTimings, TemplateManager};
+
+pub const PDF_CONTENT_TYPE: &str = "application/pdf";
+
+pub const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+pub const TXT_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+
+pub const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+/// Contexto de una generación: de quién es, con qué plantilla y opciones
+#[derive(Debug, Clone)]
+pub struct RenderContext {
+    pub tenant_id: i64,
+    pub template_id: String,
+    pub document_type: DocumentType,
+    pub options: RenderOptions,
+}
+
+/// Resultado de un engine, antes del post-procesado y la subida
+pub struct Artifact {
+    pub bytes: Vec<u8>,
+    pub extension: &'static str,
+    pub content_type: &'static str,
+    /// Miniatura PNG de la primera página (engines de PDF)
+    pub preview_png: Option<Vec<u8>>,
+    /// Advertencias del compilador (engines de PDF)
+    pub warnings: Vec<CompileDiagnostic>,
+    /// Archivo que acompaña al documento (CSV/TSV de un libro Excel)
+    pub companion: Option<(CompanionFormat, Vec<u8>)>,
+    /// Tiempos por etapa si el engine los distingue; si no, todo cuenta como render
+    pub timings: Option<RenderTimings>,
+}
+
+impl Artifact {
+    pub fn new(bytes: Vec<u8>, extension: &'static str, content_type: &'static str) -> Self {
+        Artifact {
+            bytes,
+            extension,
+            content_type,
+            preview_png: None,
+            warnings: Vec::new(),
+            companion: None,
+            timings: None,
+        }
+    }
+
+    pub fn is_pdf(&self) -> bool {
+        self.content_type == PDF_CONTENT_TYPE
+    }
+}
+
+/// Motor de generación de un formato de salida (Typst → PDF, Excel, CSV, ...).
+/// Los handlers y el worker solo ven este trait: un engine nuevo se agrega
+/// registrándolo en `EngineRegistry`
+#[async_trait]
+pub trait DocumentEngine: Send + Sync {
+    /// Nombre para logs y errores
+    fn name(&self) -> &'static str;
+
+    /// Si genera documentos de este tipo (por defecto todos)
+    fn supports(&self, _document_type: &DocumentType) -> bool {
+        true
+    }
+
+    async fn render(&self, data: Value, context: &RenderContext) -> Result<Artifact>;
+}
+
+/// Engines por formato de salida; para un formato gana el primero que
+/// soporte el tipo de documento
+pub struct EngineRegistry {
+    engines: Vec<(OutputFormat, Arc<dyn DocumentEngine>)>,
+}
+
+impl EngineRegistry {
+    pub fn new() -> Self {
+        EngineRegistry { engines: Vec::new() }
+    }
+
+    /// Engines incorporados: Typst para PDF, DGII para `fiscal_report`
+    /// (txt y Excel), Excel para reportes y CSV
+    pub fn with_defaults(template_manager: Arc<TemplateManager>) -> Self {
+        let mut registry = Self::new();
+        registry.register(OutputFormat::Pdf, Arc::new(TypstEngine::new(template_manager)));
+        registry.register(OutputFormat::Txt, Arc::new(DgiiTxtEngine));
+        registry.register(OutputFormat::Excel, Arc::new(DgiiExcelEngine));
+        registry.register(OutputFormat::Excel, Arc::new(ExcelEngine));
+        registry.register(OutputFormat::Csv, Arc::new(CsvEngine));
+        registry
+    }
+
+    pub fn register(&mut self, format: OutputFormat, engine: Arc<dyn DocumentEngine>) {
+        self.engines.push((format, engine));
+    }
+
+    pub fn resolve(&self, format: &OutputFormat, document_type: &DocumentType) -> Option<Arc<dyn DocumentEngine>> {
+        self.engines
+            .iter()
+            .find(|(f, engine)| f == format && engine.supports(document_type))
+            .map(|(_, engine)| engine.clone())
+    }
+}
+
+impl Default for EngineRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Plantillas Typst → PDF (pipeline de render del engine de plantillas)
+pub struct TypstEngine {
+    pdf: PdfGenerator,
+}
+
+impl TypstEngine {
+    pub fn new(template_manager: Arc<TemplateManager>) -> Self {
+        TypstEngine { pdf: PdfGenerator::new(template_manager) }
+    }
+}
+
+#[async_trait]
+impl DocumentEngine for TypstEngine {
+    fn name(&self) -> &'static str {
+        "typst"
+    }
+
+    fn supports(&self, document_type: &DocumentType) -> bool {
+        !matches!(document_type, DocumentType::Report)
+    }
+
+    async fn render(&self, data: Value, context: &RenderContext) -> Result<Artifact> {
+        let generated = self.pdf.generate_timed(Some(context.tenant_id), &context.template_id, data).await?;
+        Ok(Artifact {
+            preview_png: generated.preview_png,
+            warnings: generated.warnings,
+            timings: Some(generated.timings),
+            ..Artifact::new(generated.pdf, "pdf", PDF_CONTENT_TYPE)
+        })
+    }
+}
+
+/// Libro Excel de un reporte, con su CSV/TSV si se pidió
+pub struct ExcelEngine;
+
+#[async_trait]
+impl DocumentEngine for ExcelEngine {
+    fn name(&self) -> &'static str {
+        "excel"
+    }
+
+    fn supports(&self, document_type: &DocumentType) -> bool {
+        matches!(document_type, DocumentType::Report)
+    }
+
+    async fn render(&self, data: Value, _context: &RenderContext) -> Result<Artifact> {
+        let output = ExcelGenerator::new().generate_with_companion(data).await?;
+        Ok(Artifact {
+            companion: output.companion,
+            ..Artifact::new(output.workbook, "xlsx", XLSX_CONTENT_TYPE)
+        })
+    }
+}
+
+/// Filas en CSV
+pub struct CsvEngine;
+
+#[async_trait]
+impl DocumentEngine for CsvEngine {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn supports(&self, document_type: &DocumentType) -> bool {
+        !matches!(document_type, DocumentType::FiscalReport)
+    }
+
+    async fn render(&self, data: Value, _context: &RenderContext) -> Result<Artifact> {
+        let bytes = CsvGenerator::new().generate(data).await?;
+        Ok(Artifact::new(bytes, "csv", CSV_CONTENT_TYPE))
+    }
+}
+
+/// Formatos 606/607 de la DGII como texto delimitado
+pub struct DgiiTxtEngine;
+
+#[async_trait]
+impl DocumentEngine for DgiiTxtEngine {
+    fn name(&self) -> &'static str {
+        "dgii_txt"
+    }
+
+    fn supports(&self, document_type: &DocumentType) -> bool {
+        matches!(document_type, DocumentType::FiscalReport)
+    }
+
+    async fn render(&self, data: Value, _context: &RenderContext) -> Result<Artifact> {
+        let bytes = DgiiGenerator::new().generate_txt(data).await?;
+        Ok(Artifact::new(bytes, "txt", TXT_CONTENT_TYPE))
+    }
+}
+
+/// Formatos 606/607 de la DGII en Excel
+pub struct DgiiExcelEngine;
+
+#[async_trait]
+impl DocumentEngine for DgiiExcelEngine {
+    fn name(&self) -> &'static str {
+        "dgii_excel"
+    }
+
+    fn supports(&self, document_type: &DocumentType) -> bool {
+        matches!(document_type, DocumentType::FiscalReport)
+    }
+
+    async fn render(&self, data: Value, _context: &RenderContext) -> Result<Artifact> {
+        let bytes = DgiiGenerator::new().generate_excel(data).await?;
+        Ok(Artifact::new(bytes, "xlsx", XLSX_CONTENT_TYPE))
+    }
+}
@@ -1,10 +1,23 @@
-use anyhow::Result;
-use rust_xlsxwriter::{Workbook, Format, Color, FormatBorder};
+use anyhow::{bail, Result};
+use rust_xlsxwriter::{Chart, ChartType, ConditionalFormatFormula, Workbook, Worksheet, Format, Color, FormatBorder, Formula, Note, column_number_to_name};
 use serde_json::Value;
+use std::collections::HashMap;
+
+use super::csv::CsvGenerator;
+use super::report_processor::{compute_summary, filter_and_sort, group_rows, GroupedRow};
+use crate::models::{footnote_markers, ColumnDefinition, CompanionFormat, ConditionOperator, ConditionalFormat, DataType, ReportChart, ReportChartType, ReportOptions, ReportSchema};
 
 /// Generador genérico de Excel
 pub struct ExcelGenerator;
 
+/// Libro Excel y, si se pidió, su archivo plano acompañante
+pub struct ExcelOutput {
+    pub workbook: Vec<u8>,
+    pub companion: Option<(CompanionFormat, Vec<u8>)>,
+}
+
+type CompanionWriter = ::csv::Writer<Vec<u8>>;
+
 impl ExcelGenerator {
     pub fn new() -> Self {
         ExcelGenerator
@@ -12,29 +25,111 @@ impl ExcelGenerator {
 
     /// Genera un archivo Excel desde datos JSON genéricos
     pub async fn generate(&self, data: Value) -> Result<Vec<u8>> {
+        Ok(self.generate_with_companion(data).await?.workbook)
+    }
+
+    /// Como `generate`; con `companion` (`csv` o `tsv`) en los datos escribe
+    /// además las mismas filas como archivo plano, en la misma pasada
+    pub async fn generate_with_companion(&self, data: Value) -> Result<ExcelOutput> {
         // Procesar en tarea bloqueante para trabajo intensivo de CPU
         tokio::task::spawn_blocking(move || {
-            Self::generate_excel_from_json(data)
+            let format: Option<CompanionFormat> = match data.get("companion") {
+                Some(format) => Some(serde_json::from_value(format.clone())?),
+                None => None,
+            };
+            let mut companion = format.map(|format| {
+                ::csv::WriterBuilder::new()
+                    .delimiter(format.delimiter())
+                    .flexible(true)
+                    .from_writer(Vec::new())
+            });
+
+            let workbook = Self::generate_excel_from_json(data, companion.as_mut())?;
+            let companion = match (format, companion) {
+                (Some(format), Some(writer)) => Some((format, writer.into_inner().map_err(|e| e.into_error())?)),
+                _ => None,
+            };
+            Ok(ExcelOutput { workbook, companion })
         })
         .await?
     }
 
-    fn generate_excel_from_json(data: Value) -> Result<Vec<u8>> {
+    /// Genera un Excel a partir del esquema del reporte (filas como objetos JSON)
+    pub async fn generate_report(
+        &self,
+        title: String,
+        schema: ReportSchema,
+        rows: Vec<Value>,
+        options: Option<ReportOptions>,
+    ) -> Result<Vec<u8>> {
+        tokio::task::spawn_blocking(move || {
+            Self::generate_excel_from_schema(&title, &schema, &rows, options.as_ref())
+        })
+        .await?
+    }
+
+    /// Un libro con una hoja por elemento de `sheets` (cada uno con `title`,
+    /// `headers`/`rows` u `schema`, y `options`), o una sola hoja con los datos
+    /// de la raíz. El archivo acompañante lleva la primera hoja
+    fn generate_excel_from_json(data: Value, mut companion: Option<&mut CompanionWriter>) -> Result<Vec<u8>> {
         let mut workbook = Workbook::new();
 
+        match data.get("sheets").and_then(Value::as_array) {
+            Some(sheets) => {
+                if sheets.is_empty() {
+                    bail!("`sheets` must contain at least one sheet");
+                }
+                let mut titles: Vec<String> = Vec::with_capacity(sheets.len());
+                for (index, sheet) in sheets.iter().enumerate() {
+                    let default_title = format!("Sheet{}", index + 1);
+                    let title = sheet["title"].as_str().unwrap_or(&default_title);
+                    // Excel no distingue mayúsculas en los nombres de hoja
+                    if titles.iter().any(|t| t.eq_ignore_ascii_case(title)) {
+                        bail!("Duplicate sheet title '{}'", title);
+                    }
+                    titles.push(title.to_string());
+
+                    let companion = if index == 0 { companion.as_deref_mut() } else { None };
+                    Self::write_sheet(&mut workbook, sheet, title, companion)?;
+                }
+            },
+            None => {
+                let title = data["title"].as_str().unwrap_or("Sheet1");
+                Self::write_sheet(&mut workbook, &data, title, companion)?;
+            },
+        }
+
+        Ok(workbook.save_to_buffer()?)
+    }
+
+    fn write_sheet(
+        workbook: &mut Workbook,
+        data: &Value,
+        title: &str,
+        mut companion: Option<&mut CompanionWriter>,
+    ) -> Result<()> {
+        // Reportes con esquema: columnas tipadas, fórmulas, etc.
+        if let Some(schema) = data.get("schema") {
+            let schema: ReportSchema = serde_json::from_value(schema.clone())?;
+            let options: Option<ReportOptions> = match data.get("options") {
+                Some(options) => Some(serde_json::from_value(options.clone())?),
+                None => None,
+            };
+            let rows = data["rows"].as_array().cloned().unwrap_or_default();
+            return Self::write_schema_sheet(workbook, title, &schema, &rows, options.as_ref(), companion);
+        }
+
         // Extraer configuración básica del JSON
-        let title = data["title"].as_str().unwrap_or("Sheet1");
         let headers = data["headers"].as_array();
         let rows = data["rows"].as_array();
         let use_memory_optimization = data["memory_optimization"].as_bool().unwrap_or(false);
 
-        // Optimización de memoria para archivos grandes - comentado temporalmente
-        // if use_memory_optimization {
-        //     workbook.use_constant_memory(true)?;
-        // }
-
-        // Crear hoja de trabajo
-        let worksheet = workbook.add_worksheet();
+        // Archivos grandes: modo de memoria constante (las filas se escriben en orden)
+        let worksheet = if use_memory_optimization {
+            workbook.add_worksheet_with_constant_memory()
+        } else {
+            workbook.add_worksheet()
+        };
         worksheet.set_name(title)?;
 
         // Crear formato para encabezados
@@ -54,6 +149,9 @@ impl ExcelGenerator {
                 let header_text = header.as_str().unwrap_or("");
                 worksheet.write_string_with_format(0, col as u16, header_text, &header_format)?;
             }
+            if let Some(writer) = companion.as_deref_mut() {
+                writer.write_record(headers.iter().map(CsvGenerator::value_text))?;
+            }
         }
 
         // Escribir filas de datos si existen
@@ -62,6 +160,9 @@ impl ExcelGenerator {
                 let row_num = (row_idx + 1) as u32; // +1 para el header
 
                 if let Some(row_array) = row.as_array() {
+                    if let Some(writer) = companion.as_deref_mut() {
+                        writer.write_record(row_array.iter().map(CsvGenerator::value_text))?;
+                    }
                     for (col_idx, value) in row_array.iter().enumerate() {
                         let col_num = col_idx as u16;
 
@@ -129,12 +230,430 @@ impl ExcelGenerator {
             }
         }
 
-        // Guardar en buffer
-        let buffer = workbook.save_to_buffer()?;
+        Ok(())
+    }
 
-        Ok(buffer)
+    fn generate_excel_from_schema(
+        title: &str,
+        schema: &ReportSchema,
+        rows: &[Value],
+        options: Option<&ReportOptions>,
+    ) -> Result<Vec<u8>> {
+        let mut workbook = Workbook::new();
+        Self::write_schema_sheet(&mut workbook, title, schema, rows, options, None)?;
+        Ok(workbook.save_to_buffer()?)
     }
 
+    fn write_schema_sheet(
+        workbook: &mut Workbook,
+        title: &str,
+        schema: &ReportSchema,
+        rows: &[Value],
+        options: Option<&ReportOptions>,
+        mut companion: Option<&mut CompanionWriter>,
+    ) -> Result<()> {
+        // Filtros y orden del esquema antes de escribir (y de resumir)
+        let rows = filter_and_sort(schema, rows)?;
+        let rows = rows.as_ref();
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(title)?;
+
+        let header_format = Format::new()
+            .set_bold()
+            .set_background_color(Color::RGB(0x4472C4))
+            .set_font_color(Color::White)
+            .set_border(FormatBorder::Thin);
+
+        let columns = schema.visible_columns();
+        let formats: Vec<Format> = columns.iter().map(|c| Self::column_format(c)).collect();
+
+        // Posición de cada campo para resolver referencias `{campo}` en fórmulas
+        let positions: HashMap<&str, u16> = columns
+            .iter()
+            .enumerate()
+            .map(|(idx, c)| (c.field.as_str(), idx as u16))
+            .collect();
+
+        // Encabezados de dos niveles: grupos combinados arriba, columnas abajo;
+        // las columnas sin grupo se combinan verticalmente
+        let header_rows: u32 = if schema.has_header_groups() { 2 } else { 1 };
+        let spans = schema.header_spans();
+        if header_rows == 2 {
+            for span in &spans {
+                let first = span.start as u16;
+                let last = (span.start + span.len - 1) as u16;
+                match &span.title {
+                    Some(title) if span.len > 1 => {
+                        worksheet.merge_range(0, first, 0, last, title, &header_format)?;
+                    },
+                    Some(title) => {
+                        worksheet.write_string_with_format(0, first, title, &header_format)?;
+                    },
+                    None => {
+                        worksheet.merge_range(0, first, 1, first, &columns[span.start].header, &header_format)?;
+                    },
+                }
+            }
+        }
+
+        for (col, column) in columns.iter().enumerate() {
+            let in_group = spans.iter()
+                .any(|s| s.title.is_some() && (s.start..s.start + s.len).contains(&col));
+            if header_rows == 1 || in_group {
+                worksheet.write_string_with_format(header_rows - 1, col as u16, &column.header, &header_format)?;
+            }
+            if let Some(width) = column.width {
+                worksheet.set_column_width(col as u16, width as f64)?;
+            }
+        }
+        if let Some(writer) = companion.as_deref_mut() {
+            writer.write_record(columns.iter().map(|c| c.header.as_str()))?;
+        }
+
+        // Con `grouping` las filas van ordenadas por grupo, con encabezados,
+        // subtotales y niveles de esquema (plegados con `collapsed`)
+        let layout = group_rows(schema, rows)
+            .unwrap_or_else(|| (0..rows.len()).map(GroupedRow::Data).collect());
+        let grouping = schema.grouping.as_ref();
+        let show_subtotals = grouping.is_some_and(|g| g.show_subtotals);
+        let collapsed = grouping.is_some_and(|g| g.collapsed);
+        let group_format = Format::new().set_bold().set_background_color(Color::RGB(0xD9E1F2));
+
+        let mut row_num = header_rows;
+        // Primera fila de detalle de cada grupo abierto, por nivel
+        let mut group_starts: Vec<u32> = Vec::new();
+        for entry in &layout {
+            match entry {
+                GroupedRow::Header { label, .. } => {
+                    worksheet.write_string_with_format(row_num, 0, label, &group_format)?;
+                    for col in 1..columns.len() as u16 {
+                        worksheet.write_blank(row_num, col, &group_format)?;
+                    }
+                    row_num += 1;
+                    group_starts.push(row_num);
+                },
+                GroupedRow::Data(index) => {
+                    let row = &rows[*index];
+                    // El archivo plano lleva los valores (no las fórmulas), como el CSV
+                    if let Some(writer) = companion.as_deref_mut() {
+                        writer.write_record(
+                            columns
+                                .iter()
+                                .map(|c| CsvGenerator::format_cell(row.get(&c.field).unwrap_or(&Value::Null), c)),
+                        )?;
+                    }
+                    Self::write_schema_row(worksheet, row_num, row, &columns, &formats, &positions)?;
+                    row_num += 1;
+                },
+                GroupedRow::Subtotal { label, values, .. } => {
+                    let start = group_starts.pop().unwrap_or(row_num);
+                    if row_num > start {
+                        if collapsed {
+                            worksheet.group_rows_collapsed(start, row_num - 1)?;
+                        } else {
+                            worksheet.group_rows(start, row_num - 1)?;
+                        }
+                    }
+                    if show_subtotals {
+                        Self::write_subtotal_row(worksheet, row_num, label, values, &columns, &formats)?;
+                        row_num += 1;
+                    }
+                },
+            }
+        }
+        let data_row_count = row_num - header_rows;
+
+        if let Some(rules) = options.and_then(|o| o.conditional_formatting.as_deref()).filter(|_| !rows.is_empty()) {
+            let data_rows = (header_rows, header_rows + data_row_count - 1);
+            Self::apply_conditional_formats(worksheet, rules, &positions, data_rows)?;
+        }
+
+        // Resumen ejecutivo (agregaciones y métricas calculadas) bajo la tabla
+        if options.map(|o| o.include_summary).unwrap_or(false) {
+            let label_format = Format::new().set_bold();
+            let first_row = data_row_count + header_rows + 1;
+
+            for (offset, (name, value)) in compute_summary(schema, rows).into_iter().enumerate() {
+                let summary_row = first_row + offset as u32;
+                worksheet.write_string_with_format(summary_row, 0, &name, &label_format)?;
+                worksheet.write_number(summary_row, 1, value)?;
+            }
+        }
+
+        if let Some(options) = options {
+            if options.freeze_headers {
+                worksheet.set_freeze_panes(header_rows, 0)?;
+            }
+
+            if options.auto_filter && !columns.is_empty() {
+                let last_row = data_row_count + header_rows - 1;
+                worksheet.autofilter(header_rows - 1, 0, last_row, columns.len() as u16 - 1)?;
+            }
+
+            // Notas al pie como comentarios: en el encabezado de su columna,
+            // las generales en la primera celda del encabezado
+            if let Some(footnotes) = &options.footnotes {
+                let markers = footnote_markers(footnotes);
+                let mut general = Vec::new();
+
+                for (note, marker) in footnotes.iter().zip(&markers) {
+                    let text = if marker.is_empty() { note.text.clone() } else { format!("{} {}", marker, note.text) };
+                    let column = note.field.as_deref().and_then(|f| positions.get(f).copied());
+
+                    match column {
+                        Some(col) => {
+                            let in_group = spans.iter()
+                                .any(|s| s.title.is_some() && (s.start..s.start + s.len).contains(&(col as usize)));
+                            let row = if header_rows == 1 || in_group { header_rows - 1 } else { 0 };
+                            worksheet.insert_note(row, col, &Note::new(text).add_author_prefix(false))?;
+                        },
+                        None => general.push(text),
+                    }
+                }
+
+                if !general.is_empty() && !columns.is_empty() {
+                    worksheet.insert_note(0, 0, &Note::new(general.join("\n")).add_author_prefix(false))?;
+                }
+            }
+
+            if let Some(charts) = options.charts.as_deref().filter(|_| !rows.is_empty()) {
+                let data_rows = (header_rows, header_rows + data_row_count - 1);
+                // Fila con el encabezado de cada columna (las sin grupo quedan combinadas desde la primera)
+                let header_row = |col: u16| {
+                    let in_group = spans.iter()
+                        .any(|s| s.title.is_some() && (s.start..s.start + s.len).contains(&(col as usize)));
+                    if header_rows == 1 || in_group { header_rows - 1 } else { 0 }
+                };
+                Self::insert_charts(worksheet, title, charts, &positions, &header_row, data_rows, columns.len() as u16)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Formatos condicionales nativos sobre la columna de cada regla, como
+    /// fórmulas relativas a la primera fila de datos; si varias reglas se
+    /// cumplen, en lo que choquen gana la primera
+    fn apply_conditional_formats(
+        worksheet: &mut Worksheet,
+        rules: &[ConditionalFormat],
+        positions: &HashMap<&str, u16>,
+        data_rows: (u32, u32),
+    ) -> Result<()> {
+        let (first_row, last_row) = data_rows;
+
+        for rule in rules {
+            let Some(&col) = positions.get(rule.field.as_str()) else {
+                bail!("Conditional format field '{}' is not a visible column", rule.field);
+            };
+            let operator = rule.operator().map_err(anyhow::Error::msg)?;
+            let cell = format!("{}{}", column_number_to_name(col), first_row + 1);
+            let text = match &rule.value {
+                Value::String(s) => format!("\"{}\"", s.replace('"', "\"\"")),
+                other => format!("\"{}\"", other.to_string().replace('"', "\"\"")),
+            };
+            let operand = rule.numeric_value().map(|n| n.to_string()).unwrap_or_else(|| text.clone());
+            let compare = |symbol: &str| format!("AND(ISNUMBER({0}),{0}{1}{2})", cell, symbol, operand);
+
+            let criteria = match operator {
+                ConditionOperator::Equals => format!("{}={}", cell, operand),
+                ConditionOperator::NotEquals => format!("{}<>{}", cell, operand),
+                ConditionOperator::GreaterThan => compare(">"),
+                ConditionOperator::GreaterThanOrEqual => compare(">="),
+                ConditionOperator::LessThan => compare("<"),
+                ConditionOperator::LessThanOrEqual => compare("<="),
+                ConditionOperator::Between => {
+                    let (min, max) = rule.bounds().unwrap_or_default();
+                    format!("AND(ISNUMBER({0}),{0}>={1},{0}<={2})", cell, min, max)
+                },
+                ConditionOperator::Contains => format!("ISNUMBER(SEARCH({},{}))", text, cell),
+            };
+
+            let mut format = Format::new();
+            if let Some(rgb) = rule.format.background_rgb().map_err(anyhow::Error::msg)? {
+                format = format.set_background_color(Color::RGB(rgb));
+            }
+            if let Some(rgb) = rule.format.font_rgb().map_err(anyhow::Error::msg)? {
+                format = format.set_font_color(Color::RGB(rgb));
+            }
+            if rule.format.bold.unwrap_or(false) {
+                format = format.set_bold();
+            }
+
+            let conditional = ConditionalFormatFormula::new()
+                .set_rule(format!("={}", criteria).as_str())
+                .set_format(&format);
+            worksheet.add_conditional_format(first_row, col, last_row, col, &conditional)?;
+        }
+        Ok(())
+    }
+
+    /// Gráficos nativos sobre las filas de datos (`data_rows`, inclusive), uno
+    /// debajo del otro a la derecha de la tabla; cada serie toma su nombre del
+    /// encabezado de la columna
+    fn insert_charts(
+        worksheet: &mut Worksheet,
+        sheet: &str,
+        charts: &[ReportChart],
+        positions: &HashMap<&str, u16>,
+        header_row: &dyn Fn(u16) -> u32,
+        data_rows: (u32, u32),
+        table_width: u16,
+    ) -> Result<()> {
+        let (first_row, last_row) = data_rows;
+        let column_of = |field: &str| match positions.get(field) {
+            Some(col) => Ok(*col),
+            None => bail!("Chart field '{}' is not a visible column", field),
+        };
+
+        for (index, spec) in charts.iter().enumerate() {
+            if spec.series.is_empty() {
+                bail!("Chart {} has no series", index);
+            }
+            let mut chart = Chart::new(match spec.chart_type {
+                ReportChartType::Column => ChartType::Column,
+                ReportChartType::Bar => ChartType::Bar,
+                ReportChartType::Line => ChartType::Line,
+                ReportChartType::Area => ChartType::Area,
+                ReportChartType::Pie => ChartType::Pie,
+                ReportChartType::Doughnut => ChartType::Doughnut,
+                ReportChartType::Scatter => ChartType::Scatter,
+            });
+            let category_col = column_of(&spec.category_field)?;
+            for field in &spec.series {
+                let col = column_of(field)?;
+                chart
+                    .add_series()
+                    .set_categories((sheet, first_row, category_col, last_row, category_col))
+                    .set_values((sheet, first_row, col, last_row, col))
+                    .set_name((sheet, header_row(col), col));
+            }
+            if let Some(title) = &spec.title {
+                chart.title().set_name(title);
+            }
+
+            worksheet.insert_chart(index as u32 * 16, table_width + 1, &chart)?;
+        }
+        Ok(())
+    }
+
+    fn write_schema_row(
+        worksheet: &mut Worksheet,
+        row_num: u32,
+        row: &Value,
+        columns: &[&ColumnDefinition],
+        formats: &[Format],
+        positions: &HashMap<&str, u16>,
+    ) -> Result<()> {
+        for (col, column) in columns.iter().enumerate() {
+            let col_num = col as u16;
+            let value = row.get(&column.field).unwrap_or(&Value::Null);
+            let format = &formats[col];
+
+            if let Some(template) = &column.formula {
+                let mut formula = Formula::new(Self::formula_for_row(template, row_num, positions));
+                // Valor precalculado como resultado en caché (visores sin motor de cálculo)
+                if !value.is_null() {
+                    formula = formula.set_result(Self::value_text(value));
+                }
+                worksheet.write_formula_with_format(row_num, col_num, formula, format)?;
+                continue;
+            }
+
+            match (value, &column.data_type) {
+                (Value::Null, _) => {
+                    worksheet.write_blank(row_num, col_num, format)?;
+                },
+                (Value::Number(n), _) => {
+                    worksheet.write_number_with_format(row_num, col_num, n.as_f64().unwrap_or(0.0), format)?;
+                },
+                (Value::String(s), DataType::Number | DataType::Currency | DataType::Percentage) => {
+                    match s.parse::<f64>() {
+                        Ok(n) => worksheet.write_number_with_format(row_num, col_num, n, format)?,
+                        Err(_) => worksheet.write_string_with_format(row_num, col_num, s, format)?,
+                    };
+                },
+                _ => {
+                    worksheet.write_string_with_format(row_num, col_num, Self::value_text(value), format)?;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Subtotal de un grupo: la etiqueta en la primera columna (si no lleva una
+    /// agregación) y cada agregación bajo la columna de su campo
+    fn write_subtotal_row(
+        worksheet: &mut Worksheet,
+        row_num: u32,
+        label: &str,
+        values: &[(String, f64)],
+        columns: &[&ColumnDefinition],
+        formats: &[Format],
+    ) -> Result<()> {
+        for (col, column) in columns.iter().enumerate() {
+            let format = formats[col].clone().set_bold().set_background_color(Color::RGB(0xF2F2F2));
+            match values.iter().find(|(field, _)| *field == column.field) {
+                Some((_, value)) if value.is_finite() => {
+                    worksheet.write_number_with_format(row_num, col as u16, *value, &format)?;
+                },
+                _ if col == 0 => {
+                    worksheet.write_string_with_format(row_num, 0, label, &format)?;
+                },
+                _ => {
+                    worksheet.write_blank(row_num, col as u16, &format)?;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Resuelve una fórmula de columna para una fila: `{row}` es el número de
+    /// fila de Excel y `{campo}` la celda de ese campo en la misma fila
+    /// (p. ej. `=D{row}*E{row}` o `={quantity}*{price}`)
+    fn formula_for_row(template: &str, row_num: u32, positions: &HashMap<&str, u16>) -> String {
+        let excel_row = row_num + 1;
+        let mut formula = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else { break };
+            let name = &rest[start + 1..start + len];
+
+            formula.push_str(&rest[..start]);
+            match (name, positions.get(name)) {
+                ("row", _) => formula.push_str(&excel_row.to_string()),
+                (_, Some(col)) => formula.push_str(&format!("{}{}", column_number_to_name(*col), excel_row)),
+                _ => formula.push_str(&rest[start..=start + len]),
+            }
+            rest = &rest[start + len + 1..];
+        }
+
+        formula.push_str(rest);
+        formula
+    }
+
+    fn column_format(column: &ColumnDefinition) -> Format {
+        let format = Format::new().set_border(FormatBorder::Thin);
+
+        let num_format = column.format.clone().or_else(|| match column.data_type {
+            DataType::Currency => Some("#,##0.00".to_string()),
+            DataType::Percentage => Some("0.00%".to_string()),
+            _ => None,
+        });
+
+        match num_format {
+            Some(num_format) => format.set_num_format(num_format),
+            None => format,
+        }
+    }
+
+    fn value_text(value: &Value) -> String {
+        match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }
+    }
 
     /// Genera un Excel simple desde arrays de headers y rows
     pub async fn generate_simple(
@@ -158,3 +677,95 @@ impl ExcelGenerator {
         self.generate(data).await
     }
 }
+
+/// Hoja de un reporte con esquema escrita por lotes en modo de memoria
+/// constante: cada fila se vuelca a un temporal al pasar a la siguiente, así
+/// la memoria no crece con el reporte. Las filas quedan en el orden en que
+/// llegan y con encabezado de un nivel; se aplican los filtros del esquema,
+/// formatos condicionales, `freeze_headers` y `auto_filter`, pero no orden,
+/// agrupación, resumen, notas ni gráficos (necesitan todas las filas)
+pub struct StreamingExcel {
+    workbook: Workbook,
+    schema: ReportSchema,
+    options: Option<ReportOptions>,
+    formats: Vec<Format>,
+    next_row: u32,
+}
+
+impl StreamingExcel {
+    pub fn new(title: &str, schema: ReportSchema, options: Option<ReportOptions>) -> Result<Self> {
+        for filter in schema.filters.iter().flatten() {
+            filter.validate().map_err(anyhow::Error::msg)?;
+        }
+        if schema.sorting.is_some() || schema.grouping.is_some() {
+            tracing::warn!("Streaming Excel report '{}' ignores sorting and grouping", title);
+        }
+
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet_with_constant_memory();
+        worksheet.set_name(title)?;
+
+        let header_format = Format::new()
+            .set_bold()
+            .set_background_color(Color::RGB(0x4472C4))
+            .set_font_color(Color::White)
+            .set_border(FormatBorder::Thin);
+        let columns = schema.visible_columns();
+        for (col, column) in columns.iter().enumerate() {
+            worksheet.write_string_with_format(0, col as u16, &column.header, &header_format)?;
+            if let Some(width) = column.width {
+                worksheet.set_column_width(col as u16, width as f64)?;
+            }
+        }
+        let formats = columns.iter().map(|c| ExcelGenerator::column_format(c)).collect();
+
+        Ok(StreamingExcel { workbook, schema, options, formats, next_row: 1 })
+    }
+
+    /// Escribe un lote de filas a continuación de las anteriores
+    pub fn write_rows(&mut self, rows: &[Value]) -> Result<()> {
+        let worksheet = self.workbook.worksheet_from_index(0)?;
+        let columns = self.schema.visible_columns();
+        let positions = Self::positions(&columns);
+        let filters = self.schema.filters.as_deref().unwrap_or(&[]);
+
+        for row in rows.iter().filter(|row| filters.iter().all(|f| f.matches(row))) {
+            ExcelGenerator::write_schema_row(worksheet, self.next_row, row, &columns, &self.formats, &positions)?;
+            self.next_row += 1;
+        }
+        Ok(())
+    }
+
+    /// Filas de datos escritas hasta ahora
+    pub fn row_count(&self) -> u32 {
+        self.next_row - 1
+    }
+
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        let last_row = self.next_row - 1;
+        let worksheet = self.workbook.worksheet_from_index(0)?;
+        let columns = self.schema.visible_columns();
+
+        if let Some(options) = &self.options {
+            if let Some(rules) = options.conditional_formatting.as_deref().filter(|_| last_row > 0) {
+                ExcelGenerator::apply_conditional_formats(worksheet, rules, &Self::positions(&columns), (1, last_row))?;
+            }
+            if options.freeze_headers {
+                worksheet.set_freeze_panes(1, 0)?;
+            }
+            if options.auto_filter && !columns.is_empty() {
+                worksheet.autofilter(0, 0, last_row, columns.len() as u16 - 1)?;
+            }
+        }
+
+        Ok(self.workbook.save_to_buffer()?)
+    }
+
+    fn positions<'a>(columns: &[&'a ColumnDefinition]) -> HashMap<&'a str, u16> {
+        columns
+            .iter()
+            .enumerate()
+            .map(|(idx, c)| (c.field.as_str(), idx as u16))
+            .collect()
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::{bail, Result};
+
+/// Largo máximo de una expresión (bytes)
+const MAX_EXPRESSION_LEN: usize = 1024;
+
+/// Anidamiento máximo de paréntesis y signos `-`: el parser es recursivo y
+/// una expresión del request no debe poder agotar la pila
+const MAX_DEPTH: usize = 32;
+
+/// Evalúa expresiones aritméticas de métricas de resumen, p. ej.
+/// `sum(total) / count(*)` o `(sum(ventas) - sum(costo)) / sum(ventas) * 100`.
+///
+/// Soporta `+ - * /`, paréntesis, números, llamadas `func(campo)` / `func(*)`
+/// (resueltas con `call`) e identificadores sueltos (resueltos con `var`,
+/// p. ej. alias de agregaciones ya calculadas).
+pub fn evaluate(
+    expression: &str,
+    call: &dyn Fn(&str, &str) -> Result<f64>,
+    var: &dyn Fn(&str) -> Option<f64>,
+) -> Result<f64> {
+    if expression.len() > MAX_EXPRESSION_LEN {
+        bail!("Expression is too long ({} bytes, max {})", expression.len(), MAX_EXPRESSION_LEN);
+    }
+
+    let mut parser = Parser { input: expression.as_bytes(), pos: 0, depth: 0, call, var };
+    let value = parser.expr()?;
+
+    parser.skip_whitespace();
+    if parser.pos < parser.input.len() {
+        bail!("Unexpected '{}' at position {} in '{}'", parser.input[parser.pos] as char, parser.pos, expression);
+    }
+
+    Ok(value)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    depth: usize,
+    call: &'a dyn Fn(&str, &str) -> Result<f64>,
+    var: &'a dyn Fn(&str) -> Option<f64>,
+}
+
+impl Parser<'_> {
+    fn expr(&mut self) -> Result<f64> {
+        let mut value = self.term()?;
+        loop {
+            match self.peek() {
+                Some(b'+') => { self.pos += 1; value += self.term()?; },
+                Some(b'-') => { self.pos += 1; value -= self.term()?; },
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<f64> {
+        let mut value = self.factor()?;
+        loop {
+            match self.peek() {
+                Some(b'*') => { self.pos += 1; value *= self.factor()?; },
+                Some(b'/') => {
+                    self.pos += 1;
+                    let divisor = self.factor()?;
+                    if divisor == 0.0 {
+                        bail!("Division by zero");
+                    }
+                    value /= divisor;
+                },
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Result<f64> {
+        match self.peek() {
+            Some(b'-') => {
+                self.pos += 1;
+                self.nested(|parser| Ok(-parser.factor()?))
+            },
+            Some(b'(') => {
+                self.pos += 1;
+                let value = self.nested(|parser| parser.expr())?;
+                self.expect(b')')?;
+                Ok(value)
+            },
+            Some(c) if c.is_ascii_digit() || c == b'.' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() || c == b'_' => {
+                let name = self.identifier();
+                if self.peek() == Some(b'(') {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    let argument = if self.peek() == Some(b'*') {
+                        self.pos += 1;
+                        "*".to_string()
+                    } else {
+                        self.identifier()
+                    };
+                    self.expect(b')')?;
+                    (self.call)(&name.to_lowercase(), &argument)
+                } else {
+                    (self.var)(&name).ok_or_else(|| anyhow::anyhow!("Unknown metric '{}'", name))
+                }
+            },
+            Some(c) => bail!("Unexpected '{}' at position {}", c as char, self.pos),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+
+    /// Evalúa un nivel más de anidamiento, hasta `MAX_DEPTH`
+    fn nested(&mut self, parse: impl FnOnce(&mut Self) -> Result<f64>) -> Result<f64> {
+        if self.depth == MAX_DEPTH {
+            bail!("Expression is nested too deeply (max {} levels)", MAX_DEPTH);
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn number(&mut self) -> Result<f64> {
+        let start = self.pos;
+        while self.pos < self.input.len() && (self.input[self.pos].is_ascii_digit() || self.input[self.pos] == b'.') {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.input[start..self.pos])?;
+        Ok(text.parse()?)
+    }
+
+    fn identifier(&mut self) -> String {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.pos < self.input.len() && (self.input[self.pos].is_ascii_alphanumeric() || self.input[self.pos] == b'_') {
+            self.pos += 1;
+        }
+        String::from_utf8_lossy(&self.input[start..self.pos]).to_string()
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        if self.peek() != Some(expected) {
+            bail!("Expected '{}' at position {}", expected as char, self.pos);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Siguiente carácter significativo (sin consumirlo)
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> Result<f64> {
+        let call = |func: &str, field: &str| match (func, field) {
+            ("sum", "total") => Ok(200.0),
+            ("count", "*") => Ok(4.0),
+            _ => bail!("Unknown aggregation {}({})", func, field),
+        };
+        let var = |name: &str| (name == "margin").then_some(0.25);
+        evaluate(expression, &call, &var)
+    }
+
+    #[test]
+    fn respects_precedence_and_parentheses() {
+        assert_eq!(eval("1 + 2 * 3").unwrap(), 7.0);
+        assert_eq!(eval("(1 + 2) * 3").unwrap(), 9.0);
+        assert_eq!(eval("10 - 4 - 3").unwrap(), 3.0);
+        assert_eq!(eval("-2 * -(3 + 1)").unwrap(), 8.0);
+        assert_eq!(eval("sum(total) / count(*) * margin").unwrap(), 12.5);
+    }
+
+    #[test]
+    fn rejects_division_by_zero() {
+        let error = eval("sum(total) / (count(*) - 4)").unwrap_err();
+        assert_eq!(error.to_string(), "Division by zero");
+    }
+
+    #[test]
+    fn rejects_unknown_metrics_and_trailing_input() {
+        assert!(eval("unknown + 1").is_err());
+        assert!(eval("1 + 2)").is_err());
+        assert!(eval("(1 + 2").is_err());
+    }
+
+    #[test]
+    fn limits_nesting_depth() {
+        let within = format!("{}1{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+        assert_eq!(eval(&within).unwrap(), 1.0);
+
+        let nested = format!("{}1{}", "(".repeat(MAX_DEPTH + 1), ")".repeat(MAX_DEPTH + 1));
+        assert!(eval(&nested).unwrap_err().to_string().contains("nested too deeply"));
+
+        let negations = format!("{}1", "-".repeat(MAX_DEPTH + 1));
+        assert!(eval(&negations).unwrap_err().to_string().contains("nested too deeply"));
+    }
+
+    #[test]
+    fn limits_expression_length() {
+        let long = vec!["1"; MAX_EXPRESSION_LEN].join("+");
+        assert!(eval(&long).unwrap_err().to_string().contains("too long"));
+    }
+}
@@ -1,5 +1,21 @@
 pub mod pdf;
+pub mod pdf_chunks;
 pub mod excel;
+pub mod csv;
+pub mod expression;
+pub mod report_processor;
+pub mod data_source;
+pub mod data_conversion;
+pub mod post_process;
+pub mod pades;
+pub mod preflight;
+pub mod dgii;
+pub mod ecf;
+pub mod engine;
 
 pub use pdf::PdfGenerator;
-pub use excel::ExcelGenerator;
\ No newline at end of file
+pub use excel::ExcelGenerator;
+pub use self::csv::CsvGenerator;
+pub use dgii::DgiiGenerator;
+pub use ecf::XmlInvoiceGenerator;
+pub use engine::{DocumentEngine, EngineRegistry};
\ No newline at end of file
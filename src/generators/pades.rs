@@ -0,0 +1,324 @@
+use anyhow::{bail, Context, Result};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, StringFormat};
+use openssl::cms::{CMSOptions, CmsContentInfo};
+use openssl::hash::MessageDigest;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::{PKey, Private};
+use openssl::stack::Stack;
+use openssl::x509::{X509, X509NameRef};
+use serde::Serialize;
+
+use crate::storage::s3::to_hex;
+
+/// Bytes reservados en `/Contents` para la firma CMS (certificado y cadena incluidos)
+const SIGNATURE_SIZE: usize = 16_384;
+
+/// `CMS_CADES` de OpenSSL 3: agrega el atributo signingCertificateV2 que exige PAdES
+const CMS_CADES: u32 = 0x100000;
+
+/// ByteRange provisional; se reemplaza por los offsets reales con el mismo largo
+const BYTE_RANGE_PLACEHOLDER: &[u8] = b"[0 1111111111 2222222222 3333333333]";
+
+/// Certificado y llave privada con los que firma un tenant (PKCS#12)
+pub struct SigningIdentity {
+    certificate: X509,
+    key: PKey<Private>,
+    chain: Vec<X509>,
+}
+
+/// Datos públicos del certificado de firma
+#[derive(Debug, Clone, Serialize)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    pub not_before: String,
+    pub not_after: String,
+    pub fingerprint_sha256: String,
+    pub chain_length: usize,
+}
+
+impl SigningIdentity {
+    /// Lee un PKCS#12 y valida que traiga certificado y llave que coincidan
+    pub fn from_pkcs12(der: &[u8], password: &str) -> Result<Self> {
+        let parsed = Pkcs12::from_der(der)
+            .context("Invalid PKCS#12 file")?
+            .parse2(password)
+            .context("Invalid PKCS#12 password")?;
+
+        let certificate = parsed.cert.context("PKCS#12 has no certificate")?;
+        let key = parsed.pkey.context("PKCS#12 has no private key")?;
+        if !certificate.public_key()?.public_eq(&key) {
+            bail!("The private key does not match the certificate");
+        }
+        let chain = parsed.ca.map(|stack| stack.into_iter().collect()).unwrap_or_default();
+
+        Ok(SigningIdentity { certificate, key, chain })
+    }
+
+    pub fn info(&self) -> CertificateInfo {
+        let cert = &self.certificate;
+        CertificateInfo {
+            subject: name_to_string(cert.subject_name()),
+            issuer: name_to_string(cert.issuer_name()),
+            serial: cert.serial_number().to_bn().and_then(|bn| bn.to_hex_str()).map(|s| s.to_string()).unwrap_or_default(),
+            not_before: cert.not_before().to_string(),
+            not_after: cert.not_after().to_string(),
+            fingerprint_sha256: cert.digest(MessageDigest::sha256()).map(|d| to_hex(&d)).unwrap_or_default(),
+            chain_length: self.chain.len(),
+        }
+    }
+
+    /// Firma CMS separada (CAdES) de `data`, con la cadena del certificado
+    fn sign_detached(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut certs = Stack::new()?;
+        for cert in &self.chain {
+            certs.push(cert.clone())?;
+        }
+
+        let flags = CMSOptions::DETACHED
+            | CMSOptions::BINARY
+            | CMSOptions::NOSMIMECAP
+            | CMSOptions::from_bits_retain(CMS_CADES);
+        let cms = CmsContentInfo::sign(Some(&self.certificate), Some(&self.key), Some(&certs), Some(data), flags)?;
+        Ok(cms.to_der()?)
+    }
+}
+
+/// `CN=..., O=...` de un nombre X.509
+fn name_to_string(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry.data().as_utf8().map(|v| v.to_string()).unwrap_or_default();
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Firma el PDF con PAdES (SubFilter `ETSI.CAdES.detached`): agrega un campo
+/// de firma invisible en la primera página y firma todo el archivo salvo `/Contents`.
+/// Debe ser lo último que se hace al PDF: cualquier cambio posterior invalida la firma
+pub fn sign_pdf(pdf: &[u8], identity: &SigningIdentity) -> Result<Vec<u8>> {
+    let mut doc = Document::load_mem(pdf).context("Invalid PDF for signing")?;
+    if doc.is_encrypted() {
+        bail!("PAdES signing is not supported on encrypted PDFs");
+    }
+
+    let first_page = doc.page_iter().next().context("PDF has no pages")?;
+
+    let signature_id = doc.add_object(dictionary! {
+        "Type" => "Sig",
+        "Filter" => "Adobe.PPKLite",
+        "SubFilter" => "ETSI.CAdES.detached",
+        "ByteRange" => vec![0.into(), 1111111111i64.into(), 2222222222i64.into(), 3333333333i64.into()],
+        "Contents" => Object::String(vec![0; SIGNATURE_SIZE], StringFormat::Hexadecimal),
+    });
+
+    let field_id = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Widget",
+        "FT" => "Sig",
+        "T" => Object::string_literal("Signature1"),
+        "V" => signature_id,
+        "Rect" => vec![0.into(), 0.into(), 0.into(), 0.into()],
+        "F" => 132,
+        "P" => first_page,
+    });
+
+    push_to_array(&mut doc, first_page, b"Annots", field_id)?;
+    add_to_acro_form(&mut doc, field_id)?;
+
+    let mut output = Vec::new();
+    doc.save_to(&mut output)?;
+
+    // Offsets del hex de /Contents (incluidos `<` y `>`), que queda fuera del hash
+    let range_at = find(&output, BYTE_RANGE_PLACEHOLDER, 0).context("Signature ByteRange not found")?;
+    let contents_at = find(&output, b"/Contents", range_at).context("Signature Contents not found")?;
+    let start = find(&output, b"<", contents_at).context("Signature Contents not found")?;
+    let end = start + SIGNATURE_SIZE * 2 + 2;
+    if output.get(end - 1) != Some(&b'>') {
+        bail!("Unexpected signature placeholder layout");
+    }
+
+    let byte_range = format!("[0 {} {} {}", start, end, output.len() - end);
+    if byte_range.len() + 1 > BYTE_RANGE_PLACEHOLDER.len() {
+        bail!("PDF too large to sign");
+    }
+    let mut padded = byte_range.into_bytes();
+    padded.resize(BYTE_RANGE_PLACEHOLDER.len() - 1, b' ');
+    padded.push(b']');
+    output[range_at..range_at + padded.len()].copy_from_slice(&padded);
+
+    let mut signed_data = Vec::with_capacity(output.len());
+    signed_data.extend_from_slice(&output[..start]);
+    signed_data.extend_from_slice(&output[end..]);
+
+    let signature = identity.sign_detached(&signed_data)?;
+    if signature.len() > SIGNATURE_SIZE {
+        bail!("Signature does not fit in the reserved space ({} bytes)", signature.len());
+    }
+    let hex = to_hex(&signature).to_uppercase();
+    output[start + 1..start + 1 + hex.len()].copy_from_slice(hex.as_bytes());
+
+    Ok(output)
+}
+
+/// Agrega el campo a `/AcroForm /Fields` del catálogo (creándolo si falta)
+fn add_to_acro_form(doc: &mut Document, field_id: ObjectId) -> Result<()> {
+    let acro_form = doc.catalog()?.get(b"AcroForm").ok().cloned();
+
+    let form_id = match acro_form {
+        Some(Object::Reference(id)) => id,
+        Some(Object::Dictionary(dict)) => doc.add_object(dict),
+        _ => doc.add_object(dictionary! { "Fields" => Vec::<Object>::new() }),
+    };
+    doc.catalog_mut()?.set("AcroForm", form_id);
+    doc.get_dictionary_mut(form_id)?.set("SigFlags", 3);
+
+    push_to_array(doc, form_id, b"Fields", field_id)
+}
+
+/// Agrega una referencia al arreglo `key` de un diccionario (directo o referenciado)
+fn push_to_array(doc: &mut Document, dict_id: ObjectId, key: &[u8], value: ObjectId) -> Result<()> {
+    let current = doc.get_dictionary(dict_id)?.get(key).ok().cloned();
+
+    match current {
+        Some(Object::Reference(array_id)) => {
+            doc.get_object_mut(array_id)?.as_array_mut()?.push(value.into());
+        },
+        Some(Object::Array(mut array)) => {
+            array.push(value.into());
+            doc.get_dictionary_mut(dict_id)?.set(key, array);
+        },
+        _ => {
+            let dict: &mut Dictionary = doc.get_dictionary_mut(dict_id)?;
+            dict.set(key, vec![Object::from(value)]);
+        },
+    }
+
+    Ok(())
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|pos| pos + from)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::generators::pdf::tests::sample_pdf;
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::rsa::Rsa;
+    use openssl::x509::store::X509StoreBuilder;
+    use openssl::x509::X509NameBuilder;
+
+    /// PKCS#12 con un certificado autofirmado de prueba
+    pub(crate) fn test_pkcs12(password: &str) -> Vec<u8> {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "Facturazo Test").unwrap();
+        name.append_entry_by_text("O", "Pruebas").unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_serial_number(&BigNum::from_u32(42).unwrap().to_asn1_integer().unwrap()).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(365).unwrap()).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        let certificate = builder.build();
+
+        Pkcs12::builder().name("test").pkey(&key).cert(&certificate).build2(password).unwrap().to_der().unwrap()
+    }
+
+    /// Datos firmados (según `/ByteRange`) y firma CMS de un PDF firmado
+    fn signed_parts(pdf: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let at = find(pdf, b"/ByteRange", 0).unwrap();
+        let open = find(pdf, b"[", at).unwrap();
+        let close = find(pdf, b"]", open).unwrap();
+        let range: Vec<usize> = std::str::from_utf8(&pdf[open + 1..close])
+            .unwrap()
+            .split_whitespace()
+            .map(|v| v.parse().unwrap())
+            .collect();
+        let (start, end) = (range[1], range[2]);
+
+        let mut data = pdf[..start].to_vec();
+        data.extend_from_slice(&pdf[end..end + range[3]]);
+
+        let hex = std::str::from_utf8(&pdf[start + 1..end - 1]).unwrap();
+        let der: Vec<u8> = (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect();
+        // El resto del espacio reservado va en ceros: el largo sale del encabezado DER
+        let length = match der[1] {
+            short if short < 0x80 => 2 + short as usize,
+            long => {
+                let bytes = (long & 0x7f) as usize;
+                2 + bytes + der[2..2 + bytes].iter().fold(0usize, |acc, b| (acc << 8) | *b as usize)
+            },
+        };
+        (data, der[..length].to_vec())
+    }
+
+    fn verify(pdf: &[u8], identity: &SigningIdentity) -> bool {
+        let (data, der) = signed_parts(pdf);
+        let mut cms = CmsContentInfo::from_der(&der).unwrap();
+        let mut certs = Stack::new().unwrap();
+        certs.push(identity.certificate.clone()).unwrap();
+        let store = X509StoreBuilder::new().unwrap().build();
+        cms.verify(
+            Some(&certs),
+            Some(&store),
+            Some(&data),
+            None,
+            CMSOptions::BINARY | CMSOptions::NO_SIGNER_CERT_VERIFY,
+        )
+        .is_ok()
+    }
+
+    #[test]
+    fn reads_pkcs12_and_rejects_a_wrong_password() {
+        let pkcs12 = test_pkcs12("secret");
+        let identity = SigningIdentity::from_pkcs12(&pkcs12, "secret").unwrap();
+        let info = identity.info();
+        assert_eq!(info.subject, "CN=Facturazo Test, O=Pruebas");
+        assert_eq!(info.serial, "2A");
+        assert_eq!(info.fingerprint_sha256.len(), 64);
+
+        assert!(SigningIdentity::from_pkcs12(&pkcs12, "wrong").is_err());
+        assert!(SigningIdentity::from_pkcs12(b"not a pkcs12", "secret").is_err());
+    }
+
+    #[test]
+    fn signs_the_whole_file_but_the_signature() {
+        let identity = SigningIdentity::from_pkcs12(&test_pkcs12("secret"), "secret").unwrap();
+        let pdf = sample_pdf(2, [0, 0, 612, 792], "page");
+
+        let signed = sign_pdf(&pdf, &identity).unwrap();
+        assert!(find(&signed, b"/ETSI.CAdES.detached", 0).is_some());
+        assert_eq!(Document::load_mem(&signed).unwrap().get_pages().len(), 2);
+        assert!(verify(&signed, &identity));
+
+        let (data, _) = signed_parts(&signed);
+        assert_eq!(data.len() + SIGNATURE_SIZE * 2 + 2, signed.len());
+    }
+
+    #[test]
+    fn detects_changes_after_signing() {
+        let identity = SigningIdentity::from_pkcs12(&test_pkcs12("secret"), "secret").unwrap();
+        let mut signed = sign_pdf(&sample_pdf(1, [0, 0, 612, 792], "page"), &identity).unwrap();
+
+        let text = find(&signed, b"page 1", 0).unwrap();
+        signed[text] = b'P';
+        assert!(!verify(&signed, &identity));
+    }
+}
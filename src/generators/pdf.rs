@@ -1,96 +1,295 @@
 use std::sync::Arc;
 use anyhow::Result;
-use std::process::Command;
-use uuid::Uuid;
-use std::fs;
+use lopdf::{dictionary, Document, Object, ObjectId};
 
-use crate::templates::TemplateManager;
+use crate::models::{PdfProtection, PostProcessStep};
+use crate::templates::{RenderPipeline, RenderRequest, TemplateManager};
 
-/// Generador genérico de PDFs usando Typst
+pub use crate::templates::GeneratedPdf;
+
+/// Generador genérico de PDFs: delega en el pipeline de render del engine
 pub struct PdfGenerator {
     template_manager: Arc<TemplateManager>,
-    temp_dir: String,
 }
 
 impl PdfGenerator {
     pub fn new(template_manager: Arc<TemplateManager>) -> Self {
-        let temp_dir = std::env::var("TEMP_DIR")
-            .unwrap_or_else(|_| "/tmp".to_string());
-
-        PdfGenerator {
-            template_manager,
-            temp_dir,
-        }
+        PdfGenerator { template_manager }
     }
 
     /// Genera un PDF desde cualquier template y datos JSON
     pub async fn generate(&self, template_id: &str, data: serde_json::Value) -> Result<Vec<u8>> {
-        // El template engine se encarga de toda la lógica específica
-        let pdf_path = self.template_manager
-            .generate_pdf_from_json(template_id, data, None)
-            .await?;
+        self.generate_for_tenant(None, template_id, data).await
+    }
 
-        // Leer el PDF generado
-        let pdf_bytes = tokio::fs::read(&pdf_path).await?;
+    /// Genera un PDF usando la versión del tenant del template si la tiene
+    pub async fn generate_for_tenant(
+        &self,
+        tenant_id: Option<i64>,
+        template_id: &str,
+        data: serde_json::Value,
+    ) -> Result<Vec<u8>> {
+        Ok(self.generate_timed(tenant_id, template_id, data).await?.pdf)
+    }
 
-        // Limpiar el archivo temporal
-        let _ = tokio::fs::remove_file(&pdf_path).await;
+    /// Igual que `generate_for_tenant`, con la miniatura y el tiempo de render y compilación
+    pub async fn generate_timed(
+        &self,
+        tenant_id: Option<i64>,
+        template_id: &str,
+        data: serde_json::Value,
+    ) -> Result<GeneratedPdf> {
+        self.template_manager.render(RenderRequest::new(tenant_id, template_id, data)).await
+    }
 
-        Ok(pdf_bytes)
+    /// Lista todos los templates disponibles
+    pub fn list_templates(&self) -> Vec<(String, String)> {
+        self.template_manager.list_templates()
     }
 
-    /// Genera un PDF con un template personalizado (no registrado)
-    pub async fn generate_with_custom_template(&self, typst_content: &str) -> Result<Vec<u8>> {
-        self.compile_typst_to_pdf(typst_content).await
+    /// Verifica si existe un template
+    pub fn template_exists(&self, template_id: &str) -> bool {
+        self.template_manager.template_exists(template_id)
     }
+}
 
-    /// Compila contenido Typst a PDF
-    async fn compile_typst_to_pdf(&self, typst_content: &str) -> Result<Vec<u8>> {
-        // Crear archivos temporales
-        let temp_id = Uuid::new_v4();
-        let typ_path = format!("{}/temp_{}.typ", self.temp_dir, temp_id);
-        let pdf_path = format!("{}/temp_{}.pdf", self.temp_dir, temp_id);
+/// Número de páginas de un PDF (None si no se puede leer, p. ej. cifrado)
+pub fn page_count(pdf: &[u8]) -> Option<u32> {
+    lopdf::Document::load_mem(pdf)
+        .ok()
+        .map(|doc| doc.get_pages().len() as u32)
+        .filter(|pages| *pages > 0)
+}
 
-        // Escribir contenido Typst
-        tokio::fs::write(&typ_path, typst_content).await?;
+/// Une varios PDFs en uno, con las páginas en el orden de `parts`. La
+/// información del documento (título, autor) es la del primero; los
+/// marcadores y formularios de las partes no se conservan
+pub fn merge_pdfs(parts: &[Vec<u8>]) -> Result<Vec<u8>> {
+    if parts.is_empty() {
+        anyhow::bail!("No PDFs to merge");
+    }
 
-        // Compilar con Typst
-        let output = tokio::task::spawn_blocking({
-            let typ_path = typ_path.clone();
-            let pdf_path = pdf_path.clone();
-            move || {
-                Command::new("typst")
-                    .args(&["compile", &typ_path, &pdf_path])
-                    .output()
+    let mut merged = Document::with_version("1.7");
+    let mut pages: Vec<(ObjectId, Object)> = Vec::new();
+    let mut catalog: Option<(ObjectId, Object)> = None;
+    let mut pages_root: Option<ObjectId> = None;
+    let mut info: Option<Object> = None;
+    let mut next_id = 1;
+
+    for (index, bytes) in parts.iter().enumerate() {
+        let mut doc = Document::load_mem(bytes)
+            .map_err(|e| anyhow::anyhow!("Invalid PDF at position {}: {}", index + 1, e))?;
+        if doc.is_encrypted() {
+            anyhow::bail!("PDF at position {} is encrypted", index + 1);
+        }
+        doc.renumber_objects_with(next_id);
+        next_id = doc.max_id + 1;
+
+        if info.is_none() {
+            info = doc.trailer.get(b"Info").ok().cloned();
+        }
+        // Los nodos intermedios del árbol no se copian: cada página se lleva
+        // lo que heredaba de ellos
+        for page_id in doc.get_pages().into_values() {
+            let mut page = doc.get_dictionary(page_id)?.clone();
+            for key in INHERITABLE_PAGE_KEYS {
+                if !page.has(key) {
+                    if let Some(value) = inherited_attribute(&doc, page_id, key) {
+                        page.set(key.to_vec(), value);
+                    }
+                }
             }
-        }).await??;
-
-        if !output.status.success() {
-            // Limpiar archivos temporales
-            let _ = fs::remove_file(&typ_path);
-            return Err(anyhow::anyhow!(
-                "Typst compilation failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+            pages.push((page_id, Object::Dictionary(page)));
         }
 
-        // Leer bytes del PDF
-        let pdf_bytes = tokio::fs::read(&pdf_path).await?;
+        for (id, object) in doc.objects {
+            match object.type_name().unwrap_or(b"") {
+                b"Catalog" => {
+                    catalog.get_or_insert((id, object));
+                },
+                b"Pages" => {
+                    pages_root.get_or_insert(id);
+                },
+                b"Page" | b"Outlines" | b"Outline" => {},
+                _ => {
+                    merged.objects.insert(id, object);
+                },
+            }
+        }
+    }
 
-        // Limpiar archivos temporales
-        let _ = tokio::fs::remove_file(&typ_path).await;
-        let _ = tokio::fs::remove_file(&pdf_path).await;
+    let (Some((catalog_id, catalog)), Some(pages_id)) = (catalog, pages_root) else {
+        anyhow::bail!("PDF without a page tree");
+    };
 
-        Ok(pdf_bytes)
+    let kids: Vec<Object> = pages.iter().map(|(id, _)| Object::Reference(*id)).collect();
+    merged.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => kids,
+        "Count" => pages.len() as i64,
+    }));
+    for (id, page) in pages {
+        let mut page = page.as_dict()?.clone();
+        page.set("Parent", pages_id);
+        merged.objects.insert(id, Object::Dictionary(page));
     }
 
-    /// Lista todos los templates disponibles
-    pub fn list_templates(&self) -> Vec<(String, String)> {
-        self.template_manager.list_templates()
+    let mut catalog = catalog.as_dict()?.clone();
+    catalog.set("Pages", pages_id);
+    catalog.remove(b"Outlines");
+    catalog.remove(b"AcroForm");
+    merged.objects.insert(catalog_id, Object::Dictionary(catalog));
+
+    merged.trailer.set("Root", catalog_id);
+    if let Some(info) = info {
+        merged.trailer.set("Info", info);
     }
+    merged.max_id = next_id - 1;
+    merged.renumber_objects();
+    merged.compress();
 
-    /// Verifica si existe un template
-    pub fn template_exists(&self, template_id: &str) -> bool {
-        self.template_manager.template_exists(template_id)
+    let mut output = Vec::new();
+    merged.save_to(&mut output)?;
+    Ok(output)
+}
+
+/// Atributos de página que se heredan del árbol de páginas
+const INHERITABLE_PAGE_KEYS: [&[u8]; 4] = [b"MediaBox", b"CropBox", b"Resources", b"Rotate"];
+
+/// Atributo de una página: el suyo o el del nodo más cercano del árbol de
+/// páginas que lo define
+pub fn inherited_attribute(doc: &Document, page_id: ObjectId, key: &[u8]) -> Option<Object> {
+    let mut node = doc.get_dictionary(page_id).ok()?;
+    // Acotado por si el árbol tiene ciclos
+    for _ in 0..64 {
+        if let Ok(value) = node.get(key) {
+            return Some(value.clone());
+        }
+        let parent = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+        node = doc.get_dictionary(parent).ok()?;
+    }
+    None
+}
+
+/// Cifrado pedido en las opciones de render de los datos (`options`, o
+/// `options.render` en reportes); va como último paso del post-procesado
+pub fn protection_step(data: &serde_json::Value) -> Result<Option<PostProcessStep>> {
+    let Some(options) = data.get("options").filter(|v| v.is_object()) else {
+        return Ok(None);
+    };
+    let options = options.get("render").filter(|v| v.is_object()).unwrap_or(options);
+
+    let protection: PdfProtection = serde_json::from_value(options.clone())
+        .map_err(|e| anyhow::anyhow!("Invalid PDF protection options: {}", e))?;
+    if !protection.is_requested() {
+        return Ok(None);
     }
-}
\ No newline at end of file
+
+    Ok(Some(PostProcessStep::Encrypt {
+        user_password: protection.user_password.unwrap_or_default(),
+        owner_password: protection.owner_password,
+        allow_print: !protection.no_print,
+        allow_copy: !protection.no_copy,
+    }))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use lopdf::content::{Content, Operation};
+    use lopdf::Stream;
+
+    /// PDF de prueba con `pages` páginas; el MediaBox está en el nodo `Pages`
+    /// (heredado), como lo dejan algunos generadores
+    pub(crate) fn sample_pdf(pages: usize, media_box: [i64; 4], label: &str) -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+
+        let kids: Vec<Object> = (1..=pages)
+            .map(|number| {
+                let content = Content {
+                    operations: vec![
+                        Operation::new("BT", vec![]),
+                        Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                        Operation::new("Td", vec![72.into(), 720.into()]),
+                        Operation::new("Tj", vec![Object::string_literal(format!("{} {}", label, number))]),
+                        Operation::new("ET", vec![]),
+                    ],
+                };
+                let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "Contents" => content_id,
+                })
+                .into()
+            })
+            .collect();
+
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => pages as i64,
+            "Resources" => resources_id,
+            "MediaBox" => media_box.iter().map(|v| Object::from(*v)).collect::<Vec<_>>(),
+        }));
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+
+        let mut output = Vec::new();
+        doc.save_to(&mut output).unwrap();
+        output
+    }
+
+    fn media_boxes(pdf: &[u8]) -> Vec<Object> {
+        let doc = Document::load_mem(pdf).unwrap();
+        doc.get_pages()
+            .into_values()
+            .map(|page_id| inherited_attribute(&doc, page_id, b"MediaBox").expect("page without MediaBox"))
+            .collect()
+    }
+
+    #[test]
+    fn merges_pages_in_order() {
+        let first = sample_pdf(2, [0, 0, 612, 792], "first");
+        let second = sample_pdf(3, [0, 0, 595, 842], "second");
+
+        let merged = merge_pdfs(&[first, second]).unwrap();
+        assert_eq!(page_count(&merged), Some(5));
+
+        let doc = Document::load_mem(&merged).unwrap();
+        let text: Vec<String> = doc.get_pages().keys().map(|page| doc.extract_text(&[*page]).unwrap()).collect();
+        assert!(text[0].contains("first 1"), "{:?}", text);
+        assert!(text[2].contains("second 1"), "{:?}", text);
+        assert!(text[4].contains("second 3"), "{:?}", text);
+    }
+
+    #[test]
+    fn merged_pages_keep_their_inherited_media_box() {
+        let letter = sample_pdf(1, [0, 0, 612, 792], "letter");
+        let a4 = sample_pdf(1, [0, 0, 595, 842], "a4");
+
+        let boxes = media_boxes(&merge_pdfs(&[letter, a4]).unwrap());
+        let widths: Vec<i64> = boxes.iter().map(|b| b.as_array().unwrap()[2].as_i64().unwrap()).collect();
+        assert_eq!(widths, vec![612, 595]);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert!(merge_pdfs(&[]).is_err());
+
+        let error = merge_pdfs(&[sample_pdf(1, [0, 0, 612, 792], "ok"), b"not a pdf".to_vec()]).unwrap_err();
+        assert!(error.to_string().contains("position 2"), "{}", error);
+    }
+}
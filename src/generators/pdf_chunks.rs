@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use futures::{StreamExt, TryStreamExt};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, Stream};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::models::{AggregateOperation, ReportSchema};
+use crate::templates::{GeneratedPdf, RenderPipeline, RenderRequest, RenderTimings};
+use crate::templates::templates::cell_width;
+use super::pdf::{inherited_attribute, merge_pdfs};
+use super::report_processor::{compute_summary, filter_and_sort, numeric_value, split_rows};
+
+/// Partes compiladas a la vez
+const PARALLEL_CHUNKS: usize = 4;
+
+/// Genera un reporte PDF por partes si tiene más de `chunk_rows` filas: los
+/// filtros, el orden y el resumen se calculan una vez sobre todas las filas,
+/// cada parte se compila por separado (varias a la vez) con los totales
+/// acumulados de las anteriores ("Van") y hasta su final ("Pasan"), y las
+/// partes se unen con la numeración de páginas continua. Con menos filas se
+/// genera de una vez
+pub async fn render_report(
+    pipeline: &dyn RenderPipeline,
+    request: RenderRequest,
+    chunk_rows: usize,
+) -> Result<GeneratedPdf> {
+    let row_count = request.data.get("data").and_then(Value::as_array).map_or(0, Vec::len);
+    if chunk_rows == 0 || row_count <= chunk_rows {
+        return pipeline.render(request).await;
+    }
+
+    let RenderRequest { tenant_id, template_id, mut data, output_filename } = request;
+    let rows = match data.get_mut("data").map(Value::take) {
+        Some(Value::Array(rows)) => rows,
+        _ => Vec::new(),
+    };
+    let mut schema: Option<ReportSchema> = match data.get("schema").filter(|s| !s.is_null()) {
+        Some(schema) => Some(serde_json::from_value(schema.clone()).context("Invalid report schema")?),
+        None => None,
+    };
+
+    let rows = match &mut schema {
+        Some(schema) => {
+            let rows = filter_and_sort(schema, &rows)?.into_owned();
+            insert_summary(&mut data, compute_summary(schema, &rows));
+            // Las partes ya llegan filtradas y ordenadas
+            schema.filters = None;
+            schema.sorting = None;
+            data["schema"] = serde_json::to_value(&*schema)?;
+            rows
+        },
+        None => rows,
+    };
+
+    let sum_fields: Vec<String> = schema
+        .iter()
+        .flat_map(|s| s.aggregations.iter().flatten())
+        .filter(|a| matches!(a.operation, AggregateOperation::Sum))
+        .map(|a| a.field.clone())
+        .collect();
+    let column_chars = widest_cells(&rows);
+    let chunks = split_rows(schema.as_ref(), rows, chunk_rows);
+    let count = chunks.len();
+    let base_name = output_filename.unwrap_or_else(|| format!("{}_{}", template_id, chrono::Utc::now().timestamp_millis()));
+    tracing::info!("Rendering {} rows of report {} in {} chunks", row_count, template_id, count);
+
+    let mut carried: HashMap<String, f64> = sum_fields.iter().map(|f| (f.clone(), 0.0)).collect();
+    let mut first_row = 0;
+    let mut requests = Vec::with_capacity(count);
+    for (index, rows) in chunks.into_iter().enumerate() {
+        let carried_in = carried.clone();
+        for field in &sum_fields {
+            let total: f64 = rows.iter().filter_map(|row| row.get(field)).filter_map(numeric_value).sum();
+            *carried.entry(field.clone()).or_default() += total;
+        }
+
+        let mut part = data.clone();
+        part["chunk"] = json!({
+            "index": index,
+            "count": count,
+            "firstRow": first_row,
+            "carriedIn": carried_in,
+            "carriedOut": carried,
+            "columnChars": column_chars,
+        });
+        first_row += rows.len();
+        part["data"] = Value::Array(rows);
+        requests.push(
+            RenderRequest::new(tenant_id, template_id.clone(), part)
+                .with_output_filename(format!("{}_part{}", base_name, index + 1)),
+        );
+    }
+
+    let parts: Vec<GeneratedPdf> = futures::stream::iter(requests)
+        .map(|request| pipeline.render(request))
+        .buffered(PARALLEL_CHUNKS)
+        .try_collect()
+        .await?;
+
+    let mut timings = RenderTimings::default();
+    let mut warnings = Vec::new();
+    let mut pdfs = Vec::with_capacity(parts.len());
+    let mut preview_png = None;
+    for (index, part) in parts.into_iter().enumerate() {
+        timings.render_ms += part.timings.render_ms;
+        timings.assets_ms = timings.assets_ms.max(part.timings.assets_ms);
+        timings.compile_ms += part.timings.compile_ms;
+        warnings.extend(part.warnings);
+        if index == 0 {
+            preview_png = part.preview_png;
+        }
+        pdfs.push(part.pdf);
+    }
+
+    let pdf = tokio::task::spawn_blocking(move || merge_pdfs(&pdfs).and_then(|pdf| number_pages(&pdf))).await??;
+    Ok(GeneratedPdf { pdf, preview_png, timings, warnings })
+}
+
+/// Caracteres de la celda más ancha de cada campo
+fn widest_cells(rows: &[Value]) -> HashMap<String, usize> {
+    let mut widths: HashMap<String, usize> = HashMap::new();
+    for row in rows.iter().filter_map(Value::as_object) {
+        for (field, value) in row {
+            let width = match value {
+                Value::String(text) => cell_width(text),
+                Value::Null => 0,
+                other => cell_width(&other.to_string()),
+            };
+            match widths.get_mut(field) {
+                Some(widest) => *widest = (*widest).max(width),
+                None => {
+                    widths.insert(field.clone(), width);
+                },
+            }
+        }
+    }
+    widths
+}
+
+/// Agrega las métricas calculadas al resumen de los datos
+fn insert_summary(data: &mut Value, computed: Vec<(String, f64)>) {
+    if computed.is_empty() {
+        return;
+    }
+    if !data.get("summary").is_some_and(Value::is_object) {
+        data["summary"] = json!({ "metrics": {}, "highlights": [] });
+    }
+    if !data["summary"]["metrics"].is_object() {
+        data["summary"]["metrics"] = json!({});
+    }
+    for (name, value) in computed {
+        data["summary"]["metrics"][name] = json!(value);
+    }
+}
+
+/// Estampa "n / total" al pie y al centro de cada página del PDF unido (las
+/// partes se compilan sin numeración)
+fn number_pages(pdf: &[u8]) -> Result<Vec<u8>> {
+    let mut doc = Document::load_mem(pdf).context("Invalid merged PDF")?;
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+
+    let pages: Vec<_> = doc.get_pages().into_values().collect();
+    let total = pages.len();
+    for (index, page_id) in pages.into_iter().enumerate() {
+        let media_box: Vec<f32> = inherited_attribute(&doc, page_id, b"MediaBox")
+            .and_then(|b| b.as_array().ok().map(|b| b.iter().filter_map(|v| v.as_float().ok()).collect()))
+            .filter(|b: &Vec<f32>| b.len() == 4)
+            .unwrap_or_else(|| vec![0.0, 0.0, 612.0, 792.0]);
+        let text = format!("{} / {}", index + 1, total);
+        // Ancho aproximado de Helvetica a 9pt
+        let x = media_box[0] + (media_box[2] - media_box[0] - text.len() as f32 * 9.0 * 0.55) / 2.0;
+        let y = media_box[1] + 28.0;
+
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["FPage".into(), 9.into()]),
+                Operation::new("Td", vec![x.into(), y.into()]),
+                Operation::new("Tj", vec![Object::string_literal(text)]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let form = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), 10000.into(), 10000.into()],
+                "Resources" => dictionary! {
+                    "Font" => dictionary! { "FPage" => font_id },
+                },
+            },
+            content.encode()?,
+        );
+        let form_id = doc.add_object(form);
+        doc.add_xobject(page_id, "XPageNumber", form_id)?;
+        doc.add_page_contents(page_id, b"\nq /XPageNumber Do Q\n".to_vec())?;
+    }
+
+    let mut output = Vec::new();
+    doc.save_to(&mut output)?;
+    Ok(output)
+}
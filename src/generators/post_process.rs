@@ -0,0 +1,225 @@
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use lopdf::content::{Content, Operation};
+use lopdf::encryption::crypt_filters::{Aes256CryptFilter, CryptFilter};
+use lopdf::{dictionary, Document, EncryptionState, EncryptionVersion, Object, Permissions, Stream};
+use rand::RngCore;
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::models::PostProcessStep;
+use crate::storage::s3::to_hex;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Aplica la cadena de post-procesado (firma, optimización, estampado,
+/// cifrado) a los PDFs generados; la cadena viene del request o del tenant
+pub struct PostProcessor {
+    signing_key: Option<String>,
+    tenant_chains: HashMap<i64, Vec<PostProcessStep>>,
+}
+
+impl PostProcessor {
+    pub fn new(signing_key: Option<String>, tenant_chains: HashMap<i64, Vec<PostProcessStep>>) -> Self {
+        PostProcessor { signing_key, tenant_chains }
+    }
+
+    /// `PDF_SIGNING_KEY` y `POST_PROCESS_TENANT_CHAINS` (JSON `{"tenant_id": [pasos]}`)
+    pub fn from_env() -> Result<Self> {
+        let signing_key = std::env::var("PDF_SIGNING_KEY").ok().filter(|s| !s.is_empty());
+
+        let tenant_chains = match std::env::var("POST_PROCESS_TENANT_CHAINS") {
+            Ok(json) if !json.trim().is_empty() => {
+                let raw: HashMap<String, Vec<PostProcessStep>> = serde_json::from_str(&json)?;
+                raw.into_iter()
+                    .map(|(tenant, steps)| Ok((tenant.parse::<i64>()?, steps)))
+                    .collect::<Result<_>>()?
+            },
+            _ => HashMap::new(),
+        };
+
+        Ok(Self::new(signing_key, tenant_chains))
+    }
+
+    /// Cadena efectiva: la del request o, si no trae, la del tenant
+    pub fn chain_for(&self, tenant_id: i64, requested: Option<&[PostProcessStep]>) -> Vec<PostProcessStep> {
+        match requested {
+            Some(steps) => steps.to_vec(),
+            None => self.tenant_chains.get(&tenant_id).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Ejecuta los pasos en orden sobre el PDF
+    pub async fn run(&self, pdf: Vec<u8>, steps: Vec<PostProcessStep>) -> Result<Vec<u8>> {
+        if steps.is_empty() {
+            return Ok(pdf);
+        }
+
+        validate_chain(&steps)?;
+        let signing_key = self.signing_key.clone();
+
+        tokio::task::spawn_blocking(move || apply_steps(pdf, &steps, signing_key.as_deref())).await?
+    }
+}
+
+/// El cifrado debe ir al final: los demás pasos no pueden editar un PDF cifrado
+fn validate_chain(steps: &[PostProcessStep]) -> Result<()> {
+    let encrypt_at = steps.iter().position(|s| matches!(s, PostProcessStep::Encrypt { .. }));
+
+    if let Some(idx) = encrypt_at {
+        if idx != steps.len() - 1 {
+            bail!("Encrypt must be the last post-processing step");
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_steps(pdf: Vec<u8>, steps: &[PostProcessStep], signing_key: Option<&str>) -> Result<Vec<u8>> {
+    let mut doc = Document::load_mem(&pdf).context("Invalid PDF for post-processing")?;
+
+    for step in steps {
+        match step {
+            PostProcessStep::Sign => {
+                let key = signing_key.context("Sign step requires PDF_SIGNING_KEY")?;
+                sign(&mut doc, key)?;
+            },
+            PostProcessStep::Optimize => optimize(&mut doc),
+            PostProcessStep::Stamp { text } => stamp(&mut doc, text)?,
+            PostProcessStep::Encrypt { user_password, owner_password, allow_print, allow_copy } => {
+                encrypt(&mut doc, user_password, owner_password.as_deref(), *allow_print, *allow_copy)?;
+            },
+        }
+    }
+
+    let mut output = Vec::new();
+    doc.save_to(&mut output)?;
+    Ok(output)
+}
+
+/// Firma el contenido (descomprimido) de las páginas y la guarda en Info
+fn sign(doc: &mut Document, key: &str) -> Result<()> {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC acepta llaves de cualquier tamaño");
+    for page_id in doc.page_iter().collect::<Vec<_>>() {
+        mac.update(&doc.get_page_content(page_id)?);
+    }
+    let signature = to_hex(&mac.finalize().into_bytes());
+
+    let info = info_dictionary(doc)?;
+    info.set("ContentSignature", Object::string_literal(format!("hmac-sha256:{}", signature)));
+    info.set("SignedAt", Object::string_literal(chrono::Utc::now().to_rfc3339()));
+
+    Ok(())
+}
+
+fn info_dictionary(doc: &mut Document) -> Result<&mut lopdf::Dictionary> {
+    let info_id = match doc.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(id) => id,
+        Err(_) => {
+            let id = doc.add_object(dictionary! {});
+            doc.trailer.set("Info", Object::Reference(id));
+            id
+        },
+    };
+
+    Ok(doc.get_dictionary_mut(info_id)?)
+}
+
+fn optimize(doc: &mut Document) {
+    doc.delete_zero_length_streams();
+    doc.prune_objects();
+    doc.renumber_objects();
+    doc.compress();
+}
+
+/// Estampa el texto al pie de cada página con un XObject de formulario
+/// que trae su propia fuente (no toca los recursos existentes)
+fn stamp(doc: &mut Document, text: &str) -> Result<()> {
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+
+    let content = Content {
+        operations: vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec!["FStamp".into(), 8.into()]),
+            Operation::new("g", vec![0.4.into()]),
+            Operation::new("Td", vec![36.into(), 18.into()]),
+            Operation::new("Tj", vec![Object::string_literal(text)]),
+            Operation::new("ET", vec![]),
+        ],
+    };
+
+    let form = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), 10000.into(), 10000.into()],
+            "Resources" => dictionary! {
+                "Font" => dictionary! { "FStamp" => font_id },
+            },
+        },
+        content.encode()?,
+    );
+    let form_id = doc.add_object(form);
+
+    for page_id in doc.page_iter().collect::<Vec<_>>() {
+        doc.add_xobject(page_id, "XStamp", form_id)?;
+        doc.add_page_contents(page_id, b"\nq /XStamp Do Q\n".to_vec())?;
+    }
+
+    Ok(())
+}
+
+/// Cifrado AES-256 (PDF 2.0); sin contraseña de propietario se genera una aleatoria
+fn encrypt(
+    doc: &mut Document,
+    user_password: &str,
+    owner_password: Option<&str>,
+    allow_print: bool,
+    allow_copy: bool,
+) -> Result<()> {
+    let mut rng = rand::thread_rng();
+
+    let mut file_key = [0u8; 32];
+    rng.fill_bytes(&mut file_key);
+
+    let generated_owner;
+    let owner_password = match owner_password {
+        Some(password) => password,
+        None => {
+            let mut bytes = [0u8; 16];
+            rng.fill_bytes(&mut bytes);
+            generated_owner = to_hex(&bytes);
+            &generated_owner
+        },
+    };
+
+    let mut permissions = Permissions::empty();
+    if allow_print {
+        permissions |= Permissions::PRINTABLE | Permissions::PRINTABLE_IN_HIGH_QUALITY;
+    }
+    if allow_copy {
+        permissions |= Permissions::COPYABLE;
+    }
+
+    let crypt_filter: Arc<dyn CryptFilter> = Arc::new(Aes256CryptFilter);
+    let version = EncryptionVersion::V5 {
+        encrypt_metadata: true,
+        crypt_filters: BTreeMap::from([(b"StdCF".to_vec(), crypt_filter)]),
+        file_encryption_key: &file_key,
+        stream_filter: b"StdCF".to_vec(),
+        string_filter: b"StdCF".to_vec(),
+        owner_password,
+        user_password,
+        permissions,
+    };
+
+    let state = EncryptionState::try_from(version)?;
+    doc.encrypt(&state)?;
+
+    Ok(())
+}
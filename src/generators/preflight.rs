@@ -0,0 +1,235 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use super::data_source::{load_rows, source_name};
+use crate::models::{DataSource, DataType, OutputFormat, ReportSchema};
+use crate::storage::storage_trait::Storage;
+
+/// Filas de datos por hoja de Excel (la primera es el encabezado)
+pub const EXCEL_MAX_ROWS: usize = 1_048_575;
+pub const EXCEL_MAX_COLUMNS: usize = 16_384;
+
+/// Filas de tabla por página en los reportes PDF
+const PDF_ROWS_PER_PAGE: usize = 40;
+
+/// Perfil de un origen de datos: filas, tamaño y columnas inferidas
+#[derive(Debug, Clone, Serialize)]
+pub struct DataProfile {
+    pub source: &'static str,
+    pub row_count: usize,
+    /// Tamaño del origen tal como se lee (comprimido o archivo); `None` si es inline
+    pub source_bytes: Option<u64>,
+    /// Tamaño de las filas como JSON (lo que recibe el generador)
+    pub json_bytes: u64,
+    pub columns: Vec<ColumnProfile>,
+    /// Campos visibles del esquema que no aparecen en ninguna fila
+    pub missing_fields: Vec<String>,
+    pub fetch_ms: u64,
+    /// Texto de todas las celdas (base para estimar CSV y Excel)
+    #[serde(skip)]
+    text_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnProfile {
+    pub field: String,
+    pub data_type: DataType,
+    pub null_count: usize,
+    /// Hay valores de más de un tipo (se infiere `string`)
+    pub mixed_types: bool,
+}
+
+/// Estimación de la generación en un formato
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatEstimate {
+    pub processing_time_ms: u64,
+    pub output_size_bytes: u64,
+    pub pages: Option<u64>,
+    pub limits: Vec<LimitCheck>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LimitCheck {
+    pub limit: &'static str,
+    pub max: u64,
+    pub value: u64,
+    pub exceeded: bool,
+}
+
+impl LimitCheck {
+    fn new(limit: &'static str, max: u64, value: u64) -> Self {
+        LimitCheck { limit, max, value, exceeded: value > max }
+    }
+}
+
+/// Límites del servicio contra los que se compara la estimación
+pub struct ServiceLimits {
+    pub sync_max_bytes: u64,
+    pub generation_timeout_ms: u64,
+}
+
+/// Lee el origen completo (igual que al generar) y perfila sus filas
+pub async fn profile_source(
+    source: &DataSource,
+    storage: &dyn Storage,
+    schema: Option<&ReportSchema>,
+) -> Result<DataProfile> {
+    let started = std::time::Instant::now();
+    let rows = load_rows(source, storage, schema).await?;
+    let fetch_ms = started.elapsed().as_millis() as u64;
+
+    let source_bytes = match source {
+        DataSource::Compressed { data, .. } => Some(data.len() as u64),
+        DataSource::R2Reference { bucket, key, size_bytes, .. } => storage
+            .list(bucket, Some(key))
+            .await
+            .ok()
+            .and_then(|objects| objects.into_iter().find(|o| &o.key == key))
+            .map(|o| o.size.max(0) as u64)
+            .or(size_bytes.map(|s| s as u64)),
+        _ => None,
+    };
+
+    let mut profile = profile_rows(&rows, schema);
+    profile.source = source_name(source);
+    profile.source_bytes = source_bytes;
+    profile.fetch_ms = fetch_ms;
+    Ok(profile)
+}
+
+fn profile_rows(rows: &[Value], schema: Option<&ReportSchema>) -> DataProfile {
+    // Tipos vistos por campo, en el orden en que aparecen
+    let mut order: Vec<String> = Vec::new();
+    let mut seen: BTreeMap<String, (Option<DataType>, usize, bool)> = BTreeMap::new();
+    let mut json_bytes = 0u64;
+    let mut text_bytes = 0u64;
+
+    for row in rows {
+        json_bytes += serde_json::to_vec(row).map(|v| v.len() as u64).unwrap_or(0);
+        let Some(object) = row.as_object() else { continue };
+
+        for (field, value) in object {
+            let entry = seen.entry(field.clone()).or_insert_with(|| {
+                order.push(field.clone());
+                (None, 0, false)
+            });
+
+            text_bytes += cell_text_len(value) + 1;
+            match infer_type(value) {
+                None => entry.1 += 1,
+                Some(data_type) => match &entry.0 {
+                    None => entry.0 = Some(data_type),
+                    Some(current) if same_type(current, &data_type) => {},
+                    Some(_) => entry.2 = true,
+                },
+            }
+        }
+    }
+
+    let columns = order
+        .into_iter()
+        .map(|field| {
+            let (data_type, null_count, mixed_types) = seen.remove(&field).unwrap_or_default();
+            ColumnProfile {
+                field,
+                data_type: if mixed_types { DataType::String } else { data_type.unwrap_or(DataType::String) },
+                null_count,
+                mixed_types,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let missing_fields = schema
+        .map(|schema| {
+            schema.columns.iter()
+                .filter(|c| c.visible && c.formula.is_none())
+                .filter(|c| !columns.iter().any(|p| p.field == c.field))
+                .map(|c| c.field.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    DataProfile {
+        source: "inline",
+        row_count: rows.len(),
+        source_bytes: None,
+        json_bytes,
+        columns,
+        missing_fields,
+        fetch_ms: 0,
+        text_bytes,
+    }
+}
+
+/// Tipo de un valor; `None` para nulos. Los textos se miran por contenido
+/// porque los CSV sin esquema llegan como texto
+fn infer_type(value: &Value) -> Option<DataType> {
+    match value {
+        Value::Null => None,
+        Value::Bool(_) => Some(DataType::Boolean),
+        Value::Number(_) => Some(DataType::Number),
+        Value::String(s) => {
+            let s = s.trim();
+            if s.is_empty() {
+                None
+            } else if s.parse::<f64>().is_ok() {
+                Some(DataType::Number)
+            } else if s.eq_ignore_ascii_case("true") || s.eq_ignore_ascii_case("false") {
+                Some(DataType::Boolean)
+            } else if DateTime::parse_from_rfc3339(s).is_ok() {
+                Some(DataType::DateTime)
+            } else if NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok() {
+                Some(DataType::Date)
+            } else {
+                Some(DataType::String)
+            }
+        },
+        _ => Some(DataType::String),
+    }
+}
+
+fn same_type(a: &DataType, b: &DataType) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+/// Largo del valor como texto en una celda
+fn cell_text_len(value: &Value) -> u64 {
+    match value {
+        Value::Null => 0,
+        Value::String(s) => s.len() as u64,
+        other => other.to_string().len() as u64,
+    }
+}
+
+/// Tiempo y tamaño aproximados de generar el perfil en `format`, con los
+/// límites que se alcanzarían. Los costos por fila son referencias fijas
+/// (no se calibran con el historial); la espera en cola no se incluye
+pub fn estimate(profile: &DataProfile, format: &OutputFormat, limits: &ServiceLimits) -> FormatEstimate {
+    let rows = profile.row_count as u64;
+    let columns = profile.columns.len() as u64;
+    let header_bytes: u64 = profile.columns.iter().map(|c| c.field.len() as u64 + 1).sum();
+
+    let (render_ms, output_size_bytes, pages) = match format {
+        OutputFormat::Csv | OutputFormat::Txt => (20 + rows / 500, header_bytes + profile.text_bytes, None),
+        OutputFormat::Excel => (150 + rows * 15 / 1_000, 8_192 + (header_bytes + profile.text_bytes) * 45 / 100, None),
+        OutputFormat::Pdf => {
+            let pages = rows.div_ceil(PDF_ROWS_PER_PAGE as u64).max(1);
+            (800 + pages * 20, 25_600 + pages * 4_096, Some(pages))
+        },
+    };
+    let processing_time_ms = profile.fetch_ms + render_ms;
+
+    let mut checks = vec![
+        LimitCheck::new("sync_max_bytes", limits.sync_max_bytes, profile.json_bytes),
+        LimitCheck::new("generation_timeout_ms", limits.generation_timeout_ms, processing_time_ms),
+    ];
+    if matches!(format, OutputFormat::Excel) {
+        checks.push(LimitCheck::new("excel_max_rows", EXCEL_MAX_ROWS as u64, rows));
+        checks.push(LimitCheck::new("excel_max_columns", EXCEL_MAX_COLUMNS as u64, columns));
+    }
+
+    FormatEstimate { processing_time_ms, output_size_bytes, pages, limits: checks }
+}
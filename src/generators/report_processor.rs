@@ -0,0 +1,345 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use crate::models::{compare_values, AggregateOperation, MaskingRule, MaskingStrategy, ReportSchema, SortDirection};
+use super::expression;
+
+/// Valor numérico de una celda (acepta números y textos como "1,250.00")
+pub fn numeric_value(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().replace(',', "").parse().ok(),
+        _ => None,
+    }
+}
+
+/// Aplica una operación de agregación sobre un campo de las filas
+pub fn aggregate(rows: &[Value], field: &str, operation: &AggregateOperation) -> f64 {
+    let values = || rows.iter().filter_map(|row| row.get(field).and_then(numeric_value));
+
+    match operation {
+        AggregateOperation::Sum => values().sum(),
+        AggregateOperation::Average => {
+            let (sum, count) = values().fold((0.0, 0usize), |(s, c), v| (s + v, c + 1));
+            if count == 0 { 0.0 } else { sum / count as f64 }
+        },
+        AggregateOperation::Count => {
+            if field == "*" {
+                rows.len() as f64
+            } else {
+                rows.iter().filter(|row| row.get(field).map(|v| !v.is_null()).unwrap_or(false)).count() as f64
+            }
+        },
+        AggregateOperation::Min => values().fold(f64::NAN, f64::min),
+        AggregateOperation::Max => values().fold(f64::NAN, f64::max),
+        AggregateOperation::Distinct => {
+            rows.iter()
+                .filter_map(|row| row.get(field))
+                .map(|v| v.to_string())
+                .collect::<HashSet<_>>()
+                .len() as f64
+        },
+    }
+}
+
+fn operation_from_name(name: &str) -> Result<AggregateOperation> {
+    Ok(match name {
+        "sum" => AggregateOperation::Sum,
+        "avg" | "average" => AggregateOperation::Average,
+        "count" => AggregateOperation::Count,
+        "min" => AggregateOperation::Min,
+        "max" => AggregateOperation::Max,
+        "distinct" => AggregateOperation::Distinct,
+        other => bail!("Unknown aggregate function '{}'", other),
+    })
+}
+
+/// Métricas del resumen ejecutivo: agregaciones del esquema (por alias o
+/// `op(campo)`) y luego las expresiones de `summary_metrics`, que pueden
+/// usar funciones de agregación o alias ya calculados. Las expresiones
+/// inválidas se omiten con un warning para no tumbar el reporte.
+pub fn compute_summary(schema: &ReportSchema, rows: &[Value]) -> Vec<(String, f64)> {
+    let mut metrics: Vec<(String, f64)> = Vec::new();
+
+    for aggregation in schema.aggregations.iter().flatten() {
+        let name = aggregation.alias.clone().unwrap_or_else(|| {
+            format!("{:?}({})", aggregation.operation, aggregation.field).to_lowercase()
+        });
+        metrics.push((name, aggregate(rows, &aggregation.field, &aggregation.operation)));
+    }
+
+    for metric in schema.summary_metrics.iter().flatten() {
+        let known: HashMap<String, f64> = metrics.iter().cloned().collect();
+        let result = expression::evaluate(
+            &metric.expression,
+            &|function, field| Ok(aggregate(rows, field, &operation_from_name(function)?)),
+            &|name| known.get(name).copied(),
+        );
+
+        match result {
+            Ok(value) => metrics.push((metric.name.clone(), value)),
+            Err(e) => tracing::warn!("Skipping summary metric '{}' ({}): {}", metric.name, metric.expression, e),
+        }
+    }
+
+    metrics
+}
+
+/// Filas que pasan todos los `filters` del esquema, ordenadas por `sorting`
+/// (varias columnas, orden estable; los nulos al final). Sin filtros ni
+/// orden se devuelven las mismas filas
+pub fn filter_and_sort<'a>(schema: &ReportSchema, rows: &'a [Value]) -> Result<Cow<'a, [Value]>> {
+    let filters = schema.filters.as_deref().unwrap_or(&[]);
+    let sort_by = schema.sorting.as_ref().map(|s| s.sort_by.as_slice()).unwrap_or(&[]);
+    if filters.is_empty() && sort_by.is_empty() {
+        return Ok(Cow::Borrowed(rows));
+    }
+    for filter in filters {
+        filter.validate().map_err(anyhow::Error::msg)?;
+    }
+
+    let mut selected: Vec<Value> = rows
+        .iter()
+        .filter(|row| filters.iter().all(|f| f.matches(row)))
+        .cloned()
+        .collect();
+
+    if !sort_by.is_empty() {
+        selected.sort_by(|a, b| {
+            sort_by
+                .iter()
+                .map(|column| {
+                    let (x, y) = (a.get(&column.field), b.get(&column.field));
+                    let null = |v: Option<&Value>| v.is_none_or(Value::is_null);
+                    match (null(x), null(y)) {
+                        (true, true) => Ordering::Equal,
+                        (true, false) => Ordering::Greater,
+                        (false, true) => Ordering::Less,
+                        _ => {
+                            let ordering = x.zip(y).and_then(|(x, y)| compare_values(x, y)).unwrap_or(Ordering::Equal);
+                            match column.direction {
+                                SortDirection::Asc => ordering,
+                                SortDirection::Desc => ordering.reverse(),
+                            }
+                        },
+                    }
+                })
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+
+    Ok(Cow::Owned(selected))
+}
+
+/// Fila de la tabla de un reporte agrupado
+#[derive(Debug, Clone)]
+pub enum GroupedRow {
+    /// Encabezado de grupo ("Cliente: ACME"); `level` 0 es el primer campo de `group_by`
+    Header { level: usize, label: String },
+    /// Fila de datos (índice en las filas originales)
+    Data(usize),
+    /// Cierre del grupo con las agregaciones del esquema sobre sus filas
+    /// (por campo); se muestra solo con `show_subtotals`
+    Subtotal { level: usize, label: String, rows: usize, values: Vec<(String, f64)> },
+}
+
+/// Texto de un valor de agrupación
+fn group_key(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn compare_group_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    match (a.and_then(numeric_value), b.and_then(numeric_value)) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        _ => group_key(a).cmp(&group_key(b)),
+    }
+}
+
+/// Filas ordenadas por los campos de `grouping.group_by` (orden estable), con
+/// un encabezado al abrir cada grupo y un subtotal al cerrarlo; `None` si el
+/// esquema no agrupa
+pub fn group_rows(schema: &ReportSchema, rows: &[Value]) -> Option<Vec<GroupedRow>> {
+    let grouping = schema.grouping.as_ref().filter(|g| !g.group_by.is_empty())?;
+    let fields = &grouping.group_by;
+
+    let mut order: Vec<usize> = (0..rows.len()).collect();
+    order.sort_by(|a, b| {
+        fields
+            .iter()
+            .map(|f| compare_group_values(rows[*a].get(f), rows[*b].get(f)))
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let header_of = |field: &str| {
+        schema.columns
+            .iter()
+            .find(|c| c.field == field)
+            .map(|c| c.header.clone())
+            .unwrap_or_else(|| field.to_string())
+    };
+    let headers: Vec<String> = fields.iter().map(|f| header_of(f)).collect();
+
+    let mut layout = Vec::with_capacity(rows.len() + fields.len() * 2);
+    // Grupos abiertos: valor y filas de cada nivel
+    let mut open: Vec<(String, Vec<usize>)> = Vec::new();
+    for index in order {
+        let keys: Vec<String> = fields.iter().map(|f| group_key(rows[index].get(f))).collect();
+        let changed = open
+            .iter()
+            .zip(&keys)
+            .position(|((open_key, _), key)| open_key != key)
+            .unwrap_or(open.len());
+
+        close_groups(&mut layout, &mut open, changed, schema, rows);
+        for (level, key) in keys.into_iter().enumerate().skip(changed) {
+            let value = if key.is_empty() { "(vacío)" } else { key.as_str() };
+            layout.push(GroupedRow::Header { level, label: format!("{}: {}", headers[level], value) });
+            open.push((key, Vec::new()));
+        }
+
+        for (_, members) in open.iter_mut() {
+            members.push(index);
+        }
+        layout.push(GroupedRow::Data(index));
+    }
+    close_groups(&mut layout, &mut open, 0, schema, rows);
+
+    Some(layout)
+}
+
+/// Cierra los grupos abiertos desde `level`, del más interno al más externo
+fn close_groups(
+    layout: &mut Vec<GroupedRow>,
+    open: &mut Vec<(String, Vec<usize>)>,
+    level: usize,
+    schema: &ReportSchema,
+    rows: &[Value],
+) {
+    while open.len() > level {
+        let Some((key, members)) = open.pop() else { break };
+        let group: Vec<Value> = members.iter().map(|i| rows[*i].clone()).collect();
+        let values = schema.aggregations
+            .iter()
+            .flatten()
+            .map(|a| (a.field.clone(), aggregate(&group, &a.field, &a.operation)))
+            .collect();
+        let label = if key.is_empty() { "Subtotal".to_string() } else { format!("Subtotal {}", key) };
+        layout.push(GroupedRow::Subtotal { level: open.len(), label, rows: group.len(), values });
+    }
+}
+
+/// Parte las filas en bloques de hasta `chunk_rows` para generarlos por
+/// separado. Con `grouping` las filas se ordenan como en `group_rows` y los
+/// cortes caen entre grupos del primer nivel: un grupo no se parte, aunque el
+/// bloque quede más grande
+pub fn split_rows(schema: Option<&ReportSchema>, mut rows: Vec<Value>, chunk_rows: usize) -> Vec<Vec<Value>> {
+    let chunk_rows = chunk_rows.max(1);
+    let fields = schema
+        .and_then(|s| s.grouping.as_ref())
+        .map(|g| g.group_by.as_slice())
+        .filter(|fields| !fields.is_empty());
+
+    let Some(fields) = fields else {
+        let mut chunks = Vec::with_capacity(rows.len().div_ceil(chunk_rows));
+        let mut rows = rows.into_iter();
+        loop {
+            let chunk: Vec<Value> = rows.by_ref().take(chunk_rows).collect();
+            if chunk.is_empty() {
+                return chunks;
+            }
+            chunks.push(chunk);
+        }
+    };
+
+    rows.sort_by(|a, b| {
+        fields
+            .iter()
+            .map(|f| compare_group_values(a.get(f), b.get(f)))
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let mut chunks = Vec::new();
+    let mut current: Vec<Value> = Vec::with_capacity(chunk_rows);
+    let mut current_key: Option<String> = None;
+    for row in rows {
+        let key = group_key(row.get(&fields[0]));
+        if current.len() >= chunk_rows && current_key.as_ref() != Some(&key) {
+            chunks.push(std::mem::replace(&mut current, Vec::with_capacity(chunk_rows)));
+        }
+        current_key = Some(key);
+        current.push(row);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Aplica una regla de enmascarado a un valor
+pub fn mask_value(value: &Value, rule: &MaskingRule) -> Value {
+    if value.is_null() {
+        return Value::Null;
+    }
+
+    let text = match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+
+    match rule.strategy {
+        MaskingStrategy::Redact => Value::String("****".to_string()),
+        MaskingStrategy::ShowLast => {
+            let visible = rule.visible_chars.unwrap_or(4);
+            let chars: Vec<char> = text.chars().collect();
+            let hidden = chars.len().saturating_sub(visible);
+            let tail: String = chars[hidden..].iter().collect();
+            Value::String(format!("{}{}", "*".repeat(hidden.max(1)), tail))
+        },
+    }
+}
+
+/// Enmascara las columnas sensibles de las filas según el rol de quien
+/// exporta; los roles en `unmasked_roles` ven los valores completos
+pub fn apply_masking(schema: &ReportSchema, rows: &mut [Value], role: &str) {
+    let rules: Vec<(&str, &MaskingRule)> = schema.columns
+        .iter()
+        .filter_map(|c| c.masking.as_ref().map(|rule| (c.field.as_str(), rule)))
+        .filter(|(_, rule)| !rule.unmasked_roles.iter().any(|r| r == role))
+        .collect();
+
+    if rules.is_empty() {
+        return;
+    }
+
+    for row in rows.iter_mut() {
+        let Some(object) = row.as_object_mut() else { continue };
+        for (field, rule) in &rules {
+            if let Some(value) = object.get_mut(*field) {
+                *value = mask_value(value, rule);
+            }
+        }
+    }
+}
+
+/// Enmascara un payload de reporte con esquema (`schema` + `rows` o `data`)
+pub fn mask_report_payload(data: &mut Value, role: &str) {
+    let Some(schema) = data.get("schema").and_then(|s| serde_json::from_value::<ReportSchema>(s.clone()).ok()) else {
+        return;
+    };
+
+    for key in ["rows", "data"] {
+        if let Some(rows) = data.get_mut(key).and_then(|r| r.as_array_mut()) {
+            apply_masking(&schema, rows, role);
+        }
+    }
+}
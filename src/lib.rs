@@ -3,6 +3,7 @@ pub mod generators;
 pub mod models;
 pub mod storage;
 pub mod templates;
+pub mod worker;
 
 // Re-export commonly used types
 pub use models::{
@@ -13,4 +14,5 @@ pub use models::{
 
 pub use generators::{PdfGenerator, ExcelGenerator};
 pub use templates::{TemplateEngine, TemplateData, InvoiceData, ReportData, ReceiptData};
-pub use storage::s3::S3Client;
\ No newline at end of file
+pub use storage::s3::S3Client;
+pub use storage::storage_trait::Storage;
\ No newline at end of file
@@ -1,25 +1,69 @@
 // use actix_cors::Cors;
 use actix_web::{middleware, web, App, HttpServer};
 use anyhow::Result;
+use document_generator::api::redaction::RedactedFields;
+use document_generator::worker::diagnostics::LogCaptureLayer;
 use document_generator::api::state::AppConfig;
+use document_generator::storage::presign::PresignPolicy;
+use document_generator::storage::database::{self, DatabaseConfig};
+use document_generator::storage::migrations::{self, StartupMigrations};
+use document_generator::api::template_handler::{restore_template_overrides, warm_up_templates, warmup_template_ids};
 use document_generator::api::{configure_routes, ApiState};
+use document_generator::api::handlers::spawn_job_dispatcher;
+use document_generator::worker::health as worker_health;
 use prometheus::Registry;
 use std::env;
 use tracing_subscriber::EnvFilter;
+use tracing_subscriber::prelude::*;
 
 #[actix_web::main]
 async fn main() -> Result<()> {
     // Load environment variables
     dotenv::dotenv().ok();
 
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .init();
+    // Initialize logging (PII redaction enabled unless LOG_REDACTION=false)
+    let env_filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let redact_logs = env::var("LOG_REDACTION")
+        .map(|v| v != "false")
+        .unwrap_or(true);
+
+    if redact_logs {
+        tracing_subscriber::fmt()
+            .with_env_filter(env_filter)
+            .fmt_fields(RedactedFields)
+            .finish()
+            .with(LogCaptureLayer)
+            .init();
+    } else {
+        tracing_subscriber::fmt()
+            .with_env_filter(env_filter)
+            .finish()
+            .with(LogCaptureLayer)
+            .init();
+    }
+
+    // Pool de Postgres compartido (DATABASE_URL); sin él todo queda en memoria
+    let database_config = DatabaseConfig::from_env();
+
+    // Esquema de Postgres: --migrate-only aplica las migraciones y termina (CI/CD);
+    // al arrancar se aplican o verifican según DATABASE_MIGRATIONS
+    if env::args().any(|arg| arg == "--migrate-only") {
+        match &database_config {
+            Some(database_config) => {
+                migrations::run(&database_config.url, StartupMigrations::Apply).await?;
+                tracing::info!("Database schema is at version {}", migrations::latest_version());
+            },
+            None => tracing::warn!("No database configured; nothing to migrate"),
+        }
+        return Ok(());
+    }
 
     tracing::info!("Starting Document Generator API");
+    if let Some(database_config) = &database_config {
+        migrations::run(&database_config.url, StartupMigrations::from_env()?).await?;
+    }
+    let database = database_config.map(database::connect).transpose()?;
 
     // Initialize Prometheus metrics
     let _prometheus = Registry::new();
@@ -32,7 +76,33 @@ async fn main() -> Result<()> {
     let config = load_config()?;
 
     // Initialize application state
-    let state = web::Data::new(ApiState::new(config).await?);
+    let state = web::Data::new(ApiState::new(config, database).await?);
+
+    // Despachador de la cola de trabajos asíncronos (por prioridad)
+    spawn_job_dispatcher(state.clone());
+
+    // Plantillas propias de los tenants (reemplazos de las incorporadas)
+    let restored = restore_template_overrides(&state).await;
+    if restored > 0 {
+        tracing::info!("Restored {} tenant template overrides", restored);
+    }
+
+    // Precompile templates in background to avoid the first-request latency spike
+    if env::var("WARMUP_ON_STARTUP").map(|v| v != "false").unwrap_or(true) {
+        let warmup_state = state.clone();
+        tokio::spawn(async move {
+            let template_ids = warmup_template_ids(&warmup_state.template_manager);
+            warm_up_templates(&warmup_state.template_manager, &template_ids).await;
+        });
+    }
+
+    // Listener de salud del procesamiento en segundo plano (WORKER_HEALTH_PORT=0 lo desactiva)
+    let health_port = env::var("WORKER_HEALTH_PORT")
+        .unwrap_or_else(|_| "8081".to_string())
+        .parse::<u16>()?;
+    if health_port > 0 {
+        worker_health::serve(state.worker_health.clone(), state.maintenance.clone(), health_port)?;
+    }
 
     // Get server settings
     let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
@@ -74,6 +144,9 @@ fn load_config() -> Result<AppConfig> {
         sync_timeout_ms: env::var("SYNC_TIMEOUT_MS")
             .unwrap_or_else(|_| "5000".to_string())
             .parse()?,
+        generation_timeout_ms: env::var("GENERATION_TIMEOUT_MS")
+            .unwrap_or_else(|_| "120000".to_string())
+            .parse()?,
         s3_bucket_documents: env::var("S3_BUCKET_DOCUMENTS")
             .unwrap_or_else(|_| "documents".to_string()),
         s3_bucket_temp: env::var("S3_BUCKET_TEMP").unwrap_or_else(|_| "temp-uploads".to_string()),
@@ -81,6 +154,27 @@ fn load_config() -> Result<AppConfig> {
             .unwrap_or_else(|_| "true".to_string())
             .parse::<bool>()
             .unwrap_or(true),
+        maintenance_mode: env::var("MAINTENANCE_MODE")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse::<bool>()
+            .unwrap_or(false),
+        organizations_strict: env::var("ORGANIZATIONS_STRICT")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse::<bool>()
+            .unwrap_or(false),
+        retry_max_attempts: env::var("RETRY_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse()?,
+        retry_base_delay_ms: env::var("RETRY_BASE_DELAY_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()?,
+        trash_retention_hours: env::var("TRASH_RETENTION_HOURS")
+            .unwrap_or_else(|_| "72".to_string())
+            .parse()?,
+        presign: PresignPolicy::from_env()?,
+        pdf_chunk_rows: env::var("PDF_CHUNK_ROWS")
+            .unwrap_or_else(|_| "20000".to_string())
+            .parse()?,
     };
 
     Ok(config)
@@ -1,12 +1,14 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum OutputFormat {
     Pdf,
     Excel,
     Csv,
+    /// Texto delimitado de los formatos DGII (solo `fiscal_report`)
+    Txt,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,7 +57,11 @@ pub enum NumberFormat {
     Indian,     // 12,34,567
 }
 
+/// Opciones de render de un PDF; los campos que faltan toman su valor por
+/// defecto. Sin `page_size`, `orientation` o `include_qr` cada plantilla usa
+/// los suyos
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RenderOptions {
     pub locale: String,           // "es-DO", "en-US"
     pub currency: String,         // "DOP", "USD"
@@ -66,6 +72,32 @@ pub struct RenderOptions {
     pub watermark: Option<String>,
     pub page_size: Option<PageSize>,
     pub orientation: Option<Orientation>,
+    /// Segundo idioma de las etiquetas ("en-US") para documentos bilingües
+    pub secondary_locale: Option<String>,
+    /// Cómo se combinan los dos idiomas de cada etiqueta
+    pub bilingual_layout: BilingualLayout,
+    /// Contraseñas y permisos del PDF (campos al mismo nivel que el resto)
+    #[serde(flatten)]
+    pub protection: PdfProtection,
+}
+
+/// Protección del PDF generado: con cualquiera de estos campos el PDF se
+/// cifra (AES-256) como último paso del post-procesado
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfProtection {
+    /// Contraseña para abrir el documento; sin ella abre cualquiera
+    pub user_password: Option<String>,
+    /// Contraseña para cambiar permisos; si falta se genera una aleatoria
+    pub owner_password: Option<String>,
+    pub no_print: bool,
+    pub no_copy: bool,
+}
+
+impl PdfProtection {
+    pub fn is_requested(&self) -> bool {
+        self.user_password.is_some() || self.owner_password.is_some() || self.no_print || self.no_copy
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,6 +110,16 @@ pub enum PageSize {
     Custom { width: f32, height: f32 }, // in mm
 }
 
+/// Etiquetas bilingües: `side_by_side` ("Fecha / Date") o `stacked` (la del
+/// segundo idioma debajo, más pequeña)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BilingualLayout {
+    #[default]
+    SideBySide,
+    Stacked,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Orientation {
@@ -93,10 +135,37 @@ impl Default for RenderOptions {
             currency_symbol: "$".to_string(),
             date_format: "DD/MM/YYYY".to_string(),
             number_format: NumberFormat::English,
-            include_qr: Some(false),
+            include_qr: None,
             watermark: None,
-            page_size: Some(PageSize::Letter),
-            orientation: Some(Orientation::Portrait),
+            page_size: None,
+            orientation: None,
+            secondary_locale: None,
+            bilingual_layout: BilingualLayout::default(),
+            protection: PdfProtection::default(),
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Opciones de los datos de una plantilla (`options`, u `options.render`
+    /// en reportes); por defecto si no vienen
+    pub fn from_data(data: &serde_json::Value) -> serde_json::Result<Self> {
+        Self::from_data_with_defaults(data, None)
+    }
+
+    /// Como `from_data`, con los campos de `defaults` (perfil del tenant) para
+    /// los que las opciones del documento no traen
+    pub fn from_data_with_defaults(
+        data: &serde_json::Value,
+        defaults: Option<&serde_json::Map<String, serde_json::Value>>,
+    ) -> serde_json::Result<Self> {
+        let mut merged = defaults.cloned().unwrap_or_default();
+        if let Some(options) = data.get("options").filter(|v| v.is_object()) {
+            let options = options.get("render").filter(|v| v.is_object()).unwrap_or(options);
+            if let Some(options) = options.as_object() {
+                merged.extend(options.iter().map(|(k, v)| (k.clone(), v.clone())));
+            }
         }
+        serde_json::from_value(serde_json::Value::Object(merged))
     }
 }
\ No newline at end of file
@@ -8,6 +8,8 @@ use uuid::Uuid;
 pub struct DocumentRequest {
     #[serde(default = "Uuid::new_v4")]
     pub id: Uuid,
+    /// Vacío: la plantilla integrada del tipo (`certificate`, `statement`)
+    #[serde(default)]
     pub template_id: String,
     pub document_type: DocumentType,
     pub data: serde_json::Value,
@@ -15,6 +17,70 @@ pub struct DocumentRequest {
     pub format: OutputFormat,
     pub callback_url: Option<String>,
     pub metadata: DocumentMetadata,
+    /// Cadena de post-procesado del PDF; si falta se usa la del tenant
+    #[serde(default)]
+    pub post_process: Option<Vec<PostProcessStep>>,
+    /// Referencia del cliente (p. ej. id de la factura en el ERP) para buscar
+    /// el documento sin guardar nuestro UUID
+    #[serde(default)]
+    pub external_ref: Option<String>,
+    /// Número del documento tomado de una secuencia del tenant al generar
+    #[serde(default)]
+    pub numbering: Option<DocumentNumbering>,
+}
+
+/// Asigna al documento el siguiente número de `sequence` y lo deja en
+/// `data[field]` para la plantilla; los reintentos reciben el mismo número
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentNumbering {
+    pub sequence: String,
+    #[serde(default = "default_number_field")]
+    pub field: String,
+}
+
+fn default_number_field() -> String {
+    "documentNumber".to_string()
+}
+
+/// Largo máximo de `external_ref`
+pub const EXTERNAL_REF_MAX_LEN: usize = 128;
+
+/// Valida una referencia externa: no vacía, sin caracteres de control y
+/// de a lo sumo `EXTERNAL_REF_MAX_LEN` caracteres
+pub fn validate_external_ref(external_ref: &str) -> Result<(), String> {
+    if external_ref.trim().is_empty() {
+        return Err("external_ref must not be empty".to_string());
+    }
+    if external_ref.chars().count() > EXTERNAL_REF_MAX_LEN {
+        return Err(format!("external_ref must be at most {} characters", EXTERNAL_REF_MAX_LEN));
+    }
+    if external_ref.chars().any(char::is_control) {
+        return Err("external_ref must not contain control characters".to_string());
+    }
+    Ok(())
+}
+
+/// Paso de post-procesado aplicado al PDF generado, en el orden declarado
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PostProcessStep {
+    /// Firma HMAC-SHA256 del contenido guardada en el diccionario Info
+    Sign,
+    /// Elimina objetos sin uso y comprime los streams
+    Optimize,
+    /// Cifrado AES-256 con contraseña (debe ser el último paso)
+    Encrypt {
+        user_password: String,
+        owner_password: Option<String>,
+        #[serde(default)]
+        allow_print: bool,
+        #[serde(default)]
+        allow_copy: bool,
+    },
+    /// Texto estampado al pie de cada página
+    Stamp {
+        text: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,9 +91,50 @@ pub enum DocumentType {
     Certificate,
     Statement,
     Receipt,
+    /// Formatos de envío a la DGII (606 compras, 607 ventas)
+    FiscalReport,
     Custom(String),
 }
 
+impl DocumentType {
+    /// Nombre usado en claves de almacenamiento y registros
+    pub fn as_str(&self) -> &str {
+        match self {
+            DocumentType::Invoice => "invoice",
+            DocumentType::Report => "report",
+            DocumentType::Certificate => "certificate",
+            DocumentType::Statement => "statement",
+            DocumentType::Receipt => "receipt",
+            DocumentType::FiscalReport => "fiscal_report",
+            DocumentType::Custom(name) => name,
+        }
+    }
+
+    /// Formatos de salida que se generan para el tipo; los demás se rechazan
+    /// al recibir el request
+    pub fn supported_formats(&self) -> &'static [OutputFormat] {
+        match self {
+            DocumentType::FiscalReport => &[OutputFormat::Txt, OutputFormat::Excel, OutputFormat::Pdf],
+            DocumentType::Report => &[OutputFormat::Excel, OutputFormat::Csv],
+            DocumentType::Custom(_) => &[OutputFormat::Pdf, OutputFormat::Csv],
+            DocumentType::Invoice
+            | DocumentType::Certificate
+            | DocumentType::Statement
+            | DocumentType::Receipt => &[OutputFormat::Pdf],
+        }
+    }
+
+    /// Plantilla integrada del tipo, para requests sin `template_id`
+    pub fn default_template(&self) -> Option<&'static str> {
+        match self {
+            DocumentType::Certificate => Some("certificate"),
+            DocumentType::Statement => Some("statement"),
+            DocumentType::FiscalReport => Some("dgii"),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentMetadata {
     #[serde(default)]
@@ -35,10 +142,17 @@ pub struct DocumentMetadata {
     #[serde(default)]
     pub user_id: i64,
     pub organization_id: Option<String>, // Optional for backward compatibility
+    /// Rol de quien solicita (lo fija la API desde la autenticación)
+    #[serde(default)]
+    pub role: Option<String>,
     #[serde(default = "chrono::Utc::now")]
     pub request_time: DateTime<Utc>,
     pub ttl_seconds: Option<i64>,
     pub tags: Option<HashMap<String, String>>,
+    /// Campos propios del tenant (vendedor, ruta, almacén); junto con `tags`
+    /// llegan a la plantilla en `documentMetadata`
+    #[serde(default)]
+    pub custom_fields: Option<HashMap<String, serde_json::Value>>,
 }
 
 impl Default for DocumentMetadata {
@@ -47,9 +161,11 @@ impl Default for DocumentMetadata {
             tenant_id: 0,
             user_id: 0,
             organization_id: None,
+            role: None,
             request_time: Utc::now(),
             ttl_seconds: Some(86400), // 24 hours
             tags: None,
+            custom_fields: None,
         }
     }
 }
@@ -59,10 +175,18 @@ pub struct DocumentResponse {
     pub id: Uuid,
     pub status: DocumentStatus,
     pub url: Option<String>,
+    /// XML e-CF firmable de las facturas fiscales 31/32
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xml_url: Option<String>,
+    /// CSV/TSV con las filas de un reporte Excel (`companion`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub companion_url: Option<String>,
     pub error: Option<String>,
     pub processing_time_ms: u64,
     pub created_at: DateTime<Utc>,
     pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum_sha256: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
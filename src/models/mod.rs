@@ -2,8 +2,12 @@ pub mod document;
 pub mod invoice;
 pub mod report;
 pub mod common;
+pub mod organization;
+pub mod render_profile;
 
 pub use document::*;
 pub use invoice::*;
 pub use report::*;
-pub use common::*;
\ No newline at end of file
+pub use common::*;
+pub use organization::*;
+pub use render_profile::*;
@@ -0,0 +1,110 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+use crate::storage::organizations::OrganizationBackend;
+
+/// Organización dentro de un tenant (sucursal, razón social, etc.)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Organization {
+    pub id: String,
+    pub tenant_id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizationRequest {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+/// Organización por defecto de un tenant cuando el request no trae una
+pub fn default_organization_id(tenant_id: i64) -> String {
+    format!("tenant_{}", tenant_id)
+}
+
+/// Valida el formato de un id de organización (se usa como segmento de clave S3)
+pub fn validate_organization_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || id.len() > 64 {
+        return Err("organization_id must be between 1 and 64 characters".to_string());
+    }
+
+    if !id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_') {
+        return Err(format!(
+            "Invalid organization_id '{}': only lowercase letters, digits, '-' and '_' are allowed",
+            id
+        ));
+    }
+
+    Ok(())
+}
+
+/// Organización inválida o (en modo estricto) no registrada
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct InvalidOrganization(pub String);
+
+/// Registro de organizaciones por tenant; los ids ya vistos quedan en memoria
+/// para no consultar el backend en cada request (no se borran)
+pub struct OrganizationRegistry {
+    backend: Arc<dyn OrganizationBackend>,
+    known: RwLock<HashSet<(i64, String)>>,
+    strict: bool,
+}
+
+impl OrganizationRegistry {
+    /// En modo estricto solo se aceptan organizaciones registradas previamente;
+    /// en otro caso los ids válidos se registran al primer uso
+    pub fn new(backend: Arc<dyn OrganizationBackend>, strict: bool) -> Self {
+        OrganizationRegistry {
+            backend,
+            known: RwLock::new(HashSet::new()),
+            strict,
+        }
+    }
+
+    pub async fn register(&self, tenant_id: i64, request: OrganizationRequest) -> anyhow::Result<Organization> {
+        validate_organization_id(&request.id).map_err(InvalidOrganization)?;
+
+        let organization = self.backend.upsert(tenant_id, &request.id, request.name.as_deref()).await?;
+        self.known.write().unwrap().insert((tenant_id, organization.id.clone()));
+        Ok(organization)
+    }
+
+    pub async fn list(&self, tenant_id: i64) -> anyhow::Result<Vec<Organization>> {
+        self.backend.list(tenant_id).await
+    }
+
+    /// Resuelve la organización efectiva de un request: la indicada (validada)
+    /// o la organización por defecto del tenant
+    pub async fn resolve(&self, tenant_id: i64, organization_id: Option<&str>) -> anyhow::Result<String> {
+        let Some(org_id) = organization_id.filter(|id| !id.is_empty()) else {
+            let default_id = default_organization_id(tenant_id);
+            if !self.is_known(tenant_id, &default_id) {
+                self.register(tenant_id, OrganizationRequest { id: default_id.clone(), name: None }).await?;
+            }
+            return Ok(default_id);
+        };
+
+        validate_organization_id(org_id).map_err(InvalidOrganization)?;
+        if self.is_known(tenant_id, org_id) {
+            return Ok(org_id.to_string());
+        }
+
+        if self.backend.get(tenant_id, org_id).await?.is_some() {
+            self.known.write().unwrap().insert((tenant_id, org_id.to_string()));
+        } else if self.strict {
+            return Err(InvalidOrganization(format!("Unknown organization '{}' for tenant {}", org_id, tenant_id)).into());
+        } else {
+            self.register(tenant_id, OrganizationRequest { id: org_id.to_string(), name: None }).await?;
+        }
+
+        Ok(org_id.to_string())
+    }
+
+    fn is_known(&self, tenant_id: i64, org_id: &str) -> bool {
+        self.known.read().unwrap().contains(&(tenant_id, org_id.to_string()))
+    }
+}
@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+use crate::storage::render_profiles::RenderProfileBackend;
+use super::RenderOptions;
+
+/// Campos de protección del PDF: son por documento, no van en un perfil
+const PROTECTION_FIELDS: [&str; 4] = ["user_password", "owner_password", "no_print", "no_copy"];
+
+/// Opciones de render por defecto de un tenant
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderProfile {
+    pub tenant_id: i64,
+    /// Campos de `RenderOptions` que fija el perfil
+    pub options: Map<String, Value>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Perfil de render inválido (400)
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct InvalidRenderProfile(pub String);
+
+/// Perfiles de render por tenant: los campos del perfil se aplican a los
+/// documentos cuyas opciones no los traen, así cada integración no repite
+/// locale, moneda, formato de fecha o papel
+pub struct RenderProfiles {
+    backend: Arc<dyn RenderProfileBackend>,
+}
+
+impl RenderProfiles {
+    pub fn new(backend: Arc<dyn RenderProfileBackend>) -> Self {
+        RenderProfiles { backend }
+    }
+
+    pub async fn get(&self, tenant_id: i64) -> anyhow::Result<Option<RenderProfile>> {
+        self.backend.get(tenant_id).await
+    }
+
+    /// Reemplaza el perfil del tenant; los campos deben ser opciones de render
+    /// válidas (sin contraseñas ni permisos del PDF)
+    pub async fn set(&self, tenant_id: i64, options: Value) -> anyhow::Result<RenderProfile> {
+        let Value::Object(options) = options else {
+            return Err(InvalidRenderProfile("The render profile must be a JSON object of render options".to_string()).into());
+        };
+        if let Some(field) = PROTECTION_FIELDS.iter().find(|f| options.contains_key(**f)) {
+            return Err(InvalidRenderProfile(format!("'{}' is per document and cannot be part of a render profile", field)).into());
+        }
+        serde_json::from_value::<RenderOptions>(Value::Object(options.clone()))
+            .map_err(|e| InvalidRenderProfile(format!("Invalid render options: {}", e)))?;
+
+        let profile = RenderProfile { tenant_id, options, updated_at: Utc::now() };
+        self.backend.put(&profile).await?;
+        Ok(profile)
+    }
+
+    pub async fn remove(&self, tenant_id: i64) -> anyhow::Result<bool> {
+        self.backend.remove(tenant_id).await
+    }
+
+    /// Campos del perfil del tenant, para `RenderOptions::from_data_with_defaults`
+    pub async fn defaults_for(&self, tenant_id: Option<i64>) -> anyhow::Result<Option<Map<String, Value>>> {
+        let Some(tenant_id) = tenant_id else {
+            return Ok(None);
+        };
+        Ok(self.backend.get(tenant_id).await?.map(|profile| profile.options))
+    }
+}
@@ -33,6 +33,10 @@ pub enum DataSource {
         format: FileFormat,
         row_count: Option<usize>,
         size_bytes: Option<usize>,
+        #[serde(default)]
+        csv: Option<CsvOptions>,
+        #[serde(default)]
+        excel: Option<ExcelOptions>,
     },
 
     /// Stream desde endpoint
@@ -68,6 +72,59 @@ pub enum FileFormat {
     Excel,
 }
 
+/// Lectura de archivos CSV
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CsvOptions {
+    /// Separador de campos (por defecto `,`)
+    pub delimiter: Option<char>,
+    /// Si la primera fila trae los encabezados (por defecto true); sin
+    /// encabezados las celdas se asignan a las columnas del esquema en orden
+    pub has_header: Option<bool>,
+}
+
+/// Archivo plano que acompaña al Excel de un reporte (para procesos ETL);
+/// se escribe en la misma pasada sobre las filas que el libro
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompanionFormat {
+    Csv,
+    Tsv,
+}
+
+impl CompanionFormat {
+    pub fn delimiter(&self) -> u8 {
+        match self {
+            CompanionFormat::Csv => b',',
+            CompanionFormat::Tsv => b'\t',
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CompanionFormat::Csv => "csv",
+            CompanionFormat::Tsv => "tsv",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            CompanionFormat::Csv => "text/csv; charset=utf-8",
+            CompanionFormat::Tsv => "text/tab-separated-values; charset=utf-8",
+        }
+    }
+}
+
+/// Lectura de hojas de cálculo (.xlsx, .xls, .ods)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExcelOptions {
+    /// Hoja a leer (por defecto la primera)
+    pub sheet: Option<String>,
+    /// Rango en notación A1 (`B2:F100`, o solo la celda inicial `B2`)
+    pub range: Option<String>,
+    /// Si la primera fila del rango trae los encabezados (por defecto true)
+    pub has_header: Option<bool>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthMethod {
     #[serde(rename = "type")]
@@ -90,6 +147,64 @@ pub struct ReportSchema {
     pub sorting: Option<SortingConfig>,
     pub aggregations: Option<Vec<Aggregation>>,
     pub filters: Option<Vec<FilterConfig>>,
+    pub header_groups: Option<Vec<HeaderGroup>>, // Encabezados de dos niveles
+    pub summary_metrics: Option<Vec<SummaryMetric>>, // Métricas calculadas tras agregar
+}
+
+/// Métrica del resumen definida como expresión sobre agregados,
+/// p. ej. `sum(total)/count(*)` o `(sum(ventas)-sum(costo))/sum(ventas)*100`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummaryMetric {
+    pub name: String,
+    pub expression: String,
+}
+
+/// Encabezado agrupado sobre columnas contiguas (p. ej. "Q1" sobre Ene/Feb/Mar)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeaderGroup {
+    pub title: String,
+    pub fields: Vec<String>,
+}
+
+/// Celda del primer nivel de encabezados: un grupo que abarca `len` columnas
+/// desde `start`, o una columna sin grupo (`title` None, ocupa ambos niveles)
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderSpan {
+    pub title: Option<String>,
+    pub start: usize,
+    pub len: usize,
+}
+
+impl ReportSchema {
+    /// Columnas visibles en orden de salida
+    pub fn visible_columns(&self) -> Vec<&ColumnDefinition> {
+        self.columns.iter().filter(|c| c.visible).collect()
+    }
+
+    pub fn has_header_groups(&self) -> bool {
+        self.header_groups.as_ref().map(|g| !g.is_empty()).unwrap_or(false)
+    }
+
+    /// Distribución del primer nivel de encabezados sobre las columnas visibles;
+    /// los campos de un grupo que no sean contiguos generan un tramo por bloque
+    pub fn header_spans(&self) -> Vec<HeaderSpan> {
+        let groups = self.header_groups.as_deref().unwrap_or(&[]);
+        let group_of = |field: &str| groups.iter().find(|g| g.fields.iter().any(|f| f == field));
+
+        let mut spans: Vec<HeaderSpan> = Vec::new();
+        for (idx, column) in self.visible_columns().iter().enumerate() {
+            let title = group_of(&column.field).map(|g| g.title.clone());
+
+            match spans.last_mut() {
+                Some(last) if title.is_some() && last.title == title && last.start + last.len == idx => {
+                    last.len += 1;
+                },
+                _ => spans.push(HeaderSpan { title, start: idx, len: 1 }),
+            }
+        }
+
+        spans
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -102,6 +217,56 @@ pub struct ColumnDefinition {
     pub alignment: Alignment,
     pub visible: bool,
     pub formula: Option<String>, // Para columnas calculadas
+    pub masking: Option<MaskingRule>, // Para columnas sensibles (tarjetas, cuentas)
+    /// Valores largos en la tabla del PDF; sin esto se muestran completos
+    #[serde(default)]
+    pub overflow: Option<CellOverflow>,
+}
+
+/// Manejo de valores largos en las celdas de la tabla del PDF
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CellOverflow {
+    pub strategy: OverflowStrategy,
+    /// Largo a partir del cual se aplica (por defecto 40; 20 para `wrap`)
+    pub max_chars: Option<usize>,
+}
+
+impl CellOverflow {
+    pub fn limit(&self) -> usize {
+        self.max_chars.filter(|n| *n > 1).unwrap_or(match self.strategy {
+            OverflowStrategy::Wrap => 20,
+            _ => 40,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowStrategy {
+    /// Corta las palabras o números más largos que el límite
+    Wrap,
+    /// Reduce la letra en proporción al largo (hasta 60%)
+    ShrinkToFit,
+    /// Recorta con "…" y deja el valor completo en una nota bajo la tabla
+    Ellipsis,
+}
+
+/// Enmascarado de una columna sensible según el rol de quien exporta
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskingRule {
+    pub strategy: MaskingStrategy,
+    /// Caracteres visibles al final para `show_last` (por defecto 4)
+    pub visible_chars: Option<usize>,
+    /// Roles que ven el valor completo
+    #[serde(default)]
+    pub unmasked_roles: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskingStrategy {
+    ShowLast,
+    Redact,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -191,6 +356,74 @@ pub enum FilterOperator {
     Between,
 }
 
+impl FilterConfig {
+    /// Verifica que `value` tenga la forma que pide el operador
+    pub fn validate(&self) -> Result<(), String> {
+        match (&self.operator, &self.value) {
+            (FilterOperator::In | FilterOperator::NotIn, serde_json::Value::Array(_)) => Ok(()),
+            (FilterOperator::In, _) => Err(format!("Filter 'in' on {} needs an array value", self.field)),
+            (FilterOperator::NotIn, _) => Err(format!("Filter 'not_in' on {} needs an array value", self.field)),
+            (FilterOperator::Between, serde_json::Value::Array(bounds)) if bounds.len() == 2 => Ok(()),
+            (FilterOperator::Between, _) => Err(format!("Filter 'between' on {} needs value [min, max]", self.field)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Si la fila pasa el filtro. Números (también como texto) se comparan
+    /// como números; el resto como texto, así las fechas ISO se ordenan bien.
+    /// `contains`, `starts_with` y `ends_with` no distinguen mayúsculas
+    pub fn matches(&self, row: &serde_json::Value) -> bool {
+        let cell = row.get(&self.field).unwrap_or(&serde_json::Value::Null);
+        let bounds = || match self.value.as_array().map(Vec::as_slice) {
+            Some([min, max]) => Some((min, max)),
+            _ => None,
+        };
+        let listed = || {
+            self.value
+                .as_array()
+                .is_some_and(|values| values.iter().any(|v| compare_values(cell, v).is_some_and(|o| o.is_eq())))
+        };
+        let ordering = compare_values(cell, &self.value);
+        let lower = |value: &serde_json::Value| text_of(value).to_lowercase();
+
+        match self.operator {
+            FilterOperator::Equals => ordering.is_some_and(|o| o.is_eq()),
+            FilterOperator::NotEquals => !ordering.is_some_and(|o| o.is_eq()),
+            FilterOperator::GreaterThan => ordering.is_some_and(|o| o.is_gt()),
+            FilterOperator::LessThan => ordering.is_some_and(|o| o.is_lt()),
+            FilterOperator::GreaterOrEqual => ordering.is_some_and(|o| o.is_ge()),
+            FilterOperator::LessOrEqual => ordering.is_some_and(|o| o.is_le()),
+            FilterOperator::Contains => !cell.is_null() && lower(cell).contains(&lower(&self.value)),
+            FilterOperator::StartsWith => !cell.is_null() && lower(cell).starts_with(&lower(&self.value)),
+            FilterOperator::EndsWith => !cell.is_null() && lower(cell).ends_with(&lower(&self.value)),
+            FilterOperator::In => listed(),
+            FilterOperator::NotIn => !listed(),
+            FilterOperator::Between => bounds().is_some_and(|(min, max)| {
+                compare_values(cell, min).is_some_and(|o| o.is_ge()) && compare_values(cell, max).is_some_and(|o| o.is_le())
+            }),
+        }
+    }
+}
+
+/// Orden entre dos valores de celda: numérico si ambos son números (o texto
+/// numérico), si no como texto; `None` si alguno es nulo
+pub fn compare_values(a: &serde_json::Value, b: &serde_json::Value) -> Option<std::cmp::Ordering> {
+    if a.is_null() || b.is_null() {
+        return None;
+    }
+    match (number_of(a), number_of(b)) {
+        (Some(x), Some(y)) => Some(x.total_cmp(&y)),
+        _ => Some(text_of(a).cmp(&text_of(b))),
+    }
+}
+
+fn text_of(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.trim().to_string(),
+        other => other.to_string(),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReportOptions {
     pub render: RenderOptions,
@@ -200,8 +433,64 @@ pub struct ReportOptions {
     pub freeze_headers: bool,      // Para Excel
     pub auto_filter: bool,         // Para Excel
     pub conditional_formatting: Option<Vec<ConditionalFormat>>,
+    pub footnotes: Option<Vec<Footnote>>,
+    /// Gráficos nativos del libro Excel, a la derecha de la tabla
+    #[serde(default)]
+    pub charts: Option<Vec<ReportChart>>,
+}
+
+/// Gráfico de Excel sobre las filas del reporte: categorías de una columna y
+/// una serie por cada columna de valores
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportChart {
+    pub chart_type: ReportChartType,
+    pub title: Option<String>,
+    /// Campo con las categorías (eje X, o porciones del pie)
+    pub category_field: String,
+    /// Campos con los valores; cada uno es una serie con el nombre de su encabezado
+    pub series: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportChartType {
+    Column,
+    Bar,
+    Line,
+    Area,
+    Pie,
+    Doughnut,
+    Scatter,
 }
 
+/// Nota al pie del reporte; con `field` se asocia a una columna
+/// (marcador en el encabezado del PDF, comentario de celda en Excel)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Footnote {
+    pub text: String,
+    pub field: Option<String>,
+    pub marker: Option<String>,
+}
+
+/// Marcador de cada nota: el indicado, o numeración secuencial para las
+/// notas de columna; las notas generales sin marcador quedan vacías
+pub fn footnote_markers(footnotes: &[Footnote]) -> Vec<String> {
+    let mut next = 1;
+    footnotes
+        .iter()
+        .map(|note| match (&note.marker, &note.field) {
+            (Some(marker), _) => marker.clone(),
+            (None, Some(_)) => {
+                let marker = next.to_string();
+                next += 1;
+                marker
+            },
+            (None, None) => String::new(),
+        })
+        .collect()
+}
+
+/// Formato de las celdas de `field` que cumplen `condition` contra `value`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConditionalFormat {
     pub field: String,
@@ -210,6 +499,110 @@ pub struct ConditionalFormat {
     pub format: FormatStyle,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConditionOperator {
+    Equals,
+    NotEquals,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    /// `value: [min, max]`, inclusive
+    Between,
+    /// Texto que contiene `value`, sin distinguir mayúsculas
+    Contains,
+}
+
+impl ConditionalFormat {
+    /// `equals`, `not_equals`, `greater_than`, `greater_than_or_equal`,
+    /// `less_than`, `less_than_or_equal`, `between` o `contains` (también
+    /// `=`, `!=`, `>`, `>=`, `<`, `<=`)
+    pub fn operator(&self) -> Result<ConditionOperator, String> {
+        let operator = match self.condition.trim().to_ascii_lowercase().as_str() {
+            "equals" | "=" | "==" => ConditionOperator::Equals,
+            "not_equals" | "!=" | "<>" => ConditionOperator::NotEquals,
+            "greater_than" | ">" => ConditionOperator::GreaterThan,
+            "greater_than_or_equal" | ">=" => ConditionOperator::GreaterThanOrEqual,
+            "less_than" | "<" => ConditionOperator::LessThan,
+            "less_than_or_equal" | "<=" => ConditionOperator::LessThanOrEqual,
+            "between" => ConditionOperator::Between,
+            "contains" => ConditionOperator::Contains,
+            other => return Err(format!("Unknown condition '{}' for field {}", other, self.field)),
+        };
+
+        let numeric = matches!(
+            operator,
+            ConditionOperator::GreaterThan
+                | ConditionOperator::GreaterThanOrEqual
+                | ConditionOperator::LessThan
+                | ConditionOperator::LessThanOrEqual
+        );
+        if numeric && number_of(&self.value).is_none() {
+            return Err(format!("Condition '{}' on {} needs a numeric value", self.condition, self.field));
+        }
+        if operator == ConditionOperator::Between && self.bounds().is_none() {
+            return Err(format!("Condition 'between' on {} needs value [min, max]", self.field));
+        }
+        Ok(operator)
+    }
+
+    /// `value` como número, si lo es (o es texto numérico)
+    pub fn numeric_value(&self) -> Option<f64> {
+        number_of(&self.value)
+    }
+
+    /// Límites de `between`
+    pub fn bounds(&self) -> Option<(f64, f64)> {
+        match self.value.as_array().map(Vec::as_slice) {
+            Some([min, max]) => Some((number_of(min)?, number_of(max)?)),
+            _ => None,
+        }
+    }
+
+    /// Si el valor de una celda cumple la condición; los números que llegan
+    /// como texto se comparan como números
+    pub fn matches(&self, cell: &serde_json::Value) -> bool {
+        let Ok(operator) = self.operator() else {
+            return false;
+        };
+        let text = |value: &serde_json::Value| match value {
+            serde_json::Value::String(s) => s.trim().to_string(),
+            other => other.to_string(),
+        };
+        let compare = || number_of(cell).zip(number_of(&self.value)).map(|(a, b)| a.total_cmp(&b));
+
+        match operator {
+            ConditionOperator::Equals => match compare() {
+                Some(ordering) => ordering.is_eq(),
+                None => text(cell) == text(&self.value),
+            },
+            ConditionOperator::NotEquals => match compare() {
+                Some(ordering) => ordering.is_ne(),
+                None => text(cell) != text(&self.value),
+            },
+            ConditionOperator::GreaterThan => compare().is_some_and(|o| o.is_gt()),
+            ConditionOperator::GreaterThanOrEqual => compare().is_some_and(|o| o.is_ge()),
+            ConditionOperator::LessThan => compare().is_some_and(|o| o.is_lt()),
+            ConditionOperator::LessThanOrEqual => compare().is_some_and(|o| o.is_le()),
+            ConditionOperator::Between => match (number_of(cell), self.bounds()) {
+                (Some(n), Some((min, max))) => n >= min && n <= max,
+                _ => false,
+            },
+            ConditionOperator::Contains => text(cell).to_lowercase().contains(&text(&self.value).to_lowercase()),
+        }
+    }
+}
+
+fn number_of(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Estilo de un formato condicional; los colores van en hex (`#C00000`).
+/// `icon` es un texto antepuesto al valor (solo en PDF)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FormatStyle {
     pub background_color: Option<String>,
@@ -218,6 +611,25 @@ pub struct FormatStyle {
     pub icon: Option<String>,
 }
 
+impl FormatStyle {
+    pub fn background_rgb(&self) -> Result<Option<u32>, String> {
+        self.background_color.as_deref().map(parse_hex_color).transpose()
+    }
+
+    pub fn font_rgb(&self) -> Result<Option<u32>, String> {
+        self.font_color.as_deref().map(parse_hex_color).transpose()
+    }
+}
+
+/// `#RRGGBB` o `RRGGBB` → 0xRRGGBB
+fn parse_hex_color(color: &str) -> Result<u32, String> {
+    let hex = color.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid color '{}': use #RRGGBB", color));
+    }
+    u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid color '{}': use #RRGGBB", color))
+}
+
 // Helper module for base64 encoding/decoding
 mod base64 {
     use serde::{Deserialize, Deserializer, Serializer};
@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+use super::database::Database;
+
+/// Máximo de accesos retenidos por documento cuando la bitácora vive en memoria
+const MAX_ENTRIES_PER_DOCUMENT: usize = 1000;
+
+/// Registro de un acceso (presign/descarga) a un documento
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessEntry {
+    pub document_id: Uuid,
+    pub tenant_id: i64,
+    pub user_id: i64,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub accessed_at: DateTime<Utc>,
+}
+
+/// Bitácora de accesos a documentos para auditoría fiscal ("quién descargó qué y cuándo").
+/// Con `DATABASE_URL` cada acceso se escribe en la tabla
+/// `document_accesses` de Postgres antes de entregar la URL; sin ella queda en
+/// memoria (solo desarrollo)
+#[derive(Default)]
+pub struct AccessLog {
+    entries: RwLock<HashMap<Uuid, Vec<AccessEntry>>>,
+    database: Option<Database>,
+}
+
+impl AccessLog {
+    /// Tabla `document_accesses` del pool compartido; sin él, en memoria
+    pub fn new(database: Option<Database>) -> Self {
+        if database.is_none() {
+            tracing::warn!("DATABASE_URL not set: the document access log is kept in memory only");
+        }
+        AccessLog { entries: RwLock::default(), database }
+    }
+
+    /// Registra el acceso; con Postgres falla si no se pudo escribir, así no
+    /// se entrega un documento sin dejar rastro
+    pub async fn record(&self, entry: AccessEntry) -> Result<()> {
+        tracing::info!(
+            document_id = %entry.document_id,
+            tenant_id = entry.tenant_id,
+            user_id = entry.user_id,
+            "Document accessed"
+        );
+
+        if let Some(database) = &self.database {
+            let db = database.get().await.context("Failed to connect to the access log database")?;
+            db.execute(
+                "INSERT INTO document_accesses (document_id, tenant_id, user_id, ip, user_agent, accessed_at)
+                 VALUES ($1, $2, $3, $4, $5, $6)",
+                &[&entry.document_id, &entry.tenant_id, &entry.user_id, &entry.ip, &entry.user_agent, &entry.accessed_at],
+            ).await.context("Failed to record the document access")?;
+            return Ok(());
+        }
+
+        let mut entries = self.entries.write().unwrap();
+        let log = entries.entry(entry.document_id).or_default();
+        if log.len() >= MAX_ENTRIES_PER_DOCUMENT {
+            log.remove(0);
+        }
+        log.push(entry);
+        Ok(())
+    }
+
+    /// Accesos de un documento visibles para el tenant dado, del más antiguo al más reciente
+    pub async fn for_document(&self, document_id: &Uuid, tenant_id: i64) -> Result<Vec<AccessEntry>> {
+        if let Some(database) = &self.database {
+            let db = database.get().await.context("Failed to connect to the access log database")?;
+            let rows = db.query(
+                "SELECT user_id, ip, user_agent, accessed_at FROM document_accesses
+                 WHERE document_id = $1 AND tenant_id = $2 ORDER BY accessed_at, id",
+                &[document_id, &tenant_id],
+            ).await?;
+            return Ok(rows
+                .iter()
+                .map(|row| AccessEntry {
+                    document_id: *document_id,
+                    tenant_id,
+                    user_id: row.get(0),
+                    ip: row.get(1),
+                    user_agent: row.get(2),
+                    accessed_at: row.get(3),
+                })
+                .collect());
+        }
+
+        Ok(self.entries
+            .read()
+            .unwrap()
+            .get(document_id)
+            .map(|log| {
+                log.iter()
+                    .filter(|e| e.tenant_id == tenant_id)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,220 @@
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Firma URLs con HMAC-SHA256 y expiración (`?verify={exp}-{firma}`); las
+/// valida la propia API (endpoint `/files` del storage local)
+#[derive(Clone)]
+pub struct CdnSigner {
+    key: Vec<u8>,
+}
+
+impl CdnSigner {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        CdnSigner { key: key.into() }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        std::env::var("CDN_SIGNING_KEY")
+            .ok()
+            .filter(|k| !k.is_empty())
+            .map(CdnSigner::new)
+    }
+
+    /// Construye la URL firmada para `{cdn_url}/{key}` válida por `expires_in_seconds`
+    pub fn sign(&self, cdn_url: &str, key: &str, expires_in_seconds: u64) -> String {
+        let expires = chrono::Utc::now().timestamp() as u64 + expires_in_seconds;
+        let path = format!("/{}", key.trim_start_matches('/'));
+
+        format!(
+            "{}{}?verify={}-{}",
+            cdn_url.trim_end_matches('/'),
+            path,
+            expires,
+            self.signature(&path, expires)
+        )
+    }
+
+    /// Verifica una firma producida por `sign` (útil para el worker del edge y pruebas)
+    pub fn verify(&self, path: &str, token: &str) -> bool {
+        let Some((expires, signature)) = token.split_once('-') else {
+            return false;
+        };
+        let Ok(expires) = expires.parse::<u64>() else {
+            return false;
+        };
+
+        if expires < chrono::Utc::now().timestamp() as u64 {
+            return false;
+        }
+
+        let Ok(signature) = URL_SAFE_NO_PAD.decode(signature) else {
+            return false;
+        };
+
+        let mut mac = self.mac();
+        mac.update(format!("{}{}", path, expires).as_bytes());
+        mac.verify_slice(&signature).is_ok()
+    }
+
+    fn signature(&self, path: &str, expires: u64) -> String {
+        let mut mac = self.mac();
+        mac.update(format!("{}{}", path, expires).as_bytes());
+        URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.key).expect("HMAC acepta llaves de cualquier tamaño")
+    }
+}
+
+/// CDN delante del bucket (`CDN_URL`): las descargas salen del CDN con URLs
+/// firmadas para su validación en el edge
+#[derive(Clone)]
+pub struct Cdn {
+    pub url: String,
+    pub signer: CloudflareSigner,
+}
+
+impl Cdn {
+    /// `None` sin `CDN_URL`. Sin `CDN_SIGNING_KEY` tampoco se usa el CDN (las
+    /// descargas quedan con URLs presignadas de S3): una URL sin firma lo
+    /// dejaría abierto
+    pub fn from_env() -> Option<Self> {
+        let url = std::env::var("CDN_URL").ok().filter(|url| !url.trim().is_empty())?;
+        let Some(signer) = CloudflareSigner::from_env() else {
+            tracing::warn!("CDN_URL set without CDN_SIGNING_KEY: downloads will use presigned S3 URLs");
+            return None;
+        };
+        Some(Cdn { url, signer })
+    }
+
+    /// URL firmada de `{url}/{key}` válida por `expires_in_seconds` (como
+    /// máximo el TTL de la regla del edge)
+    pub fn signed_url(&self, key: &str, expires_in_seconds: u64) -> String {
+        self.signer.sign(&self.url, key, expires_in_seconds)
+    }
+}
+
+/// Tokens del esquema nativo de Cloudflare, `is_timed_hmac_valid_v0`: la firma
+/// es HMAC-SHA256 en base64 de `{path}{emitido}` y va como
+/// `?verify={emitido}-{firma}`. La regla del WAF valida con
+/// `is_timed_hmac_valid_v0("<CDN_SIGNING_KEY>", http.request.uri, <CDN_TOKEN_TTL_SECS>, http.request.timestamp.sec, 8)`
+#[derive(Clone)]
+pub struct CloudflareSigner {
+    key: Vec<u8>,
+    /// TTL configurado en la regla del edge
+    ttl: u64,
+}
+
+impl CloudflareSigner {
+    pub fn new(key: impl Into<Vec<u8>>, ttl: u64) -> Self {
+        CloudflareSigner { key: key.into(), ttl }
+    }
+
+    /// `CDN_SIGNING_KEY` y `CDN_TOKEN_TTL_SECS` (3600 por defecto; debe coincidir con la regla)
+    pub fn from_env() -> Option<Self> {
+        let key = std::env::var("CDN_SIGNING_KEY").ok().filter(|k| !k.is_empty())?;
+        let ttl = std::env::var("CDN_TOKEN_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3600);
+        Some(CloudflareSigner::new(key, ttl))
+    }
+
+    /// URL firmada de `{cdn_url}/{key}`. El edge acepta el token hasta
+    /// `emitido + ttl`, así que para vigencias menores que el TTL la emisión
+    /// se adelanta: la URL vence a los `expires_in_seconds`
+    pub fn sign(&self, cdn_url: &str, key: &str, expires_in_seconds: u64) -> String {
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.sign_at(cdn_url, key, now.saturating_sub(self.ttl.saturating_sub(expires_in_seconds)))
+    }
+
+    fn sign_at(&self, cdn_url: &str, key: &str, issued: u64) -> String {
+        let path = format!("/{}", key.trim_start_matches('/'));
+        format!(
+            "{}{}?verify={}-{}",
+            cdn_url.trim_end_matches('/'),
+            path,
+            issued,
+            url_encode(&STANDARD.encode(self.mac(&path, issued).finalize().into_bytes())),
+        )
+    }
+
+    /// Valida un token `{emitido}-{firma}` como lo hace el edge
+    pub fn verify(&self, path: &str, token: &str) -> bool {
+        self.verify_at(path, token, chrono::Utc::now().timestamp() as u64)
+    }
+
+    fn verify_at(&self, path: &str, token: &str, now: u64) -> bool {
+        let Some((issued, signature)) = token.split_once('-') else {
+            return false;
+        };
+        let Ok(issued) = issued.parse::<u64>() else {
+            return false;
+        };
+        if issued > now || now - issued > self.ttl {
+            return false;
+        }
+        let Ok(signature) = STANDARD.decode(url_decode(signature)) else {
+            return false;
+        };
+
+        self.mac(path, issued).verify_slice(&signature).is_ok()
+    }
+
+    fn mac(&self, path: &str, issued: u64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC acepta llaves de cualquier tamaño");
+        mac.update(format!("{}{}", path, issued).as_bytes());
+        mac
+    }
+}
+
+/// Escapa los caracteres de base64 que no pueden ir tal cual en la query
+fn url_encode(value: &str) -> String {
+    value.replace('+', "%2B").replace('/', "%2F").replace('=', "%3D")
+}
+
+fn url_decode(value: &str) -> String {
+    value.replace("%2B", "+").replace("%2F", "/").replace("%3D", "=")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cloudflare_token_matches_the_edge_scheme() {
+        // HMAC-SHA256("secret", "/docs/a.pdf1700000000") en base64, escapado
+        let signer = CloudflareSigner::new("secret", 3600);
+        assert_eq!(
+            signer.sign_at("https://cdn.example.com/", "docs/a.pdf", 1_700_000_000),
+            "https://cdn.example.com/docs/a.pdf?verify=1700000000-mBQ8TQPbwfML6X%2B9LuAa1tOyOT6Leh2GtmQfA51FLiQ%3D"
+        );
+    }
+
+    #[test]
+    fn cloudflare_token_expires_with_the_edge_ttl() {
+        let signer = CloudflareSigner::new("secret", 3600);
+        let token = "1700000000-mBQ8TQPbwfML6X%2B9LuAa1tOyOT6Leh2GtmQfA51FLiQ%3D";
+
+        assert!(signer.verify_at("/docs/a.pdf", token, 1_700_000_000 + 3600));
+        assert!(!signer.verify_at("/docs/a.pdf", token, 1_700_000_000 + 3601));
+        assert!(!signer.verify_at("/docs/b.pdf", token, 1_700_000_000));
+        assert!(!CloudflareSigner::new("other", 3600).verify_at("/docs/a.pdf", token, 1_700_000_000));
+    }
+
+    #[test]
+    fn shorter_expiry_backdates_the_token() {
+        let signer = CloudflareSigner::new("secret", 3600);
+        let url = signer.sign("https://cdn.example.com", "docs/a.pdf", 600);
+        let token = url.split_once("?verify=").unwrap().1;
+        let now = chrono::Utc::now().timestamp() as u64;
+
+        assert!(signer.verify_at("/docs/a.pdf", token, now));
+        assert!(!signer.verify_at("/docs/a.pdf", token, now + 601));
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use super::keys::signing_certificate_key;
+use super::storage_trait::Storage;
+use crate::generators::pades::SigningIdentity;
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Cada cuánto se vuelve a buscar el certificado de un tenant que no tenía
+const MISSING_RECHECK: Duration = Duration::from_secs(300);
+
+/// Identidad de un tenant y cuándo se leyó; `None` = no tenía certificado
+type CachedIdentity = (Option<Arc<SigningIdentity>>, Instant);
+
+/// Contenido cifrado del objeto: el PKCS#12 y su contraseña
+#[derive(Serialize, Deserialize)]
+struct StoredCertificate {
+    pkcs12: String,
+    password: String,
+}
+
+/// Certificados de firma (PKCS#12) de los tenants, guardados en el storage
+/// cifrados con AES-256-GCM bajo `SIGNING_MASTER_KEY`. Sin llave maestra la
+/// firma PAdES queda deshabilitada
+pub struct CertificateStore {
+    storage: Arc<dyn Storage>,
+    bucket: String,
+    master_key: Option<[u8; 32]>,
+    /// Identidades ya descifradas por tenant
+    cache: RwLock<HashMap<i64, CachedIdentity>>,
+}
+
+impl CertificateStore {
+    pub fn new(storage: Arc<dyn Storage>, bucket: String, master_key: Option<[u8; 32]>) -> Self {
+        CertificateStore {
+            storage,
+            bucket,
+            master_key,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// `SIGNING_MASTER_KEY`: 32 bytes en hex (64 caracteres)
+    pub fn from_env(storage: Arc<dyn Storage>, bucket: String) -> Result<Self> {
+        let master_key = match std::env::var("SIGNING_MASTER_KEY") {
+            Ok(hex) if !hex.trim().is_empty() => Some(parse_master_key(hex.trim())?),
+            _ => None,
+        };
+
+        Ok(Self::new(storage, bucket, master_key))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.master_key.is_some()
+    }
+
+    /// Valida y guarda (cifrado) el certificado del tenant, reemplazando el anterior
+    pub async fn put(&self, tenant_id: i64, pkcs12: &[u8], password: &str) -> Result<Arc<SigningIdentity>> {
+        let master_key = self.master_key()?;
+        let identity = Arc::new(SigningIdentity::from_pkcs12(pkcs12, password)?);
+
+        let key = signing_certificate_key(tenant_id);
+        let plaintext = serde_json::to_vec(&StoredCertificate {
+            pkcs12: STANDARD.encode(pkcs12),
+            password: password.to_string(),
+        })?;
+        let sealed = seal(master_key, key.as_bytes(), &plaintext)?;
+
+        self.storage.put(&self.bucket, &key, sealed, "application/octet-stream").await?;
+        self.cache.write().unwrap().insert(tenant_id, (Some(identity.clone()), Instant::now()));
+
+        Ok(identity)
+    }
+
+    pub async fn delete(&self, tenant_id: i64) -> Result<()> {
+        self.storage.delete(&self.bucket, &signing_certificate_key(tenant_id)).await?;
+        self.cache.write().unwrap().insert(tenant_id, (None, Instant::now()));
+        Ok(())
+    }
+
+    /// Identidad de firma del tenant; `None` si no registró certificado o la firma está deshabilitada
+    pub async fn identity(&self, tenant_id: i64) -> Result<Option<Arc<SigningIdentity>>> {
+        let Some(master_key) = &self.master_key else {
+            return Ok(None);
+        };
+        if let Some((cached, at)) = self.cache.read().unwrap().get(&tenant_id) {
+            if cached.is_some() || at.elapsed() < MISSING_RECHECK {
+                return Ok(cached.clone());
+            }
+        }
+
+        let key = signing_certificate_key(tenant_id);
+        let identity = match self.storage.get(&self.bucket, &key).await {
+            Ok(sealed) => {
+                let plaintext = open(master_key, key.as_bytes(), &sealed)
+                    .with_context(|| format!("Failed to decrypt signing certificate of tenant {}", tenant_id))?;
+                let stored: StoredCertificate = serde_json::from_slice(&plaintext)?;
+                let pkcs12 = STANDARD.decode(stored.pkcs12)?;
+                Some(Arc::new(SigningIdentity::from_pkcs12(&pkcs12, &stored.password)?))
+            },
+            Err(_) => None,
+        };
+
+        self.cache.write().unwrap().insert(tenant_id, (identity.clone(), Instant::now()));
+        Ok(identity)
+    }
+
+    fn master_key(&self) -> Result<&[u8; 32]> {
+        self.master_key.as_ref().context("PDF signing is not enabled (SIGNING_MASTER_KEY)")
+    }
+}
+
+fn parse_master_key(hex: &str) -> Result<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        bail!("SIGNING_MASTER_KEY must be 32 bytes in hex");
+    }
+
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).context("SIGNING_MASTER_KEY must be hex")?;
+    }
+    Ok(key)
+}
+
+/// `nonce || tag || ciphertext`; la clave del objeto va como AAD para que
+/// el archivo de un tenant no se pueda copiar al de otro
+fn seal(master_key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand_bytes(&mut nonce)?;
+
+    let mut tag = [0u8; TAG_LEN];
+    let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), master_key, Some(&nonce), aad, plaintext, &mut tag)?;
+
+    let mut sealed = Vec::with_capacity(NONCE_LEN + TAG_LEN + ciphertext.len());
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&tag);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+fn open(master_key: &[u8; 32], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        bail!("Encrypted certificate is truncated");
+    }
+    let (nonce, rest) = sealed.split_at(NONCE_LEN);
+    let (tag, ciphertext) = rest.split_at(TAG_LEN);
+
+    Ok(decrypt_aead(Cipher::aes_256_gcm(), master_key, Some(nonce), aad, ciphertext, tag)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generators::pades::tests::test_pkcs12;
+    use crate::storage::cdn::CdnSigner;
+    use crate::storage::local::LocalFsStorage;
+
+    const MASTER_KEY: [u8; 32] = [7; 32];
+
+    fn store(root: &std::path::Path, master_key: Option<[u8; 32]>) -> CertificateStore {
+        let storage = Arc::new(LocalFsStorage::new(root, "http://localhost/files".to_string(), CdnSigner::new("key")));
+        CertificateStore::new(storage, "documents".to_string(), master_key)
+    }
+
+    fn temp_root() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("certificates-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn parses_the_master_key() {
+        assert_eq!(parse_master_key(&"07".repeat(32)).unwrap(), MASTER_KEY);
+        assert!(parse_master_key("07").is_err());
+        assert!(parse_master_key(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn sealed_certificates_only_open_with_the_same_key_and_object() {
+        let sealed = seal(&MASTER_KEY, b"signing/tenant_1/certificate.p12", b"pkcs12").unwrap();
+        assert_eq!(open(&MASTER_KEY, b"signing/tenant_1/certificate.p12", &sealed).unwrap(), b"pkcs12");
+
+        // Copiado al objeto de otro tenant, con otra llave o truncado no abre
+        assert!(open(&MASTER_KEY, b"signing/tenant_2/certificate.p12", &sealed).is_err());
+        assert!(open(&[8; 32], b"signing/tenant_1/certificate.p12", &sealed).is_err());
+        assert!(open(&MASTER_KEY, b"signing/tenant_1/certificate.p12", &sealed[..NONCE_LEN]).is_err());
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(open(&MASTER_KEY, b"signing/tenant_1/certificate.p12", &tampered).is_err());
+    }
+
+    #[tokio::test]
+    async fn stores_certificates_encrypted_per_tenant() {
+        let root = temp_root();
+        let pkcs12 = test_pkcs12("secret");
+        let saved = store(&root, Some(MASTER_KEY)).put(1, &pkcs12, "secret").await.unwrap();
+
+        // Otra instancia (sin caché) lee y descifra el mismo certificado
+        let reloaded = store(&root, Some(MASTER_KEY));
+        let identity = reloaded.identity(1).await.unwrap().expect("certificate not found");
+        assert_eq!(identity.info().fingerprint_sha256, saved.info().fingerprint_sha256);
+        assert!(reloaded.identity(2).await.unwrap().is_none());
+
+        // En el storage no queda el PKCS#12 en claro
+        let raw = std::fs::read(root.join("documents").join(signing_certificate_key(1))).unwrap();
+        assert!(!raw.windows(64).any(|window| window == &pkcs12[..64]));
+
+        assert!(store(&root, Some([8; 32])).identity(1).await.is_err());
+        assert!(store(&root, None).identity(1).await.unwrap().is_none());
+
+        reloaded.delete(1).await.unwrap();
+        assert!(reloaded.identity(1).await.unwrap().is_none());
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_certificates_and_disabled_signing() {
+        let root = temp_root();
+        assert!(store(&root, Some(MASTER_KEY)).put(1, &test_pkcs12("secret"), "wrong").await.is_err());
+        assert!(store(&root, None).put(1, &test_pkcs12("secret"), "secret").await.is_err());
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::{bail, Context, Result};
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod, Runtime};
+use std::time::Duration;
+
+/// Pool de conexiones a Postgres compartido por todos los módulos (documentos,
+/// eventos, accesos, numeración, NCF, organizaciones)
+pub type Database = Pool;
+
+/// Configuración del pool (`DATABASE_URL`, `DATABASE_POOL_SIZE`,
+/// `DATABASE_POOL_TIMEOUT_MS`)
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_size: usize,
+    /// Espera máxima por una conexión libre, y para crear o revisar una
+    pub pool_timeout: Duration,
+}
+
+impl DatabaseConfig {
+    /// `None` si no hay `DATABASE_URL`. `EVENTS_DATABASE_URL` y
+    /// `NUMBERING_DATABASE_URL` se aceptan todavía como alias
+    pub fn from_env() -> Option<Self> {
+        let url = match std::env::var("DATABASE_URL").ok().filter(|url| !url.trim().is_empty()) {
+            Some(url) => url,
+            None => {
+                let (var, url) = ["EVENTS_DATABASE_URL", "NUMBERING_DATABASE_URL"]
+                    .into_iter()
+                    .find_map(|var| std::env::var(var).ok().filter(|url| !url.trim().is_empty()).map(|url| (var, url)))?;
+                tracing::warn!("{} is deprecated, use DATABASE_URL", var);
+                url
+            },
+        };
+
+        Some(DatabaseConfig {
+            url,
+            max_size: std::env::var("DATABASE_POOL_SIZE").ok().and_then(|v| v.parse().ok()).unwrap_or(16),
+            pool_timeout: Duration::from_millis(
+                std::env::var("DATABASE_POOL_TIMEOUT_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(5_000),
+            ),
+        })
+    }
+}
+
+/// Crea el pool. Usa TLS cuando el servidor lo ofrece (`sslmode=prefer`, el
+/// valor por defecto); `sslmode=require` en la URL lo vuelve obligatorio.
+/// Las conexiones caídas se descartan al tomarlas del pool
+pub fn connect(config: DatabaseConfig) -> Result<Database> {
+    if !config.url.starts_with("postgres://") && !config.url.starts_with("postgresql://") {
+        bail!("DATABASE_URL must be a postgres:// URL");
+    }
+    let pg_config: tokio_postgres::Config = config.url.parse().context("Invalid DATABASE_URL")?;
+    let tls = postgres_native_tls::MakeTlsConnector::new(
+        native_tls::TlsConnector::new().context("Failed to initialize TLS for the database")?,
+    );
+
+    let manager = Manager::from_config(pg_config, tls, ManagerConfig { recycling_method: RecyclingMethod::Fast });
+    Pool::builder(manager)
+        .max_size(config.max_size.max(1))
+        .wait_timeout(Some(config.pool_timeout))
+        .create_timeout(Some(config.pool_timeout))
+        .recycle_timeout(Some(config.pool_timeout))
+        .runtime(Runtime::Tokio1)
+        .build()
+        .context("Invalid database pool configuration")
+}
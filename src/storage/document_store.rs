@@ -0,0 +1,590 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio_postgres::types::ToSql;
+use uuid::Uuid;
+
+use crate::models::{DocumentRequest, DocumentStatus, DocumentStatusUpdate, Priority};
+use crate::templates::CompileDiagnostic;
+use super::database::Database;
+use super::redis_pool::RedisPool;
+use super::statistics::{CompletedGroup, TenantStatistics};
+
+/// Registro de un documento: estado de la generación y dónde quedó almacenado
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentRecord {
+    pub id: Uuid,
+    pub tenant_id: i64,
+    pub user_id: i64,
+    pub organization_id: String,
+    pub document_type: String,
+    pub template_id: String,
+    pub priority: Priority,
+    pub status: DocumentStatus,
+    /// Avance 0-100 mientras se procesa
+    pub progress: Option<f32>,
+    pub error: Option<String>,
+    /// Intentos de generación realizados (reintentos por fallas transitorias)
+    pub attempts: u32,
+    pub bucket: String,
+    pub storage_key: Option<String>,
+    /// Miniatura PNG de la primera página (solo PDFs)
+    #[serde(default)]
+    pub preview_key: Option<String>,
+    /// XML e-CF de las facturas fiscales 31/32, junto al PDF
+    #[serde(default)]
+    pub xml_key: Option<String>,
+    /// CSV/TSV de un reporte Excel, junto al libro
+    #[serde(default)]
+    pub companion_key: Option<String>,
+    pub content_type: Option<String>,
+    pub checksum_sha256: Option<String>,
+    pub size_bytes: u64,
+    /// Páginas del PDF generado (None para formatos sin páginas)
+    pub page_count: Option<u32>,
+    pub processing_time_ms: u64,
+    /// Duración de cada etapa del último intento
+    #[serde(default)]
+    pub stages: StageTimings,
+    /// Advertencias de Typst al compilar el documento
+    #[serde(default)]
+    pub compile_warnings: Vec<CompileDiagnostic>,
+    /// Clase de archivo en frío a la que la retención movió el documento
+    /// (p. ej. `GLACIER`); `None` mientras está en almacenamiento caliente
+    #[serde(default)]
+    pub storage_class: Option<String>,
+    /// En la papelera desde esta fecha (se puede restaurar hasta la purga)
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Referencia del cliente (`external_ref` del request)
+    #[serde(default)]
+    pub external_ref: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Duración (ms) de las etapas de la generación; `None` si la etapa no aplica
+/// o aún no ocurre (p. ej. `compile_ms` en Excel, `callback_ms` sin callback)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StageTimings {
+    /// Desde que se registró el documento hasta el primer intento
+    pub queue_wait_ms: Option<u64>,
+    /// Resolución del origen de datos y descarga de assets de la plantilla
+    pub data_fetch_ms: Option<u64>,
+    /// Generación del fuente Typst o del archivo (Excel, CSV)
+    pub render_ms: Option<u64>,
+    /// Compilación con Typst
+    pub compile_ms: Option<u64>,
+    /// Cadena de post-procesado del PDF
+    pub post_process_ms: Option<u64>,
+    pub upload_ms: Option<u64>,
+    pub callback_ms: Option<u64>,
+}
+
+impl DocumentRecord {
+    /// Registro inicial (en cola) de un request cuya organización ya fue resuelta
+    pub fn queued(request: &DocumentRequest, organization_id: String, bucket: String) -> Self {
+        let now = Utc::now();
+        DocumentRecord {
+            id: request.id,
+            tenant_id: request.metadata.tenant_id,
+            user_id: request.metadata.user_id,
+            organization_id,
+            document_type: request.document_type.as_str().to_string(),
+            template_id: request.template_id.clone(),
+            priority: request.priority.clone(),
+            status: DocumentStatus::Queued,
+            progress: Some(0.0),
+            error: None,
+            attempts: 0,
+            bucket,
+            storage_key: None,
+            preview_key: None,
+            xml_key: None,
+            companion_key: None,
+            content_type: None,
+            checksum_sha256: None,
+            size_bytes: 0,
+            page_count: None,
+            processing_time_ms: 0,
+            stages: StageTimings::default(),
+            compile_warnings: Vec::new(),
+            storage_class: None,
+            deleted_at: None,
+            external_ref: request.external_ref.clone(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Resultado de enviar un documento a la papelera
+#[derive(Debug, Clone, PartialEq)]
+pub enum SoftDelete {
+    Deleted(DateTime<Utc>),
+    /// Sigue en cola o generándose: no se puede borrar aún
+    InProgress(DocumentStatus),
+    NotFound,
+}
+
+/// Intentos de una actualización que compite con otra réplica por el mismo documento
+const MAX_UPDATE_ATTEMPTS: usize = 10;
+
+const UPSERT_DOCUMENT: &str = "INSERT INTO documents
+    (id, tenant_id, template_id, external_ref, status, created_at, deleted_at, record)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+    ON CONFLICT (id) DO UPDATE SET
+        tenant_id = EXCLUDED.tenant_id, template_id = EXCLUDED.template_id, external_ref = EXCLUDED.external_ref,
+        status = EXCLUDED.status, created_at = EXCLUDED.created_at, deleted_at = EXCLUDED.deleted_at,
+        record = EXCLUDED.record, version = documents.version + 1";
+
+/// Totales por estado y prioridad de los documentos terminados de un tenant
+const DOCUMENT_STATISTICS: &str = "SELECT status, record->>'priority', COUNT(*),
+        COALESCE(SUM((record->>'size_bytes')::bigint), 0)::bigint,
+        COALESCE(SUM((record->>'page_count')::bigint), 0)::bigint,
+        COALESCE(SUM((record->>'processing_time_ms')::bigint), 0)::bigint,
+        COALESCE(MAX((record->>'processing_time_ms')::bigint), 0)::bigint
+    FROM documents
+    WHERE tenant_id = $1 AND status IN ('completed', 'failed')
+    GROUP BY 1, 2";
+
+const UPDATE_DOCUMENT: &str = "UPDATE documents
+    SET status = $3, deleted_at = $4, record = $5, version = version + 1
+    WHERE id = $1 AND version = $2";
+
+/// Registro de documentos por id, con índice por referencia externa. Con
+/// `DATABASE_URL` vive en la tabla `documents` de Postgres (compartida entre
+/// réplicas y reinicios); sin ella, en memoria del proceso
+#[derive(Default)]
+pub struct DocumentStore {
+    records: RwLock<HashMap<Uuid, DocumentRecord>>,
+    /// Documentos por (tenant, `external_ref`), en orden de registro
+    by_ref: RwLock<HashMap<(i64, String), Vec<Uuid>>>,
+    database: Option<DocumentDatabase>,
+}
+
+struct DocumentDatabase {
+    pool: Database,
+    cache: Option<DocumentCache>,
+}
+
+/// Copia en Redis de los registros leídos por id (`documents:record:{id}`),
+/// para que el polling de status no vaya a Postgres en cada consulta. Cada
+/// escritura del documento borra la copia; el TTL corto acota lo que puede
+/// quedar desactualizada si una lectura concurrente la repone
+struct DocumentCache {
+    redis: Arc<RedisPool>,
+    ttl: Duration,
+}
+
+impl DocumentStore {
+    /// Tabla `documents` del pool compartido; sin él, en memoria del proceso
+    pub fn new(database: Option<Database>) -> Self {
+        if database.is_none() {
+            tracing::warn!("DATABASE_URL not set: document records are kept in memory only");
+        }
+        DocumentStore {
+            database: database.map(|pool| DocumentDatabase { pool, cache: None }),
+            ..Self::default()
+        }
+    }
+
+    /// Cachea en Redis las lecturas por id durante `ttl` (solo con Postgres;
+    /// en memoria no hace falta)
+    pub fn with_cache(mut self, redis: Arc<RedisPool>, ttl: Duration) -> Self {
+        if let Some(database) = &mut self.database {
+            database.cache = Some(DocumentCache { redis, ttl });
+        }
+        self
+    }
+
+    pub async fn upsert(&self, record: DocumentRecord) -> Result<()> {
+        if let Some(database) = &self.database {
+            let db = database.client().await?;
+            db.execute(UPSERT_DOCUMENT, &[
+                &record.id,
+                &record.tenant_id,
+                &record.template_id,
+                &record.external_ref,
+                &record.status.to_string(),
+                &record.created_at,
+                &record.deleted_at,
+                &serde_json::to_value(&record)?,
+            ]).await.context("Failed to save the document record")?;
+            database.invalidate(&record.id).await;
+            return Ok(());
+        }
+
+        if let Some(external_ref) = &record.external_ref {
+            let mut by_ref = self.by_ref.write().unwrap();
+            let ids = by_ref.entry((record.tenant_id, external_ref.clone())).or_default();
+            if !ids.contains(&record.id) {
+                ids.push(record.id);
+            }
+        }
+        self.records.write().unwrap().insert(record.id, record);
+        Ok(())
+    }
+
+    /// Documentos del tenant con la referencia externa dada, más recientes
+    /// primero (excluye la papelera)
+    pub async fn by_external_ref(&self, tenant_id: i64, external_ref: &str) -> Result<Vec<DocumentRecord>> {
+        if let Some(database) = &self.database {
+            return database.select(
+                "WHERE tenant_id = $1 AND external_ref = $2 AND deleted_at IS NULL ORDER BY created_at DESC",
+                &[&tenant_id, &external_ref],
+            ).await;
+        }
+
+        let by_ref = self.by_ref.read().unwrap();
+        let Some(ids) = by_ref.get(&(tenant_id, external_ref.to_string())) else {
+            return Ok(Vec::new());
+        };
+
+        let records = self.records.read().unwrap();
+        let mut found: Vec<DocumentRecord> = ids
+            .iter()
+            .filter_map(|id| records.get(id))
+            .filter(|r| r.deleted_at.is_none())
+            .cloned()
+            .collect();
+        found.sort_by_key(|r| std::cmp::Reverse(r.created_at));
+        Ok(found)
+    }
+
+    /// Aplica un cambio de estado; retorna false si el documento no existe
+    pub async fn apply(&self, update: DocumentStatusUpdate) -> Result<bool> {
+        let applied = self.modify(&update.id, |record| {
+            if update.status == DocumentStatus::Failed {
+                record.error = update.message.clone();
+            }
+            record.status = update.status.clone();
+            record.progress = update.progress;
+            record.updated_at = update.updated_at;
+            Some(())
+        }).await?;
+        Ok(applied.is_some())
+    }
+
+    /// Registra el inicio de un intento de generación; el primero fija la espera en cola
+    pub async fn record_attempt(&self, id: &Uuid, attempt: u32) -> Result<()> {
+        self.modify(id, |record| {
+            let now = Utc::now();
+            if record.stages.queue_wait_ms.is_none() {
+                record.stages.queue_wait_ms = Some((now - record.created_at).num_milliseconds().max(0) as u64);
+            }
+            record.attempts = attempt;
+            record.updated_at = now;
+            Some(())
+        }).await?;
+        Ok(())
+    }
+
+    pub async fn set_priority(&self, id: &Uuid, priority: Priority) -> Result<()> {
+        self.modify(id, |record| {
+            record.priority = priority.clone();
+            record.updated_at = Utc::now();
+            Some(())
+        }).await?;
+        Ok(())
+    }
+
+    /// Guarda la duración de la entrega del callback
+    pub async fn record_callback_time(&self, id: &Uuid, elapsed_ms: u64) -> Result<()> {
+        self.modify(id, |record| {
+            record.stages.callback_ms = Some(elapsed_ms);
+            Some(())
+        }).await?;
+        Ok(())
+    }
+
+    /// Anota la clase de archivo a la que la retención movió el documento
+    pub async fn set_storage_class(&self, id: &Uuid, storage_class: &str) -> Result<()> {
+        self.modify(id, |record| {
+            if record.storage_class.as_deref() == Some(storage_class) {
+                return None;
+            }
+            record.storage_class = Some(storage_class.to_string());
+            record.updated_at = Utc::now();
+            Some(())
+        }).await?;
+        Ok(())
+    }
+
+    /// Documento visible para el tenant dado (excluye la papelera)
+    pub async fn get(&self, id: &Uuid, tenant_id: i64) -> Result<Option<DocumentRecord>> {
+        Ok(self.find(id).await?.filter(|r| r.tenant_id == tenant_id && r.deleted_at.is_none()))
+    }
+
+    /// Envía a la papelera un documento terminado del tenant
+    pub async fn soft_delete(&self, id: &Uuid, tenant_id: i64) -> Result<SoftDelete> {
+        let deleted = self.modify(id, |record| {
+            if record.tenant_id != tenant_id || record.deleted_at.is_some() {
+                return None;
+            }
+            if matches!(record.status, DocumentStatus::Queued | DocumentStatus::Processing) {
+                return Some(SoftDelete::InProgress(record.status.clone()));
+            }
+
+            let now = Utc::now();
+            record.deleted_at = Some(now);
+            record.updated_at = now;
+            Some(SoftDelete::Deleted(now))
+        }).await?;
+        Ok(deleted.unwrap_or(SoftDelete::NotFound))
+    }
+
+    /// Saca de la papelera un documento borrado después de `deleted_after`
+    /// (los anteriores ya vencieron su ventana de restauración)
+    pub async fn restore(&self, id: &Uuid, tenant_id: i64, deleted_after: DateTime<Utc>) -> Result<bool> {
+        let restored = self.modify(id, |record| {
+            if record.tenant_id != tenant_id || record.deleted_at.is_none_or(|at| at <= deleted_after) {
+                return None;
+            }
+            record.deleted_at = None;
+            record.updated_at = Utc::now();
+            Some(())
+        }).await?;
+        Ok(restored.is_some())
+    }
+
+    /// Papelera del tenant, borrados más recientes primero
+    pub async fn trashed(&self, tenant_id: i64) -> Result<Vec<DocumentRecord>> {
+        if let Some(database) = &self.database {
+            return database.select(
+                "WHERE tenant_id = $1 AND deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+                &[&tenant_id],
+            ).await;
+        }
+
+        let mut records = self.matching(|r| r.tenant_id == tenant_id && r.deleted_at.is_some());
+        records.sort_by_key(|r| std::cmp::Reverse(r.deleted_at));
+        Ok(records)
+    }
+
+    /// Documentos (de todos los tenants) en la papelera desde antes de `cutoff`
+    pub async fn trashed_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<DocumentRecord>> {
+        if let Some(database) = &self.database {
+            return database.select("WHERE deleted_at <= $1", &[&cutoff]).await;
+        }
+
+        Ok(self.matching(|r| r.deleted_at.is_some_and(|at| at <= cutoff)))
+    }
+
+    pub async fn remove(&self, id: &Uuid) -> Result<Option<DocumentRecord>> {
+        if let Some(database) = &self.database {
+            let db = database.client().await?;
+            let row = db.query_opt("DELETE FROM documents WHERE id = $1 RETURNING record", &[id]).await?;
+            database.invalidate(id).await;
+            return row.map(|row| record_from_row(&row)).transpose();
+        }
+
+        let Some(record) = self.records.write().unwrap().remove(id) else {
+            return Ok(None);
+        };
+        if let Some(external_ref) = &record.external_ref {
+            let mut by_ref = self.by_ref.write().unwrap();
+            let key = (record.tenant_id, external_ref.clone());
+            if let Some(ids) = by_ref.get_mut(&key) {
+                ids.retain(|other| other != id);
+                if ids.is_empty() {
+                    by_ref.remove(&key);
+                }
+            }
+        }
+        Ok(Some(record))
+    }
+
+    /// Documento por id sin filtrar por tenant (uso interno)
+    pub async fn find(&self, id: &Uuid) -> Result<Option<DocumentRecord>> {
+        if let Some(database) = &self.database {
+            return database.cached(id).await;
+        }
+
+        Ok(self.records.read().unwrap().get(id).cloned())
+    }
+
+    /// Documento (de cualquier tenant, incluida la papelera) cuyo archivo
+    /// principal es `bucket/key` (uso del job de retención)
+    pub async fn by_storage_key(&self, bucket: &str, key: &str) -> Result<Option<DocumentRecord>> {
+        if let Some(database) = &self.database {
+            let mut records = database.select(
+                "WHERE record->>'storage_key' = $1 AND record->>'bucket' = $2 LIMIT 1",
+                &[&key, &bucket],
+            ).await?;
+            return Ok(records.pop());
+        }
+
+        Ok(self.matching(|r| r.bucket == bucket && r.storage_key.as_deref() == Some(key)).pop())
+    }
+
+    /// Documentos del tenant generados con la plantilla desde `since`, más los
+    /// que aún la esperan en cola o en proceso (excluye la papelera)
+    pub async fn using_template(&self, tenant_id: i64, template_id: &str, since: DateTime<Utc>) -> Result<Vec<DocumentRecord>> {
+        if let Some(database) = &self.database {
+            return database.select(
+                "WHERE tenant_id = $1 AND template_id = $2 AND deleted_at IS NULL
+                 AND (created_at >= $3 OR status IN ('queued', 'processing'))",
+                &[&tenant_id, &template_id, &since],
+            ).await;
+        }
+
+        Ok(self.matching(|r| {
+            r.tenant_id == tenant_id
+                && r.template_id == template_id
+                && r.deleted_at.is_none()
+                && (r.created_at >= since || matches!(r.status, DocumentStatus::Queued | DocumentStatus::Processing))
+        }))
+    }
+
+    /// Estadísticas de uso del tenant, agregadas de sus documentos terminados
+    /// (incluye los de la papelera: ya se generaron)
+    pub async fn statistics(&self, tenant_id: i64) -> Result<TenantStatistics> {
+        let Some(database) = &self.database else {
+            return Ok(TenantStatistics::from_records(tenant_id, &self.matching(|r| r.tenant_id == tenant_id)));
+        };
+
+        let db = database.client().await?;
+        let rows = db.query(DOCUMENT_STATISTICS, &[&tenant_id]).await?;
+        let mut failed = 0;
+        let mut completed = Vec::new();
+        for row in &rows {
+            let count = row.get::<_, i64>(2) as u64;
+            if row.get::<_, String>(0) == DocumentStatus::Failed.to_string() {
+                failed += count;
+                continue;
+            }
+            completed.push(CompletedGroup {
+                priority: row.get::<_, Option<String>>(1).unwrap_or_default(),
+                count,
+                size_bytes: row.get::<_, i64>(3) as u64,
+                page_count: row.get::<_, i64>(4) as u64,
+                total_ms: row.get::<_, i64>(5) as u64,
+                max_ms: row.get::<_, i64>(6) as u64,
+            });
+        }
+        Ok(TenantStatistics::from_groups(tenant_id, failed, completed))
+    }
+
+    /// Documentos del tenant, más recientes primero
+    pub async fn list(&self, tenant_id: i64, limit: usize) -> Result<Vec<DocumentRecord>> {
+        if let Some(database) = &self.database {
+            return database.select(
+                "WHERE tenant_id = $1 AND deleted_at IS NULL ORDER BY created_at DESC LIMIT $2",
+                &[&tenant_id, &(limit as i64)],
+            ).await;
+        }
+
+        let mut records = self.matching(|r| r.tenant_id == tenant_id && r.deleted_at.is_none());
+        records.sort_by_key(|r| std::cmp::Reverse(r.created_at));
+        records.truncate(limit);
+        Ok(records)
+    }
+
+    /// Aplica `change` al documento y lo guarda; `change` retorna `None` para
+    /// dejarlo como estaba. En Postgres la escritura es condicional a la
+    /// versión leída: si otra réplica lo cambió en medio se vuelve a aplicar
+    async fn modify<T>(&self, id: &Uuid, mut change: impl FnMut(&mut DocumentRecord) -> Option<T>) -> Result<Option<T>> {
+        let Some(database) = &self.database else {
+            return Ok(self.records.write().unwrap().get_mut(id).and_then(change));
+        };
+
+        for _ in 0..MAX_UPDATE_ATTEMPTS {
+            let Some((mut record, version)) = database.fetch(id).await? else {
+                return Ok(None);
+            };
+            let Some(result) = change(&mut record) else {
+                return Ok(None);
+            };
+
+            let db = database.client().await?;
+            let updated = db.execute(UPDATE_DOCUMENT, &[
+                id,
+                &version,
+                &record.status.to_string(),
+                &record.deleted_at,
+                &serde_json::to_value(&record)?,
+            ]).await?;
+            if updated == 1 {
+                database.invalidate(id).await;
+                return Ok(Some(result));
+            }
+        }
+        anyhow::bail!("Document {} kept changing while updating it", id)
+    }
+
+    fn matching(&self, filter: impl Fn(&DocumentRecord) -> bool) -> Vec<DocumentRecord> {
+        self.records.read().unwrap().values().filter(|r| filter(r)).cloned().collect()
+    }
+}
+
+impl DocumentDatabase {
+    async fn client(&self) -> Result<deadpool_postgres::Object> {
+        self.pool.get().await.context("Failed to connect to the documents database")
+    }
+
+    /// Registro y versión de un documento
+    async fn fetch(&self, id: &Uuid) -> Result<Option<(DocumentRecord, i64)>> {
+        let db = self.client().await?;
+        let row = db.query_opt("SELECT record, version FROM documents WHERE id = $1", &[id]).await?;
+        row.map(|row| Ok((record_from_row(&row)?, row.get(1)))).transpose()
+    }
+
+    /// Registro de un documento, de la cache si está y si no de la tabla
+    async fn cached(&self, id: &Uuid) -> Result<Option<DocumentRecord>> {
+        let Some(cache) = &self.cache else {
+            return Ok(self.fetch(id).await?.map(|(record, _)| record));
+        };
+
+        let key = cache_key(id);
+        match cache.redis.query::<Option<String>>(deadpool_redis::redis::cmd("GET").arg(&key)).await {
+            Ok(Some(cached)) => match serde_json::from_str(&cached) {
+                Ok(record) => return Ok(Some(record)),
+                Err(e) => tracing::warn!("Discarding cached document {}: {}", id, e),
+            },
+            Ok(None) => {},
+            Err(e) => tracing::warn!("Document cache unavailable, reading {} from Postgres: {:#}", id, e),
+        }
+
+        let record = self.fetch(id).await?.map(|(record, _)| record);
+        if let Some(record) = &record {
+            let set = deadpool_redis::redis::cmd("SET")
+                .arg(&key)
+                .arg(serde_json::to_string(record)?)
+                .arg("EX")
+                .arg(cache.ttl.as_secs().max(1))
+                .to_owned();
+            if let Err(e) = cache.redis.query::<()>(&set).await {
+                tracing::warn!("Failed to cache document {}: {:#}", id, e);
+            }
+        }
+        Ok(record)
+    }
+
+    /// Borra la copia cacheada tras escribir el documento
+    async fn invalidate(&self, id: &Uuid) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.redis.query::<()>(deadpool_redis::redis::cmd("DEL").arg(cache_key(id))).await {
+                tracing::warn!("Failed to invalidate cached document {}: {:#}", id, e);
+            }
+        }
+    }
+
+    /// Registros que cumplen `filter` (condición y orden en SQL)
+    async fn select(&self, filter: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<DocumentRecord>> {
+        let db = self.client().await?;
+        let rows = db.query(&format!("SELECT record FROM documents {}", filter), params).await?;
+        rows.iter().map(record_from_row).collect()
+    }
+}
+
+fn cache_key(id: &Uuid) -> String {
+    format!("documents:record:{}", id)
+}
+
+fn record_from_row(row: &tokio_postgres::Row) -> Result<DocumentRecord> {
+    Ok(serde_json::from_value(row.get(0))?)
+}
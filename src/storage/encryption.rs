@@ -0,0 +1,99 @@
+use anyhow::Result;
+use aws_sdk_s3::types::ServerSideEncryption;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use super::retention::tenant_from_key;
+
+/// Cifrado del lado del servidor por defecto de los objetos que sube el servicio
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum SseMode {
+    /// Sin encabezado: aplica el cifrado por defecto del bucket
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    /// SSE-S3 (llaves administradas por S3)
+    #[serde(rename = "AES256")]
+    S3,
+    /// SSE-KMS
+    #[serde(rename = "aws:kms")]
+    Kms,
+}
+
+/// Cifrado en reposo de los objetos: modo por defecto y llave KMS propia de
+/// los tenants que la exigen
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EncryptionPolicy {
+    #[serde(default)]
+    pub mode: SseMode,
+    /// Llave KMS (id, alias o ARN) para `aws:kms`; sin ella S3 usa `aws/s3`
+    #[serde(default)]
+    pub kms_key_id: Option<String>,
+    /// Llave KMS por tenant; sus objetos van con SSE-KMS aunque `mode` sea otro
+    #[serde(default)]
+    pub tenants: HashMap<i64, String>,
+    /// S3 Bucket Keys: menos llamadas (y costo) a KMS
+    #[serde(default)]
+    pub bucket_key: bool,
+}
+
+/// Cifrado que corresponde a un objeto
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectEncryption {
+    S3,
+    Kms { key_id: Option<String>, bucket_key: bool },
+}
+
+impl ObjectEncryption {
+    pub fn algorithm(&self) -> ServerSideEncryption {
+        match self {
+            ObjectEncryption::S3 => ServerSideEncryption::Aes256,
+            ObjectEncryption::Kms { .. } => ServerSideEncryption::AwsKms,
+        }
+    }
+
+    pub fn kms_key_id(&self) -> Option<String> {
+        match self {
+            ObjectEncryption::Kms { key_id, .. } => key_id.clone(),
+            ObjectEncryption::S3 => None,
+        }
+    }
+
+    pub fn bucket_key_enabled(&self) -> Option<bool> {
+        match self {
+            ObjectEncryption::Kms { bucket_key: true, .. } => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl EncryptionPolicy {
+    /// Lee `S3_ENCRYPTION_POLICY` (JSON), p. ej.
+    /// `{"mode": "AES256", "tenants": {"7": "arn:aws:kms:us-east-1:123:key/abc"}, "bucket_key": true}`
+    pub fn from_env() -> Result<Self> {
+        let policy: EncryptionPolicy = match std::env::var("S3_ENCRYPTION_POLICY") {
+            Ok(raw) if !raw.trim().is_empty() => serde_json::from_str(&raw)?,
+            _ => EncryptionPolicy::default(),
+        };
+
+        if policy.kms_key_id.is_some() && policy.mode != SseMode::Kms {
+            anyhow::bail!("S3_ENCRYPTION_POLICY kms_key_id requires mode \"aws:kms\"");
+        }
+        if let Some((tenant_id, _)) = policy.tenants.iter().find(|(_, key_id)| key_id.trim().is_empty()) {
+            anyhow::bail!("S3_ENCRYPTION_POLICY has an empty KMS key for tenant {}", tenant_id);
+        }
+        Ok(policy)
+    }
+
+    /// Cifrado del objeto según el tenant de su clave (`tenant_{id}`);
+    /// `None` deja el del bucket
+    pub fn for_key(&self, key: &str) -> Option<ObjectEncryption> {
+        let tenant_key = tenant_from_key(key).and_then(|tenant_id| self.tenants.get(&tenant_id));
+        match (tenant_key, self.mode) {
+            (Some(key_id), _) => Some(ObjectEncryption::Kms { key_id: Some(key_id.clone()), bucket_key: self.bucket_key }),
+            (None, SseMode::Kms) => Some(ObjectEncryption::Kms { key_id: self.kms_key_id.clone(), bucket_key: self.bucket_key }),
+            (None, SseMode::S3) => Some(ObjectEncryption::S3),
+            (None, SseMode::None) => None,
+        }
+    }
+}
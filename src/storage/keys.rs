@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// Clave de un documento generado:
+/// `tenant_{tenant}/{organization}/{tipo}/{yyyy}/{mm}/{dd}/{id}.{ext}`.
+/// El id (UUID) garantiza unicidad y el prefijo de tenant aísla clientes
+/// (lo usa también el job de retención para resolver la política).
+pub fn document_key(
+    tenant_id: i64,
+    organization_id: &str,
+    document_type: &str,
+    document_id: Uuid,
+    extension: &str,
+    created_at: DateTime<Utc>,
+) -> String {
+    format!(
+        "tenant_{}/{}/{}/{}/{}.{}",
+        tenant_id,
+        sanitize_segment(organization_id),
+        sanitize_segment(document_type),
+        created_at.format("%Y/%m/%d"),
+        document_id,
+        sanitize_segment(extension)
+    )
+}
+
+/// Clave de la miniatura de un documento, junto a él: `{id}.preview.png`
+pub fn preview_key(document_key: &str) -> String {
+    let stem = document_key.rsplit_once('.').map_or(document_key, |(stem, _)| stem);
+    format!("{}.preview.png", stem)
+}
+
+/// Clave del XML e-CF de una factura fiscal, junto al PDF: `{id}.ecf.xml`
+pub fn ecf_xml_key(document_key: &str) -> String {
+    let stem = document_key.rsplit_once('.').map_or(document_key, |(stem, _)| stem);
+    format!("{}.ecf.xml", stem)
+}
+
+/// Clave del archivo plano (CSV/TSV) de un reporte Excel, junto al libro:
+/// `{id}.{csv|tsv}`
+pub fn companion_key(document_key: &str, extension: &str) -> String {
+    let stem = document_key.rsplit_once('.').map_or(document_key, |(stem, _)| stem);
+    format!("{}.{}", stem, sanitize_segment(extension))
+}
+
+/// Clave del reporte de filas rechazadas de un lote:
+/// `tenant_{tenant}/batches/{yyyy}/{mm}/{dd}/{batch_id}/errors.xlsx`
+pub fn batch_errors_key(tenant_id: i64, batch_id: Uuid, created_at: DateTime<Utc>) -> String {
+    format!("tenant_{}/batches/{}/{}/errors.xlsx", tenant_id, created_at.format("%Y/%m/%d"), batch_id)
+}
+
+/// Clave del zip con los documentos de un lote, junto a su `errors.xlsx`:
+/// `tenant_{tenant}/batches/{yyyy}/{mm}/{dd}/{batch_id}/documents.zip`
+pub fn batch_archive_key(tenant_id: i64, batch_id: Uuid, created_at: DateTime<Utc>) -> String {
+    format!("tenant_{}/batches/{}/{}/documents.zip", tenant_id, created_at.format("%Y/%m/%d"), batch_id)
+}
+
+/// Prefijo de las claves de un tenant en el bucket de documentos
+pub fn tenant_prefix(tenant_id: i64) -> String {
+    format!("tenant_{}/", tenant_id)
+}
+
+/// Clave de un PDF combinado con `/documents/merge`:
+/// `tenant_{tenant}/merged/{yyyy}/{mm}/{dd}/{merge_id}.pdf`
+pub fn merged_document_key(tenant_id: i64, merge_id: Uuid, created_at: DateTime<Utc>) -> String {
+    format!("{}merged/{}/{}.pdf", tenant_prefix(tenant_id), created_at.format("%Y/%m/%d"), merge_id)
+}
+
+/// Prefijo de los archivos de datos que sube un tenant al bucket temporal
+pub fn upload_prefix(tenant_id: i64) -> String {
+    format!("uploads/tenant_{}/", tenant_id)
+}
+
+/// Clave de un archivo de datos subido al bucket temporal
+pub fn upload_key(tenant_id: i64, user_id: i64, upload_id: Uuid, created_at: DateTime<Utc>) -> String {
+    format!(
+        "{}user_{}/{}/{}.json",
+        upload_prefix(tenant_id),
+        user_id,
+        created_at.format("%Y/%m/%d"),
+        upload_id
+    )
+}
+
+/// Clave de un archivo de datos convertido a otro formato, junto al
+/// original: `{id}.converted.{ext}`
+pub fn converted_upload_key(upload_key: &str, extension: &str) -> String {
+    let stem = upload_key.rsplit_once('.').map_or(upload_key, |(stem, _)| stem);
+    let stem = stem.strip_suffix(".converted").unwrap_or(stem);
+    format!("{}.converted.{}", stem, sanitize_segment(extension))
+}
+
+/// Prefijo de las plantillas subidas por tenants (fuera de la retención)
+pub const TEMPLATES_PREFIX: &str = "templates/";
+
+/// Clave del fuente de una plantilla que un tenant reemplaza:
+/// `templates/tenant_{tenant}/{template_id}.typ`
+pub fn template_override_key(tenant_id: i64, template_id: &str) -> String {
+    format!("{}tenant_{}/{}.typ", TEMPLATES_PREFIX, tenant_id, sanitize_segment(template_id))
+}
+
+/// Clave de una versión archivada al borrar la plantilla del tenant:
+/// `templates/tenant_{tenant}/.archive/{template_id}/{version}.typ`. El `.`
+/// no es válido en ids de plantilla, así que no choca con sus assets
+pub fn template_archive_key(tenant_id: i64, template_id: &str, version: &str) -> String {
+    format!(
+        "{}tenant_{}/.archive/{}/{}.typ",
+        TEMPLATES_PREFIX,
+        tenant_id,
+        sanitize_segment(template_id),
+        sanitize_segment(version)
+    )
+}
+
+/// Prefijo de los assets (imágenes, fuentes, includes) de una plantilla del tenant:
+/// `templates/tenant_{tenant}/{template_id}/assets/`
+pub fn template_assets_prefix(tenant_id: i64, template_id: &str) -> String {
+    format!("{}tenant_{}/{}/assets/", TEMPLATES_PREFIX, tenant_id, sanitize_segment(template_id))
+}
+
+/// Prefijo de los certificados de firma de los tenants (fuera de la retención)
+pub const SIGNING_PREFIX: &str = "signing/";
+
+/// Clave del certificado de firma (PKCS#12 cifrado) de un tenant
+pub fn signing_certificate_key(tenant_id: i64) -> String {
+    format!("{}tenant_{}/certificate.p12.enc", SIGNING_PREFIX, tenant_id)
+}
+
+/// Tenant e id de plantilla de una clave generada por `template_override_key`
+pub fn parse_template_override_key(key: &str) -> Option<(i64, String)> {
+    let rest = key.strip_prefix(TEMPLATES_PREFIX)?;
+    let (tenant, file) = rest.split_once('/')?;
+    if file.contains('/') {
+        return None;
+    }
+    let tenant_id = tenant.strip_prefix("tenant_")?.parse().ok()?;
+    let template_id = file.strip_suffix(".typ")?;
+    Some((tenant_id, template_id.to_string()))
+}
+
+/// Normaliza un segmento de clave: minúsculas, solo `[a-z0-9_-]`
+fn sanitize_segment(segment: &str) -> String {
+    let sanitized: String = segment
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+
+    if sanitized.is_empty() {
+        "unknown".to_string()
+    } else {
+        sanitized
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::{Component, Path, PathBuf};
+
+use super::cdn::CdnSigner;
+use super::s3::sha256_hex;
+use super::storage_trait::{ObjectInfo, PresignedUpload, Storage, StoredObject};
+
+/// Almacenamiento en el sistema de archivos local para despliegues on-prem
+/// sin credenciales de AWS. Los objetos viven en `{root}/{bucket}/{key}` y las
+/// URLs temporales apuntan al endpoint `/files` de la API, firmadas con HMAC.
+pub struct LocalFsStorage {
+    root: PathBuf,
+    public_url: String,
+    signer: CdnSigner,
+}
+
+impl LocalFsStorage {
+    pub fn new(root: impl Into<PathBuf>, public_url: String, signer: CdnSigner) -> Self {
+        LocalFsStorage {
+            root: root.into(),
+            public_url,
+            signer,
+        }
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let root = std::env::var("LOCAL_STORAGE_PATH").unwrap_or_else(|_| "data/storage".to_string());
+        let public_url = std::env::var("LOCAL_STORAGE_PUBLIC_URL")
+            .unwrap_or_else(|_| "http://localhost:8080/files".to_string());
+
+        let signer = match std::env::var("LOCAL_STORAGE_SIGNING_KEY") {
+            Ok(key) if !key.is_empty() => CdnSigner::new(key),
+            _ => {
+                tracing::warn!("LOCAL_STORAGE_SIGNING_KEY not set: download URLs will not survive restarts");
+                CdnSigner::new(uuid::Uuid::new_v4().as_bytes().to_vec())
+            }
+        };
+
+        std::fs::create_dir_all(&root)?;
+
+        Ok(Self::new(root, public_url, signer))
+    }
+
+    /// Ruta en disco de un objeto; rechaza claves que escapen del bucket
+    pub fn object_path(&self, bucket: &str, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(bucket).join(key);
+
+        if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+            anyhow::bail!("Invalid storage key: {}/{}", bucket, key);
+        }
+
+        Ok(self.root.join(relative))
+    }
+
+    fn checksum_path(path: &Path) -> PathBuf {
+        let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+        name.push(".sha256");
+        path.with_file_name(name)
+    }
+}
+
+#[async_trait]
+impl Storage for LocalFsStorage {
+    fn backend_name(&self) -> &str {
+        "local"
+    }
+
+    fn verify_download_token(&self, bucket: &str, key: &str, token: &str) -> bool {
+        self.signer.verify(&format!("/{}/{}", bucket, key), token)
+    }
+
+    async fn put(&self, bucket: &str, key: &str, data: Vec<u8>, _content_type: &str) -> Result<StoredObject> {
+        let path = self.object_path(bucket, key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let checksum = sha256_hex(&data);
+        tokio::fs::write(&path, data).await?;
+        tokio::fs::write(Self::checksum_path(&path), &checksum).await?;
+
+        Ok(StoredObject {
+            url: self.presign(bucket, key, 3600).await?,
+            checksum_sha256: checksum,
+        })
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Vec<u8>> {
+        let path = self.object_path(bucket, key)?;
+        let data = tokio::fs::read(&path).await?;
+
+        // Misma verificación de integridad que el backend S3
+        if let Ok(expected) = tokio::fs::read_to_string(Self::checksum_path(&path)).await {
+            let actual = sha256_hex(&data);
+            if actual != expected.trim() {
+                anyhow::bail!("Checksum mismatch for {}/{}: expected {}, got {}", bucket, key, expected.trim(), actual);
+            }
+        }
+
+        Ok(data)
+    }
+
+    async fn presign(&self, bucket: &str, key: &str, expires_in_seconds: u64) -> Result<String> {
+        Ok(self.signer.sign(&self.public_url, &format!("{}/{}", bucket, key), expires_in_seconds))
+    }
+
+    async fn presign_upload(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        _expires_in_seconds: u64,
+        _content_type: Option<&str>,
+    ) -> Result<PresignedUpload> {
+        anyhow::bail!("Presigned uploads are not supported by the local backend; use the upload endpoint")
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        let path = self.object_path(bucket, key)?;
+        // Igual que S3: borrar un objeto inexistente no es error
+        match tokio::fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {},
+        }
+        let _ = tokio::fs::remove_file(Self::checksum_path(&path)).await;
+        Ok(())
+    }
+
+    async fn list(&self, bucket: &str, prefix: Option<&str>) -> Result<Vec<ObjectInfo>> {
+        let bucket_root = self.object_path(bucket, "")?;
+        let mut objects = Vec::new();
+        let mut pending = vec![bucket_root.clone()];
+
+        while let Some(dir) = pending.pop() {
+            let Ok(mut entries) = tokio::fs::read_dir(&dir).await else { continue };
+
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                let metadata = entry.metadata().await?;
+
+                if metadata.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+
+                if path.extension().map(|e| e == "sha256").unwrap_or(false) {
+                    continue;
+                }
+
+                let key = path.strip_prefix(&bucket_root)?.to_string_lossy().replace('\\', "/");
+                if prefix.map(|p| !key.starts_with(p)).unwrap_or(false) {
+                    continue;
+                }
+
+                objects.push(ObjectInfo {
+                    key,
+                    size: metadata.len() as i64,
+                    last_modified: metadata.modified().ok().map(chrono::DateTime::<chrono::Utc>::from),
+                    storage_class: None,
+                });
+            }
+        }
+
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn checksum(&self, bucket: &str, key: &str) -> Result<Option<String>> {
+        let path = self.object_path(bucket, key)?;
+        match tokio::fs::read_to_string(Self::checksum_path(&path)).await {
+            Ok(checksum) => Ok(Some(checksum.trim().to_string())),
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn move_object(&self, bucket: &str, from_key: &str, to_key: &str) -> Result<()> {
+        let from = self.object_path(bucket, from_key)?;
+        let to = self.object_path(bucket, to_key)?;
+        if let Some(parent) = to.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        tokio::fs::rename(&from, &to).await?;
+        let _ = tokio::fs::rename(Self::checksum_path(&from), Self::checksum_path(&to)).await;
+        Ok(())
+    }
+}
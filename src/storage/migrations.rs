@@ -0,0 +1,120 @@
+use anyhow::{bail, Context, Result};
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::{Connection, PgConnection};
+
+/// Migraciones de `migrations/postgres/` (`NNNN_nombre.sql`), embebidas en el
+/// binario. Una migración aplicada no se edita: los cambios van en una nueva
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Versión del esquema que espera este binario
+pub fn latest_version() -> i64 {
+    MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+/// Qué hacer con el esquema al arrancar (`DATABASE_MIGRATIONS`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StartupMigrations {
+    /// Aplica las pendientes (por defecto)
+    Apply,
+    /// Solo verifica la versión; falla si está desactualizado
+    Verify,
+}
+
+impl StartupMigrations {
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("DATABASE_MIGRATIONS").as_deref() {
+            Err(_) | Ok("apply") => Ok(StartupMigrations::Apply),
+            Ok("verify") => Ok(StartupMigrations::Verify),
+            Ok(other) => bail!("DATABASE_MIGRATIONS must be apply or verify, got {}", other),
+        }
+    }
+}
+
+/// Aplica o verifica las migraciones en la base de `url` (`DATABASE_URL`),
+/// con una conexión propia fuera del pool
+pub async fn run(url: &str, mode: StartupMigrations) -> Result<()> {
+    let mut conn = PgConnection::connect(url)
+        .await
+        .context("Failed to connect to the database for migrations")?;
+
+    let result = match mode {
+        StartupMigrations::Apply => migrate(&mut conn).await,
+        StartupMigrations::Verify => verify(&mut conn).await,
+    };
+    let _ = conn.close().await;
+    result
+}
+
+/// Aplica las pendientes, cada una en su transacción y bajo el advisory lock
+/// de sqlx. Falla si una ya aplicada cambió de contenido
+async fn migrate(conn: &mut PgConnection) -> Result<()> {
+    conn.ensure_migrations_table().await?;
+    let before = conn.list_applied_migrations().await?;
+
+    MIGRATOR.run(&mut *conn).await.context("Failed to apply database migrations")?;
+
+    let applied: Vec<i64> = MIGRATOR
+        .iter()
+        .map(|m| m.version)
+        .filter(|version| !before.iter().any(|m| m.version == *version))
+        .collect();
+    if !applied.is_empty() {
+        tracing::info!("Applied database migrations {:?}", applied);
+    }
+    Ok(())
+}
+
+/// Verifica que la base tenga aplicadas, sin cambios, las migraciones de este binario
+async fn verify(conn: &mut PgConnection) -> Result<()> {
+    let exists: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(&mut *conn)
+        .await?;
+    let applied = if exists { conn.list_applied_migrations().await? } else { Vec::new() };
+    if let Some(version) = if exists { conn.dirty_version().await? } else { None } {
+        bail!("Database migration {} failed halfway; fix it by hand before starting", version);
+    }
+
+    let pending: Vec<i64> = MIGRATOR
+        .iter()
+        .filter(|m| !applied.iter().any(|a| a.version == m.version))
+        .map(|m| m.version)
+        .collect();
+    if !pending.is_empty() {
+        bail!(
+            "Database schema is missing migrations {:?} needed by this build; run with --migrate-only or DATABASE_MIGRATIONS=apply",
+            pending
+        );
+    }
+
+    for migration in MIGRATOR.iter() {
+        if applied.iter().any(|a| a.version == migration.version && a.checksum != migration.checksum) {
+            bail!(
+                "Migration {} ({}) changed after being applied; add a new migration instead",
+                migration.version, migration.description
+            );
+        }
+    }
+
+    let latest = latest_version();
+    if let Some(newer) = applied.iter().map(|a| a.version).filter(|v| *v > latest).max() {
+        bail!("Database schema is at version {}, newer than this build ({}); deploy a newer build", newer, latest);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embeds_every_migration_in_version_order() {
+        let versions: Vec<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+        let files = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/migrations/postgres"))
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "sql"))
+            .count();
+
+        assert_eq!(versions, (1..=files as i64).collect::<Vec<_>>());
+        assert_eq!(latest_version(), files as i64);
+    }
+}
@@ -1 +1,21 @@
-pub mod s3;
\ No newline at end of file
+pub mod storage_trait;
+pub mod s3;
+pub mod local;
+pub mod cdn;
+pub mod retention;
+pub mod multipart_janitor;
+pub mod trash;
+pub mod access_log;
+pub mod keys;
+pub mod document_store;
+pub mod certificates;
+pub mod statistics;
+pub mod numbering;
+pub mod ncf;
+pub mod migrations;
+pub mod database;
+pub mod redis_pool;
+pub mod presign;
+pub mod encryption;
+pub mod organizations;
+pub mod render_profiles;
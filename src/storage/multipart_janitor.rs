@@ -0,0 +1,66 @@
+use anyhow::Result;
+use chrono::Utc;
+use std::sync::Arc;
+
+use super::storage_trait::Storage;
+
+/// Antigüedad por defecto a partir de la cual un multipart incompleto se aborta
+const DEFAULT_MAX_AGE_HOURS: i64 = 24;
+
+/// Job periódico que aborta los multipart uploads abandonados (procesos
+/// caídos, aborts fallidos) para que sus partes no sigan acumulando costo
+pub struct MultipartJanitor {
+    storage: Arc<dyn Storage>,
+    buckets: Vec<String>,
+    max_age: chrono::Duration,
+}
+
+impl MultipartJanitor {
+    pub fn new(storage: Arc<dyn Storage>, buckets: Vec<String>, max_age_hours: i64) -> Self {
+        MultipartJanitor {
+            storage,
+            buckets,
+            max_age: chrono::Duration::hours(max_age_hours),
+        }
+    }
+
+    /// `MULTIPART_MAX_AGE_HOURS` (por defecto 24)
+    pub fn from_env(storage: Arc<dyn Storage>, buckets: Vec<String>) -> Self {
+        let max_age_hours = std::env::var("MULTIPART_MAX_AGE_HOURS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|h| *h > 0)
+            .unwrap_or(DEFAULT_MAX_AGE_HOURS);
+
+        Self::new(storage, buckets, max_age_hours)
+    }
+
+    pub fn spawn(self, interval_secs: u64) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+            loop {
+                interval.tick().await;
+                match self.run_once().await {
+                    Ok(0) => tracing::debug!("Multipart janitor: no stale uploads"),
+                    Ok(aborted) => tracing::info!("Multipart janitor aborted {} stale uploads", aborted),
+                    Err(e) => tracing::error!("Multipart janitor run failed: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Una pasada sobre todos los buckets; retorna cuántos uploads abortó
+    pub async fn run_once(&self) -> Result<usize> {
+        let older_than = Utc::now() - self.max_age;
+        let mut aborted = 0;
+
+        for bucket in &self.buckets {
+            match self.storage.abort_stale_uploads(bucket, older_than).await {
+                Ok(count) => aborted += count,
+                Err(e) => tracing::warn!("Failed to clean multipart uploads in {}: {}", bucket, e),
+            }
+        }
+
+        Ok(aborted)
+    }
+}
@@ -0,0 +1,380 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use super::database::Database;
+
+/// Motivos por los que una serie no puede emitir
+#[derive(Debug, thiserror::Error)]
+pub enum NcfError {
+    #[error("NCF series '{0}' is not registered")]
+    SeriesNotFound(String),
+    #[error("NCF series '{0}' has no numbers left in its authorized range")]
+    Exhausted(String),
+    #[error("NCF series '{0}' expired on {1}")]
+    Expired(String, NaiveDate),
+}
+
+/// Rango autorizado por la DGII para una serie (`E31`, `E32`, `B01`, ...)
+#[derive(Debug, Clone, Serialize)]
+pub struct NcfSeries {
+    pub series: String,
+    /// Próximo secuencial a emitir
+    pub next_value: i64,
+    /// Último secuencial autorizado
+    pub range_end: i64,
+    /// Fecha de vencimiento de la secuencia
+    pub expires_on: Option<NaiveDate>,
+    pub remaining: i64,
+}
+
+/// e-NCF (o NCF) asignado a un documento
+#[derive(Debug, Clone, Serialize)]
+pub struct AllocatedNcf {
+    pub series: String,
+    pub ncf: String,
+    pub document_id: Option<Uuid>,
+    pub expires_on: Option<NaiveDate>,
+    pub issued_at: DateTime<Utc>,
+}
+
+/// Asignación de NCF por tenant y serie, sin duplicados ni huecos
+#[async_trait]
+pub trait NcfAllocator: Send + Sync {
+    /// Registra o amplía el rango autorizado de la serie; nunca retrocede el
+    /// próximo secuencial, así no se reemiten números ya usados
+    async fn register(
+        &self,
+        tenant_id: i64,
+        series: &str,
+        range_start: i64,
+        range_end: i64,
+        expires_on: Option<NaiveDate>,
+    ) -> Result<NcfSeries>;
+
+    async fn list(&self, tenant_id: i64) -> Result<Vec<NcfSeries>>;
+
+    /// Siguiente NCF de la serie; con `document_id` es idempotente
+    async fn allocate(&self, tenant_id: i64, series: &str, document_id: Option<Uuid>) -> Result<AllocatedNcf>;
+}
+
+/// Usa el pool compartido; sin él las series viven en memoria
+pub fn ncf_allocator(database: Option<Database>) -> Arc<dyn NcfAllocator> {
+    match database {
+        Some(database) => Arc::new(PostgresNcf::new(database)),
+        None => Arc::new(MemoryNcf::default()),
+    }
+}
+
+/// Serie de comprobantes: `E` (electrónico) o `B` seguida del tipo de 2 dígitos
+pub fn validate_series(series: &str) -> Result<()> {
+    let valid = series.len() == 3
+        && matches!(series.chars().next(), Some('E' | 'B'))
+        && series.chars().skip(1).all(|c| c.is_ascii_digit());
+    if !valid {
+        bail!("Invalid NCF series '{}': expected E or B followed by the 2-digit type (e.g. E31)", series);
+    }
+    Ok(())
+}
+
+/// Secuenciales que admite la serie: 10 dígitos en e-NCF, 8 en NCF
+fn max_value(series: &str) -> i64 {
+    if series.starts_with('E') { 9_999_999_999 } else { 99_999_999 }
+}
+
+/// Valida el rango autorizado de una serie
+pub fn validate_range(series: &str, range_start: i64, range_end: i64) -> Result<()> {
+    validate_series(series)?;
+    if range_start < 1 || range_end < range_start || range_end > max_value(series) {
+        bail!("Invalid range {}..={} for series {} (1..={})", range_start, range_end, series, max_value(series));
+    }
+    Ok(())
+}
+
+/// `E31` + 10 dígitos o `B01` + 8 dígitos
+fn format_ncf(series: &str, value: i64) -> String {
+    let width = if series.starts_with('E') { 10 } else { 8 };
+    format!("{}{:0width$}", series, value, width = width)
+}
+
+fn check_available(series: &str, next_value: i64, range_end: i64, expires_on: Option<NaiveDate>) -> Result<()> {
+    if let Some(expires_on) = expires_on.filter(|d| *d < Utc::now().date_naive()) {
+        return Err(NcfError::Expired(series.to_string(), expires_on).into());
+    }
+    if next_value > range_end {
+        return Err(NcfError::Exhausted(series.to_string()).into());
+    }
+    Ok(())
+}
+
+struct MemorySeries {
+    next_value: i64,
+    range_end: i64,
+    expires_on: Option<NaiveDate>,
+    by_document: HashMap<Uuid, AllocatedNcf>,
+}
+
+impl MemorySeries {
+    fn summary(&self, series: &str) -> NcfSeries {
+        NcfSeries {
+            series: series.to_string(),
+            next_value: self.next_value,
+            range_end: self.range_end,
+            expires_on: self.expires_on,
+            remaining: (self.range_end - self.next_value + 1).max(0),
+        }
+    }
+}
+
+/// Series en memoria (desarrollo y pruebas)
+#[derive(Default)]
+pub struct MemoryNcf {
+    series: Mutex<HashMap<(i64, String), MemorySeries>>,
+}
+
+#[async_trait]
+impl NcfAllocator for MemoryNcf {
+    async fn register(
+        &self,
+        tenant_id: i64,
+        series: &str,
+        range_start: i64,
+        range_end: i64,
+        expires_on: Option<NaiveDate>,
+    ) -> Result<NcfSeries> {
+        validate_range(series, range_start, range_end)?;
+        let mut all = self.series.lock().unwrap();
+        let entry = all.entry((tenant_id, series.to_string())).or_insert_with(|| MemorySeries {
+            next_value: range_start,
+            range_end,
+            expires_on,
+            by_document: HashMap::new(),
+        });
+        entry.next_value = entry.next_value.max(range_start);
+        entry.range_end = range_end;
+        entry.expires_on = expires_on;
+
+        Ok(entry.summary(series))
+    }
+
+    async fn list(&self, tenant_id: i64) -> Result<Vec<NcfSeries>> {
+        let all = self.series.lock().unwrap();
+        let mut list: Vec<NcfSeries> = all
+            .iter()
+            .filter(|((tenant, _), _)| *tenant == tenant_id)
+            .map(|((_, series), s)| s.summary(series))
+            .collect();
+        list.sort_by(|a, b| a.series.cmp(&b.series));
+        Ok(list)
+    }
+
+    async fn allocate(&self, tenant_id: i64, series: &str, document_id: Option<Uuid>) -> Result<AllocatedNcf> {
+        let mut all = self.series.lock().unwrap();
+        let entry = all
+            .get_mut(&(tenant_id, series.to_string()))
+            .ok_or_else(|| NcfError::SeriesNotFound(series.to_string()))?;
+
+        if let Some(allocated) = document_id.and_then(|id| entry.by_document.get(&id)) {
+            return Ok(allocated.clone());
+        }
+        check_available(series, entry.next_value, entry.range_end, entry.expires_on)?;
+
+        let allocated = AllocatedNcf {
+            series: series.to_string(),
+            ncf: format_ncf(series, entry.next_value),
+            document_id,
+            expires_on: entry.expires_on,
+            issued_at: Utc::now(),
+        };
+        entry.next_value += 1;
+        if let Some(id) = document_id {
+            entry.by_document.insert(id, allocated.clone());
+        }
+        Ok(allocated)
+    }
+}
+
+/// Series en Postgres. Cada asignación bloquea la fila de la serie
+/// (`SELECT ... FOR UPDATE`) y registra el NCF en la misma transacción, así
+/// dos workers nunca emiten el mismo número ni queda un hueco si uno cae
+pub struct PostgresNcf {
+    database: Database,
+}
+
+impl PostgresNcf {
+    pub fn new(database: Database) -> Self {
+        PostgresNcf { database }
+    }
+
+    async fn client(&self) -> Result<deadpool_postgres::Object> {
+        self.database.get().await.context("Failed to connect to the NCF database")
+    }
+}
+
+fn series_from_row(row: &tokio_postgres::Row) -> NcfSeries {
+    let next_value: i64 = row.get(1);
+    let range_end: i64 = row.get(2);
+    NcfSeries {
+        series: row.get(0),
+        next_value,
+        range_end,
+        expires_on: row.get(3),
+        remaining: (range_end - next_value + 1).max(0),
+    }
+}
+
+#[async_trait]
+impl NcfAllocator for PostgresNcf {
+    async fn register(
+        &self,
+        tenant_id: i64,
+        series: &str,
+        range_start: i64,
+        range_end: i64,
+        expires_on: Option<NaiveDate>,
+    ) -> Result<NcfSeries> {
+        validate_range(series, range_start, range_end)?;
+        let db = self.client().await?;
+
+        let row = db.query_one(
+            "INSERT INTO ncf_series (tenant_id, series, next_value, range_end, expires_on) VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (tenant_id, series) DO UPDATE SET
+                 next_value = GREATEST(ncf_series.next_value, EXCLUDED.next_value),
+                 range_end = EXCLUDED.range_end,
+                 expires_on = EXCLUDED.expires_on,
+                 updated_at = now()
+             RETURNING series, next_value, range_end, expires_on",
+            &[&tenant_id, &series, &range_start, &range_end, &expires_on],
+        ).await?;
+
+        Ok(series_from_row(&row))
+    }
+
+    async fn list(&self, tenant_id: i64) -> Result<Vec<NcfSeries>> {
+        let db = self.client().await?;
+
+        let rows = db.query(
+            "SELECT series, next_value, range_end, expires_on FROM ncf_series WHERE tenant_id = $1 ORDER BY series",
+            &[&tenant_id],
+        ).await?;
+        Ok(rows.iter().map(series_from_row).collect())
+    }
+
+    async fn allocate(&self, tenant_id: i64, series: &str, document_id: Option<Uuid>) -> Result<AllocatedNcf> {
+        validate_series(series)?;
+        let mut db = self.client().await?;
+        let tx = db.transaction().await?;
+
+        // El bloqueo va primero: serializa también los reintentos del mismo documento
+        let row = tx
+            .query_opt(
+                "SELECT next_value, range_end, expires_on FROM ncf_series
+                 WHERE tenant_id = $1 AND series = $2 FOR UPDATE",
+                &[&tenant_id, &series],
+            )
+            .await?
+            .ok_or_else(|| NcfError::SeriesNotFound(series.to_string()))?;
+        let (next_value, range_end, expires_on): (i64, i64, Option<NaiveDate>) = (row.get(0), row.get(1), row.get(2));
+
+        if let Some(id) = document_id {
+            let existing = tx.query_opt(
+                "SELECT ncf, issued_at FROM ncf_issued WHERE tenant_id = $1 AND series = $2 AND document_id = $3",
+                &[&tenant_id, &series, &id],
+            ).await?;
+            if let Some(existing) = existing {
+                tx.commit().await?;
+                return Ok(AllocatedNcf {
+                    series: series.to_string(),
+                    ncf: existing.get(0),
+                    document_id,
+                    expires_on,
+                    issued_at: existing.get(1),
+                });
+            }
+        }
+        check_available(series, next_value, range_end, expires_on)?;
+
+        let ncf = format_ncf(series, next_value);
+        let issued_at = Utc::now();
+        tx.execute(
+            "UPDATE ncf_series SET next_value = next_value + 1, updated_at = now() WHERE tenant_id = $1 AND series = $2",
+            &[&tenant_id, &series],
+        ).await?;
+        tx.execute(
+            "INSERT INTO ncf_issued (tenant_id, series, ncf, document_id, issued_at) VALUES ($1, $2, $3, $4, $5)",
+            &[&tenant_id, &series, &ncf, &document_id, &issued_at],
+        ).await?;
+        tx.commit().await?;
+
+        Ok(AllocatedNcf { series: series.to_string(), ncf, document_id, expires_on, issued_at })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn reason(error: anyhow::Error) -> NcfError {
+        error.downcast::<NcfError>().expect("not an NcfError")
+    }
+
+    #[tokio::test]
+    async fn allocates_consecutive_numbers_per_tenant_and_series() {
+        let ncf = MemoryNcf::default();
+        ncf.register(1, "E31", 1, 100, None).await.unwrap();
+        ncf.register(1, "B01", 50, 100, None).await.unwrap();
+        ncf.register(2, "E31", 1, 100, None).await.unwrap();
+
+        assert_eq!(ncf.allocate(1, "E31", None).await.unwrap().ncf, "E310000000001");
+        assert_eq!(ncf.allocate(1, "E31", None).await.unwrap().ncf, "E310000000002");
+        assert_eq!(ncf.allocate(1, "B01", None).await.unwrap().ncf, "B0100000050");
+        assert_eq!(ncf.allocate(2, "E31", None).await.unwrap().ncf, "E310000000001");
+
+        let listed = ncf.list(1).await.unwrap();
+        assert_eq!(listed.iter().map(|s| (s.series.as_str(), s.remaining)).collect::<Vec<_>>(), vec![("B01", 50), ("E31", 98)]);
+    }
+
+    #[tokio::test]
+    async fn retries_of_a_document_get_the_same_number() {
+        let ncf = MemoryNcf::default();
+        ncf.register(1, "E31", 1, 100, None).await.unwrap();
+        let document_id = Uuid::new_v4();
+
+        let first = ncf.allocate(1, "E31", Some(document_id)).await.unwrap();
+        let retry = ncf.allocate(1, "E31", Some(document_id)).await.unwrap();
+        assert_eq!(first.ncf, retry.ncf);
+        assert_eq!(ncf.allocate(1, "E31", None).await.unwrap().ncf, "E310000000002");
+    }
+
+    #[tokio::test]
+    async fn extending_a_range_never_reissues_numbers() {
+        let ncf = MemoryNcf::default();
+        ncf.register(1, "E31", 1, 2, None).await.unwrap();
+        ncf.allocate(1, "E31", None).await.unwrap();
+        ncf.allocate(1, "E31", None).await.unwrap();
+        assert!(matches!(reason(ncf.allocate(1, "E31", None).await.unwrap_err()), NcfError::Exhausted(_)));
+
+        let extended = ncf.register(1, "E31", 1, 10, None).await.unwrap();
+        assert_eq!(extended.next_value, 3);
+        assert_eq!(ncf.allocate(1, "E31", None).await.unwrap().ncf, "E310000000003");
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_expired_and_invalid_series() {
+        let ncf = MemoryNcf::default();
+        assert!(matches!(reason(ncf.allocate(1, "E31", None).await.unwrap_err()), NcfError::SeriesNotFound(_)));
+
+        let yesterday = Utc::now().date_naive() - Duration::days(1);
+        ncf.register(1, "E32", 1, 10, Some(yesterday)).await.unwrap();
+        assert!(matches!(reason(ncf.allocate(1, "E32", None).await.unwrap_err()), NcfError::Expired(_, _)));
+
+        assert!(ncf.register(1, "X31", 1, 10, None).await.is_err());
+        assert!(ncf.register(1, "B01", 1, 100_000_000, None).await.is_err());
+        assert!(ncf.register(1, "E31", 10, 5, None).await.is_err());
+    }
+}
@@ -0,0 +1,394 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Utc};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use super::database::Database;
+
+/// Números faltantes que se reportan como máximo por auditoría
+const MAX_GAPS: usize = 1_000;
+
+/// El tenant no definió la secuencia pedida
+#[derive(Debug, thiserror::Error)]
+#[error("Sequence '{0}' not found")]
+pub struct SequenceNotFound(pub String);
+
+/// Secuencia con nombre de un tenant (`INV`, `E31`, ...)
+#[derive(Debug, Clone, Serialize)]
+pub struct SequenceDefinition {
+    pub name: String,
+    /// Formato del número, p. ej. `INV-{year}-{seq:06}`
+    pub format: String,
+    pub start: i64,
+}
+
+/// Número emitido por una secuencia
+#[derive(Debug, Clone, Serialize)]
+pub struct IssuedNumber {
+    pub name: String,
+    pub seq: i64,
+    pub number: String,
+    pub document_id: Option<Uuid>,
+    pub issued_at: DateTime<Utc>,
+}
+
+/// Rango de valores consumidos de la secuencia sin número emitido registrado
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Gap {
+    pub from: i64,
+    pub to: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GapReport {
+    pub name: String,
+    pub start: i64,
+    /// Último valor consumido de la secuencia (`None` si no se usó)
+    pub last_seq: Option<i64>,
+    pub issued: i64,
+    pub gaps: Vec<Gap>,
+}
+
+/// Backend de las secuencias; con `DATABASE_URL`, Postgres
+#[async_trait]
+pub trait NumberingBackend: Send + Sync {
+    /// Crea la secuencia o cambia su formato (`start` solo aplica al crearla)
+    async fn define(&self, tenant_id: i64, name: &str, format: &str, start: i64) -> Result<SequenceDefinition>;
+
+    async fn list(&self, tenant_id: i64) -> Result<Vec<SequenceDefinition>>;
+
+    /// Siguiente número; con `document_id` es idempotente (el reintento de un
+    /// documento recibe el mismo número)
+    async fn next(
+        &self,
+        tenant_id: i64,
+        name: &str,
+        document_id: Option<Uuid>,
+        reference: Option<&str>,
+    ) -> Result<IssuedNumber>;
+
+    /// Valores consumidos sin número emitido (caídas entre `nextval` y el registro)
+    async fn gaps(&self, tenant_id: i64, name: &str) -> Result<GapReport>;
+}
+
+/// Secuencias en Postgres con el pool compartido; sin él viven en memoria y
+/// se reinician con el proceso
+pub fn numbering_backend(database: Option<Database>) -> Arc<dyn NumberingBackend> {
+    match database {
+        Some(database) => Arc::new(PostgresNumbering::new(database)),
+        None => {
+            tracing::warn!("DATABASE_URL not set: numbering sequences are kept in memory only");
+            Arc::new(MemoryNumbering::default())
+        },
+    }
+}
+
+/// Nombre de secuencia: letras, dígitos, `_` y `-`, hasta 64 caracteres
+pub fn validate_sequence_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > 64 || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        bail!("Invalid sequence name '{}': use letters, digits, '_' or '-' (max 64)", name);
+    }
+    Ok(())
+}
+
+/// Aplica el formato: `{seq}`, `{seq:0N}` (relleno con ceros), `{year}`,
+/// `{yy}`, `{month}` y `{day}`. El formato debe incluir `{seq}`
+pub fn format_number(format: &str, seq: i64, at: DateTime<Utc>) -> Result<String> {
+    let mut output = String::new();
+    let mut rest = format;
+    let mut has_seq = false;
+
+    while let Some(open) = rest.find('{') {
+        output.push_str(&rest[..open]);
+        let close = rest[open..].find('}').map(|i| open + i).context("Unclosed '{' in number format")?;
+        let placeholder = &rest[open + 1..close];
+
+        match placeholder {
+            "seq" => {
+                has_seq = true;
+                output.push_str(&seq.to_string());
+            },
+            "year" => output.push_str(&format!("{:04}", at.year())),
+            "yy" => output.push_str(&format!("{:02}", at.year() % 100)),
+            "month" => output.push_str(&format!("{:02}", at.month())),
+            "day" => output.push_str(&format!("{:02}", at.day())),
+            other => {
+                let width = other
+                    .strip_prefix("seq:")
+                    .and_then(|w| w.parse::<usize>().ok())
+                    .with_context(|| format!("Unknown placeholder '{{{}}}' in number format", other))?;
+                has_seq = true;
+                output.push_str(&format!("{:0width$}", seq, width = width));
+            },
+        }
+        rest = &rest[close + 1..];
+    }
+    output.push_str(rest);
+
+    if !has_seq {
+        bail!("Number format must include {{seq}}");
+    }
+    Ok(output)
+}
+
+/// Valores de `start..=last` que no están en `issued` (hasta `MAX_GAPS`)
+fn missing_values(start: i64, last: i64, issued: &BTreeSet<i64>) -> Vec<i64> {
+    (start..=last).filter(|seq| !issued.contains(seq)).take(MAX_GAPS).collect()
+}
+
+/// Agrupa valores ordenados en rangos consecutivos
+fn to_ranges(values: impl IntoIterator<Item = i64>) -> Vec<Gap> {
+    let mut gaps: Vec<Gap> = Vec::new();
+    for seq in values {
+        match gaps.last_mut() {
+            Some(gap) if gap.to + 1 == seq => gap.to = seq,
+            _ => gaps.push(Gap { from: seq, to: seq }),
+        }
+    }
+    gaps
+}
+
+#[derive(Default)]
+struct MemorySequence {
+    format: String,
+    start: i64,
+    last: Option<i64>,
+    issued: BTreeSet<i64>,
+    by_document: HashMap<Uuid, IssuedNumber>,
+}
+
+/// Secuencias en memoria (desarrollo y pruebas)
+#[derive(Default)]
+pub struct MemoryNumbering {
+    sequences: Mutex<HashMap<(i64, String), MemorySequence>>,
+}
+
+#[async_trait]
+impl NumberingBackend for MemoryNumbering {
+    async fn define(&self, tenant_id: i64, name: &str, format: &str, start: i64) -> Result<SequenceDefinition> {
+        let mut sequences = self.sequences.lock().unwrap();
+        let sequence = sequences.entry((tenant_id, name.to_string())).or_insert_with(|| MemorySequence {
+            start,
+            ..MemorySequence::default()
+        });
+        sequence.format = format.to_string();
+
+        Ok(SequenceDefinition { name: name.to_string(), format: format.to_string(), start: sequence.start })
+    }
+
+    async fn list(&self, tenant_id: i64) -> Result<Vec<SequenceDefinition>> {
+        let sequences = self.sequences.lock().unwrap();
+        let mut list: Vec<SequenceDefinition> = sequences
+            .iter()
+            .filter(|((tenant, _), _)| *tenant == tenant_id)
+            .map(|((_, name), s)| SequenceDefinition { name: name.clone(), format: s.format.clone(), start: s.start })
+            .collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(list)
+    }
+
+    async fn next(
+        &self,
+        tenant_id: i64,
+        name: &str,
+        document_id: Option<Uuid>,
+        _reference: Option<&str>,
+    ) -> Result<IssuedNumber> {
+        let mut sequences = self.sequences.lock().unwrap();
+        let sequence = sequences
+            .get_mut(&(tenant_id, name.to_string()))
+            .ok_or_else(|| SequenceNotFound(name.to_string()))?;
+
+        if let Some(issued) = document_id.and_then(|id| sequence.by_document.get(&id)) {
+            return Ok(issued.clone());
+        }
+
+        let seq = sequence.last.map_or(sequence.start, |last| last + 1);
+        let issued_at = Utc::now();
+        let number = format_number(&sequence.format, seq, issued_at)?;
+        sequence.last = Some(seq);
+        sequence.issued.insert(seq);
+
+        let issued = IssuedNumber { name: name.to_string(), seq, number, document_id, issued_at };
+        if let Some(id) = document_id {
+            sequence.by_document.insert(id, issued.clone());
+        }
+        Ok(issued)
+    }
+
+    async fn gaps(&self, tenant_id: i64, name: &str) -> Result<GapReport> {
+        let sequences = self.sequences.lock().unwrap();
+        let sequence = sequences
+            .get(&(tenant_id, name.to_string()))
+            .ok_or_else(|| SequenceNotFound(name.to_string()))?;
+
+        let gaps = match sequence.last {
+            Some(last) => to_ranges(missing_values(sequence.start, last, &sequence.issued)),
+            None => Vec::new(),
+        };
+        Ok(GapReport {
+            name: name.to_string(),
+            start: sequence.start,
+            last_seq: sequence.last,
+            issued: sequence.issued.len() as i64,
+            gaps,
+        })
+    }
+}
+
+/// Secuencias de Postgres (`CREATE SEQUENCE` por tenant y nombre): `nextval`
+/// es seguro entre procesos. Cada número emitido queda en `numbering_issued`,
+/// así los valores consumidos sin registro aparecen como huecos
+pub struct PostgresNumbering {
+    database: Database,
+}
+
+impl PostgresNumbering {
+    pub fn new(database: Database) -> Self {
+        PostgresNumbering { database }
+    }
+
+    async fn client(&self) -> Result<deadpool_postgres::Object> {
+        self.database.get().await.context("Failed to connect to the numbering database")
+    }
+
+    async fn definition(&self, db: &tokio_postgres::Client, tenant_id: i64, name: &str) -> Result<(String, i64)> {
+        let row = db
+            .query_opt(
+                "SELECT format, start_value FROM numbering_sequences WHERE tenant_id = $1 AND name = $2",
+                &[&tenant_id, &name],
+            )
+            .await?
+            .ok_or_else(|| SequenceNotFound(name.to_string()))?;
+        Ok((row.get(0), row.get(1)))
+    }
+}
+
+/// Identificador (entre comillas) de la secuencia de Postgres; el nombre ya
+/// fue validado, así que no lleva comillas propias
+fn pg_sequence(tenant_id: i64, name: &str) -> String {
+    format!("\"numbering_{}_{}\"", tenant_id, name)
+}
+
+#[async_trait]
+impl NumberingBackend for PostgresNumbering {
+    async fn define(&self, tenant_id: i64, name: &str, format: &str, start: i64) -> Result<SequenceDefinition> {
+        validate_sequence_name(name)?;
+        let db = self.client().await?;
+
+        db.batch_execute(&format!(
+            "CREATE SEQUENCE IF NOT EXISTS {} START WITH {} MINVALUE {}",
+            pg_sequence(tenant_id, name), start, start.min(1)
+        )).await?;
+        let row = db.query_one(
+            "INSERT INTO numbering_sequences (tenant_id, name, format, start_value) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (tenant_id, name) DO UPDATE SET format = EXCLUDED.format
+             RETURNING start_value",
+            &[&tenant_id, &name, &format, &start],
+        ).await?;
+
+        Ok(SequenceDefinition { name: name.to_string(), format: format.to_string(), start: row.get(0) })
+    }
+
+    async fn list(&self, tenant_id: i64) -> Result<Vec<SequenceDefinition>> {
+        let db = self.client().await?;
+        let rows = db.query(
+            "SELECT name, format, start_value FROM numbering_sequences WHERE tenant_id = $1 ORDER BY name",
+            &[&tenant_id],
+        ).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| SequenceDefinition { name: row.get(0), format: row.get(1), start: row.get(2) })
+            .collect())
+    }
+
+    async fn next(
+        &self,
+        tenant_id: i64,
+        name: &str,
+        document_id: Option<Uuid>,
+        reference: Option<&str>,
+    ) -> Result<IssuedNumber> {
+        validate_sequence_name(name)?;
+        let db = self.client().await?;
+        let (format, _) = self.definition(&db, tenant_id, name).await?;
+
+        let issued_for = |row: &tokio_postgres::Row| IssuedNumber {
+            name: name.to_string(),
+            seq: row.get(0),
+            number: row.get(1),
+            document_id,
+            issued_at: row.get(2),
+        };
+        let existing = "SELECT seq, number, issued_at FROM numbering_issued
+             WHERE tenant_id = $1 AND name = $2 AND document_id = $3";
+
+        if let Some(id) = document_id {
+            if let Some(row) = db.query_opt(existing, &[&tenant_id, &name, &id]).await? {
+                return Ok(issued_for(&row));
+            }
+        }
+
+        let seq: i64 = db
+            .query_one("SELECT nextval($1::text::regclass)", &[&pg_sequence(tenant_id, name)])
+            .await?
+            .get(0);
+        let issued_at = Utc::now();
+        let number = format_number(&format, seq, issued_at)?;
+
+        let inserted = db.execute(
+            "INSERT INTO numbering_issued (tenant_id, name, seq, number, document_id, reference, issued_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             ON CONFLICT DO NOTHING",
+            &[&tenant_id, &name, &seq, &number, &document_id, &reference, &issued_at],
+        ).await?;
+
+        // Otro proceso emitió primero para el mismo documento: `seq` queda como hueco
+        if inserted == 0 {
+            if let Some(id) = document_id {
+                let row = db.query_one(existing, &[&tenant_id, &name, &id]).await?;
+                return Ok(issued_for(&row));
+            }
+        }
+
+        Ok(IssuedNumber { name: name.to_string(), seq, number, document_id, issued_at })
+    }
+
+    async fn gaps(&self, tenant_id: i64, name: &str) -> Result<GapReport> {
+        validate_sequence_name(name)?;
+        let db = self.client().await?;
+        let (_, start) = self.definition(&db, tenant_id, name).await?;
+
+        let row = db.query_one(
+            &format!("SELECT last_value, is_called FROM {}", pg_sequence(tenant_id, name)),
+            &[],
+        ).await?;
+        let last_seq = row.get::<_, bool>(1).then(|| row.get::<_, i64>(0));
+
+        let issued: i64 = db.query_one(
+            "SELECT count(*) FROM numbering_issued WHERE tenant_id = $1 AND name = $2",
+            &[&tenant_id, &name],
+        ).await?.get(0);
+
+        let gaps = match last_seq {
+            Some(last) => {
+                let rows = db.query(
+                    "SELECT g FROM generate_series($3::bigint, $4::bigint) AS g
+                     WHERE NOT EXISTS (
+                         SELECT 1 FROM numbering_issued i WHERE i.tenant_id = $1 AND i.name = $2 AND i.seq = g
+                     )
+                     ORDER BY g LIMIT $5",
+                    &[&tenant_id, &name, &start, &last, &(MAX_GAPS as i64)],
+                ).await?;
+                to_ranges(rows.iter().map(|row| row.get::<_, i64>(0)))
+            },
+            None => Vec::new(),
+        };
+
+        Ok(GapReport { name: name.to_string(), start, last_seq, issued, gaps })
+    }
+}
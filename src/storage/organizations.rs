@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::models::Organization;
+use super::database::Database;
+
+/// Dónde se guardan las organizaciones de los tenants
+#[async_trait]
+pub trait OrganizationBackend: Send + Sync {
+    /// Crea la organización o, si trae nombre, lo cambia
+    async fn upsert(&self, tenant_id: i64, id: &str, name: Option<&str>) -> Result<Organization>;
+
+    async fn get(&self, tenant_id: i64, id: &str) -> Result<Option<Organization>>;
+
+    /// Organizaciones del tenant ordenadas por id
+    async fn list(&self, tenant_id: i64) -> Result<Vec<Organization>>;
+}
+
+/// Usa el pool compartido; sin él las organizaciones viven en memoria y se
+/// pierden con el proceso
+pub fn organizations_backend(database: Option<Database>) -> Arc<dyn OrganizationBackend> {
+    match database {
+        Some(database) => Arc::new(PostgresOrganizations::new(database)),
+        None => Arc::new(MemoryOrganizations::default()),
+    }
+}
+
+/// Organizaciones en memoria (desarrollo y pruebas)
+#[derive(Default)]
+pub struct MemoryOrganizations {
+    organizations: RwLock<HashMap<i64, HashMap<String, Organization>>>,
+}
+
+#[async_trait]
+impl OrganizationBackend for MemoryOrganizations {
+    async fn upsert(&self, tenant_id: i64, id: &str, name: Option<&str>) -> Result<Organization> {
+        let mut organizations = self.organizations.write().unwrap();
+        let organization = organizations
+            .entry(tenant_id)
+            .or_default()
+            .entry(id.to_string())
+            .or_insert_with(|| Organization {
+                id: id.to_string(),
+                tenant_id,
+                name: id.to_string(),
+                created_at: Utc::now(),
+            });
+
+        if let Some(name) = name {
+            organization.name = name.to_string();
+        }
+        Ok(organization.clone())
+    }
+
+    async fn get(&self, tenant_id: i64, id: &str) -> Result<Option<Organization>> {
+        Ok(self.organizations.read().unwrap().get(&tenant_id).and_then(|orgs| orgs.get(id)).cloned())
+    }
+
+    async fn list(&self, tenant_id: i64) -> Result<Vec<Organization>> {
+        let mut orgs: Vec<Organization> = self.organizations
+            .read()
+            .unwrap()
+            .get(&tenant_id)
+            .map(|orgs| orgs.values().cloned().collect())
+            .unwrap_or_default();
+        orgs.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(orgs)
+    }
+}
+
+/// Organizaciones en la tabla `organizations` de Postgres, compartidas entre
+/// réplicas y reinicios
+pub struct PostgresOrganizations {
+    database: Database,
+}
+
+impl PostgresOrganizations {
+    pub fn new(database: Database) -> Self {
+        PostgresOrganizations { database }
+    }
+
+    async fn client(&self) -> Result<deadpool_postgres::Object> {
+        self.database.get().await.context("Failed to connect to the organizations database")
+    }
+}
+
+fn organization_from_row(tenant_id: i64, row: &tokio_postgres::Row) -> Organization {
+    Organization { id: row.get(0), tenant_id, name: row.get(1), created_at: row.get(2) }
+}
+
+#[async_trait]
+impl OrganizationBackend for PostgresOrganizations {
+    async fn upsert(&self, tenant_id: i64, id: &str, name: Option<&str>) -> Result<Organization> {
+        let db = self.client().await?;
+        let row = db.query_one(
+            "INSERT INTO organizations (tenant_id, id, name) VALUES ($1, $2, COALESCE($3::text, $2))
+             ON CONFLICT (tenant_id, id) DO UPDATE SET name = COALESCE($3::text, organizations.name)
+             RETURNING id, name, created_at",
+            &[&tenant_id, &id, &name],
+        ).await?;
+
+        Ok(organization_from_row(tenant_id, &row))
+    }
+
+    async fn get(&self, tenant_id: i64, id: &str) -> Result<Option<Organization>> {
+        let db = self.client().await?;
+        let row = db.query_opt(
+            "SELECT id, name, created_at FROM organizations WHERE tenant_id = $1 AND id = $2",
+            &[&tenant_id, &id],
+        ).await?;
+
+        Ok(row.map(|row| organization_from_row(tenant_id, &row)))
+    }
+
+    async fn list(&self, tenant_id: i64) -> Result<Vec<Organization>> {
+        let db = self.client().await?;
+        let rows = db.query(
+            "SELECT id, name, created_at FROM organizations WHERE tenant_id = $1 ORDER BY id",
+            &[&tenant_id],
+        ).await?;
+
+        Ok(rows.iter().map(|row| organization_from_row(tenant_id, row)).collect())
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Límite de SigV4: una URL presignada no puede durar más de 7 días
+pub const MAX_PRESIGN_TTL_SECS: u64 = 7 * 24 * 3600;
+
+fn default_ttl() -> u64 {
+    3600
+}
+
+fn default_max() -> u64 {
+    MAX_PRESIGN_TTL_SECS
+}
+
+/// Duración de las URLs firmadas (descarga y subida): valor por defecto,
+/// overrides por tenant y un máximo global que ningún tenant supera
+#[derive(Debug, Clone, Deserialize)]
+pub struct PresignPolicy {
+    #[serde(default = "default_ttl")]
+    pub default_secs: u64,
+    #[serde(default = "default_max")]
+    pub max_secs: u64,
+    #[serde(default)]
+    pub tenants: HashMap<i64, u64>,
+}
+
+impl Default for PresignPolicy {
+    fn default() -> Self {
+        PresignPolicy {
+            default_secs: default_ttl(),
+            max_secs: default_max(),
+            tenants: HashMap::new(),
+        }
+    }
+}
+
+impl PresignPolicy {
+    /// Lee `PRESIGN_TTL_POLICY` (JSON), p. ej.
+    /// `{"default_secs": 3600, "max_secs": 86400, "tenants": {"7": 300, "9": 604800}}`
+    pub fn from_env() -> Result<Self> {
+        let policy: PresignPolicy = match std::env::var("PRESIGN_TTL_POLICY") {
+            Ok(raw) if !raw.trim().is_empty() => serde_json::from_str(&raw)?,
+            _ => PresignPolicy::default(),
+        };
+
+        if policy.max_secs == 0 || policy.max_secs > MAX_PRESIGN_TTL_SECS {
+            anyhow::bail!("PRESIGN_TTL_POLICY max_secs must be between 1 and {}", MAX_PRESIGN_TTL_SECS);
+        }
+        for (tenant_id, secs) in &policy.tenants {
+            if *secs > policy.max_secs {
+                tracing::warn!(
+                    "Presign TTL of tenant {} ({}s) exceeds the maximum; capped to {}s",
+                    tenant_id, secs, policy.max_secs
+                );
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Segundos de validez de una URL firmada para el tenant, acotados al máximo global
+    pub fn ttl_for(&self, tenant_id: i64) -> u64 {
+        self.tenants
+            .get(&tenant_id)
+            .copied()
+            .unwrap_or(self.default_secs)
+            .clamp(1, self.max_secs)
+    }
+}
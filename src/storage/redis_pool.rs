@@ -0,0 +1,166 @@
+use anyhow::{bail, Context, Result};
+use deadpool_redis::redis::{Cmd, FromRedisValue, Pipeline, RedisResult};
+use deadpool_redis::{Config, Connection, Pool, PoolConfig, Runtime, Timeouts};
+use once_cell::sync::Lazy;
+use prometheus::{register_histogram, register_int_counter_vec, register_int_gauge_vec, Histogram, IntCounterVec, IntGaugeVec};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+static COMMANDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("redis_commands_total", "Comandos a Redis por resultado", &["outcome"]).unwrap()
+});
+
+static COMMAND_SECONDS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "redis_command_duration_seconds",
+        "Duración de los comandos a Redis, incluida la espera por conexión",
+        vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0]
+    )
+    .unwrap()
+});
+
+static POOL_CONNECTIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!("redis_pool_connections", "Conexiones del pool de Redis", &["state"]).unwrap()
+});
+
+/// Configuración del pool (`REDIS_URL`, `REDIS_POOL_SIZE`, `REDIS_POOL_TIMEOUT_MS`,
+/// `REDIS_COMMAND_TIMEOUT_MS`)
+#[derive(Debug, Clone)]
+pub struct RedisPoolConfig {
+    pub url: String,
+    pub max_size: usize,
+    /// Espera máxima por una conexión libre, y para crear o revisar una
+    pub pool_timeout: Duration,
+    pub command_timeout: Duration,
+}
+
+impl RedisPoolConfig {
+    /// `None` si no hay `REDIS_URL`
+    pub fn from_env() -> Option<Self> {
+        let url = std::env::var("REDIS_URL").ok().filter(|url| !url.trim().is_empty())?;
+        let millis = |var: &str, default: u64| {
+            Duration::from_millis(std::env::var(var).ok().and_then(|v| v.parse().ok()).unwrap_or(default))
+        };
+
+        Some(RedisPoolConfig {
+            url,
+            max_size: std::env::var("REDIS_POOL_SIZE").ok().and_then(|v| v.parse().ok()).unwrap_or(16),
+            pool_timeout: millis("REDIS_POOL_TIMEOUT_MS", 500),
+            command_timeout: millis("REDIS_COMMAND_TIMEOUT_MS", 1_000),
+        })
+    }
+}
+
+/// Estado del pool para diagnóstico
+#[derive(Debug, Clone, Serialize)]
+pub struct RedisPoolStatus {
+    pub max_size: usize,
+    pub size: usize,
+    pub available: usize,
+}
+
+/// Pool de conexiones a Redis. Cada conexión se revisa con `PING` al tomarla:
+/// las que cayeron (p. ej. tras un failover) se descartan y se abre otra, así
+/// que la reconexión es automática. Un comando que vence su timeout o pierde
+/// la conexión la saca del pool en vez de devolverla
+pub struct RedisPool {
+    pool: Pool,
+    command_timeout: Duration,
+}
+
+impl RedisPool {
+    pub fn new(config: RedisPoolConfig) -> Result<Self> {
+        let mut pool_config = PoolConfig::new(config.max_size.max(1));
+        pool_config.timeouts = Timeouts {
+            wait: Some(config.pool_timeout),
+            create: Some(config.pool_timeout),
+            recycle: Some(config.pool_timeout),
+        };
+
+        let mut redis_config = Config::from_url(config.url);
+        redis_config.pool = Some(pool_config);
+        let pool = redis_config
+            .create_pool(Some(Runtime::Tokio1))
+            .context("Invalid Redis configuration")?;
+
+        Ok(RedisPool { pool, command_timeout: config.command_timeout })
+    }
+
+    pub async fn query<T: FromRedisValue>(&self, cmd: &Cmd) -> Result<T> {
+        let started = Instant::now();
+        let mut conn = self.checkout().await?;
+        let result = tokio::time::timeout(self.command_timeout, cmd.query_async(&mut conn)).await;
+        self.settle(conn, result, started)
+    }
+
+    /// Como `query`, para varios comandos en un solo viaje
+    pub async fn query_pipeline<T: FromRedisValue>(&self, pipe: &Pipeline) -> Result<T> {
+        let started = Instant::now();
+        let mut conn = self.checkout().await?;
+        let result = tokio::time::timeout(self.command_timeout, pipe.query_async(&mut conn)).await;
+        self.settle(conn, result, started)
+    }
+
+    /// Sondeo de salud
+    pub async fn ping(&self) -> Result<()> {
+        let _: String = self.query(&deadpool_redis::redis::cmd("PING")).await?;
+        Ok(())
+    }
+
+    pub fn status(&self) -> RedisPoolStatus {
+        let status = self.pool.status();
+        RedisPoolStatus {
+            max_size: status.max_size,
+            size: status.size,
+            available: status.available,
+        }
+    }
+
+    async fn checkout(&self) -> Result<Connection> {
+        let conn = self.pool.get().await;
+        let status = self.status();
+        POOL_CONNECTIONS.with_label_values(&["idle"]).set(status.available as i64);
+        POOL_CONNECTIONS.with_label_values(&["in_use"]).set(status.size.saturating_sub(status.available) as i64);
+
+        conn.map_err(|e| {
+            COMMANDS.with_label_values(&["pool_error"]).inc();
+            anyhow::anyhow!("No Redis connection available: {}", e)
+        })
+    }
+
+    fn settle<T>(
+        &self,
+        conn: Connection,
+        result: Result<RedisResult<T>, tokio::time::error::Elapsed>,
+        started: Instant,
+    ) -> Result<T> {
+        COMMAND_SECONDS.observe(started.elapsed().as_secs_f64());
+        match result {
+            Ok(Ok(value)) => {
+                COMMANDS.with_label_values(&["ok"]).inc();
+                Ok(value)
+            },
+            Ok(Err(e)) => {
+                COMMANDS.with_label_values(&["error"]).inc();
+                if e.is_connection_dropped() || e.is_io_error() || e.is_timeout() {
+                    let _ = Connection::take(conn);
+                }
+                Err(anyhow::Error::new(e).context("Redis command failed"))
+            },
+            Err(_) => {
+                COMMANDS.with_label_values(&["timeout"]).inc();
+                // La respuesta pendiente desordenaría la conexión: no vuelve al pool
+                let _ = Connection::take(conn);
+                bail!("Redis command timed out after {:?}", self.command_timeout)
+            },
+        }
+    }
+}
+
+/// Pool compartido si hay `REDIS_URL`; sin ella, `None` y todo queda en memoria
+pub fn redis_from_env() -> Result<Option<Arc<RedisPool>>> {
+    RedisPoolConfig::from_env()
+        .map(|config| RedisPool::new(config).map(Arc::new))
+        .transpose()
+}
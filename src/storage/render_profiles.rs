@@ -0,0 +1,109 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::models::RenderProfile;
+use super::database::Database;
+
+/// Dónde se guardan los perfiles de render de los tenants
+#[async_trait]
+pub trait RenderProfileBackend: Send + Sync {
+    async fn get(&self, tenant_id: i64) -> Result<Option<RenderProfile>>;
+
+    /// Crea o reemplaza el perfil del tenant
+    async fn put(&self, profile: &RenderProfile) -> Result<()>;
+
+    /// `false` si el tenant no tenía perfil
+    async fn remove(&self, tenant_id: i64) -> Result<bool>;
+}
+
+/// Usa el pool compartido; sin él los perfiles viven en memoria y se pierden
+/// con el proceso
+pub fn render_profiles_backend(database: Option<Database>) -> Arc<dyn RenderProfileBackend> {
+    match database {
+        Some(database) => Arc::new(PostgresRenderProfiles::new(database)),
+        None => Arc::new(MemoryRenderProfiles::default()),
+    }
+}
+
+/// Perfiles en memoria (desarrollo y pruebas)
+#[derive(Default)]
+pub struct MemoryRenderProfiles {
+    profiles: RwLock<HashMap<i64, RenderProfile>>,
+}
+
+#[async_trait]
+impl RenderProfileBackend for MemoryRenderProfiles {
+    async fn get(&self, tenant_id: i64) -> Result<Option<RenderProfile>> {
+        Ok(self.profiles.read().unwrap().get(&tenant_id).cloned())
+    }
+
+    async fn put(&self, profile: &RenderProfile) -> Result<()> {
+        self.profiles.write().unwrap().insert(profile.tenant_id, profile.clone());
+        Ok(())
+    }
+
+    async fn remove(&self, tenant_id: i64) -> Result<bool> {
+        Ok(self.profiles.write().unwrap().remove(&tenant_id).is_some())
+    }
+}
+
+/// Perfiles en la columna `render_profile` de la tabla `tenants`, compartidos
+/// entre réplicas y reinicios
+pub struct PostgresRenderProfiles {
+    database: Database,
+}
+
+impl PostgresRenderProfiles {
+    pub fn new(database: Database) -> Self {
+        PostgresRenderProfiles { database }
+    }
+
+    async fn client(&self) -> Result<deadpool_postgres::Object> {
+        self.database.get().await.context("Failed to connect to the tenants database")
+    }
+}
+
+#[async_trait]
+impl RenderProfileBackend for PostgresRenderProfiles {
+    async fn get(&self, tenant_id: i64) -> Result<Option<RenderProfile>> {
+        let db = self.client().await?;
+        let row = db.query_opt(
+            "SELECT render_profile, render_profile_updated_at FROM tenants
+             WHERE tenant_id = $1 AND render_profile IS NOT NULL",
+            &[&tenant_id],
+        ).await?;
+
+        row.map(|row| {
+            let Value::Object(options) = row.get(0) else {
+                anyhow::bail!("Render profile of tenant {} is not a JSON object", tenant_id);
+            };
+            Ok(RenderProfile { tenant_id, options, updated_at: row.get(1) })
+        })
+        .transpose()
+    }
+
+    async fn put(&self, profile: &RenderProfile) -> Result<()> {
+        let db = self.client().await?;
+        db.execute(
+            "INSERT INTO tenants (tenant_id, render_profile, render_profile_updated_at) VALUES ($1, $2, $3)
+             ON CONFLICT (tenant_id) DO UPDATE SET
+                 render_profile = EXCLUDED.render_profile,
+                 render_profile_updated_at = EXCLUDED.render_profile_updated_at",
+            &[&profile.tenant_id, &Value::Object(profile.options.clone()), &profile.updated_at],
+        ).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, tenant_id: i64) -> Result<bool> {
+        let db = self.client().await?;
+        let removed = db.execute(
+            "UPDATE tenants SET render_profile = NULL, render_profile_updated_at = NULL
+             WHERE tenant_id = $1 AND render_profile IS NOT NULL",
+            &[&tenant_id],
+        ).await?;
+        Ok(removed == 1)
+    }
+}
@@ -0,0 +1,194 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::document_store::DocumentStore;
+use super::storage_trait::{ObjectInfo, Storage};
+use super::keys::{SIGNING_PREFIX, TEMPLATES_PREFIX};
+
+/// Política de retención: días en almacenamiento "caliente", clase de
+/// archivo a la que se transiciona y borrado definitivo opcional
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub hot_days: i64,
+    #[serde(default = "default_archive_class")]
+    pub archive_storage_class: String, // "GLACIER", "STANDARD_IA", "DEEP_ARCHIVE"
+    pub delete_after_days: Option<i64>,
+}
+
+fn default_archive_class() -> String {
+    "GLACIER".to_string()
+}
+
+/// Configuración de retención: política por defecto y overrides por tenant
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    pub default: Option<RetentionPolicy>,
+    #[serde(default)]
+    pub tenants: HashMap<i64, RetentionPolicy>,
+}
+
+impl RetentionConfig {
+    /// Lee `RETENTION_POLICIES` (JSON), p. ej.
+    /// `{"default": {"hot_days": 90, "delete_after_days": 3650}, "tenants": {"7": {"hot_days": 30}}}`
+    pub fn from_env() -> Result<Option<Self>> {
+        match std::env::var("RETENTION_POLICIES") {
+            Ok(raw) if !raw.trim().is_empty() => Ok(Some(serde_json::from_str(&raw)?)),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn policy_for(&self, tenant_id: Option<i64>) -> Option<&RetentionPolicy> {
+        tenant_id
+            .and_then(|id| self.tenants.get(&id))
+            .or(self.default.as_ref())
+    }
+}
+
+/// Acción a aplicar sobre un objeto según su antigüedad
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetentionAction {
+    Keep,
+    Archive(String),
+    Delete,
+}
+
+impl RetentionPolicy {
+    pub fn action_for(&self, age_days: i64, current_class: Option<&str>) -> RetentionAction {
+        if let Some(delete_after) = self.delete_after_days {
+            if age_days >= delete_after {
+                return RetentionAction::Delete;
+            }
+        }
+
+        if age_days >= self.hot_days && current_class != Some(self.archive_storage_class.as_str()) {
+            return RetentionAction::Archive(self.archive_storage_class.clone());
+        }
+
+        RetentionAction::Keep
+    }
+}
+
+/// Extrae el tenant de una clave S3 buscando un segmento `tenant_{id}`
+pub fn tenant_from_key(key: &str) -> Option<i64> {
+    key.split('/')
+        .find_map(|segment| segment.strip_prefix("tenant_"))
+        .and_then(|id| id.parse().ok())
+}
+
+/// Job en segundo plano que aplica las políticas de retención al bucket de
+/// documentos y refleja el resultado en sus registros
+pub struct RetentionJob {
+    storage: Arc<dyn Storage>,
+    documents: Arc<DocumentStore>,
+    bucket: String,
+    config: RetentionConfig,
+}
+
+impl RetentionJob {
+    pub fn new(storage: Arc<dyn Storage>, documents: Arc<DocumentStore>, bucket: String, config: RetentionConfig) -> Self {
+        RetentionJob { storage, documents, bucket, config }
+    }
+
+    /// Ejecuta el job periódicamente en una tarea de tokio
+    pub fn spawn(self, interval_secs: u64) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+            loop {
+                interval.tick().await;
+                match self.run_once().await {
+                    Ok(summary) => tracing::info!(
+                        "Retention run on {}: {} archived, {} deleted",
+                        self.bucket, summary.archived, summary.deleted
+                    ),
+                    Err(e) => tracing::error!("Retention run on {} failed: {}", self.bucket, e),
+                }
+            }
+        })
+    }
+
+    /// Recorre el bucket página a página (continuation token), sin cargar el
+    /// listado completo en memoria
+    pub async fn run_once(&self) -> Result<RetentionSummary> {
+        let mut summary = RetentionSummary::default();
+        let now = Utc::now();
+        let mut continuation = None;
+
+        loop {
+            let page = self.storage.list_page(&self.bucket, None, continuation).await?;
+            for object in &page.objects {
+                self.apply(object, now, &mut summary).await;
+            }
+            match page.next {
+                Some(token) => continuation = Some(token),
+                None => break,
+            }
+        }
+
+        Ok(summary)
+    }
+
+    async fn apply(&self, object: &ObjectInfo, now: DateTime<Utc>, summary: &mut RetentionSummary) {
+        // Las plantillas y certificados de los tenants no son documentos: no expiran
+        if object.key.starts_with(TEMPLATES_PREFIX) || object.key.starts_with(SIGNING_PREFIX) {
+            return;
+        }
+        let Some(policy) = self.config.policy_for(tenant_from_key(&object.key)) else {
+            return;
+        };
+        let Some(last_modified) = object.last_modified else {
+            return;
+        };
+
+        let age_days = (now - last_modified).num_days();
+
+        match policy.action_for(age_days, object.storage_class.as_deref()) {
+            RetentionAction::Keep => {},
+            RetentionAction::Archive(class) => {
+                if let Err(e) = self.storage.transition_storage_class(&self.bucket, &object.key, &class).await {
+                    tracing::warn!("Failed to archive {}: {}", object.key, e);
+                    return;
+                }
+                summary.archived += 1;
+                if let Err(e) = self.record_archived(&object.key, &class).await {
+                    tracing::warn!("Archived {} but failed to update its document: {:#}", object.key, e);
+                }
+            },
+            RetentionAction::Delete => {
+                if let Err(e) = self.storage.delete(&self.bucket, &object.key).await {
+                    tracing::warn!("Failed to delete expired {}: {}", object.key, e);
+                    return;
+                }
+                summary.deleted += 1;
+                if let Err(e) = self.record_deleted(&object.key).await {
+                    tracing::warn!("Deleted {} but failed to remove its document: {:#}", object.key, e);
+                }
+            },
+        }
+    }
+
+    /// Anota la clase en el documento cuyo archivo principal es `key` (las
+    /// miniaturas y anexos no tienen registro propio)
+    async fn record_archived(&self, key: &str, class: &str) -> Result<()> {
+        if let Some(record) = self.documents.by_storage_key(&self.bucket, key).await? {
+            self.documents.set_storage_class(&record.id, class).await?;
+        }
+        Ok(())
+    }
+
+    /// Sin su archivo principal el documento ya no existe: se quita el registro
+    async fn record_deleted(&self, key: &str) -> Result<()> {
+        if let Some(record) = self.documents.by_storage_key(&self.bucket, key).await? {
+            self.documents.remove(&record.id).await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RetentionSummary {
+    pub archived: usize,
+    pub deleted: usize,
+}
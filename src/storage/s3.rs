@@ -1,19 +1,278 @@
 use aws_sdk_s3::{Client, Config};
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::SdkError;
 use aws_sdk_s3::config::Region;
 use aws_sdk_s3::primitives::ByteStream;
 use aws_sdk_s3::presigning::PresigningConfig;
-use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::types::{
+    ChecksumAlgorithm, ChecksumMode, CompletedMultipartUpload, CompletedPart, GlacierJobParameters,
+    MetadataDirective, RestoreRequest, ServerSideEncryption, StorageClass, Tier,
+};
 use aws_config::meta::region::RegionProviderChain;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use anyhow::Result;
 use bytes::Bytes;
 use futures::stream::Stream;
 use std::pin::Pin;
 use futures::StreamExt;
 
+use super::cdn::Cdn;
+use super::encryption::{EncryptionPolicy, ObjectEncryption};
+pub use super::storage_trait::{ObjectInfo, PresignedUpload, StoredObject};
+use super::storage_trait::{ArchiveState, ObjectPage, Storage};
+use crate::worker::retry::{retry_with_backoff, RetryPolicy};
+use async_trait::async_trait;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+/// Agrega los encabezados de cifrado (`Option<ObjectEncryption>`) a un put,
+/// multipart o copia
+macro_rules! with_encryption {
+    ($request:expr, $encryption:expr) => {{
+        let encryption = $encryption;
+        $request
+            .set_server_side_encryption(encryption.as_ref().map(ObjectEncryption::algorithm))
+            .set_ssekms_key_id(encryption.as_ref().and_then(ObjectEncryption::kms_key_id))
+            .set_bucket_key_enabled(encryption.as_ref().and_then(ObjectEncryption::bucket_key_enabled))
+    }};
+}
+
+/// Vigencia por defecto de las URLs firmadas devueltas tras un upload
+const DEFAULT_URL_EXPIRATION_SECS: u64 = 3600;
+
 pub struct S3Client {
     client: Client,
-    cdn_url: Option<String>,
+    provider: S3Provider,
+    cdn: Option<Cdn>,
+    replica: Option<S3Replica>,
+    uploads: Arc<MultipartTracker>,
+    /// Cifrado en reposo (solo AWS; R2 y GCS cifran siempre con sus propias llaves)
+    encryption: EncryptionPolicy,
+}
+
+/// Proveedor detrás del API compatible con S3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum S3Provider {
+    Aws,
+    R2,
+    /// Google Cloud Storage vía la API XML interoperable (llaves HMAC)
+    Gcs,
+}
+
+impl S3Provider {
+    pub fn name(&self) -> &'static str {
+        match self {
+            S3Provider::Aws => "s3",
+            S3Provider::R2 => "r2",
+            S3Provider::Gcs => "gcs",
+        }
+    }
+
+    /// GCS no soporta los checksums flexibles de S3 (`x-amz-checksum-*`);
+    /// el SHA-256 se conserva igual en la metadata del objeto
+    fn supports_flexible_checksums(&self) -> bool {
+        !matches!(self, S3Provider::Gcs)
+    }
+}
+
+/// Multipart uploads iniciados por este proceso y aún sin completar; el
+/// janitor no aborta los que siguen en curso aunque superen la antigüedad
+#[derive(Default)]
+struct MultipartTracker {
+    in_flight: Mutex<HashMap<String, chrono::DateTime<chrono::Utc>>>,
+}
+
+impl MultipartTracker {
+    fn start(&self, upload_id: &str) {
+        self.in_flight.lock().unwrap().insert(upload_id.to_string(), chrono::Utc::now());
+    }
+
+    fn finish(&self, upload_id: &str) {
+        self.in_flight.lock().unwrap().remove(upload_id);
+    }
+
+    fn is_in_flight(&self, upload_id: &str) -> bool {
+        self.in_flight.lock().unwrap().contains_key(upload_id)
+    }
+}
+
+/// Aborta un multipart upload con reintentos ante fallas transitorias;
+/// un upload que ya no existe (NoSuchUpload) se da por abortado
+async fn abort_multipart(client: &Client, bucket: &str, key: &str, upload_id: &str) -> Result<()> {
+    let label = format!("Abort multipart upload {}", upload_id);
+
+    retry_with_backoff(&RetryPolicy::default(), &label, |_| async {
+        match client
+            .abort_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_upload()) => Ok(()),
+            Err(e) => Err(anyhow::Error::new(e)),
+        }
+    })
+    .await
+}
+
+/// Aborta un multipart upload incompleto cuando se descarta sin completarse
+/// (error o cancelación de la tarea), para no dejar partes huérfanas
+struct MultipartAbortGuard {
+    client: Client,
+    tracker: Arc<MultipartTracker>,
+    bucket: String,
+    key: String,
+    upload_id: String,
+    armed: bool,
+}
+
+impl MultipartAbortGuard {
+    fn new(client: &Client, tracker: &Arc<MultipartTracker>, bucket: &str, key: &str, upload_id: &str) -> Self {
+        tracker.start(upload_id);
+
+        MultipartAbortGuard {
+            client: client.clone(),
+            tracker: tracker.clone(),
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            upload_id: upload_id.to_string(),
+            armed: true,
+        }
+    }
+
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for MultipartAbortGuard {
+    fn drop(&mut self) {
+        self.tracker.finish(&self.upload_id);
+
+        if !self.armed {
+            return;
+        }
+
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("Cannot abort multipart upload {} outside a runtime", self.upload_id);
+            return;
+        };
+
+        let client = self.client.clone();
+        let bucket = std::mem::take(&mut self.bucket);
+        let key = std::mem::take(&mut self.key);
+        let upload_id = std::mem::take(&mut self.upload_id);
+
+        runtime.spawn(async move {
+            match abort_multipart(&client, &bucket, &key, &upload_id).await {
+                Ok(_) => tracing::info!("Aborted incomplete multipart upload {} for {}/{}", upload_id, bucket, key),
+                // Si no se pudo abortar, el janitor lo recoge cuando supere la antigüedad
+                Err(e) => tracing::warn!("Failed to abort multipart upload {}: {}", upload_id, e),
+            }
+        });
+    }
+}
+
+/// Clave de metadata donde se guarda el SHA-256 en hex junto al objeto
+const CHECKSUM_METADATA_KEY: &str = "sha256";
+
+/// Calcula el SHA-256 de un artefacto en hex
+pub fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Cifrado de la copia en la réplica: las llaves KMS son regionales, así que
+/// la réplica usa SSE-S3 siempre que el primario vaya cifrado
+fn replica_encryption(encryption: &Option<ObjectEncryption>) -> Option<ServerSideEncryption> {
+    encryption.as_ref().map(|_| ServerSideEncryption::Aes256)
+}
+
+/// Región/bucket secundario: recibe una copia de cada upload y se usa
+/// para las URLs firmadas cuando el primario no responde
+struct S3Replica {
+    client: Client,
+    bucket_suffix: String,
+    /// Último sondeo del primario, para no hacer un HEAD por cada URL firmada
+    primary_health: PrimaryHealth,
+}
+
+/// Resultado del último sondeo del primario, válido durante `ttl`
+/// (`S3_PRIMARY_HEALTH_TTL_SECS`, 30)
+struct PrimaryHealth {
+    ttl: Duration,
+    last: Mutex<Option<(Instant, bool)>>,
+}
+
+impl PrimaryHealth {
+    fn new(ttl: Duration) -> Self {
+        PrimaryHealth { ttl, last: Mutex::new(None) }
+    }
+
+    /// `Some(disponible)` si el último sondeo sigue vigente
+    fn cached(&self) -> Option<bool> {
+        self.last
+            .lock()
+            .unwrap()
+            .filter(|(at, _)| at.elapsed() < self.ttl)
+            .map(|(_, healthy)| healthy)
+    }
+
+    fn record(&self, healthy: bool) {
+        *self.last.lock().unwrap() = Some((Instant::now(), healthy));
+    }
+}
+
+/// Si el error indica que el primario no está disponible (sin conexión,
+/// timeout, respuesta cortada o 5xx). Un 404 o 403 es una respuesta válida
+/// del primario: no justifica irse a la réplica
+fn is_primary_outage<E>(error: &SdkError<E, HttpResponse>) -> bool {
+    match error {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => true,
+        SdkError::ServiceError(e) => e.raw().status().is_server_error(),
+        _ => false,
+    }
+}
+
+impl S3Replica {
+    async fn from_env() -> Option<Self> {
+        let region = std::env::var("S3_REPLICA_REGION").ok()?;
+
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .region(Region::new(region.clone()));
+        if let Ok(endpoint) = std::env::var("S3_REPLICA_ENDPOINT") {
+            loader = loader.endpoint_url(endpoint);
+        }
+        let config = loader.load().await;
+
+        let bucket_suffix = std::env::var("S3_REPLICA_BUCKET_SUFFIX")
+            .unwrap_or_else(|_| "-replica".to_string());
+
+        tracing::info!("S3 replica enabled in region {} (bucket suffix '{}')", region, bucket_suffix);
+
+        let health_ttl = std::env::var("S3_PRIMARY_HEALTH_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+
+        Some(S3Replica {
+            client: Client::new(&config),
+            bucket_suffix,
+            primary_health: PrimaryHealth::new(Duration::from_secs(health_ttl)),
+        })
+    }
+
+    fn bucket(&self, primary_bucket: &str) -> String {
+        format!("{}{}", primary_bucket, self.bucket_suffix)
+    }
 }
 
 impl S3Client {
@@ -21,18 +280,33 @@ impl S3Client {
         let region_provider = RegionProviderChain::default_provider()
             .or_else("us-east-1");
 
-        let config = aws_config::from_env()
-            .region(region_provider)
-            .load()
-            .await;
+        let mut loader = aws_config::from_env().region(region_provider);
+        if let Ok(endpoint) = std::env::var("S3_ENDPOINT") {
+            loader = loader.endpoint_url(endpoint);
+        }
+        let config = loader.load().await;
 
-        let client = Client::new(&config);
+        // MinIO y otros compatibles sin subdominio por bucket
+        let force_path_style = std::env::var("S3_FORCE_PATH_STYLE")
+            .map(|v| v == "true")
+            .unwrap_or(false);
+        let client = Client::from_conf(
+            aws_sdk_s3::config::Builder::from(&config)
+                .force_path_style(force_path_style)
+                .build(),
+        );
 
-        let cdn_url = std::env::var("CDN_URL").ok();
+        let cdn = Cdn::from_env();
+        let replica = S3Replica::from_env().await;
+        let encryption = EncryptionPolicy::from_env()?;
 
         Ok(S3Client {
             client,
-            cdn_url,
+            provider: S3Provider::Aws,
+            cdn,
+            replica,
+            uploads: Arc::default(),
+            encryption,
         })
     }
 
@@ -59,36 +333,118 @@ impl S3Client {
 
         Ok(S3Client {
             client,
-            cdn_url: None,
+            provider: S3Provider::R2,
+            cdn: None,
+            replica: None,
+            uploads: Arc::default(),
+            encryption: EncryptionPolicy::default(),
+        })
+    }
+
+    /// Cliente para Google Cloud Storage usando la API XML compatible con S3
+    /// y llaves HMAC de una cuenta de servicio (las URLs presignadas son SigV4)
+    pub async fn new_for_gcs(access_key_id: String, secret_access_key: String) -> Result<Self> {
+        let credentials = aws_sdk_s3::config::Credentials::new(
+            access_key_id,
+            secret_access_key,
+            None,
+            None,
+            "gcs",
+        );
+
+        let endpoint = std::env::var("GCS_ENDPOINT")
+            .unwrap_or_else(|_| "https://storage.googleapis.com".to_string());
+
+        let config = Config::builder()
+            .behavior_version(aws_sdk_s3::config::BehaviorVersion::latest())
+            .region(Region::new("auto"))
+            .endpoint_url(endpoint)
+            .force_path_style(true)
+            .credentials_provider(credentials)
+            .build();
+
+        let client = Client::from_conf(config);
+
+        let cdn = Cdn::from_env();
+
+        Ok(S3Client {
+            client,
+            provider: S3Provider::Gcs,
+            cdn,
+            replica: None,
+            uploads: Arc::default(),
+            encryption: EncryptionPolicy::default(),
         })
     }
 
+    /// Construye el cliente GCS desde `GCS_HMAC_ACCESS_ID` y `GCS_HMAC_SECRET`
+    pub async fn gcs_from_env() -> Result<Self> {
+        let access_id = std::env::var("GCS_HMAC_ACCESS_ID")
+            .map_err(|_| anyhow::anyhow!("GCS_HMAC_ACCESS_ID is required for STORAGE_BACKEND=gcs"))?;
+        let secret = std::env::var("GCS_HMAC_SECRET")
+            .map_err(|_| anyhow::anyhow!("GCS_HMAC_SECRET is required for STORAGE_BACKEND=gcs"))?;
+
+        Self::new_for_gcs(access_id, secret).await
+    }
+
+    pub fn provider(&self) -> S3Provider {
+        self.provider
+    }
+
     pub async fn put_object(
         &self,
         bucket: &str,
         key: &str,
         data: Vec<u8>,
         content_type: &str,
-    ) -> Result<String> {
-        let body = ByteStream::from(data);
+    ) -> Result<StoredObject> {
+        let digest = Sha256::digest(&data);
+        let checksum_hex = to_hex(&digest);
+        let checksum_b64 = base64::engine::general_purpose::STANDARD.encode(digest);
+        let body = ByteStream::from(data.clone());
 
-        self.client
+        // S3 valida el checksum al recibir el objeto; el hex queda en metadata
+        let mut request = self.client
             .put_object()
             .bucket(bucket)
             .key(key)
             .body(body)
             .content_type(content_type)
-            .send()
-            .await?;
+            .metadata(CHECKSUM_METADATA_KEY, &checksum_hex);
 
-        // Return CDN URL if configured, otherwise S3 URL
-        let url = if let Some(cdn) = &self.cdn_url {
-            format!("{}/{}", cdn, key)
-        } else {
-            format!("https://{}.s3.amazonaws.com/{}", bucket, key)
-        };
+        if self.provider.supports_flexible_checksums() {
+            request = request
+                .checksum_algorithm(ChecksumAlgorithm::Sha256)
+                .checksum_sha256(&checksum_b64);
+        }
+
+        let encryption = self.encryption.for_key(key);
+        with_encryption!(request, &encryption).send().await?;
 
-        Ok(url)
+        // Escritura dual en la réplica; un fallo aquí no invalida el upload
+        if let Some(replica) = &self.replica {
+            let result = replica.client
+                .put_object()
+                .bucket(replica.bucket(bucket))
+                .key(key)
+                .body(ByteStream::from(data))
+                .content_type(content_type)
+                .checksum_algorithm(ChecksumAlgorithm::Sha256)
+                .checksum_sha256(&checksum_b64)
+                .metadata(CHECKSUM_METADATA_KEY, &checksum_hex)
+                .set_server_side_encryption(replica_encryption(&encryption))
+                .send()
+                .await;
+
+            if let Err(e) = result {
+                tracing::warn!("Failed to replicate {}/{} to replica: {}", bucket, key, e);
+            }
+        }
+
+        Ok(StoredObject {
+            url: self.object_url(bucket, key),
+            checksum_sha256: checksum_hex,
+        })
     }
 
     pub async fn get_object(&self, bucket: &str, key: &str) -> Result<String> {
@@ -106,15 +462,75 @@ impl S3Client {
     }
 
     pub async fn get_object_bytes(&self, bucket: &str, key: &str) -> Result<Vec<u8>> {
-        let response = self.client
+        let mut request = self.client
             .get_object()
             .bucket(bucket)
+            .key(key);
+
+        if self.provider.supports_flexible_checksums() {
+            request = request.checksum_mode(ChecksumMode::Enabled);
+        }
+
+        let response = request.send().await?;
+
+        let expected = response.metadata()
+            .and_then(|m| m.get(CHECKSUM_METADATA_KEY))
+            .cloned();
+
+        let data = response.body.collect().await?.to_vec();
+
+        // Detecta corrupción en almacenamiento comparando con el hash guardado
+        if let Some(expected) = expected {
+            let actual = sha256_hex(&data);
+            if actual != expected {
+                anyhow::bail!(
+                    "Checksum mismatch for {}/{}: expected {}, got {}",
+                    bucket, key, expected, actual
+                );
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Obtiene el SHA-256 (hex) almacenado en la metadata del objeto
+    pub async fn get_object_checksum(&self, bucket: &str, key: &str) -> Result<Option<String>> {
+        let response = self.client
+            .head_object()
+            .bucket(bucket)
             .key(key)
             .send()
             .await?;
 
-        let data = response.body.collect().await?;
-        Ok(data.to_vec())
+        Ok(response.metadata()
+            .and_then(|m| m.get(CHECKSUM_METADATA_KEY))
+            .cloned())
+    }
+
+    /// URL devuelta tras un upload: CDN firmado si está configurado, si no la URL S3
+    fn object_url(&self, bucket: &str, key: &str) -> String {
+        match &self.cdn {
+            Some(cdn) => cdn.signed_url(key, DEFAULT_URL_EXPIRATION_SECS),
+            None if self.provider == S3Provider::Gcs => {
+                format!("https://storage.googleapis.com/{}/{}", bucket, key)
+            },
+            None => format!("https://{}.s3.amazonaws.com/{}", bucket, key),
+        }
+    }
+
+    /// URL de descarga temporal: CDN firmado cuando hay llave de firma,
+    /// en otro caso URL presignada de S3 con la misma expiración
+    pub async fn create_download_url(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires_in_seconds: u64,
+    ) -> Result<String> {
+        if let Some(cdn) = &self.cdn {
+            return Ok(cdn.signed_url(key, expires_in_seconds));
+        }
+
+        self.create_presigned_url(bucket, key, expires_in_seconds).await
     }
 
     pub async fn create_presigned_url(
@@ -122,12 +538,36 @@ impl S3Client {
         bucket: &str,
         key: &str,
         expires_in_seconds: u64,
+    ) -> Result<String> {
+        // Si el primario no está disponible, firmar contra la réplica
+        if let Some(replica) = &self.replica {
+            let available = match replica.primary_health.cached() {
+                Some(available) => available,
+                None => {
+                    let available = self.primary_reachable(bucket, key).await;
+                    replica.primary_health.record(available);
+                    available
+                },
+            };
+            if !available {
+                return Self::presign_get(&replica.client, &replica.bucket(bucket), key, expires_in_seconds).await;
+            }
+        }
+
+        Self::presign_get(&self.client, bucket, key, expires_in_seconds).await
+    }
+
+    async fn presign_get(
+        client: &Client,
+        bucket: &str,
+        key: &str,
+        expires_in_seconds: u64,
     ) -> Result<String> {
         let presigning_config = PresigningConfig::builder()
             .expires_in(Duration::from_secs(expires_in_seconds))
             .build()?;
 
-        let presigned = self.client
+        let presigned = client
             .get_object()
             .bucket(bucket)
             .key(key)
@@ -137,13 +577,35 @@ impl S3Client {
         Ok(presigned.uri().to_string())
     }
 
+    /// Sondea el primario con un HEAD del objeto (timeout corto); solo las
+    /// fallas de conectividad y los 5xx lo dan por caído
+    async fn primary_reachable(&self, bucket: &str, key: &str) -> bool {
+        let head = self.client
+            .head_object()
+            .bucket(bucket)
+            .key(key)
+            .send();
+
+        match tokio::time::timeout(Duration::from_secs(2), head).await {
+            Err(_) => {
+                tracing::warn!("Primary storage timed out for {}/{}, using replica", bucket, key);
+                false
+            },
+            Ok(Err(e)) if is_primary_outage(&e) => {
+                tracing::warn!("Primary storage unavailable for {}/{}, using replica: {}", bucket, key, e);
+                false
+            },
+            Ok(_) => true,
+        }
+    }
+
     pub async fn create_presigned_upload_url(
         &self,
         bucket: &str,
         key: &str,
         expires_in_seconds: u64,
         content_type: Option<&str>,
-    ) -> Result<String> {
+    ) -> Result<PresignedUpload> {
         let presigning_config = PresigningConfig::builder()
             .expires_in(Duration::from_secs(expires_in_seconds))
             .build()?;
@@ -157,9 +619,15 @@ impl S3Client {
             request = request.content_type(ct);
         }
 
-        let presigned = request.presigned(presigning_config).await?;
+        // Los encabezados SSE quedan firmados: el cliente los debe enviar en el PUT
+        let presigned = with_encryption!(request, self.encryption.for_key(key))
+            .presigned(presigning_config)
+            .await?;
 
-        Ok(presigned.uri().to_string())
+        Ok(PresignedUpload {
+            url: presigned.uri().to_string(),
+            headers: presigned.headers().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+        })
     }
 
     pub async fn delete_object(&self, bucket: &str, key: &str) -> Result<()> {
@@ -170,19 +638,37 @@ impl S3Client {
             .send()
             .await?;
 
+        if let Some(replica) = &self.replica {
+            if let Err(e) = replica.client
+                .delete_object()
+                .bucket(replica.bucket(bucket))
+                .key(key)
+                .send()
+                .await
+            {
+                tracing::warn!("Failed to delete {}/{} from replica: {}", bucket, key, e);
+            }
+        }
+
         Ok(())
     }
 
+    /// Sube un objeto por partes. Cada parte lleva su SHA-256 (S3 la valida
+    /// al recibirla) y el checksum compuesto que devuelve S3 al completar se
+    /// compara con el calculado aquí. El SHA-256 del objeto completo se conoce
+    /// solo al final: se agrega a la metadata con una copia sobre sí mismo
     pub async fn multipart_upload<S>(
         &self,
         bucket: &str,
         key: &str,
         mut data_stream: Pin<Box<S>>,
         content_type: Option<&str>,
-    ) -> Result<String>
+    ) -> Result<StoredObject>
     where
         S: Stream<Item = Result<Bytes>> + Send,
     {
+        let flexible_checksums = self.provider.supports_flexible_checksums();
+
         // Initiate multipart upload
         let mut multipart = self.client
             .create_multipart_upload()
@@ -192,35 +678,53 @@ impl S3Client {
         if let Some(ct) = content_type {
             multipart = multipart.content_type(ct);
         }
+        if flexible_checksums {
+            multipart = multipart.checksum_algorithm(ChecksumAlgorithm::Sha256);
+        }
 
-        let multipart = multipart.send().await?;
+        let encryption = self.encryption.for_key(key);
+        let multipart = with_encryption!(multipart, &encryption).send().await?;
         let upload_id = multipart.upload_id()
             .ok_or_else(|| anyhow::anyhow!("No upload ID returned"))?;
 
+        // Aborta el upload si falla o se cancela (timeout) antes de completarse
+        let mut abort_guard = MultipartAbortGuard::new(&self.client, &self.uploads, bucket, key, upload_id);
+
         let mut part_number = 1;
         let mut parts = Vec::new();
+        let mut object_digest = Sha256::new();
+        let mut part_digests = Vec::new();
 
         // Upload parts (minimum 5MB per part except last)
         while let Some(chunk_result) = data_stream.next().await {
             let chunk = chunk_result?;
+            object_digest.update(&chunk);
+            let part_digest = Sha256::digest(&chunk);
+            let part_checksum = base64::engine::general_purpose::STANDARD.encode(part_digest);
+            part_digests.extend_from_slice(&part_digest);
 
-            let part = self.client
+            let mut request = self.client
                 .upload_part()
                 .bucket(bucket)
                 .key(key)
                 .upload_id(upload_id)
                 .part_number(part_number)
-                .body(ByteStream::from(chunk))
-                .send()
-                .await?;
+                .body(ByteStream::from(chunk));
+            if flexible_checksums {
+                request = request
+                    .checksum_algorithm(ChecksumAlgorithm::Sha256)
+                    .checksum_sha256(&part_checksum);
+            }
+            let part = request.send().await?;
 
             if let Some(etag) = part.e_tag() {
-                parts.push(
-                    CompletedPart::builder()
-                        .part_number(part_number)
-                        .e_tag(etag)
-                        .build()
-                );
+                let mut completed = CompletedPart::builder()
+                    .part_number(part_number)
+                    .e_tag(etag);
+                if flexible_checksums {
+                    completed = completed.checksum_sha256(&part_checksum);
+                }
+                parts.push(completed.build());
             }
 
             part_number += 1;
@@ -231,7 +735,7 @@ impl S3Client {
             .set_parts(Some(parts))
             .build();
 
-        self.client
+        let response = self.client
             .complete_multipart_upload()
             .bucket(bucket)
             .key(key)
@@ -240,14 +744,55 @@ impl S3Client {
             .send()
             .await?;
 
-        // Return URL
-        let url = if let Some(cdn) = &self.cdn_url {
-            format!("{}/{}", cdn, key)
-        } else {
-            format!("https://{}.s3.amazonaws.com/{}", bucket, key)
-        };
+        abort_guard.disarm();
 
-        Ok(url)
+        // Checksum compuesto: SHA-256 de los SHA-256 de las partes (`{base64}-{partes}`)
+        if let Some(actual) = response.checksum_sha256().filter(|_| flexible_checksums) {
+            let expected = base64::engine::general_purpose::STANDARD.encode(Sha256::digest(&part_digests));
+            if actual.split('-').next() != Some(expected.as_str()) {
+                anyhow::bail!(
+                    "Composite checksum mismatch for {}/{}: expected {}-{}, got {}",
+                    bucket, key, expected, part_number - 1, actual
+                );
+            }
+        }
+
+        let checksum_hex = to_hex(&object_digest.finalize());
+        let mut copy = self.client
+            .copy_object()
+            .copy_source(format!("{}/{}", bucket, key))
+            .bucket(bucket)
+            .key(key)
+            .metadata_directive(MetadataDirective::Replace)
+            .metadata(CHECKSUM_METADATA_KEY, &checksum_hex);
+        if let Some(ct) = content_type {
+            copy = copy.content_type(ct);
+        }
+        if flexible_checksums {
+            copy = copy.checksum_algorithm(ChecksumAlgorithm::Sha256);
+        }
+        with_encryption!(copy, &encryption).send().await?;
+
+        // Los uploads multipart se replican con copia servidor a servidor
+        if let Some(replica) = &self.replica {
+            let result = replica.client
+                .copy_object()
+                .copy_source(format!("{}/{}", bucket, key))
+                .bucket(replica.bucket(bucket))
+                .key(key)
+                .set_server_side_encryption(replica_encryption(&encryption))
+                .send()
+                .await;
+
+            if let Err(e) = result {
+                tracing::warn!("Failed to replicate {}/{} to replica: {}", bucket, key, e);
+            }
+        }
+
+        Ok(StoredObject {
+            url: self.object_url(bucket, key),
+            checksum_sha256: checksum_hex,
+        })
     }
 
     pub async fn list_objects(&self, bucket: &str, prefix: Option<&str>) -> Result<Vec<String>> {
@@ -270,4 +815,292 @@ impl S3Client {
 
         Ok(keys)
     }
-}
\ No newline at end of file
+
+    /// Lista todos los objetos bajo un prefijo (paginado) con fecha y clase de almacenamiento
+    pub async fn list_objects_detailed(&self, bucket: &str, prefix: Option<&str>) -> Result<Vec<ObjectInfo>> {
+        let mut objects = Vec::new();
+        let mut continuation: Option<String> = None;
+
+        loop {
+            let page = self.list_objects_page(bucket, prefix, continuation).await?;
+            objects.extend(page.objects);
+            match page.next {
+                Some(token) => continuation = Some(token),
+                None => break,
+            }
+        }
+
+        Ok(objects)
+    }
+
+    /// Una página (hasta 1000 objetos) del listado, desde `continuation`
+    pub async fn list_objects_page(&self, bucket: &str, prefix: Option<&str>, continuation: Option<String>) -> Result<ObjectPage> {
+        let mut request = self.client
+            .list_objects_v2()
+            .bucket(bucket)
+            .max_keys(1000)
+            .set_continuation_token(continuation);
+
+        if let Some(p) = prefix {
+            request = request.prefix(p);
+        }
+
+        let response = request.send().await?;
+
+        let objects = response.contents()
+            .iter()
+            .filter_map(|obj| {
+                Some(ObjectInfo {
+                    key: obj.key()?.to_string(),
+                    size: obj.size().unwrap_or(0),
+                    last_modified: obj.last_modified()
+                        .and_then(|t| chrono::DateTime::from_timestamp(t.secs(), t.subsec_nanos())),
+                    storage_class: obj.storage_class().map(|c| c.as_str().to_string()),
+                })
+            })
+            .collect();
+
+        let next = response.next_continuation_token()
+            .filter(|_| response.is_truncated().unwrap_or(false))
+            .map(str::to_string);
+
+        Ok(ObjectPage { objects, next })
+    }
+
+    /// Clase y restauración del objeto (cabecera `x-amz-restore` del HEAD)
+    pub async fn object_archive_state(&self, bucket: &str, key: &str) -> Result<ArchiveState> {
+        let response = self.client
+            .head_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await?;
+
+        let archived = matches!(
+            response.storage_class(),
+            Some(StorageClass::Glacier) | Some(StorageClass::DeepArchive)
+        );
+        Ok(match response.restore() {
+            _ if !archived => ArchiveState::Available,
+            Some(restore) if restore.contains("ongoing-request=\"true\"") => ArchiveState::Restoring,
+            // Restauración terminada: la copia temporal se puede leer
+            Some(restore) if restore.contains("ongoing-request=\"false\"") => ArchiveState::Available,
+            _ => ArchiveState::Archived,
+        })
+    }
+
+    /// Pide a S3 la restauración (tier Standard, de 3 a 5 horas en Glacier)
+    /// de un objeto archivado; una restauración ya en curso no es error
+    pub async fn restore_archived_object(&self, bucket: &str, key: &str, days: i32) -> Result<()> {
+        let request = RestoreRequest::builder()
+            .days(days)
+            .glacier_job_parameters(GlacierJobParameters::builder().tier(Tier::Standard).build()?)
+            .build();
+
+        let result = self.client
+            .restore_object()
+            .bucket(bucket)
+            .key(key)
+            .restore_request(request)
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) if e.as_service_error().and_then(|e| e.meta().code()) == Some("RestoreAlreadyInProgress") => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Aborta los multipart uploads del bucket iniciados antes de `older_than`,
+    /// salvo los que este proceso sigue subiendo. Retorna cuántos abortó
+    pub async fn abort_stale_multipart_uploads(&self, bucket: &str, older_than: chrono::DateTime<chrono::Utc>) -> Result<usize> {
+        let mut stale = Vec::new();
+        let mut key_marker: Option<String> = None;
+        let mut upload_id_marker: Option<String> = None;
+
+        loop {
+            let response = self.client
+                .list_multipart_uploads()
+                .bucket(bucket)
+                .set_key_marker(key_marker.clone())
+                .set_upload_id_marker(upload_id_marker.clone())
+                .send()
+                .await?;
+
+            for upload in response.uploads() {
+                let (Some(key), Some(upload_id)) = (upload.key(), upload.upload_id()) else { continue };
+                let initiated = upload.initiated()
+                    .and_then(|t| chrono::DateTime::from_timestamp(t.secs(), t.subsec_nanos()));
+
+                if initiated.is_some_and(|t| t < older_than) && !self.uploads.is_in_flight(upload_id) {
+                    stale.push((key.to_string(), upload_id.to_string()));
+                }
+            }
+
+            if !response.is_truncated().unwrap_or(false) {
+                break;
+            }
+            key_marker = response.next_key_marker().map(str::to_string);
+            upload_id_marker = response.next_upload_id_marker().map(str::to_string);
+            if key_marker.is_none() && upload_id_marker.is_none() {
+                break;
+            }
+        }
+
+        let mut aborted = 0;
+        for (key, upload_id) in stale {
+            match abort_multipart(&self.client, bucket, &key, &upload_id).await {
+                Ok(_) => {
+                    tracing::info!("Aborted stale multipart upload {} for {}/{}", upload_id, bucket, key);
+                    aborted += 1;
+                },
+                Err(e) => tracing::warn!("Failed to abort stale multipart upload {}: {}", upload_id, e),
+            }
+        }
+
+        Ok(aborted)
+    }
+
+    /// Cambia la clase de almacenamiento de un objeto (copia sobre sí mismo)
+    pub async fn transition_storage_class(&self, bucket: &str, key: &str, storage_class: &str) -> Result<()> {
+        let request = self.client
+            .copy_object()
+            .copy_source(format!("{}/{}", bucket, key))
+            .bucket(bucket)
+            .key(key)
+            .storage_class(StorageClass::from(storage_class))
+            .metadata_directive(MetadataDirective::Copy);
+        with_encryption!(request, self.encryption.for_key(key)).send().await?;
+
+        Ok(())
+    }
+
+    /// Mueve un objeto dentro del bucket (copia + borrado del original)
+    pub async fn move_object(&self, bucket: &str, from_key: &str, to_key: &str) -> Result<()> {
+        let request = self.client
+            .copy_object()
+            .copy_source(format!("{}/{}", bucket, from_key))
+            .bucket(bucket)
+            .key(to_key)
+            .metadata_directive(MetadataDirective::Copy);
+        with_encryption!(request, self.encryption.for_key(to_key)).send().await?;
+
+        self.delete_object(bucket, from_key).await
+    }
+}
+
+#[async_trait]
+impl Storage for S3Client {
+    fn backend_name(&self) -> &str {
+        self.provider.name()
+    }
+
+    async fn put(&self, bucket: &str, key: &str, data: Vec<u8>, content_type: &str) -> Result<StoredObject> {
+        self.put_object(bucket, key, data, content_type).await
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Vec<u8>> {
+        self.get_object_bytes(bucket, key).await
+    }
+
+    /// Sube cada parte como una parte del multipart upload
+    async fn put_stream(
+        &self,
+        bucket: &str,
+        key: &str,
+        parts: futures::stream::BoxStream<'static, Result<Bytes>>,
+        content_type: &str,
+    ) -> Result<()> {
+        self.multipart_upload(bucket, key, Box::pin(parts), Some(content_type)).await.map(|_| ())
+    }
+
+    async fn presign(&self, bucket: &str, key: &str, expires_in_seconds: u64) -> Result<String> {
+        self.create_download_url(bucket, key, expires_in_seconds).await
+    }
+
+    async fn presign_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires_in_seconds: u64,
+        content_type: Option<&str>,
+    ) -> Result<PresignedUpload> {
+        self.create_presigned_upload_url(bucket, key, expires_in_seconds, content_type).await
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        self.delete_object(bucket, key).await
+    }
+
+    async fn list(&self, bucket: &str, prefix: Option<&str>) -> Result<Vec<ObjectInfo>> {
+        self.list_objects_detailed(bucket, prefix).await
+    }
+
+    async fn list_page(&self, bucket: &str, prefix: Option<&str>, continuation: Option<String>) -> Result<ObjectPage> {
+        self.list_objects_page(bucket, prefix, continuation).await
+    }
+
+    async fn checksum(&self, bucket: &str, key: &str) -> Result<Option<String>> {
+        self.get_object_checksum(bucket, key).await
+    }
+
+    async fn move_object(&self, bucket: &str, from_key: &str, to_key: &str) -> Result<()> {
+        S3Client::move_object(self, bucket, from_key, to_key).await
+    }
+
+    async fn transition_storage_class(&self, bucket: &str, key: &str, storage_class: &str) -> Result<()> {
+        S3Client::transition_storage_class(self, bucket, key, storage_class).await
+    }
+
+    async fn archive_state(&self, bucket: &str, key: &str) -> Result<ArchiveState> {
+        self.object_archive_state(bucket, key).await
+    }
+
+    async fn restore_archived(&self, bucket: &str, key: &str, days: i32) -> Result<()> {
+        self.restore_archived_object(bucket, key, days).await
+    }
+
+    async fn abort_stale_uploads(&self, bucket: &str, older_than: chrono::DateTime<chrono::Utc>) -> Result<usize> {
+        self.abort_stale_multipart_uploads(bucket, older_than).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_s3::error::ConnectorError;
+    use aws_sdk_s3::operation::head_object::HeadObjectError;
+    use aws_sdk_s3::primitives::SdkBody;
+
+    fn service_error(status: u16) -> SdkError<HeadObjectError, HttpResponse> {
+        let not_found = HeadObjectError::NotFound(aws_sdk_s3::types::error::NotFound::builder().build());
+        SdkError::service_error(not_found, HttpResponse::new(status.try_into().unwrap(), SdkBody::empty()))
+    }
+
+    #[test]
+    fn fails_over_only_on_connectivity_and_server_errors() {
+        let refused = ConnectorError::io("connection refused".into());
+
+        assert!(is_primary_outage(&SdkError::<HeadObjectError, HttpResponse>::dispatch_failure(refused)));
+        assert!(is_primary_outage(&SdkError::<HeadObjectError, HttpResponse>::timeout_error("timed out")));
+        assert!(is_primary_outage(&service_error(503)));
+        assert!(!is_primary_outage(&service_error(404)));
+        assert!(!is_primary_outage(&service_error(403)));
+    }
+
+    #[test]
+    fn caches_the_primary_health_until_the_ttl() {
+        let health = PrimaryHealth::new(Duration::from_secs(30));
+        assert_eq!(health.cached(), None);
+
+        health.record(false);
+        assert_eq!(health.cached(), Some(false));
+        health.record(true);
+        assert_eq!(health.cached(), Some(true));
+
+        let expired = PrimaryHealth::new(Duration::ZERO);
+        expired.record(false);
+        assert_eq!(expired.cached(), None);
+    }
+}
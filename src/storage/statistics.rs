@@ -0,0 +1,94 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::models::DocumentStatus;
+use super::document_store::DocumentRecord;
+
+/// Latencias de generación de una prioridad
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencyStats {
+    pub count: u64,
+    pub total_ms: u64,
+    pub max_ms: u64,
+    pub avg_ms: f64,
+}
+
+/// Estadísticas agregadas de un tenant. Se calculan al consultar desde el
+/// registro de documentos: cada documento cuenta una vez, así los reintentos
+/// o callbacks repetidos no inflan los totales
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TenantStatistics {
+    pub tenant_id: i64,
+    pub documents_completed: u64,
+    pub documents_failed: u64,
+    pub bytes_generated: u64,
+    pub pages_generated: u64,
+    pub latency_by_priority: BTreeMap<String, LatencyStats>,
+}
+
+/// Totales de los documentos completados de un tenant con una misma prioridad
+#[derive(Debug, Clone, Default)]
+pub struct CompletedGroup {
+    pub priority: String,
+    pub count: u64,
+    pub size_bytes: u64,
+    pub page_count: u64,
+    pub total_ms: u64,
+    pub max_ms: u64,
+}
+
+impl TenantStatistics {
+    /// Agrupa los totales ya sumados (p. ej. por un `GROUP BY` en Postgres)
+    pub fn from_groups(tenant_id: i64, failed: u64, completed: impl IntoIterator<Item = CompletedGroup>) -> Self {
+        let mut stats = TenantStatistics { tenant_id, documents_failed: failed, ..TenantStatistics::default() };
+
+        for group in completed {
+            stats.documents_completed += group.count;
+            stats.bytes_generated += group.size_bytes;
+            stats.pages_generated += group.page_count;
+
+            let latency = stats.latency_by_priority.entry(group.priority).or_default();
+            latency.count += group.count;
+            latency.total_ms += group.total_ms;
+            latency.max_ms = latency.max_ms.max(group.max_ms);
+        }
+
+        for latency in stats.latency_by_priority.values_mut() {
+            latency.avg_ms = latency.total_ms as f64 / latency.count as f64;
+        }
+
+        stats
+    }
+
+    /// Agrega los documentos terminados del tenant; los que siguen en cola o
+    /// en proceso se ignoran
+    pub fn from_records<'a>(tenant_id: i64, records: impl IntoIterator<Item = &'a DocumentRecord>) -> Self {
+        let mut failed = 0;
+        let mut completed = Vec::new();
+
+        for record in records.into_iter().filter(|r| r.tenant_id == tenant_id) {
+            match record.status {
+                DocumentStatus::Completed => completed.push(CompletedGroup {
+                    priority: priority_name(record),
+                    count: 1,
+                    size_bytes: record.size_bytes,
+                    page_count: record.page_count.unwrap_or(0) as u64,
+                    total_ms: record.processing_time_ms,
+                    max_ms: record.processing_time_ms,
+                }),
+                DocumentStatus::Failed => failed += 1,
+                _ => {},
+            }
+        }
+
+        Self::from_groups(tenant_id, failed, completed)
+    }
+}
+
+/// Prioridad tal como se serializa (`high`, `normal`, `low`)
+fn priority_name(record: &DocumentRecord) -> String {
+    serde_json::to_value(&record.priority)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{BoxStream, TryStreamExt};
+use std::sync::Arc;
+
+/// Resultado de un upload: URL y SHA-256 (hex) del contenido almacenado
+#[derive(Debug, Clone)]
+pub struct StoredObject {
+    pub url: String,
+    pub checksum_sha256: String,
+}
+
+/// URL firmada para que el cliente suba un objeto; `headers` (p. ej. los de
+/// SSE) van firmados y el PUT debe enviarlos tal cual
+#[derive(Debug, Clone)]
+pub struct PresignedUpload {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+}
+
+/// Información de un objeto listado (para jobs de mantenimiento)
+#[derive(Debug, Clone)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: i64,
+    pub last_modified: Option<chrono::DateTime<chrono::Utc>>,
+    pub storage_class: Option<String>,
+}
+
+/// Página de un listado; `next` es el continuation token de la siguiente
+#[derive(Debug, Clone, Default)]
+pub struct ObjectPage {
+    pub objects: Vec<ObjectInfo>,
+    pub next: Option<String>,
+}
+
+/// Si un objeto se puede leer o está en una clase de archivo que hay que
+/// restaurar antes (Glacier Flexible Retrieval, Deep Archive)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveState {
+    Available,
+    Archived,
+    /// Se pidió la restauración y S3 aún no la completa
+    Restoring,
+}
+
+/// Trait base para los backends de almacenamiento de documentos.
+/// Handlers y jobs programan contra este trait; el backend se elige con `STORAGE_BACKEND`.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Nombre del backend ("s3", "gcs", "local", ...)
+    fn backend_name(&self) -> &str;
+
+    /// Guarda un objeto y retorna su URL y checksum
+    async fn put(&self, bucket: &str, key: &str, data: Vec<u8>, content_type: &str) -> Result<StoredObject>;
+
+    /// Lee un objeto completo
+    async fn get(&self, bucket: &str, key: &str) -> Result<Vec<u8>>;
+
+    /// URL temporal de descarga
+    async fn presign(&self, bucket: &str, key: &str, expires_in_seconds: u64) -> Result<String>;
+
+    /// URL temporal para que el cliente suba un objeto
+    async fn presign_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires_in_seconds: u64,
+        content_type: Option<&str>,
+    ) -> Result<PresignedUpload>;
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()>;
+
+    /// Lista los objetos bajo un prefijo
+    async fn list(&self, bucket: &str, prefix: Option<&str>) -> Result<Vec<ObjectInfo>>;
+
+    /// Una página del listado bajo un prefijo, desde `continuation`. Por
+    /// defecto todo el listado en una sola página
+    async fn list_page(&self, bucket: &str, prefix: Option<&str>, _continuation: Option<String>) -> Result<ObjectPage> {
+        Ok(ObjectPage { objects: self.list(bucket, prefix).await?, next: None })
+    }
+
+    /// SHA-256 (hex) almacenado para el objeto, si existe
+    async fn checksum(&self, bucket: &str, key: &str) -> Result<Option<String>>;
+
+    /// Guarda un objeto que llega por partes (p. ej. un zip que se arma
+    /// mientras se sube). Por defecto junta las partes y usa `put`
+    async fn put_stream(
+        &self,
+        bucket: &str,
+        key: &str,
+        parts: BoxStream<'static, Result<Bytes>>,
+        content_type: &str,
+    ) -> Result<()> {
+        let data = parts
+            .try_fold(Vec::new(), |mut data, part| async move {
+                data.extend_from_slice(&part);
+                Ok(data)
+            })
+            .await?;
+        self.put(bucket, key, data, content_type).await.map(|_| ())
+    }
+
+    /// Mueve un objeto dentro del bucket
+    async fn move_object(&self, bucket: &str, from_key: &str, to_key: &str) -> Result<()> {
+        let data = self.get(bucket, from_key).await?;
+        self.put(bucket, to_key, data, "application/octet-stream").await?;
+        self.delete(bucket, from_key).await
+    }
+
+    /// Valida el token `verify` de una URL servida por la propia API (`/files`);
+    /// solo los backends sin endpoint propio (local) lo implementan
+    fn verify_download_token(&self, _bucket: &str, _key: &str, _token: &str) -> bool {
+        false
+    }
+
+    /// Cambia la clase de almacenamiento (archivado en frío); no todos los backends lo soportan
+    async fn transition_storage_class(&self, _bucket: &str, _key: &str, storage_class: &str) -> Result<()> {
+        anyhow::bail!(
+            "Storage class '{}' not supported by the {} backend",
+            storage_class,
+            self.backend_name()
+        )
+    }
+
+    /// Estado de archivo del objeto; los backends sin clases de archivo lo
+    /// tienen siempre disponible
+    async fn archive_state(&self, _bucket: &str, _key: &str) -> Result<ArchiveState> {
+        Ok(ArchiveState::Available)
+    }
+
+    /// Pide una copia legible de un objeto archivado durante `days` días
+    async fn restore_archived(&self, _bucket: &str, _key: &str, _days: i32) -> Result<()> {
+        anyhow::bail!("Archive restore not supported by the {} backend", self.backend_name())
+    }
+
+    /// Aborta los multipart uploads incompletos iniciados antes de `older_than`;
+    /// los backends sin multipart no tienen nada que limpiar
+    async fn abort_stale_uploads(&self, _bucket: &str, _older_than: chrono::DateTime<chrono::Utc>) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+/// Crea el backend configurado en `STORAGE_BACKEND` (por defecto "s3")
+pub async fn storage_from_env() -> Result<Arc<dyn Storage>> {
+    let backend = std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "s3".to_string());
+
+    let storage: Arc<dyn Storage> = match backend.as_str() {
+        "s3" => Arc::new(super::s3::S3Client::new().await?),
+        "gcs" => Arc::new(super::s3::S3Client::gcs_from_env().await?),
+        "local" => Arc::new(super::local::LocalFsStorage::from_env()?),
+        other => anyhow::bail!("Unknown STORAGE_BACKEND '{}' (expected s3, gcs or local)", other),
+    };
+
+    tracing::info!("Using {} storage backend", storage.backend_name());
+    Ok(storage)
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use chrono::Utc;
+use std::sync::Arc;
+
+use super::document_store::DocumentStore;
+use super::storage_trait::Storage;
+
+/// Job periódico que purga la papelera: los documentos cuya ventana de
+/// restauración venció se borran del storage (documento y miniatura)
+pub struct TrashPurgeJob {
+    storage: Arc<dyn Storage>,
+    documents: Arc<DocumentStore>,
+    retention: chrono::Duration,
+}
+
+impl TrashPurgeJob {
+    pub fn new(storage: Arc<dyn Storage>, documents: Arc<DocumentStore>, retention: chrono::Duration) -> Self {
+        TrashPurgeJob { storage, documents, retention }
+    }
+
+    pub fn spawn(self, interval_secs: u64) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+            loop {
+                interval.tick().await;
+                match self.run_once().await {
+                    Ok(0) => tracing::debug!("Trash purge: nothing expired"),
+                    Ok(purged) => tracing::info!("Trash purge removed {} documents", purged),
+                    Err(e) => tracing::error!("Trash purge run failed: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Una pasada; retorna cuántos documentos purgó. Si un borrado falla el
+    /// registro se conserva y se reintenta en la siguiente pasada
+    pub async fn run_once(&self) -> Result<usize> {
+        let cutoff = Utc::now() - self.retention;
+        let mut purged = 0;
+
+        for record in self.documents.trashed_before(cutoff).await? {
+            let keys = record.storage_key.iter()
+                .chain(record.preview_key.iter())
+                .chain(record.xml_key.iter())
+                .chain(record.companion_key.iter());
+            let mut failed = false;
+
+            for key in keys {
+                if let Err(e) = self.storage.delete(&record.bucket, key).await {
+                    tracing::warn!("Failed to purge {}: {}", key, e);
+                    failed = true;
+                }
+            }
+
+            if !failed {
+                self.documents.remove(&record.id).await?;
+                purged += 1;
+            }
+        }
+
+        Ok(purged)
+    }
+}
@@ -1,11 +1,25 @@
 pub mod template_engine;
 pub mod template_models;
 pub mod template_trait;
+pub mod template_stats;
+pub mod template_access;
+pub mod template_overrides;
+pub mod template_amounts;
+pub mod template_assets;
+pub mod template_charts;
+pub mod template_fields;
+pub mod template_sandbox;
+pub mod template_files;
+pub mod template_inline_images;
+pub mod template_labels;
+pub mod template_logos;
+pub mod template_pipeline;
 pub mod templates;
 
 pub use template_engine::*;
 pub use template_models::*;
 pub use template_trait::{TypstTemplate, TemplateRegistry};
+pub use template_pipeline::{GeneratedPdf, RenderPipeline, RenderRequest};
 
 // Re-export TemplateEngine as TemplateManager for backward compatibility
 pub type TemplateManager = template_engine::TemplateEngine;
\ No newline at end of file
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Quiénes pueden usar una plantilla global privada: los tenants listados y
+/// los tenants con alguno de los planes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TemplateRestriction {
+    #[serde(default)]
+    pub tenants: Vec<i64>,
+    #[serde(default)]
+    pub plans: Vec<String>,
+}
+
+/// Configuración inicial de `TEMPLATE_ACCESS`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TemplateAccessConfig {
+    /// Plantillas privadas por id
+    #[serde(default)]
+    pub templates: HashMap<String, TemplateRestriction>,
+    /// Plan de cada tenant
+    #[serde(default)]
+    pub tenant_plans: HashMap<i64, String>,
+}
+
+impl TemplateAccessConfig {
+    /// `TEMPLATE_ACCESS` (JSON `{"templates": {"premium_invoice": {"tenants": [12],
+    /// "plans": ["enterprise"]}}, "tenant_plans": {"7": "enterprise"}}`)
+    pub fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("TEMPLATE_ACCESS") {
+            Ok(json) if !json.trim().is_empty() => serde_json::from_str(&json)
+                .map_err(|e| anyhow::anyhow!("Invalid TEMPLATE_ACCESS: {}", e)),
+            _ => Ok(Self::default()),
+        }
+    }
+}
+
+/// Restricciones de acceso a plantillas globales. Una plantilla sin
+/// restricción es pública; las versiones propias de un tenant siempre son suyas
+#[derive(Default)]
+pub struct TemplateAccess {
+    templates: RwLock<HashMap<String, TemplateRestriction>>,
+    tenant_plans: RwLock<HashMap<i64, String>>,
+}
+
+impl TemplateAccess {
+    /// Reemplaza restricciones y planes con los de la configuración
+    pub fn configure(&self, config: TemplateAccessConfig) {
+        *self.templates.write().unwrap() = config.templates;
+        *self.tenant_plans.write().unwrap() = config.tenant_plans;
+    }
+
+    /// Si el tenant puede usar la plantilla global; sin tenant (llamadas
+    /// internas como el warm-up) no hay restricción
+    pub fn allows(&self, tenant_id: Option<i64>, template_id: &str) -> bool {
+        let templates = self.templates.read().unwrap();
+        let (Some(tenant_id), Some(restriction)) = (tenant_id, templates.get(template_id)) else {
+            return true;
+        };
+        if restriction.tenants.contains(&tenant_id) {
+            return true;
+        }
+        self.tenant_plans
+            .read()
+            .unwrap()
+            .get(&tenant_id)
+            .is_some_and(|plan| restriction.plans.iter().any(|p| p == plan))
+    }
+
+    pub fn restriction(&self, template_id: &str) -> Option<TemplateRestriction> {
+        self.templates.read().unwrap().get(template_id).cloned()
+    }
+
+    /// Restricciones de todas las plantillas privadas
+    pub fn restrictions(&self) -> HashMap<String, TemplateRestriction> {
+        self.templates.read().unwrap().clone()
+    }
+
+    pub fn set_restriction(&self, template_id: &str, restriction: TemplateRestriction) {
+        self.templates.write().unwrap().insert(template_id.to_string(), restriction);
+    }
+
+    /// Vuelve pública la plantilla; retorna false si no tenía restricción
+    pub fn remove_restriction(&self, template_id: &str) -> bool {
+        self.templates.write().unwrap().remove(template_id).is_some()
+    }
+
+    pub fn plan_of(&self, tenant_id: i64) -> Option<String> {
+        self.tenant_plans.read().unwrap().get(&tenant_id).cloned()
+    }
+
+    /// Asigna (o quita, con `None`) el plan del tenant
+    pub fn set_plan(&self, tenant_id: i64, plan: Option<String>) {
+        let mut plans = self.tenant_plans.write().unwrap();
+        match plan.filter(|p| !p.trim().is_empty()) {
+            Some(plan) => plans.insert(tenant_id, plan),
+            None => plans.remove(&tenant_id),
+        };
+    }
+}
@@ -0,0 +1,200 @@
+/// Monto en letras como se escribe en facturas y recibos:
+/// "DOSCIENTOS OCHENTA Y SEIS MIL CIENTO CINCUENTA PESOS 00/100". En inglés
+/// si `locale` empieza con `en`, en español en cualquier otro caso
+pub fn amount_in_words(amount: f64, currency: &str, locale: &str) -> String {
+    let cents_total = (amount.abs() * 100.0).round() as u64;
+    let (integer, cents) = (cents_total / 100, cents_total % 100);
+    let english = locale.trim().to_ascii_lowercase().starts_with("en");
+
+    let (singular, plural) = currency_names(currency, english);
+    let noun = if integer == 1 { singular } else { plural };
+    let words = if english { english_words(integer) } else { spanish_words(integer) };
+    // "UN MILLÓN DE PESOS", "DOS MILLONES DE PESOS"
+    let of = if !english && integer >= 1_000_000 && integer % 1_000_000 == 0 { " DE" } else { "" };
+    let sign = match (amount < 0.0 && cents_total > 0, english) {
+        (true, true) => "MINUS ",
+        (true, false) => "MENOS ",
+        _ => "",
+    };
+
+    format!("{}{}{} {} {:02}/100", sign, words, of, noun, cents)
+}
+
+fn currency_names(currency: &str, english: bool) -> (String, String) {
+    let (singular, plural) = match (currency.trim().to_ascii_uppercase().as_str(), english) {
+        ("DOP" | "RD$", false) => ("PESO", "PESOS"),
+        ("DOP" | "RD$", true) => ("DOMINICAN PESO", "DOMINICAN PESOS"),
+        ("USD" | "US$", false) => ("DÓLAR", "DÓLARES"),
+        ("USD" | "US$", true) => ("DOLLAR", "DOLLARS"),
+        ("EUR", _) => ("EURO", "EUROS"),
+        (other, _) => return (other.to_string(), other.to_string()),
+    };
+    (singular.to_string(), plural.to_string())
+}
+
+const ES_UNITS: [&str; 30] = [
+    "CERO", "UNO", "DOS", "TRES", "CUATRO", "CINCO", "SEIS", "SIETE", "OCHO", "NUEVE",
+    "DIEZ", "ONCE", "DOCE", "TRECE", "CATORCE", "QUINCE", "DIECISÉIS", "DIECISIETE", "DIECIOCHO", "DIECINUEVE",
+    "VEINTE", "VEINTIUNO", "VEINTIDÓS", "VEINTITRÉS", "VEINTICUATRO", "VEINTICINCO", "VEINTISÉIS", "VEINTISIETE",
+    "VEINTIOCHO", "VEINTINUEVE",
+];
+
+const ES_TENS: [&str; 10] = [
+    "", "", "", "TREINTA", "CUARENTA", "CINCUENTA", "SESENTA", "SETENTA", "OCHENTA", "NOVENTA",
+];
+
+const ES_HUNDREDS: [&str; 10] = [
+    "", "CIENTO", "DOSCIENTOS", "TRESCIENTOS", "CUATROCIENTOS", "QUINIENTOS", "SEISCIENTOS", "SETECIENTOS",
+    "OCHOCIENTOS", "NOVECIENTOS",
+];
+
+/// Escalas largas del español: cada una es un millón de la anterior
+const ES_SCALES: [(&str, &str); 3] = [("MILLÓN", "MILLONES"), ("BILLÓN", "BILLONES"), ("TRILLÓN", "TRILLONES")];
+
+/// Entero en español, con "UN" en lugar de "UNO" al final porque siempre
+/// precede a un sustantivo (la moneda, "MIL", "MILLONES"). Usa la escala
+/// larga: 10^12 es "UN BILLÓN"
+fn spanish_words(n: u64) -> String {
+    if n == 0 {
+        return "CERO".to_string();
+    }
+
+    let mut groups = Vec::new();
+    let mut rest = n;
+    while rest > 0 {
+        groups.push(rest % 1_000_000);
+        rest /= 1_000_000;
+    }
+
+    let mut parts = Vec::new();
+    for (scale, group) in groups.iter().enumerate().rev() {
+        match (*group, scale) {
+            (0, _) => {},
+            (group, 0) => parts.push(spanish_below_million(group)),
+            (1, scale) => parts.push(format!("UN {}", ES_SCALES[scale - 1].0)),
+            (group, scale) => parts.push(format!("{} {}", spanish_below_million(group), ES_SCALES[scale - 1].1)),
+        }
+    }
+    parts.join(" ")
+}
+
+/// 1..=999_999 en español
+fn spanish_below_million(n: u64) -> String {
+    let (thousands, rest) = (n / 1_000, n % 1_000);
+    let mut parts = Vec::new();
+    match thousands {
+        0 => {},
+        1 => parts.push("MIL".to_string()),
+        t => parts.push(format!("{} MIL", spanish_hundreds(t as usize))),
+    }
+    if rest > 0 {
+        parts.push(spanish_hundreds(rest as usize));
+    }
+    parts.join(" ")
+}
+
+/// 1..=999 en español, con apócope final ("UN", "VEINTIÚN", "Y UN")
+fn spanish_hundreds(n: usize) -> String {
+    let (hundreds, below) = (n / 100, n % 100);
+    let mut words = Vec::new();
+    if hundreds > 0 {
+        words.push(if n == 100 { "CIEN" } else { ES_HUNDREDS[hundreds] }.to_string());
+    }
+    match below {
+        0 => {},
+        1 => words.push("UN".to_string()),
+        21 => words.push("VEINTIÚN".to_string()),
+        n if n < 30 => words.push(ES_UNITS[n].to_string()),
+        n if n % 10 == 0 => words.push(ES_TENS[n / 10].to_string()),
+        n if n % 10 == 1 => words.push(format!("{} Y UN", ES_TENS[n / 10])),
+        n => words.push(format!("{} Y {}", ES_TENS[n / 10], ES_UNITS[n % 10])),
+    }
+    words.join(" ")
+}
+
+const EN_UNITS: [&str; 20] = [
+    "ZERO", "ONE", "TWO", "THREE", "FOUR", "FIVE", "SIX", "SEVEN", "EIGHT", "NINE",
+    "TEN", "ELEVEN", "TWELVE", "THIRTEEN", "FOURTEEN", "FIFTEEN", "SIXTEEN", "SEVENTEEN", "EIGHTEEN", "NINETEEN",
+];
+
+const EN_TENS: [&str; 10] = [
+    "", "", "TWENTY", "THIRTY", "FORTY", "FIFTY", "SIXTY", "SEVENTY", "EIGHTY", "NINETY",
+];
+
+/// Escalas cortas del inglés, hasta donde llega un `u64`
+const EN_SCALES: [&str; 7] = ["", "THOUSAND", "MILLION", "BILLION", "TRILLION", "QUADRILLION", "QUINTILLION"];
+
+fn english_words(n: u64) -> String {
+    if n == 0 {
+        return "ZERO".to_string();
+    }
+
+    let mut groups = Vec::new();
+    let mut rest = n;
+    while rest > 0 {
+        groups.push((rest % 1_000) as usize);
+        rest /= 1_000;
+    }
+
+    let mut parts = Vec::new();
+    for (scale, group) in groups.iter().enumerate().rev() {
+        if *group == 0 {
+            continue;
+        }
+        let words = english_hundreds(*group);
+        match EN_SCALES[scale] {
+            "" => parts.push(words),
+            scale => parts.push(format!("{} {}", words, scale)),
+        }
+    }
+    parts.join(" ")
+}
+
+/// 1..=999 en inglés ("ONE HUNDRED FIFTY", "TWENTY-ONE")
+fn english_hundreds(n: usize) -> String {
+    let (hundreds, below) = (n / 100, n % 100);
+    let mut words = Vec::new();
+    if hundreds > 0 {
+        words.push(format!("{} HUNDRED", EN_UNITS[hundreds]));
+    }
+    match below {
+        0 => {},
+        n if n < 20 => words.push(EN_UNITS[n].to_string()),
+        n if n % 10 == 0 => words.push(EN_TENS[n / 10].to_string()),
+        n => words.push(format!("{}-{}", EN_TENS[n / 10], EN_UNITS[n % 10])),
+    }
+    words.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_spanish_amounts() {
+        assert_eq!(amount_in_words(286_150.0, "DOP", "es-DO"), "DOSCIENTOS OCHENTA Y SEIS MIL CIENTO CINCUENTA PESOS 00/100");
+        assert_eq!(amount_in_words(1.5, "DOP", "es"), "UN PESO 50/100");
+        assert_eq!(amount_in_words(21_021.0, "USD", "es"), "VEINTIÚN MIL VEINTIÚN DÓLARES 00/100");
+        assert_eq!(amount_in_words(-100.0, "EUR", "es"), "MENOS CIEN EUROS 00/100");
+    }
+
+    #[test]
+    fn uses_long_scales_in_spanish() {
+        assert_eq!(spanish_words(1_000_000), "UN MILLÓN");
+        assert_eq!(spanish_words(2_500_000), "DOS MILLONES QUINIENTOS MIL");
+        assert_eq!(spanish_words(1_000_000_000), "MIL MILLONES");
+        assert_eq!(spanish_words(1_000_000_000_000), "UN BILLÓN");
+        assert_eq!(spanish_words(3_000_001_000_000), "TRES BILLONES UN MILLÓN");
+        assert_eq!(spanish_words(1_000_000_000_000_000_000), "UN TRILLÓN");
+        assert_eq!(amount_in_words(1e12, "DOP", "es"), "UN BILLÓN DE PESOS 00/100");
+    }
+
+    #[test]
+    fn uses_short_scales_in_english() {
+        assert_eq!(amount_in_words(1_021.05, "USD", "en-US"), "ONE THOUSAND TWENTY-ONE DOLLARS 05/100");
+        assert_eq!(english_words(1_000_000_000_000), "ONE TRILLION");
+        assert_eq!(english_words(2_000_000_000_000_000), "TWO QUADRILLION");
+        assert_eq!(english_words(u64::MAX).split(' ').next(), Some("EIGHTEEN"));
+        assert!(english_words(u64::MAX).contains("QUINTILLION"));
+    }
+}
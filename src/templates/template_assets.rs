@@ -0,0 +1,99 @@
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::storage::keys::template_assets_prefix;
+use crate::storage::storage_trait::Storage;
+
+/// Assets auxiliares de las plantillas (imágenes, fuentes, includes `.typ`)
+/// guardados en el storage y materializados junto al fuente al compilar,
+/// para que la plantilla los referencie con rutas relativas (`image("logo.png")`)
+pub struct TemplateAssetStore {
+    storage: Arc<dyn Storage>,
+    bucket: String,
+}
+
+/// Asset listado de una plantilla
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TemplateAssetInfo {
+    pub name: String,
+    pub size: i64,
+}
+
+impl TemplateAssetStore {
+    pub fn new(storage: Arc<dyn Storage>, bucket: String) -> Self {
+        TemplateAssetStore { storage, bucket }
+    }
+
+    pub async fn put(&self, tenant_id: i64, template_id: &str, name: &str, bytes: Vec<u8>, content_type: &str) -> Result<()> {
+        validate_asset_name(name)?;
+        let key = format!("{}{}", template_assets_prefix(tenant_id, template_id), name);
+        self.storage.put(&self.bucket, &key, bytes, content_type).await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, tenant_id: i64, template_id: &str, name: &str) -> Result<()> {
+        validate_asset_name(name)?;
+        let key = format!("{}{}", template_assets_prefix(tenant_id, template_id), name);
+        self.storage.delete(&self.bucket, &key).await
+    }
+
+    pub async fn list(&self, tenant_id: i64, template_id: &str) -> Result<Vec<TemplateAssetInfo>> {
+        let prefix = template_assets_prefix(tenant_id, template_id);
+        let objects = self.storage.list(&self.bucket, Some(&prefix)).await?;
+
+        Ok(objects
+            .into_iter()
+            .filter_map(|o| {
+                let name = o.key.strip_prefix(&prefix)?.to_string();
+                Some(TemplateAssetInfo { name, size: o.size })
+            })
+            .collect())
+    }
+
+    /// Descarga los assets de la plantilla en `dir`; retorna cuántos escribió
+    pub async fn materialize(&self, tenant_id: i64, template_id: &str, dir: &Path) -> Result<usize> {
+        let prefix = template_assets_prefix(tenant_id, template_id);
+        let objects = self.storage.list(&self.bucket, Some(&prefix)).await?;
+
+        let mut written = 0;
+        for object in objects {
+            let Some(name) = object.key.strip_prefix(&prefix) else { continue };
+            if validate_asset_name(name).is_err() {
+                tracing::warn!("Skipping template asset with invalid name: {}", object.key);
+                continue;
+            }
+
+            let bytes = self.storage.get(&self.bucket, &object.key).await?;
+            let path = asset_path(dir, name);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&path, bytes).await?;
+            written += 1;
+        }
+
+        Ok(written)
+    }
+}
+
+/// Nombre relativo seguro: segmentos `[A-Za-z0-9._-]`, sin `..` ni rutas absolutas
+pub fn validate_asset_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('/')
+        && name.split('/').all(|segment| {
+            !segment.is_empty()
+                && segment != "."
+                && segment != ".."
+                && segment.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        });
+
+    if !valid {
+        bail!("Invalid asset name: {}", name);
+    }
+    Ok(())
+}
+
+fn asset_path(dir: &Path, name: &str) -> PathBuf {
+    name.split('/').fold(dir.to_path_buf(), |path, segment| path.join(segment))
+}
@@ -0,0 +1,178 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use plotters::coord::types::RangedCoordf64;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use serde_json::Value;
+
+use crate::templates::template_models::{ChartData, DataPoint};
+use crate::templates::template_trait::utils;
+
+/// Gráficos que se dibujan por documento; el resto se omite
+pub const MAX_CHARTS: usize = 6;
+
+const WIDTH: u32 = 720;
+const HEIGHT: u32 = 360;
+
+const FONT: &str = "sans-serif";
+
+const PALETTE: [RGBColor; 8] = [
+    RGBColor(70, 130, 180),
+    RGBColor(46, 160, 110),
+    RGBColor(240, 160, 40),
+    RGBColor(210, 80, 70),
+    RGBColor(130, 100, 190),
+    RGBColor(40, 170, 180),
+    RGBColor(200, 110, 160),
+    RGBColor(120, 120, 120),
+];
+
+enum ChartKind {
+    Bar,
+    Line,
+    Pie,
+}
+
+impl ChartKind {
+    /// `line`, `pie` (o `donut`); cualquier otro tipo se dibuja como barras
+    fn parse(chart_type: &str) -> Self {
+        match chart_type.trim().to_ascii_lowercase().as_str() {
+            "line" => ChartKind::Line,
+            "pie" | "donut" | "doughnut" => ChartKind::Pie,
+            _ => ChartKind::Bar,
+        }
+    }
+}
+
+/// Datos con los gráficos de `charts` dibujados como SVG en `imagePath` (data
+/// URI, que sigue el camino de las imágenes inline); `None` si no hay
+/// gráficos que dibujar. Los que ya traen `imagePath` o no tienen puntos se
+/// dejan como están
+pub fn render_charts(data: &Value) -> Result<Option<Value>> {
+    let Some(charts) = data.get("charts").and_then(Value::as_array) else {
+        return Ok(None);
+    };
+    let pending: Vec<usize> = charts
+        .iter()
+        .enumerate()
+        .filter(|(_, chart)| chart.get("imagePath").and_then(Value::as_str).is_none_or(str::is_empty))
+        .filter(|(_, chart)| chart.get("dataPoints").and_then(Value::as_array).is_some_and(|p| !p.is_empty()))
+        .map(|(index, _)| index)
+        .take(MAX_CHARTS)
+        .collect();
+    if pending.is_empty() {
+        return Ok(None);
+    }
+
+    let mut rendered = data.clone();
+    for index in pending {
+        let chart: ChartData = serde_json::from_value(charts[index].clone())
+            .with_context(|| format!("Invalid chart at charts[{}]", index))?;
+        let svg = render_svg(&chart).with_context(|| format!("Failed to draw chart {}", index))?;
+        rendered["charts"][index]["imagePath"] = Value::String(format!(
+            "data:image/svg+xml;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(svg)
+        ));
+    }
+    Ok(Some(rendered))
+}
+
+/// Gráfico en SVG; los textos quedan como texto y los resuelve Typst con sus fuentes
+fn render_svg(chart: &ChartData) -> Result<String> {
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let area = match chart.title.as_deref().filter(|t| !t.trim().is_empty()) {
+            Some(title) => root.titled(title, (FONT, 18))?,
+            None => root.clone(),
+        };
+
+        match ChartKind::parse(&chart.chart_type) {
+            ChartKind::Bar => draw_bars(&area, &chart.data_points)?,
+            ChartKind::Line => draw_line(&area, &chart.data_points)?,
+            ChartKind::Pie => draw_pie(&area, &chart.data_points)?,
+        }
+        root.present()?;
+    }
+    Ok(svg)
+}
+
+fn draw_bars(area: &DrawingArea<SVGBackend<'_>, Shift>, points: &[DataPoint]) -> Result<()> {
+    let mut chart = category_chart(area, points)?;
+    chart.draw_series(points.iter().enumerate().map(|(i, point)| {
+        let x = i as f64;
+        Rectangle::new([(x - 0.35, 0.0), (x + 0.35, point.value)], PALETTE[0].filled())
+    }))?;
+    Ok(())
+}
+
+fn draw_line(area: &DrawingArea<SVGBackend<'_>, Shift>, points: &[DataPoint]) -> Result<()> {
+    let mut chart = category_chart(area, points)?;
+    let series: Vec<(f64, f64)> = points.iter().enumerate().map(|(i, p)| (i as f64, p.value)).collect();
+    chart.draw_series(LineSeries::new(series.iter().copied(), PALETTE[0].stroke_width(2)))?;
+    chart.draw_series(series.iter().map(|&point| Circle::new(point, 3, PALETTE[0].filled())))?;
+    Ok(())
+}
+
+/// Ejes de un gráfico por categorías: una posición por punto, con su etiqueta
+fn category_chart<'a, 'b>(
+    area: &'a DrawingArea<SVGBackend<'b>, Shift>,
+    points: &'a [DataPoint],
+) -> Result<ChartContext<'a, SVGBackend<'b>, Cartesian2d<RangedCoordf64, RangedCoordf64>>> {
+    let min = points.iter().map(|p| p.value).fold(0.0, f64::min);
+    let max = points.iter().map(|p| p.value).fold(0.0, f64::max);
+    let padding = ((max - min) * 0.1).max(1.0);
+    let y_range = if min < 0.0 { min - padding..max + padding } else { 0.0..max + padding };
+
+    let mut chart = ChartBuilder::on(area)
+        .margin(12)
+        .x_label_area_size(40)
+        .y_label_area_size(64)
+        .build_cartesian_2d(-0.5..points.len() as f64 - 0.5, y_range)?;
+
+    let label = |x: &f64| {
+        let index = x.round();
+        if (x - index).abs() > 1e-6 || index < 0.0 {
+            return String::new();
+        }
+        points.get(index as usize).map(|p| p.label.clone()).unwrap_or_default()
+    };
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(points.len())
+        .x_label_formatter(&label)
+        .y_label_formatter(&|y| format_value(*y))
+        .label_style((FONT, 12))
+        .draw()?;
+    Ok(chart)
+}
+
+fn draw_pie(area: &DrawingArea<SVGBackend<'_>, Shift>, points: &[DataPoint]) -> Result<()> {
+    // Solo los valores positivos tienen porción
+    let slices: Vec<&DataPoint> = points.iter().filter(|p| p.value > 0.0).collect();
+    if slices.is_empty() {
+        return Ok(());
+    }
+
+    let (width, height) = area.dim_in_pixel();
+    let center = (width as i32 / 2, height as i32 / 2);
+    let radius = (width.min(height) as f64 / 2.0 - 40.0).max(20.0);
+    let sizes: Vec<f64> = slices.iter().map(|p| p.value).collect();
+    let colors: Vec<RGBColor> = (0..slices.len()).map(|i| PALETTE[i % PALETTE.len()]).collect();
+    let labels: Vec<&str> = slices.iter().map(|p| p.label.as_str()).collect();
+
+    let mut pie = Pie::new(&center, &radius, &sizes, &colors, &labels);
+    pie.start_angle(-90.0);
+    pie.label_style((FONT, 12).into_font().color(&BLACK));
+    pie.percentages((FONT, 11).into_font().color(&WHITE));
+    area.draw(&pie)?;
+    Ok(())
+}
+
+/// Valores del eje con separador de miles y sin decimales si no hacen falta
+fn format_value(value: f64) -> String {
+    let decimals = if value.fract().abs() < 1e-9 { 0 } else { 2 };
+    utils::format_number(value, decimals)
+}
@@ -1,78 +1,129 @@
+use crate::models::{RenderOptions, RenderProfiles};
 use crate::templates::template_models::*;
 use crate::templates::template_trait::{TemplateRegistry, TypstTemplate};
+use crate::templates::template_stats::{RegressionPolicy, TemplateStats, TemplateUsage};
+use crate::templates::template_access::TemplateAccessConfig;
+use crate::templates::template_assets::TemplateAssetStore;
+use crate::templates::template_sandbox::{BLACKHOLE_PROXY, SANDBOX_COMPILE_TIMEOUT};
+use crate::templates::template_overrides::UploadedTemplate;
+use crate::templates::template_files;
+use crate::templates::template_logos::LogoCache;
+use crate::templates::template_charts::render_charts;
+use crate::templates::template_inline_images::{extract_inline_images, has_inline_images, write_inline_images};
 use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
-use std::process::Command;
 use std::sync::Arc;
 use serde_json;
-use std::collections::HashMap;
 
 pub struct TemplateEngine {
+    templates_dir: String,
     output_dir: String,
     registry: Arc<TemplateRegistry>,
+    stats: Arc<TemplateStats>,
+    assets: Option<Arc<TemplateAssetStore>>,
+    preview_ppi: Option<u32>,
+    logos: Option<Arc<LogoCache>>,
+    render_profiles: Option<Arc<RenderProfiles>>,
+    /// Vigila `templates_dir` mientras exista (ver `with_hot_reload`)
+    watcher: Option<notify::RecommendedWatcher>,
 }
 
 impl TemplateEngine {
-    pub fn new(_templates_dir: String, output_dir: String) -> Self {
+    /// Las plantillas `{templates_dir}/*.typ` se cargan al crear el motor y
+    /// reemplazan a las incorporadas con el mismo id
+    pub fn new(templates_dir: String, output_dir: String) -> Self {
+        let registry = Arc::new(TemplateRegistry::new());
+        let loaded = template_files::reload_dir(Path::new(&templates_dir), &registry);
+        if loaded > 0 {
+            tracing::info!("Loaded {} template files from {}", loaded, templates_dir);
+        }
+
         Self {
+            templates_dir,
             output_dir,
-            registry: Arc::new(TemplateRegistry::new()),
+            registry,
+            stats: Arc::new(TemplateStats::with_policy(RegressionPolicy::from_env())),
+            assets: None,
+            preview_ppi: None,
+            logos: None,
+            render_profiles: None,
+            watcher: None,
         }
     }
 
-    pub async fn generate_pdf(
-        &self,
-        template_id: &str,
-        data: TemplateData,
-        output_filename: Option<String>,
-    ) -> Result<String> {
-        fs::create_dir_all(&self.output_dir)?;
-
-        // Obtener la plantilla del registro
-        let template = self.registry.get(template_id)
-            .ok_or_else(|| anyhow::anyhow!("Template no encontrado: {}", template_id))?;
-
-        // Convertir TemplateData a JSON para la plantilla
-        let json_data = serde_json::to_value(&data)?;
-
-        // Validar los datos
-        template.validate(&json_data)?;
+    /// Recarga las plantillas en disco al cambiar un `.typ`, sin reiniciar la API.
+    /// Si el watcher no se puede crear se sigue sin recarga automática
+    pub fn with_hot_reload(mut self) -> Self {
+        match template_files::watch_dir(self.templates_dir.clone().into(), self.registry.clone()) {
+            Ok(watcher) => self.watcher = Some(watcher),
+            Err(e) => tracing::warn!("Template hot reload disabled for {}: {}", self.templates_dir, e),
+        }
+        self
+    }
 
-        // Generar contenido Typst usando la plantilla dinámica
-        let typst_content = template.generate(&json_data)?;
+    /// Vuelve a leer todas las plantillas en disco; retorna cuántas quedaron cargadas
+    pub fn reload_template_files(&self) -> usize {
+        template_files::reload_dir(Path::new(&self.templates_dir), &self.registry)
+    }
 
-        // Assets vacíos por ahora (se pueden manejar dentro de cada plantilla si es necesario)
-        let _assets: HashMap<String, String> = HashMap::new();
+    /// Habilita los assets de plantillas (imágenes, fuentes, includes) guardados en el storage
+    pub fn with_assets(mut self, assets: Arc<TemplateAssetStore>) -> Self {
+        self.assets = Some(assets);
+        self
+    }
 
-        let timestamp = chrono::Utc::now().timestamp();
-        let base_filename = output_filename.unwrap_or_else(|| format!("{}_{}", template_id, timestamp));
+    /// Genera además una miniatura PNG de la primera página a `ppi` puntos por pulgada
+    pub fn with_previews(mut self, ppi: u32) -> Self {
+        self.preview_ppi = Some(ppi).filter(|ppi| *ppi > 0);
+        self
+    }
 
-        let typ_path = format!("{}/{}.typ", self.output_dir, base_filename);
-        let pdf_path = format!("{}/{}.pdf", self.output_dir, base_filename);
+    /// Descarga (con caché) los logos de `companyInfo.logoUrl` al generar
+    pub fn with_logos(mut self, logos: Arc<LogoCache>) -> Self {
+        self.logos = Some(logos);
+        self
+    }
 
-        // Guardar el archivo Typst temporal
-        fs::write(&typ_path, &typst_content)?;
+    /// Completa las opciones de render de cada documento con el perfil de su tenant
+    pub fn with_render_profiles(mut self, profiles: Arc<RenderProfiles>) -> Self {
+        self.render_profiles = Some(profiles);
+        self
+    }
 
-        // Compilar Typst a PDF
-        let output = Command::new("typst")
-            .args(&["compile", &typ_path, &pdf_path])
-            .output()?;
+    /// Carga las restricciones de acceso a plantillas y los planes de los tenants
+    pub fn with_template_access(self, config: TemplateAccessConfig) -> Self {
+        self.registry.access().configure(config);
+        self
+    }
 
-        // Limpiar archivo temporal
-        fs::remove_file(&typ_path).ok();
+    /// Almacén de assets de plantillas, si está habilitado
+    pub fn assets(&self) -> Option<Arc<TemplateAssetStore>> {
+        self.assets.clone()
+    }
 
-        // Limpiar assets temporales (si hubiera alguno)
-        // Los assets ahora se manejan dentro de cada plantilla
+    pub async fn generate_pdf(
+        &self,
+        template_id: &str,
+        data: TemplateData,
+        output_filename: Option<String>,
+    ) -> Result<String> {
+        self.generate_pdf_for_tenant(None, template_id, data, output_filename).await
+    }
 
-        if !output.status.success() {
-            return Err(anyhow::anyhow!(
-                "Typst compilation failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
+    /// Genera un PDF usando la plantilla del tenant si reemplaza el id
+    pub async fn generate_pdf_for_tenant(
+        &self,
+        tenant_id: Option<i64>,
+        template_id: &str,
+        data: TemplateData,
+        output_filename: Option<String>,
+    ) -> Result<String> {
+        // Convertir TemplateData a JSON para la plantilla
+        let json_data = serde_json::to_value(&data)?;
 
-        Ok(pdf_path)
+        self.generate_pdf_from_json_for_tenant(tenant_id, template_id, json_data, output_filename).await
     }
 
     /// Genera un PDF desde datos JSON genéricos
@@ -82,43 +133,243 @@ impl TemplateEngine {
         json_data: serde_json::Value,
         output_filename: Option<String>,
     ) -> Result<String> {
+        self.generate_pdf_from_json_for_tenant(None, template_id, json_data, output_filename).await
+    }
+
+    /// Genera un PDF desde datos JSON resolviendo la plantilla tenant → global
+    pub async fn generate_pdf_from_json_for_tenant(
+        &self,
+        tenant_id: Option<i64>,
+        template_id: &str,
+        json_data: serde_json::Value,
+        output_filename: Option<String>,
+    ) -> Result<String> {
+        let rendered = self.generate_pdf_from_json_timed(tenant_id, template_id, json_data, output_filename).await?;
+        if let Some(preview_path) = &rendered.preview_path {
+            let _ = fs::remove_file(preview_path);
+        }
+        Ok(rendered.pdf_path)
+    }
+
+    /// Igual que `generate_pdf_from_json_for_tenant`, con el tiempo de cada
+    /// etapa y la miniatura de la primera página (si están habilitadas)
+    pub async fn generate_pdf_from_json_timed(
+        &self,
+        tenant_id: Option<i64>,
+        template_id: &str,
+        json_data: serde_json::Value,
+        output_filename: Option<String>,
+    ) -> Result<RenderedPdf> {
         fs::create_dir_all(&self.output_dir)?;
 
-        // Obtener la plantilla del registro
-        let template = self.registry.get(template_id)
+        // Obtener la plantilla del registro (reemplazo del tenant primero)
+        let template = self.registry.resolve(tenant_id, template_id)
             .ok_or_else(|| anyhow::anyhow!("Template no encontrado: {}", template_id))?;
 
+        let start = std::time::Instant::now();
+        let result = self.render_template(tenant_id, template.as_ref(), &json_data, output_filename).await;
+
+        match &result {
+            Ok(_) => self.stats.record_success(
+                template_id,
+                template.version(),
+                start.elapsed().as_millis() as u64,
+            ),
+            Err(e) => self.stats.record_failure(template_id, template.version(), &e.to_string()),
+        }
+
+        result
+    }
+
+    async fn render_template(
+        &self,
+        tenant_id: Option<i64>,
+        template: &dyn TypstTemplate,
+        json_data: &serde_json::Value,
+        output_filename: Option<String>,
+    ) -> Result<RenderedPdf> {
+        let template_id = template.template_id();
+        let mut timings = RenderTimings::default();
+
+        // Gráficos de `charts`: se dibujan en SVG y siguen como imágenes inline
+        let with_charts = render_charts(json_data)?;
+        let json_data = with_charts.as_ref().unwrap_or(json_data);
+
+        // Logos remotos: se descargan y siguen como imágenes inline
+        let with_logos = match &self.logos {
+            Some(logos) => logos.resolve_logos(json_data).await,
+            None => None,
+        };
+        let json_data = with_logos.as_ref().unwrap_or(json_data);
+
+        // Imágenes inline (data URIs): los datos pasan a referenciarlas por ruta
+        let mut inline_images = Vec::new();
+        let inlined;
+        let json_data = if has_inline_images(json_data) {
+            let mut data = json_data.clone();
+            inline_images = extract_inline_images(&mut data)?;
+            inlined = data;
+            &inlined
+        } else {
+            json_data
+        };
+
         // Validar los datos
-        template.validate(&json_data)?;
+        let stage = std::time::Instant::now();
+        template.validate(json_data)?;
 
-        // Generar contenido Typst
-        let typst_content = template.generate(&json_data)?;
+        // Generar contenido Typst con las opciones de render de los datos (sobre el perfil del tenant)
+        let defaults = match &self.render_profiles {
+            Some(profiles) => profiles.defaults_for(tenant_id).await?,
+            None => None,
+        };
+        let options = RenderOptions::from_data_with_defaults(json_data, defaults.as_ref())
+            .context("Invalid render options")?;
+        let typst_content = template.generate(json_data, &options)?;
+        timings.render_ms = stage.elapsed().as_millis() as u64;
 
         let timestamp = chrono::Utc::now().timestamp();
         let base_filename = output_filename.unwrap_or_else(|| format!("{}_{}", template_id, timestamp));
 
-        let typ_path = format!("{}/{}.typ", self.output_dir, base_filename);
         let pdf_path = format!("{}/{}.pdf", self.output_dir, base_filename);
+        let preview_path = format!("{}/{}.preview.png", self.output_dir, base_filename);
+        let bundle_dir = format!("{}/{}_bundle", self.output_dir, base_filename);
+
+        // Los temporales se eliminan aunque la tarea se cancele (timeout)
+        let mut artifacts = TempArtifacts::new(vec![pdf_path.clone(), preview_path.clone()]);
+
+        // Con assets, el fuente se compila dentro de un directorio propio que
+        // los contiene y que actúa como raíz y ruta de fuentes del compilador.
+        // Las plantillas de tenants usan siempre uno: no ven otros documentos
+        let stage = std::time::Instant::now();
+        let sandboxed = template.sandboxed();
+        let has_assets = match (&self.assets, tenant_id) {
+            (Some(assets), Some(tenant_id)) => {
+                artifacts.add_dir(&bundle_dir);
+                assets.materialize(tenant_id, template_id, Path::new(&bundle_dir)).await? > 0
+            },
+            _ => false,
+        };
+        let inline = !inline_images.is_empty();
+        if (sandboxed || inline) && !has_assets {
+            artifacts.add_dir(&bundle_dir);
+            tokio::fs::create_dir_all(&bundle_dir).await?;
+        }
+        write_inline_images(Path::new(&bundle_dir), &inline_images).await?;
+        timings.assets_ms = stage.elapsed().as_millis() as u64;
+
+        let isolated = has_assets || sandboxed || inline;
+        let typ_path = if isolated {
+            format!("{}/{}.typ", bundle_dir, base_filename)
+        } else {
+            format!("{}/{}.typ", self.output_dir, base_filename)
+        };
+        artifacts.add(&typ_path);
 
         // Guardar el archivo Typst temporal
-        fs::write(&typ_path, &typst_content)?;
+        tokio::fs::write(&typ_path, &typst_content).await?;
 
-        // Compilar Typst a PDF
-        let output = Command::new("typst")
-            .args(&["compile", &typ_path, &pdf_path])
-            .output()?;
+        let bundle = isolated.then_some(bundle_dir.as_str());
 
-        // Limpiar archivo temporal
-        fs::remove_file(&typ_path).ok();
+        // Compilar Typst a PDF; kill_on_drop termina el proceso si se aborta la generación
+        let stage = std::time::Instant::now();
+        let output = typst_compile(bundle, sandboxed)
+            .args([&typ_path, &pdf_path])
+            .output()
+            .await?;
 
         if !output.status.success() {
-            return Err(anyhow::anyhow!(
-                "Typst compilation failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+            return Err(CompileError {
+                typst_source: typst_content,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }.into());
+        }
+
+        timings.compile_ms = stage.elapsed().as_millis() as u64;
+
+        let warnings = parse_diagnostics(&String::from_utf8_lossy(&output.stderr), "warning");
+        if !warnings.is_empty() {
+            tracing::debug!("Template {} compiled with {} warnings", template_id, warnings.len());
+        }
+
+        // La miniatura es opcional: si falla, el documento se entrega sin ella
+        let mut preview = None;
+        if let Some(ppi) = self.preview_ppi {
+            let ppi = ppi.to_string();
+            let output = typst_compile(bundle, sandboxed)
+                .args(["--format", "png", "--pages", "1", "--ppi", &ppi, &typ_path, &preview_path])
+                .output()
+                .await?;
+
+            if output.status.success() {
+                artifacts.keep(&preview_path);
+                preview = Some(preview_path);
+            } else {
+                tracing::warn!(
+                    "Preview rendering failed for template {}: {}",
+                    template_id,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
         }
 
-        Ok(pdf_path)
+        // El PDF queda en disco para quien lo solicitó
+        artifacts.keep(&pdf_path);
+
+        Ok(RenderedPdf { pdf_path, preview_path: preview, timings, warnings })
+    }
+
+    /// Compilación de prueba de una plantilla aún no registrada (subida de un
+    /// tenant), acotada por `SANDBOX_COMPILE_TIMEOUT`; no guarda nada ni
+    /// cuenta en las estadísticas. Retorna las advertencias de Typst
+    pub async fn compile_check(
+        &self,
+        tenant_id: i64,
+        template: &dyn TypstTemplate,
+        json_data: &serde_json::Value,
+    ) -> Result<Vec<CompileDiagnostic>> {
+        fs::create_dir_all(&self.output_dir)?;
+        let output_filename = format!("sandbox_{}_{}", template.template_id(), uuid::Uuid::new_v4());
+
+        let rendered = tokio::time::timeout(
+            SANDBOX_COMPILE_TIMEOUT,
+            self.render_template(Some(tenant_id), template, json_data, Some(output_filename)),
+        )
+        .await
+        .map_err(|_| anyhow::anyhow!("Template compilation exceeded {}s", SANDBOX_COMPILE_TIMEOUT.as_secs()))??;
+
+        let _ = fs::remove_file(&rendered.pdf_path);
+        if let Some(path) = &rendered.preview_path {
+            let _ = fs::remove_file(path);
+        }
+        Ok(rendered.warnings)
+    }
+
+    /// Plantilla que usa el tenant para un id: su versión o la global
+    pub fn get_template(&self, tenant_id: Option<i64>, template_id: &str) -> Option<Arc<dyn TypstTemplate>> {
+        self.registry.resolve(tenant_id, template_id)
+    }
+
+    /// Registra (o reemplaza) la versión del tenant de una plantilla
+    pub fn update_template(&self, tenant_id: i64, template: Arc<dyn TypstTemplate>) {
+        self.registry.set_override(tenant_id, template);
+    }
+
+    /// Vuelve a cargar la versión del tenant con el `(fuente, versión)` dado
+    /// (el guardado en el storage); sin fuente la quita y queda la global.
+    /// Retorna si había una versión del tenant registrada
+    pub fn reload_template(&self, tenant_id: i64, template_id: &str, source: Option<(String, String)>) -> Result<bool> {
+        let had_override = self.registry.get_override(tenant_id, template_id).is_some();
+        match source {
+            Some((source, version)) => {
+                let template = UploadedTemplate::new(tenant_id, template_id, source, version)?;
+                self.registry.set_override(tenant_id, Arc::new(template));
+            },
+            None => {
+                self.registry.remove_override(tenant_id, template_id);
+            },
+        }
+        Ok(had_override)
     }
 
     /// Lista todas las plantillas disponibles
@@ -131,6 +382,11 @@ impl TemplateEngine {
         self.registry.exists(template_id)
     }
 
+    /// Estadísticas de uso (renders, fallos, tiempo de compilación) de una plantilla
+    pub fn template_stats(&self, template_id: &str) -> Vec<TemplateUsage> {
+        self.stats.for_template(template_id)
+    }
+
     /// Obtiene el registro de plantillas para operaciones avanzadas
     pub fn get_registry(&self) -> Arc<TemplateRegistry> {
         self.registry.clone()
@@ -174,4 +430,139 @@ impl TemplateEngine {
         let data = TemplateData::Receipt(receipt_data);
         self.generate_pdf("receipt", data, output_filename).await
     }
-}
\ No newline at end of file
+}
+
+/// Comando `typst compile`; con bundle de assets, este es la raíz y la ruta de
+/// fuentes. `sandboxed` deja los paquetes en el bundle y corta la red con un
+/// proxy inexistente
+fn typst_compile(bundle_dir: Option<&str>, sandboxed: bool) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new("typst");
+    command.arg("compile").kill_on_drop(true);
+    if let Some(dir) = bundle_dir {
+        command.args(["--root", dir, "--font-path", dir]);
+        if sandboxed {
+            let packages = format!("{}/.packages", dir);
+            command
+                .env("TYPST_PACKAGE_PATH", &packages)
+                .env("TYPST_PACKAGE_CACHE_PATH", &packages)
+                .env("HTTP_PROXY", BLACKHOLE_PROXY)
+                .env("HTTPS_PROXY", BLACKHOLE_PROXY)
+                .env("ALL_PROXY", BLACKHOLE_PROXY)
+                .env_remove("NO_PROXY");
+        }
+    }
+    command
+}
+
+/// PDF compilado en disco y, si se pidió, la miniatura PNG de su primera página
+#[derive(Debug, Clone)]
+pub struct RenderedPdf {
+    pub pdf_path: String,
+    pub preview_path: Option<String>,
+    pub timings: RenderTimings,
+    /// Advertencias del compilador (fuentes faltantes, etc.)
+    pub warnings: Vec<CompileDiagnostic>,
+}
+
+/// Advertencia o error reportado por `typst compile`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompileDiagnostic {
+    pub message: String,
+    /// `archivo:línea:columna`
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hints: Vec<String>,
+}
+
+/// Diagnósticos `kind` ("warning" o "error") de la salida legible de Typst:
+///
+/// ```text
+/// warning: unknown font family: inter
+///   ┌─ factura.typ:3:17
+///   = hint: ...
+/// ```
+pub fn parse_diagnostics(stderr: &str, kind: &str) -> Vec<CompileDiagnostic> {
+    let prefix = format!("{}: ", kind);
+    let mut diagnostics = Vec::new();
+    let mut current: Option<CompileDiagnostic> = None;
+
+    for line in stderr.lines() {
+        let trimmed = line.trim();
+
+        if let Some(message) = line.strip_prefix(&prefix) {
+            diagnostics.extend(current.take());
+            current = Some(CompileDiagnostic { message: message.trim().to_string(), location: None, hints: Vec::new() });
+        } else if line.starts_with("error: ") || line.starts_with("warning: ") {
+            // Otro diagnóstico (de otro tipo) empieza aquí
+            diagnostics.extend(current.take());
+        } else if let Some(diagnostic) = current.as_mut() {
+            if let Some(location) = trimmed.strip_prefix("┌─") {
+                // Solo el nombre del archivo: el resto es el directorio temporal
+                let location = location.trim();
+                let location = location.rsplit('/').next().unwrap_or(location);
+                diagnostic.location.get_or_insert_with(|| location.to_string());
+            } else if let Some(hint) = trimmed.strip_prefix("= hint:") {
+                diagnostic.hints.push(hint.trim().to_string());
+            }
+        }
+    }
+
+    diagnostics.extend(current);
+    diagnostics
+}
+
+/// Falla de `typst compile`; conserva el fuente generado y la salida del
+/// compilador para el bundle de soporte
+#[derive(Debug, thiserror::Error)]
+#[error("Typst compilation failed: {stderr}")]
+pub struct CompileError {
+    pub typst_source: String,
+    pub stderr: String,
+}
+
+/// Duración de las etapas de una generación de PDF (ms)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderTimings {
+    /// Validación y generación del fuente Typst
+    pub render_ms: u64,
+    /// Descarga de los assets de la plantilla
+    pub assets_ms: u64,
+    /// Compilación con `typst`
+    pub compile_ms: u64,
+}
+
+/// Archivos temporales de una compilación: se eliminan al salir del scope
+/// (incluida la cancelación por timeout), salvo los marcados con `keep`
+struct TempArtifacts {
+    paths: Vec<String>,
+    dirs: Vec<String>,
+}
+
+impl TempArtifacts {
+    fn new(paths: Vec<String>) -> Self {
+        Self { paths, dirs: Vec::new() }
+    }
+
+    fn add(&mut self, path: &str) {
+        self.paths.push(path.to_string());
+    }
+
+    fn add_dir(&mut self, dir: &str) {
+        self.dirs.push(dir.to_string());
+    }
+
+    fn keep(&mut self, path: &str) {
+        self.paths.retain(|p| p != path);
+    }
+}
+
+impl Drop for TempArtifacts {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+        for dir in &self.dirs {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
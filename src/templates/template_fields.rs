@@ -0,0 +1,78 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Campos que la plantilla usa frente a los que declara el modelo de datos
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldUsage {
+    /// Rutas que lee la plantilla (`companyInfo.name`)
+    pub referenced: Vec<String>,
+    /// Rutas hoja del modelo; los arreglos cuentan como hoja (`items`)
+    pub declared: Vec<String>,
+    /// Declarados que la plantilla no lee
+    pub unused: Vec<String>,
+    /// Leídos por la plantilla que el modelo no declara: fallan o quedan vacíos
+    pub missing: Vec<String>,
+}
+
+/// Compara las rutas que lee la plantilla con las del modelo, tomadas de
+/// unos datos de ejemplo serializados. Las variables de un `{% for %}` no se
+/// pueden seguir estáticamente, por eso leer el arreglo cubre sus campos
+pub fn analyze_field_usage(referenced: &[String], schema: &Value) -> FieldUsage {
+    // Hoja → abierta: puede tener claves arbitrarias (mapa u opcional nulo en
+    // los datos de ejemplo), así cualquier ruta debajo cuenta como declarada
+    let mut leaves = BTreeMap::new();
+    if let Some(object) = schema.as_object() {
+        for (key, value) in object {
+            // Etiqueta de `TemplateData`, no es un campo de los datos
+            if key != "type" {
+                collect_leaves(key.clone(), value, &mut leaves);
+            }
+        }
+    }
+
+    let mut referenced: Vec<String> = referenced.to_vec();
+    referenced.sort();
+    referenced.dedup();
+
+    let unused = leaves
+        .keys()
+        .filter(|leaf| !referenced.iter().any(|r| covers(r, leaf) || covers(leaf, r)))
+        .cloned()
+        .collect();
+    let missing = referenced
+        .iter()
+        .filter(|r| {
+            !leaves.iter().any(|(leaf, open)| covers(r, leaf) || (*open && covers(leaf, r)))
+        })
+        .cloned()
+        .collect();
+
+    FieldUsage {
+        declared: leaves.keys().cloned().collect(),
+        referenced,
+        unused,
+        missing,
+    }
+}
+
+/// `path` es igual a `other` o un ancestro suyo
+fn covers(path: &str, other: &str) -> bool {
+    other == path || other.strip_prefix(path).is_some_and(|rest| rest.starts_with('.'))
+}
+
+fn collect_leaves(path: String, value: &Value, leaves: &mut BTreeMap<String, bool>) {
+    match value {
+        Value::Object(object) if !object.is_empty() => {
+            for (key, child) in object {
+                collect_leaves(format!("{}.{}", path, key), child, leaves);
+            }
+        },
+        Value::Object(_) | Value::Null => {
+            leaves.insert(path, true);
+        },
+        _ => {
+            leaves.insert(path, false);
+        },
+    }
+}
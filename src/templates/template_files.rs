@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use minijinja::Environment;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::models::RenderOptions;
+use crate::templates::template_overrides::typst_environment;
+use crate::templates::template_trait::{utils, TemplateRegistry, TypstTemplate};
+
+/// Plantilla global en disco: `{templates_dir}/{id}.typ`, Typst con marcadores
+/// minijinja como las de tenants. La escribe el equipo de operaciones, así que
+/// no pasa por el sandbox
+pub struct FileTemplate {
+    template_id: String,
+    source: String,
+    version: String,
+    env: Environment<'static>,
+}
+
+impl FileTemplate {
+    /// Lee y valida la sintaxis de un `.typ`; la versión sale de la fecha de modificación
+    pub fn load(path: &Path) -> Result<Self> {
+        let template_id = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .with_context(|| format!("Invalid template file name: {}", path.display()))?
+            .to_string();
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let modified: chrono::DateTime<chrono::Utc> = std::fs::metadata(path)?.modified()?.into();
+
+        let env = typst_environment();
+        env.template_from_str(&source)
+            .map_err(|e| anyhow::anyhow!("Invalid template syntax in {}: {}", path.display(), e))?;
+
+        Ok(FileTemplate {
+            template_id,
+            source,
+            version: format!("file-{}", modified.format("%Y%m%d%H%M%S")),
+            env,
+        })
+    }
+}
+
+impl TypstTemplate for FileTemplate {
+    fn generate(&self, data: &Value, options: &RenderOptions) -> Result<String> {
+        self.env
+            .render_str(&self.source, utils::with_render_options(data, options))
+            .map(|source| format!("{}{}", utils::watermark_rule(options), source))
+            .with_context(|| format!("Error renderizando plantilla {} en disco", self.template_id))
+    }
+
+    fn template_id(&self) -> &str {
+        &self.template_id
+    }
+
+    fn validate(&self, data: &Value) -> Result<()> {
+        if !data.is_object() {
+            anyhow::bail!("Los datos deben ser un objeto JSON");
+        }
+        Ok(())
+    }
+
+    fn description(&self) -> &str {
+        "Plantilla en disco"
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn source(&self) -> Option<&str> {
+        Some(&self.source)
+    }
+
+    fn referenced_fields(&self) -> Option<Vec<String>> {
+        let template = self.env.template_from_str(&self.source).ok()?;
+        let mut fields: Vec<String> = template.undeclared_variables(true).into_iter().collect();
+        fields.sort();
+        Some(fields)
+    }
+}
+
+/// Carga los `.typ` del directorio en el registro, reemplazando los anteriores.
+/// Un archivo inválido se omite (y se registra) sin afectar a los demás;
+/// retorna cuántos quedaron cargados
+pub fn reload_dir(dir: &Path, registry: &TemplateRegistry) -> usize {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            registry.replace_files(Vec::new());
+            return 0;
+        },
+        Err(e) => {
+            tracing::warn!("Failed to read templates directory {}: {}", dir.display(), e);
+            return 0;
+        },
+    };
+
+    let mut templates: Vec<Arc<dyn TypstTemplate>> = Vec::new();
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        if !is_template_file(&path) {
+            continue;
+        }
+        match FileTemplate::load(&path) {
+            Ok(template) => templates.push(Arc::new(template)),
+            Err(e) => tracing::warn!("Skipping template file {}: {:#}", path.display(), e),
+        }
+    }
+
+    let loaded = templates.len();
+    registry.replace_files(templates);
+    loaded
+}
+
+/// Vigila el directorio y recarga las plantillas cuando cambia un `.typ`
+/// (creado, editado, renombrado o borrado). El watcher deja de vigilar al
+/// descartarse, así que quien lo crea lo conserva
+pub fn watch_dir(dir: PathBuf, registry: Arc<TemplateRegistry>) -> Result<RecommendedWatcher> {
+    std::fs::create_dir_all(&dir)?;
+    let watched = dir.clone();
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::warn!("Template watcher error: {}", e);
+                return;
+            },
+        };
+        if matches!(event.kind, EventKind::Access(_)) || !event.paths.iter().any(|p| is_template_file(p)) {
+            return;
+        }
+
+        let loaded = reload_dir(&watched, &registry);
+        tracing::info!("Reloaded {} template files from {} after a change", loaded, watched.display());
+    })?;
+
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+fn is_template_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "typ")
+}
@@ -0,0 +1,129 @@
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use serde_json::Value;
+use std::path::Path;
+
+/// Tamaño máximo de una imagen inline ya decodificada
+pub const INLINE_IMAGE_MAX_BYTES: usize = 512 * 1024;
+
+/// Imágenes inline por documento
+pub const MAX_INLINE_IMAGES: usize = 10;
+
+/// Subdirectorio del bundle de compilación donde quedan las imágenes
+const INLINE_DIR: &str = "_inline";
+
+const DATA_URI_PREFIX: &str = "data:image/";
+
+/// Imagen de un data URI de los datos, ya validada
+pub struct InlineImage {
+    /// Ruta relativa con la que la plantilla la referencia (`_inline/0.png`)
+    pub path: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Si algún string de los datos es un data URI de imagen
+pub fn has_inline_images(data: &Value) -> bool {
+    match data {
+        Value::String(s) => s.starts_with(DATA_URI_PREFIX),
+        Value::Array(items) => items.iter().any(has_inline_images),
+        Value::Object(fields) => fields.values().any(has_inline_images),
+        _ => false,
+    }
+}
+
+/// Valida los data URIs de los datos sin extraerlos (al recibir el request)
+pub fn check_inline_images(data: &Value) -> Result<()> {
+    if has_inline_images(data) {
+        extract_inline_images(&mut data.clone())?;
+    }
+    Ok(())
+}
+
+/// Decodifica los data URIs de imagen (`logoPath`, o cualquier campo) y
+/// los reemplaza en los datos por la ruta relativa con la que el compilador
+/// los encuentra en el bundle. Solo PNG, JPEG, GIF y SVG, con el contenido
+/// acorde al tipo declarado y hasta `INLINE_IMAGE_MAX_BYTES`
+pub fn extract_inline_images(data: &mut Value) -> Result<Vec<InlineImage>> {
+    let mut images = Vec::new();
+    extract_into(data, &mut images)?;
+    Ok(images)
+}
+
+fn extract_into(value: &mut Value, images: &mut Vec<InlineImage>) -> Result<()> {
+    match value {
+        Value::String(s) if s.starts_with(DATA_URI_PREFIX) => {
+            if images.len() == MAX_INLINE_IMAGES {
+                bail!("At most {} inline images are allowed per document", MAX_INLINE_IMAGES);
+            }
+            let (extension, bytes) = decode_data_uri(s)?;
+            let path = format!("{}/{}.{}", INLINE_DIR, images.len(), extension);
+            *s = path.clone();
+            images.push(InlineImage { path, bytes });
+        },
+        Value::Array(items) => {
+            for item in items {
+                extract_into(item, images)?;
+            }
+        },
+        Value::Object(fields) => {
+            for field in fields.values_mut() {
+                extract_into(field, images)?;
+            }
+        },
+        _ => {},
+    }
+    Ok(())
+}
+
+/// `data:image/png;base64,...` → (extensión, bytes)
+fn decode_data_uri(uri: &str) -> Result<(&'static str, Vec<u8>)> {
+    let (header, payload) = uri
+        .split_once(',')
+        .context("Invalid image data URI: missing ','")?;
+    let Some(media_type) = header.strip_prefix("data:").and_then(|h| h.strip_suffix(";base64")) else {
+        bail!("Invalid image data URI: only base64 data URIs are supported");
+    };
+
+    let extension = match media_type {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/gif" => "gif",
+        "image/svg+xml" => "svg",
+        other => bail!("Unsupported inline image type {}; use png, jpeg, gif or svg", other),
+    };
+
+    // Se descarta antes de decodificar lo que no puede entrar en el límite
+    if payload.len() / 4 * 3 > INLINE_IMAGE_MAX_BYTES + 3 {
+        bail!("Inline image exceeds {} bytes", INLINE_IMAGE_MAX_BYTES);
+    }
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(payload.trim())
+        .context("Invalid base64 in image data URI")?;
+    if bytes.len() > INLINE_IMAGE_MAX_BYTES {
+        bail!("Inline image exceeds {} bytes", INLINE_IMAGE_MAX_BYTES);
+    }
+
+    let matches_type = match extension {
+        "png" => bytes.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "jpg" => bytes.starts_with(&[0xFF, 0xD8, 0xFF]),
+        "gif" => bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a"),
+        _ => std::str::from_utf8(&bytes).is_ok_and(|svg| svg.contains("<svg")),
+    };
+    if !matches_type {
+        bail!("Inline image content does not match its declared type {}", media_type);
+    }
+
+    Ok((extension, bytes))
+}
+
+/// Escribe las imágenes en el bundle de compilación
+pub async fn write_inline_images(bundle_dir: &Path, images: &[InlineImage]) -> Result<()> {
+    if images.is_empty() {
+        return Ok(());
+    }
+    tokio::fs::create_dir_all(bundle_dir.join(INLINE_DIR)).await?;
+    for image in images {
+        tokio::fs::write(bundle_dir.join(&image.path), &image.bytes).await?;
+    }
+    Ok(())
+}
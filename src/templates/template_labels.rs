@@ -0,0 +1,150 @@
+use serde_json::{Map, Value};
+
+use crate::models::{BilingualLayout, RenderOptions};
+use crate::templates::template_amounts::amount_in_words;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Language {
+    Es,
+    En,
+}
+
+impl Language {
+    fn code(self) -> &'static str {
+        match self {
+            Language::Es => "es",
+            Language::En => "en",
+        }
+    }
+
+    /// Idioma de un locale ("en-US" → inglés); `None` si no hay etiquetas para él
+    fn from_locale(locale: &str) -> Option<Self> {
+        match locale.split(['-', '_']).next()?.trim().to_ascii_lowercase().as_str() {
+            "es" => Some(Language::Es),
+            "en" => Some(Language::En),
+            _ => None,
+        }
+    }
+}
+
+/// Etiquetas de los documentos incorporados: clave, español, inglés
+const LABELS: &[(&str, &str, &str)] = &[
+    ("invoice", "FACTURA", "INVOICE"),
+    ("invoice_title", "Factura", "Invoice"),
+    ("invoice_no", "Factura No", "Invoice No"),
+    ("fiscal_invoice", "Factura de Crédito Fiscal Electrónica", "Electronic Tax Credit Invoice"),
+    ("fiscal_invoice_title", "Factura Fiscal Electrónica", "Electronic Tax Invoice"),
+    ("date", "Fecha", "Date"),
+    ("issue_date", "Fecha Emisión", "Issue Date"),
+    ("due_date", "Vencimiento", "Due Date"),
+    ("branch", "Sucursal", "Branch"),
+    ("main_branch", "Principal", "Main"),
+    ("rnc", "RNC", "Tax ID (RNC)"),
+    ("address", "Dirección", "Address"),
+    ("phone", "Tel", "Phone"),
+    ("client", "Cliente", "Client"),
+    ("tax_id", "RNC/ID", "Tax ID"),
+    ("client_name", "Razón Social Cliente", "Client Legal Name"),
+    ("client_tax_id", "RNC/Cédula Cliente", "Client Tax ID"),
+    ("description", "Descripción", "Description"),
+    ("quantity", "Cantidad", "Quantity"),
+    ("unit", "Unidad", "Unit"),
+    ("price", "Precio", "Price"),
+    ("subtotal", "Subtotal", "Subtotal"),
+    ("discount", "Descuento", "Discount"),
+    ("taxes", "Impuestos", "Taxes"),
+    ("itbis", "ITBIS (18%)", "ITBIS (18% VAT)"),
+    ("total", "Total", "Total"),
+    ("notes", "Notas", "Notes"),
+    ("security_code", "Código de Seguridad", "Security Code"),
+    ("signature_date", "Fecha Firma", "Signature Date"),
+    ("payment_method", "Método de pago", "Payment method"),
+    ("payment_terms", "Términos", "Terms"),
+    ("immediate", "Inmediato", "Immediate"),
+    ("paid", "PAGADO", "PAID"),
+    ("valid_until", "Esta factura fiscal electrónica es válida hasta", "This electronic tax invoice is valid until"),
+    ("indefinite", "Indefinido", "Indefinite"),
+    ("keep_document", "Conserve este documento para futuras referencias.", "Keep this document for future reference."),
+    ("thanks", "¡Gracias por su compra!", "Thank you for your purchase!"),
+    ("amount_in_words", "Son", "Amount in words"),
+];
+
+/// Etiquetas de un documento en el idioma de `locale` (español si no hay
+/// etiquetas para él) y, con `secondary_locale`, también en el segundo idioma
+/// según `bilingual_layout`. Así un documento bilingüe sale de la misma plantilla
+#[derive(Debug, Clone)]
+pub struct Labels {
+    primary: Language,
+    secondary: Option<Language>,
+    layout: BilingualLayout,
+}
+
+impl Labels {
+    pub fn for_options(options: &RenderOptions) -> Self {
+        let primary = Language::from_locale(&options.locale).unwrap_or(Language::Es);
+        let secondary = options
+            .secondary_locale
+            .as_deref()
+            .and_then(Language::from_locale)
+            .filter(|language| *language != primary);
+
+        Labels { primary, secondary, layout: options.bilingual_layout }
+    }
+
+    /// Etiqueta como contenido Typst
+    pub fn get(&self, key: &str) -> String {
+        let (first, second) = self.pair(key);
+        self.combine(first, second)
+    }
+
+    /// Monto en letras en el idioma o idiomas del documento, como contenido Typst
+    pub fn amount_in_words(&self, amount: f64, currency: &str) -> String {
+        let first = amount_in_words(amount, currency, self.primary.code());
+        let second = self.secondary.map(|language| amount_in_words(amount, currency, language.code()));
+        self.combine(&first, second.as_deref())
+    }
+
+    fn combine(&self, first: &str, second: Option<&str>) -> String {
+        match (second, self.layout) {
+            (None, _) => first.to_string(),
+            (Some(second), BilingualLayout::SideBySide) => format!("{} / {}", first, second),
+            (Some(second), BilingualLayout::Stacked) => format!(
+                "{} \\ #text(size: 0.8em, weight: \"regular\", fill: rgb(100, 100, 100))[{}]",
+                first, second
+            ),
+        }
+    }
+
+    /// Etiqueta en texto plano, para strings (título del PDF, marca de agua):
+    /// en documentos bilingües siempre "primera / segunda"
+    pub fn plain(&self, key: &str) -> String {
+        match self.pair(key) {
+            (first, None) => first.to_string(),
+            (first, Some(second)) => format!("{} / {}", first, second),
+        }
+    }
+
+    /// Todas las etiquetas como contenido Typst, para las plantillas con fuente
+    pub fn to_json(&self) -> Value {
+        let labels: Map<String, Value> = LABELS
+            .iter()
+            .map(|(key, ..)| (key.to_string(), Value::String(self.get(key))))
+            .collect();
+        Value::Object(labels)
+    }
+
+    /// Textos de la etiqueta; el segundo solo si es bilingüe y difiere del primero
+    fn pair<'a>(&self, key: &'a str) -> (&'a str, Option<&'a str>) {
+        let Some(&(_, es, en)) = LABELS.iter().find(|(k, ..)| *k == key) else {
+            return (key, None);
+        };
+        let text = |language: Language| match language {
+            Language::Es => es,
+            Language::En => en,
+        };
+
+        let first = text(self.primary);
+        let second = self.secondary.map(text).filter(|second| *second != first);
+        (first, second)
+    }
+}
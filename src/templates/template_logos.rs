@@ -0,0 +1,159 @@
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::templates::template_inline_images::{check_inline_images, INLINE_IMAGE_MAX_BYTES};
+
+/// Objetos de los datos con la identidad de la empresa (facturas, cotizaciones,
+/// estados de cuenta y recibos)
+const COMPANY_KEYS: [&str; 2] = ["companyInfo", "vendor"];
+
+/// Logos distintos en caché
+const MAX_CACHED_LOGOS: usize = 500;
+
+/// Un logo que no se pudo descargar se reintenta pasado este tiempo
+const FAILURE_TTL: Duration = Duration::from_secs(300);
+
+struct CachedLogo {
+    /// Data URI del logo; `None` si la descarga falló
+    data_uri: Option<String>,
+    fetched_at: Instant,
+}
+
+/// Descarga los logos de `companyInfo.logoUrl` (solo https), con límite de
+/// tamaño y caché en memoria, y los entrega como data URI en `logoPath` para
+/// que lleguen al compilador como imagen inline. Un logo que no se puede
+/// descargar no falla el documento: la plantilla muestra las iniciales
+pub struct LogoCache {
+    client: reqwest::Client,
+    ttl: Duration,
+    max_bytes: usize,
+    entries: RwLock<HashMap<String, CachedLogo>>,
+}
+
+impl LogoCache {
+    pub fn new(ttl: Duration, max_bytes: usize) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .redirect(reqwest::redirect::Policy::limited(3))
+            .build()
+            .unwrap_or_default();
+
+        LogoCache {
+            client,
+            ttl,
+            max_bytes: max_bytes.min(INLINE_IMAGE_MAX_BYTES),
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// `LOGO_CACHE_TTL_SECS` (por defecto 3600) y `LOGO_MAX_BYTES` (hasta el
+    /// límite de las imágenes inline)
+    pub fn from_env() -> Self {
+        let ttl = std::env::var("LOGO_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3_600);
+        let max_bytes = std::env::var("LOGO_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(INLINE_IMAGE_MAX_BYTES);
+
+        Self::new(Duration::from_secs(ttl), max_bytes)
+    }
+
+    /// Datos con los logos remotos resueltos; `None` si no hay `logoUrl` que
+    /// resolver (sin `logoPath` propio)
+    pub async fn resolve_logos(&self, data: &Value) -> Option<Value> {
+        let pending: Vec<(&str, String)> = COMPANY_KEYS
+            .iter()
+            .filter_map(|key| {
+                let company = data.get(*key)?;
+                let has_path = company.get("logoPath").and_then(Value::as_str).is_some_and(|p| !p.is_empty());
+                let url = company.get("logoUrl").and_then(Value::as_str).filter(|u| !u.is_empty())?;
+                (!has_path).then(|| (*key, url.to_string()))
+            })
+            .collect();
+        if pending.is_empty() {
+            return None;
+        }
+
+        let mut resolved = data.clone();
+        for (key, url) in pending {
+            if let Some(data_uri) = self.get(&url).await {
+                resolved[key]["logoPath"] = Value::String(data_uri);
+            }
+        }
+        Some(resolved)
+    }
+
+    /// Logo de la URL como data URI, de la caché o descargado
+    pub async fn get(&self, url: &str) -> Option<String> {
+        if let Some(cached) = self.entries.read().unwrap().get(url) {
+            let ttl = if cached.data_uri.is_some() { self.ttl } else { FAILURE_TTL };
+            if cached.fetched_at.elapsed() < ttl {
+                return cached.data_uri.clone();
+            }
+        }
+
+        let data_uri = match self.fetch(url).await {
+            Ok(data_uri) => Some(data_uri),
+            Err(e) => {
+                tracing::warn!("Failed to fetch logo {}: {:#}", url, e);
+                None
+            },
+        };
+
+        let mut entries = self.entries.write().unwrap();
+        if entries.len() >= MAX_CACHED_LOGOS && !entries.contains_key(url) {
+            let oldest = entries.iter().min_by_key(|(_, logo)| logo.fetched_at).map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(url.to_string(), CachedLogo { data_uri: data_uri.clone(), fetched_at: Instant::now() });
+        data_uri
+    }
+
+    async fn fetch(&self, url: &str) -> Result<String> {
+        let parsed = reqwest::Url::parse(url).context("Invalid logo URL")?;
+        if parsed.scheme() != "https" {
+            bail!("Logo URLs must use https");
+        }
+
+        let mut response = self.client.get(parsed).send().await?.error_for_status()?;
+        if response.content_length().is_some_and(|len| len as usize > self.max_bytes) {
+            bail!("Logo exceeds {} bytes", self.max_bytes);
+        }
+        let media_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split(';').next().unwrap_or(v).trim().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if bytes.len() + chunk.len() > self.max_bytes {
+                bail!("Logo exceeds {} bytes", self.max_bytes);
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        if !matches!(media_type.as_str(), "image/png" | "image/jpeg" | "image/gif" | "image/svg+xml") {
+            bail!("Unsupported logo content type {:?}", media_type);
+        }
+
+        let data_uri = format!(
+            "data:{};base64,{}",
+            media_type,
+            base64::engine::general_purpose::STANDARD.encode(bytes)
+        );
+        // Mismas reglas que una imagen inline: el contenido debe ser del tipo declarado
+        check_inline_images(&Value::String(data_uri.clone()))?;
+        Ok(data_uri)
+    }
+}
@@ -28,7 +28,11 @@ pub struct CompanyInfo {
     pub phone: Option<String>,
     pub email: Option<String>,
     pub website: Option<String>,
+    /// Asset de la plantilla o data URI de la imagen
     pub logo_path: Option<String>,
+    /// Logo remoto (https); se descarga si no hay `logo_path`
+    #[serde(default)]
+    pub logo_url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,7 +83,12 @@ pub struct InvoiceTotals {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FiscalInfo {
+    /// Vacío: se asigna el siguiente de `series` al generar
+    #[serde(default)]
     pub e_ncf: String,
+    /// Serie de la que se toma el e-NCF cuando no viene (`E31`, `E32`, ...)
+    #[serde(default)]
+    pub series: Option<String>,
     pub security_code: String,
     pub signature_date: String,
     pub qr_data: String,
@@ -114,6 +123,50 @@ pub struct ReportData {
     pub data: Vec<HashMap<String, String>>,
     pub summary: Option<ReportSummary>,
     pub charts: Option<Vec<ChartData>>,
+    /// Esquema opcional: orden de columnas, encabezados y grupos de encabezados
+    #[serde(default)]
+    pub schema: Option<crate::models::ReportSchema>,
+    /// Notas al pie (generales o por columna) debajo de la tabla
+    #[serde(default)]
+    pub footnotes: Option<Vec<crate::models::Footnote>>,
+    /// Formatos condicionales de las celdas (también en `options.conditional_formatting`)
+    #[serde(default)]
+    pub conditional_formatting: Option<Vec<crate::models::ConditionalFormat>>,
+    /// Parte del reporte cuando se genera por partes (lo fija el generador)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<ReportChunk>,
+}
+
+/// Parte de un reporte PDF generado por partes: el encabezado y el resumen van
+/// en la primera, notas, gráficos y pie en la última
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportChunk {
+    pub index: usize,
+    pub count: usize,
+    /// Posición de la primera fila de la parte en el reporte
+    #[serde(default)]
+    pub first_row: usize,
+    /// Sumas acumuladas de las partes anteriores, por campo
+    #[serde(default)]
+    pub carried_in: HashMap<String, f64>,
+    /// Sumas acumuladas hasta el final de esta parte
+    #[serde(default)]
+    pub carried_out: HashMap<String, f64>,
+    /// Caracteres de la celda más ancha de cada campo en todo el reporte, así
+    /// todas las partes tienen los mismos anchos y la misma orientación
+    #[serde(default)]
+    pub column_chars: HashMap<String, usize>,
+}
+
+impl ReportChunk {
+    pub fn is_first(&self) -> bool {
+        self.index == 0
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.index + 1 >= self.count
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -133,8 +186,14 @@ pub struct ReportSummary {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChartData {
+    /// `bar`, `line` o `pie`
     pub chart_type: String,
     pub data_points: Vec<DataPoint>,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Imagen del gráfico; el motor la dibuja si no viene
+    #[serde(default)]
+    pub image_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -165,11 +224,138 @@ pub struct ReceiptItem {
     pub total: f64,
 }
 
+/// Cotización: precios válidos hasta `valid_until`; los ítems opcionales
+/// se listan aparte y no suman al total
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteData {
+    pub quote_number: String,
+    pub issue_date: String,
+    pub valid_until: String,
+    pub company_info: CompanyInfo,
+    pub client_info: ClientInfo,
+    pub items: Vec<QuoteItem>,
+    pub totals: InvoiceTotals,
+    pub terms: Option<String>,
+    pub notes: Option<String>,
+    /// Datos de quien acepta; sin ellos el bloque de firma queda en blanco
+    #[serde(default)]
+    pub acceptance: Option<QuoteAcceptance>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteItem {
+    pub description: String,
+    pub quantity: f64,
+    pub unit_price: f64,
+    pub total: f64,
+    #[serde(default)]
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteAcceptance {
+    pub name: Option<String>,
+    pub title: Option<String>,
+    pub date: Option<String>,
+}
+
+/// Estado de cuenta del cliente: facturas abiertas, pagos del período y
+/// antigüedad de saldos. Sin `aging`, los tramos se calculan por días de
+/// vencimiento de cada factura a `statementDate`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementData {
+    pub statement_number: String,
+    /// Fecha de corte (YYYY-MM-DD)
+    pub statement_date: String,
+    pub period: Option<ReportPeriod>,
+    pub company_info: CompanyInfo,
+    pub client_info: ClientInfo,
+    pub currency: String,
+    #[serde(default)]
+    pub opening_balance: f64,
+    pub invoices: Vec<StatementInvoice>,
+    #[serde(default)]
+    pub payments: Vec<StatementPayment>,
+    #[serde(default)]
+    pub aging: Option<AgingBuckets>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementInvoice {
+    pub invoice_number: String,
+    pub issue_date: String,
+    /// Vencimiento (YYYY-MM-DD); base del tramo de antigüedad
+    pub due_date: String,
+    pub amount: f64,
+    /// Saldo pendiente de la factura
+    pub balance: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementPayment {
+    pub date: String,
+    pub reference: String,
+    pub method: Option<String>,
+    pub amount: f64,
+}
+
+/// Saldo pendiente por días de vencido: 0-30 (incluye lo no vencido), 31-60, 61-90 y más de 90
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgingBuckets {
+    pub current: f64,
+    pub days_31_60: f64,
+    pub days_61_90: f64,
+    pub over_90: f64,
+}
+
+impl AgingBuckets {
+    pub fn total(&self) -> f64 {
+        self.current + self.days_31_60 + self.days_61_90 + self.over_90
+    }
+}
+
+/// Certificado (curso, reconocimiento, participación); con `verificationUrl`
+/// se imprime un QR para verificar su autenticidad
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateData {
+    pub certificate_number: String,
+    /// Título grande, p. ej. "Certificado de Participación"
+    pub title: String,
+    pub recipient_name: String,
+    /// Texto que explica por qué se otorga
+    pub description: String,
+    pub issue_date: String,
+    pub issuer_name: String,
+    pub expiration_date: Option<String>,
+    #[serde(default)]
+    pub signatories: Vec<CertificateSignatory>,
+    pub verification_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateSignatory {
+    pub name: String,
+    pub title: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum TemplateData {
     Invoice(InvoiceData),
     Report(ReportData),
     Receipt(ReceiptData),
+    Quote(QuoteData),
+    Statement(StatementData),
+    Certificate(CertificateData),
     Custom(HashMap<String, serde_json::Value>),
 }
\ No newline at end of file
@@ -0,0 +1,123 @@
+use anyhow::{Context, Result};
+use minijinja::Environment;
+use serde_json::Value;
+
+use crate::models::RenderOptions;
+use crate::templates::template_amounts::amount_in_words;
+use crate::templates::template_sandbox::check_source;
+use crate::templates::template_trait::{utils, TypstTemplate};
+
+/// Entorno minijinja de las plantillas con fuente: los valores se escapan
+/// para Typst salvo que usen `|safe`; `none` y valores indefinidos quedan vacíos.
+/// Filtro `amount_in_words(moneda, locale)`: `{{ totals.total|amount_in_words("DOP") }}`
+/// Función `barcode(valor, tipo, ancho, alto)` (`code128` o `ean13`):
+/// `{{ barcode(invoice.ncf, "code128", "160pt", "36pt") }}`
+pub(crate) fn typst_environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_formatter(|out, _state, value| {
+        if value.is_none() || value.is_undefined() {
+            return Ok(());
+        }
+        if value.is_safe() {
+            write!(out, "{}", value)?;
+        } else {
+            out.write_str(&utils::escape_typst(&value.to_string()))?;
+        }
+        Ok(())
+    });
+    env.add_filter("amount_in_words", |amount: f64, currency: Option<String>, locale: Option<String>| {
+        amount_in_words(amount, currency.as_deref().unwrap_or("DOP"), locale.as_deref().unwrap_or("es"))
+    });
+    env.add_function(
+        "barcode",
+        |value: String, kind: Option<String>, width: Option<String>, height: Option<String>| {
+            utils::barcode_image(
+                &value,
+                kind.as_deref().unwrap_or("code128"),
+                width.as_deref().unwrap_or("160pt"),
+                height.as_deref().unwrap_or("40pt"),
+            )
+            .map(|image| minijinja::Value::from_safe_string(format!("#{}", image)))
+            .map_err(|e| minijinja::Error::new(minijinja::ErrorKind::InvalidOperation, e.to_string()))
+        },
+    );
+    env
+}
+
+/// Plantilla subida por un tenant para reemplazar una incorporada con el
+/// mismo id. El fuente es Typst con marcadores minijinja (`{{ campo }}`,
+/// `{% for %}`); los valores se escapan para Typst salvo que usen `|safe`
+pub struct UploadedTemplate {
+    template_id: String,
+    tenant_id: i64,
+    source: String,
+    version: String,
+    env: Environment<'static>,
+}
+
+impl UploadedTemplate {
+    /// Valida la sintaxis y las restricciones del sandbox antes de aceptarlo
+    pub fn new(tenant_id: i64, template_id: &str, source: String, version: String) -> Result<Self> {
+        check_source(&source)?;
+
+        let env = typst_environment();
+        env.template_from_str(&source)
+            .map_err(|e| anyhow::anyhow!("Invalid template syntax: {}", e))?;
+
+        Ok(UploadedTemplate {
+            template_id: template_id.to_string(),
+            tenant_id,
+            source,
+            version,
+            env,
+        })
+    }
+
+    pub fn tenant_id(&self) -> i64 {
+        self.tenant_id
+    }
+
+}
+
+impl TypstTemplate for UploadedTemplate {
+    fn generate(&self, data: &Value, options: &RenderOptions) -> Result<String> {
+        self.env
+            .render_str(&self.source, utils::with_render_options(data, options))
+            .map(|source| format!("{}{}", utils::watermark_rule(options), source))
+            .with_context(|| format!("Error renderizando plantilla {} del tenant {}", self.template_id, self.tenant_id))
+    }
+
+    fn template_id(&self) -> &str {
+        &self.template_id
+    }
+
+    fn validate(&self, data: &Value) -> Result<()> {
+        if !data.is_object() {
+            anyhow::bail!("Los datos deben ser un objeto JSON");
+        }
+        Ok(())
+    }
+
+    fn description(&self) -> &str {
+        "Plantilla personalizada del tenant"
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn sandboxed(&self) -> bool {
+        true
+    }
+
+    fn source(&self) -> Option<&str> {
+        Some(&self.source)
+    }
+
+    fn referenced_fields(&self) -> Option<Vec<String>> {
+        let template = self.env.template_from_str(&self.source).ok()?;
+        let mut fields: Vec<String> = template.undeclared_variables(true).into_iter().collect();
+        fields.sort();
+        Some(fields)
+    }
+}
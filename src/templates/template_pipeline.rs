@@ -0,0 +1,66 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::templates::template_engine::{CompileDiagnostic, RenderTimings, TemplateEngine};
+
+/// Pedido de render: plantilla (la del tenant si la reemplaza) y sus datos
+#[derive(Debug, Clone)]
+pub struct RenderRequest {
+    pub tenant_id: Option<i64>,
+    pub template_id: String,
+    pub data: Value,
+    /// Nombre base de los temporales; por defecto `{template_id}_{timestamp}`
+    pub output_filename: Option<String>,
+}
+
+impl RenderRequest {
+    pub fn new(tenant_id: Option<i64>, template_id: impl Into<String>, data: Value) -> Self {
+        RenderRequest { tenant_id, template_id: template_id.into(), data, output_filename: None }
+    }
+
+    pub fn with_output_filename(mut self, output_filename: impl Into<String>) -> Self {
+        self.output_filename = Some(output_filename.into());
+        self
+    }
+}
+
+/// PDF generado con su miniatura (si el engine las genera), los tiempos de
+/// cada etapa y las advertencias del compilador
+pub struct GeneratedPdf {
+    pub pdf: Vec<u8>,
+    pub preview_png: Option<Vec<u8>>,
+    pub timings: RenderTimings,
+    pub warnings: Vec<CompileDiagnostic>,
+}
+
+/// Pipeline único de render de PDFs: datos → plantilla → Typst → PDF. Todos
+/// los endpoints pasan por aquí, así comparten la resolución de plantillas,
+/// la validación de datos, los assets, las estadísticas y la limpieza de
+/// temporales
+#[async_trait]
+pub trait RenderPipeline: Send + Sync {
+    async fn render(&self, request: RenderRequest) -> Result<GeneratedPdf>;
+}
+
+#[async_trait]
+impl RenderPipeline for TemplateEngine {
+    async fn render(&self, request: RenderRequest) -> Result<GeneratedPdf> {
+        let rendered = self
+            .generate_pdf_from_json_timed(request.tenant_id, &request.template_id, request.data, request.output_filename)
+            .await?;
+
+        let pdf = tokio::fs::read(&rendered.pdf_path).await;
+        let preview_png = match &rendered.preview_path {
+            Some(path) => tokio::fs::read(path).await.ok(),
+            None => None,
+        };
+
+        let _ = tokio::fs::remove_file(&rendered.pdf_path).await;
+        if let Some(path) = &rendered.preview_path {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+
+        Ok(GeneratedPdf { pdf: pdf?, preview_png, timings: rendered.timings, warnings: rendered.warnings })
+    }
+}
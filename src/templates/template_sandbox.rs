@@ -0,0 +1,52 @@
+use anyhow::{bail, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::time::Duration;
+
+/// Tamaño máximo del fuente de una plantilla subida por un tenant
+pub const MAX_TEMPLATE_SOURCE_BYTES: usize = 256 * 1024;
+
+/// Tiempo máximo de la compilación de prueba al subir una plantilla
+pub const SANDBOX_COMPILE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Proxy inexistente para las compilaciones aisladas: si el fuente arma una
+/// importación de paquete en tiempo de ejecución, la descarga falla
+pub const BLACKHOLE_PROXY: &str = "http://127.0.0.1:9";
+
+/// Funciones de Typst que leen archivos, con su primer argumento literal
+static FILE_ACCESS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"\b(read|json|yaml|toml|csv|xml|cbor|image|import|include)\s*\(?\s*"([^"]*)""#).unwrap()
+});
+
+/// Carga de plugins WebAssembly
+static PLUGIN: Lazy<Regex> = Lazy::new(|| Regex::new(r"\bplugin\s*\(").unwrap());
+
+/// Revisa el fuente de una plantilla de tenant antes de aceptarlo: tamaño,
+/// sin plugins, sin paquetes (`@preview/...` se descargan de la red) y solo
+/// rutas relativas dentro de los assets de la plantilla. La compilación
+/// aislada (raíz propia, sin red) cubre las rutas que se arman en ejecución
+pub fn check_source(source: &str) -> Result<()> {
+    if source.len() > MAX_TEMPLATE_SOURCE_BYTES {
+        bail!("Template source is {} bytes; the limit is {}", source.len(), MAX_TEMPLATE_SOURCE_BYTES);
+    }
+
+    let mut violations = Vec::new();
+    if PLUGIN.is_match(source) {
+        violations.push("plugins are not allowed".to_string());
+    }
+    for capture in FILE_ACCESS.captures_iter(source) {
+        let (function, path) = (&capture[1], &capture[2]);
+        if path.starts_with('@') {
+            violations.push(format!("{}(\"{}\"): packages are not allowed", function, path));
+        } else if path.contains("://") {
+            violations.push(format!("{}(\"{}\"): network access is not allowed", function, path));
+        } else if path.starts_with('/') || path.starts_with('\\') || path.split(['/', '\\']).any(|s| s == "..") {
+            violations.push(format!("{}(\"{}\"): only paths relative to the template assets are allowed", function, path));
+        }
+    }
+
+    if !violations.is_empty() {
+        bail!("Template source rejected: {}", violations.join("; "));
+    }
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use prometheus::{register_histogram_vec, register_int_gauge_vec, HistogramVec, IntGaugeVec};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::RwLock;
+
+static COMPILE_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "template_compile_duration_seconds",
+        "Duración de la generación de PDFs por plantilla y versión",
+        &["template_id", "version"],
+        vec![0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0]
+    )
+    .unwrap()
+});
+
+static REGRESSED: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "template_version_p95_regressed",
+        "1 si el p95 de la versión empeoró respecto a la versión anterior",
+        &["template_id", "version"]
+    )
+    .unwrap()
+});
+
+/// Muestras de compilación que se guardan por versión para los percentiles
+const MAX_SAMPLES: usize = 512;
+
+/// Cuándo una versión nueva se considera más lenta que la anterior
+#[derive(Debug, Clone)]
+pub struct RegressionPolicy {
+    /// p95 nuevo / p95 anterior a partir del cual se marca la versión
+    pub ratio: f64,
+    /// Renders de cada versión antes de comparar
+    pub min_samples: usize,
+}
+
+impl RegressionPolicy {
+    /// `TEMPLATE_P95_REGRESSION_RATIO` (1.5) y `TEMPLATE_REGRESSION_MIN_SAMPLES` (20)
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        RegressionPolicy {
+            ratio: std::env::var("TEMPLATE_P95_REGRESSION_RATIO")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|ratio: &f64| *ratio > 1.0)
+                .unwrap_or(default.ratio),
+            min_samples: std::env::var("TEMPLATE_REGRESSION_MIN_SAMPLES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.min_samples)
+                .max(1),
+        }
+    }
+}
+
+impl Default for RegressionPolicy {
+    fn default() -> Self {
+        RegressionPolicy { ratio: 1.5, min_samples: 20 }
+    }
+}
+
+/// Métricas de uso de una versión de plantilla
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TemplateUsage {
+    pub template_id: String,
+    pub version: String,
+    pub renders: u64,
+    pub failures: u64,
+    pub total_compile_ms: u64,
+    pub avg_compile_ms: f64,
+    /// Percentiles de las últimas compilaciones
+    pub p50_compile_ms: u64,
+    pub p95_compile_ms: u64,
+    pub first_rendered_at: Option<DateTime<Utc>>,
+    pub last_rendered_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Versión anterior contra la que se compara el p95
+    pub baseline_version: Option<String>,
+    pub baseline_p95_compile_ms: Option<u64>,
+    /// El p95 superó al de la versión anterior por más del umbral
+    pub regressed: bool,
+    #[serde(skip)]
+    samples: VecDeque<u64>,
+}
+
+/// Estadísticas de renderizado por plantilla y versión
+#[derive(Default)]
+pub struct TemplateStats {
+    usage: RwLock<HashMap<(String, String), TemplateUsage>>,
+    policy: RegressionPolicy,
+}
+
+impl TemplateStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_policy(policy: RegressionPolicy) -> Self {
+        TemplateStats { policy, ..Self::default() }
+    }
+
+    pub fn record_success(&self, template_id: &str, version: &str, compile_ms: u64) {
+        COMPILE_SECONDS
+            .with_label_values(&[template_id, version])
+            .observe(compile_ms as f64 / 1000.0);
+
+        let mut usage = self.usage.write().unwrap();
+        let entry = Self::entry(&mut usage, template_id, version);
+        let now = Utc::now();
+        entry.renders += 1;
+        entry.total_compile_ms += compile_ms;
+        entry.avg_compile_ms = entry.total_compile_ms as f64 / entry.renders as f64;
+        entry.first_rendered_at.get_or_insert(now);
+        entry.last_rendered_at = Some(now);
+        if entry.samples.len() == MAX_SAMPLES {
+            entry.samples.pop_front();
+        }
+        entry.samples.push_back(compile_ms);
+        entry.p50_compile_ms = percentile(&entry.samples, 0.50);
+        entry.p95_compile_ms = percentile(&entry.samples, 0.95);
+
+        self.check_regression(&mut usage, template_id, version);
+    }
+
+    pub fn record_failure(&self, template_id: &str, version: &str, error: &str) {
+        let mut usage = self.usage.write().unwrap();
+        let entry = Self::entry(&mut usage, template_id, version);
+        entry.failures += 1;
+        entry.last_error = Some(error.chars().take(500).collect());
+    }
+
+    /// Métricas de todas las versiones de una plantilla
+    pub fn for_template(&self, template_id: &str) -> Vec<TemplateUsage> {
+        let mut versions: Vec<TemplateUsage> = self.usage
+            .read()
+            .unwrap()
+            .values()
+            .filter(|u| u.template_id == template_id)
+            .cloned()
+            .collect();
+        versions.sort_by(|a, b| a.version.cmp(&b.version));
+        versions
+    }
+
+    /// Compara el p95 de la versión con el de la versión que se usaba antes
+    /// (la última que empezó a renderizarse antes que ella, con suficientes muestras)
+    fn check_regression(&self, usage: &mut HashMap<(String, String), TemplateUsage>, template_id: &str, version: &str) {
+        let key = (template_id.to_string(), version.to_string());
+        let Some(current) = usage.get(&key) else {
+            return;
+        };
+        if current.samples.len() < self.policy.min_samples {
+            return;
+        }
+
+        let baseline = usage
+            .values()
+            .filter(|u| u.template_id == template_id && u.version != version)
+            .filter(|u| u.samples.len() >= self.policy.min_samples)
+            .filter(|u| u.first_rendered_at < current.first_rendered_at)
+            .max_by_key(|u| u.first_rendered_at)
+            .map(|u| (u.version.clone(), u.p95_compile_ms));
+
+        let Some(entry) = usage.get_mut(&key) else {
+            return;
+        };
+        let regressed = baseline
+            .as_ref()
+            .is_some_and(|(_, p95)| entry.p95_compile_ms as f64 > *p95 as f64 * self.policy.ratio);
+
+        if regressed && !entry.regressed {
+            tracing::warn!(
+                "Template {} version {} p95 regressed: {}ms vs {}ms in version {}",
+                template_id,
+                version,
+                entry.p95_compile_ms,
+                baseline.as_ref().map(|(_, p95)| *p95).unwrap_or_default(),
+                baseline.as_ref().map(|(v, _)| v.as_str()).unwrap_or_default()
+            );
+        }
+
+        entry.regressed = regressed;
+        entry.baseline_p95_compile_ms = baseline.as_ref().map(|(_, p95)| *p95);
+        entry.baseline_version = baseline.map(|(v, _)| v);
+        REGRESSED
+            .with_label_values(&[template_id, version])
+            .set(regressed as i64);
+    }
+
+    fn entry<'a>(
+        usage: &'a mut HashMap<(String, String), TemplateUsage>,
+        template_id: &str,
+        version: &str,
+    ) -> &'a mut TemplateUsage {
+        usage
+            .entry((template_id.to_string(), version.to_string()))
+            .or_insert_with(|| TemplateUsage {
+                template_id: template_id.to_string(),
+                version: version.to_string(),
+                ..Default::default()
+            })
+    }
+}
+
+/// Percentil por rango más cercano
+fn percentile(samples: &VecDeque<u64>, p: f64) -> u64 {
+    if samples.is_empty() {
+        return 0;
+    }
+    let mut sorted: Vec<u64> = samples.iter().copied().collect();
+    sorted.sort_unstable();
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
@@ -1,12 +1,17 @@
 use anyhow::Result;
 use serde_json::Value;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+
+use crate::models::RenderOptions;
+use crate::templates::template_labels::Labels;
+use crate::templates::template_access::TemplateAccess;
 
 /// Trait base para todas las plantillas de documentos
 pub trait TypstTemplate: Send + Sync {
-    /// Genera el contenido Typst a partir de los datos JSON
-    fn generate(&self, data: &Value) -> Result<String>;
+    /// Genera el contenido Typst a partir de los datos JSON y las opciones de
+    /// render (tamaño y orientación de página, idioma, marca de agua, QR)
+    fn generate(&self, data: &Value, options: &RenderOptions) -> Result<String>;
 
     /// Retorna el ID único de la plantilla
     fn template_id(&self) -> &str;
@@ -18,11 +23,40 @@ pub trait TypstTemplate: Send + Sync {
     fn description(&self) -> &str {
         "Template de documento"
     }
+
+    /// Versión de la plantilla (para métricas por versión)
+    fn version(&self) -> &str {
+        "1.0"
+    }
+
+    /// Rutas de datos que lee la plantilla (`companyInfo.name`), si se pueden
+    /// obtener del fuente; las plantillas en Rust no las exponen
+    fn referenced_fields(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Fuente de terceros (plantillas de tenants): se compila en un directorio
+    /// propio como raíz y sin acceso a la red
+    fn sandboxed(&self) -> bool {
+        false
+    }
+
+    /// Fuente editable de la plantilla; las plantillas en Rust no lo tienen
+    fn source(&self) -> Option<&str> {
+        None
+    }
 }
 
 /// Registry central de todas las plantillas disponibles
 pub struct TemplateRegistry {
     templates: HashMap<String, Arc<dyn TypstTemplate>>,
+    /// Plantillas globales en disco (`{templates_dir}/{id}.typ`); reemplazan a
+    /// las incorporadas con el mismo id y se recargan al editarse
+    files: RwLock<HashMap<String, Arc<dyn TypstTemplate>>>,
+    /// Reemplazos por tenant de plantillas, por (tenant, id)
+    overrides: RwLock<HashMap<(i64, String), Arc<dyn TypstTemplate>>>,
+    /// Plantillas globales privadas de ciertos tenants o planes
+    access: TemplateAccess,
 }
 
 impl TemplateRegistry {
@@ -48,25 +82,124 @@ impl TemplateRegistry {
         let report = Arc::new(ReportTemplate::new());
         templates.insert(report.template_id().to_string(), report);
 
-        Self { templates }
+        // Cotización
+        let quote = Arc::new(QuoteTemplate::new());
+        templates.insert(quote.template_id().to_string(), quote);
+
+        // Estado de cuenta
+        let statement = Arc::new(StatementTemplate::new());
+        templates.insert(statement.template_id().to_string(), statement);
+
+        // Certificado
+        let certificate = Arc::new(CertificateTemplate::new());
+        templates.insert(certificate.template_id().to_string(), certificate);
+
+        // Formatos DGII 606/607
+        let dgii = Arc::new(DgiiReportTemplate::new());
+        templates.insert(dgii.template_id().to_string(), dgii);
+
+        Self {
+            templates,
+            files: RwLock::new(HashMap::new()),
+            overrides: RwLock::new(HashMap::new()),
+            access: TemplateAccess::default(),
+        }
     }
 
-    /// Obtiene una plantilla por su ID
+    /// Obtiene una plantilla global por su ID (la de disco antes que la incorporada)
     pub fn get(&self, template_id: &str) -> Option<Arc<dyn TypstTemplate>> {
-        self.templates.get(template_id).cloned()
+        self.files
+            .read()
+            .unwrap()
+            .get(template_id)
+            .cloned()
+            .or_else(|| self.templates.get(template_id).cloned())
+    }
+
+    /// Reemplaza de una vez todas las plantillas en disco (recarga del directorio)
+    pub fn replace_files(&self, templates: Vec<Arc<dyn TypstTemplate>>) {
+        let files = templates
+            .into_iter()
+            .map(|template| (template.template_id().to_string(), template))
+            .collect();
+        *self.files.write().unwrap() = files;
+    }
+
+    /// Resuelve una plantilla con precedencia tenant → global; una global
+    /// privada a la que el tenant no tiene acceso no existe para él
+    pub fn resolve(&self, tenant_id: Option<i64>, template_id: &str) -> Option<Arc<dyn TypstTemplate>> {
+        tenant_id
+            .and_then(|tenant| {
+                self.overrides
+                    .read()
+                    .unwrap()
+                    .get(&(tenant, template_id.to_string()))
+                    .cloned()
+            })
+            .or_else(|| self.get(template_id).filter(|_| self.access.allows(tenant_id, template_id)))
+    }
+
+    /// Restricciones de acceso a las plantillas globales
+    pub fn access(&self) -> &TemplateAccess {
+        &self.access
+    }
+
+    /// Registra (o reemplaza) la versión de un tenant para un id de plantilla
+    pub fn set_override(&self, tenant_id: i64, template: Arc<dyn TypstTemplate>) {
+        let key = (tenant_id, template.template_id().to_string());
+        self.overrides.write().unwrap().insert(key, template);
+    }
+
+    /// Versión del tenant de un id de plantilla, sin caer en la global
+    pub fn get_override(&self, tenant_id: i64, template_id: &str) -> Option<Arc<dyn TypstTemplate>> {
+        self.overrides
+            .read()
+            .unwrap()
+            .get(&(tenant_id, template_id.to_string()))
+            .cloned()
+    }
+
+    /// Quita el reemplazo del tenant; retorna false si no existía
+    pub fn remove_override(&self, tenant_id: i64, template_id: &str) -> bool {
+        self.overrides
+            .write()
+            .unwrap()
+            .remove(&(tenant_id, template_id.to_string()))
+            .is_some()
+    }
+
+    /// Ids de plantilla que el tenant reemplaza
+    pub fn overrides_for(&self, tenant_id: i64) -> Vec<String> {
+        let mut ids: Vec<String> = self.overrides
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|(tenant, _)| *tenant == tenant_id)
+            .map(|(_, id)| id.clone())
+            .collect();
+        ids.sort();
+        ids
     }
 
     /// Lista todas las plantillas disponibles
     pub fn list(&self) -> Vec<(String, String)> {
-        self.templates
+        let files = self.files.read().unwrap();
+        let mut list: Vec<(String, String)> = files
             .iter()
             .map(|(id, template)| (id.clone(), template.description().to_string()))
-            .collect()
+            .collect();
+        list.extend(
+            self.templates
+                .iter()
+                .filter(|(id, _)| !files.contains_key(*id))
+                .map(|(id, template)| (id.clone(), template.description().to_string())),
+        );
+        list
     }
 
     /// Valida si existe una plantilla con el ID dado
     pub fn exists(&self, template_id: &str) -> bool {
-        self.templates.contains_key(template_id)
+        self.templates.contains_key(template_id) || self.files.read().unwrap().contains_key(template_id)
     }
 }
 
@@ -79,6 +212,163 @@ impl Default for TemplateRegistry {
 // Utilidades compartidas para generar elementos Typst
 pub mod utils {
     use super::*;
+    use std::collections::HashMap;
+
+    /// Clave de los datos de la plantilla con los `tags` y `customFields` del
+    /// request (en plantillas de tenant: `{{ documentMetadata.customFields.vendedor }}`)
+    pub const DOCUMENT_METADATA_KEY: &str = "documentMetadata";
+
+    /// Agrega los tags y campos propios del request a los datos de la
+    /// plantilla; no pisa un `documentMetadata` que ya venga en los datos
+    pub fn insert_document_metadata(
+        data: &mut Value,
+        tags: Option<&HashMap<String, String>>,
+        custom_fields: Option<&HashMap<String, Value>>,
+    ) {
+        if tags.is_none() && custom_fields.is_none() {
+            return;
+        }
+        if let Some(object) = data.as_object_mut() {
+            object.entry(DOCUMENT_METADATA_KEY).or_insert_with(|| serde_json::json!({
+                "tags": tags.cloned().unwrap_or_default(),
+                "customFields": custom_fields.cloned().unwrap_or_default(),
+            }));
+        }
+    }
+
+    /// Bloque Typst con los `customFields` del request (`*clave:* valor`, en
+    /// orden alfabético); vacío si no hay
+    pub fn custom_fields_block(data: &Value, size: &str) -> String {
+        let Some(fields) = data
+            .get(DOCUMENT_METADATA_KEY)
+            .and_then(|m| m.get("customFields"))
+            .and_then(Value::as_object)
+            .filter(|fields| !fields.is_empty())
+        else {
+            return String::new();
+        };
+
+        let mut entries: Vec<(&String, &Value)> = fields.iter().collect();
+        entries.sort_by_key(|(key, _)| key.as_str());
+        let cells = entries
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                format!("[*{}:*], [{}]", escape_typst(key), escape_typst(&value))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "\n#v(10pt)\n#text(size: {})[#grid(columns: 2, column-gutter: 8pt, row-gutter: 4pt, {})]",
+            size, cells
+        )
+    }
+
+    /// Reglas `#set page` y `#set text(lang, region)` de las opciones de
+    /// render. `paper` y `flipped` son los de la plantilla y se usan si las
+    /// opciones no fijan tamaño u orientación; `page_args` agrega el resto
+    /// (márgenes, numeración)
+    pub fn page_setup(options: &RenderOptions, paper: &str, flipped: bool, page_args: &str) -> String {
+        use crate::models::{Orientation, PageSize};
+
+        let size = match &options.page_size {
+            None => format!("paper: \"{}\"", paper),
+            Some(PageSize::A4) => "paper: \"a4\"".to_string(),
+            Some(PageSize::Letter) => "paper: \"us-letter\"".to_string(),
+            Some(PageSize::Legal) => "paper: \"us-legal\"".to_string(),
+            Some(PageSize::A3) => "paper: \"a3\"".to_string(),
+            Some(PageSize::Custom { width, height }) => format!("width: {}mm, height: {}mm", width, height),
+        };
+        let flipped = match &options.orientation {
+            None => flipped,
+            Some(Orientation::Portrait) => false,
+            Some(Orientation::Landscape) => true,
+        };
+
+        let mut page = format!("#set page({}, flipped: {}, {}", size, flipped, page_args);
+        if let Some(overlay) = watermark_overlay(options) {
+            page.push_str(&format!(", foreground: {}", overlay));
+        }
+        page.push(')');
+
+        // "es-DO" → lang: "es", region: "DO"; un locale mal formado se ignora
+        let mut parts = options.locale.split(['-', '_']);
+        let lang = parts.next().filter(|l| (2..=3).contains(&l.len()) && l.chars().all(|c| c.is_ascii_alphabetic()));
+        let region = parts.next().filter(|r| r.len() == 2 && r.chars().all(|c| c.is_ascii_alphabetic()));
+        match (lang, region) {
+            (Some(lang), Some(region)) => format!(
+                "{}\n#set text(lang: \"{}\", region: \"{}\")",
+                page, lang.to_ascii_lowercase(), region.to_ascii_uppercase()
+            ),
+            (Some(lang), None) => format!("{}\n#set text(lang: \"{}\")", page, lang.to_ascii_lowercase()),
+            _ => page,
+        }
+    }
+
+    /// Marca de agua de las opciones (`BORRADOR`, `COPIA`, `ANULADA`): texto
+    /// rotado y translúcido sobre el contenido de cada página, con el tamaño
+    /// ajustado al largo del texto. `None` sin marca
+    pub fn watermark_overlay(options: &RenderOptions) -> Option<String> {
+        let text = options.watermark.as_deref().map(str::trim).filter(|w| !w.is_empty())?;
+        let size = (720 / text.chars().count().max(1)).clamp(36, 120);
+        Some(format!(
+            "place(center + horizon, rotate(-45deg, text(size: {}pt, weight: \"bold\", fill: rgb(150, 150, 150, 70), {})))",
+            size,
+            typst_string(text)
+        ))
+    }
+
+    /// Regla `#set page` con solo la marca de agua, para anteponer al fuente
+    /// de plantillas que fijan su propia página; vacía sin marca
+    pub fn watermark_rule(options: &RenderOptions) -> String {
+        watermark_overlay(options)
+            .map(|overlay| format!("#set page(foreground: {})\n", overlay))
+            .unwrap_or_default()
+    }
+
+    /// Si la plantilla muestra su código QR: lo que pidan las opciones o el
+    /// valor por defecto de la plantilla
+    pub fn include_qr(options: &RenderOptions, default: bool) -> bool {
+        options.include_qr.unwrap_or(default)
+    }
+
+    /// Opciones de render para las plantillas con fuente (minijinja), en
+    /// `renderOptions`: `locale`, `secondaryLocale`, `watermark`, `pageSize`,
+    /// `orientation`, `includeQr`, `pageSetup` (reglas de página listas para
+    /// `|safe`) y `labels` (etiquetas en el idioma o idiomas del documento).
+    /// Las contraseñas del PDF no se exponen
+    pub fn with_render_options(data: &Value, options: &RenderOptions) -> Value {
+        let mut context = data.clone();
+        if let Some(object) = context.as_object_mut() {
+            object.insert("renderOptions".to_string(), serde_json::json!({
+                "locale": options.locale,
+                "secondaryLocale": options.secondary_locale,
+                "watermark": options.watermark,
+                "pageSize": options.page_size,
+                "orientation": options.orientation,
+                "includeQr": options.include_qr,
+                "pageSetup": page_setup(options, "us-letter", false, "margin: 2cm"),
+                "labels": Labels::for_options(options).to_json(),
+            }));
+        }
+        context
+    }
+
+    /// `image(...)` del logo de la empresa (asset de la plantilla o imagen
+    /// inline ya extraída de los datos); `None` sin logo
+    pub fn logo_image(logo_path: Option<&str>, size: &str) -> Option<String> {
+        let path = logo_path.map(str::trim).filter(|p| !p.is_empty())?;
+        Some(format!("image({}, {}, fit: \"contain\")", typst_string(path), size))
+    }
+
+    /// Literal de string de Typst (`"..."`) con comillas y barras escapadas
+    pub fn typst_string(text: &str) -> String {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    }
 
     /// Escapa caracteres especiales para Typst
     pub fn escape_typst(text: &str) -> String {
@@ -106,36 +396,110 @@ pub mod utils {
             .collect()
     }
 
-    /// Genera código QR y retorna la ruta del archivo
-    pub fn generate_qr_code(data: &str, output_path: &str) -> Result<String> {
+    /// Código QR como expresión Typst `image(...)` con el SVG embebido en el
+    /// fuente: sin archivos temporales, así las generaciones concurrentes no
+    /// se pisan ni quedan PNGs en disco. `size` es el lado (p. ej. `100pt`)
+    pub fn qr_code_image(data: &str, size: &str) -> Result<String> {
         use qrcode::{QrCode, Color};
-        use image::{ImageBuffer, Rgb};
+
+        // Margen de 2 módulos alrededor del código
+        const QUIET_ZONE: usize = 2;
 
         let code = QrCode::new(data)?;
         let width = code.width();
-        let scale = 5;
-        let img_size = width * scale;
-
-        let mut image = ImageBuffer::<Rgb<u8>, Vec<u8>>::new(img_size as u32, img_size as u32);
+        let side = width + 2 * QUIET_ZONE;
 
+        let mut path = String::new();
         for y in 0..width {
             for x in 0..width {
-                let color = match code[(x, y)] {
-                    Color::Dark => Rgb([0, 0, 0]),
-                    Color::Light => Rgb([255, 255, 255]),
-                };
+                if matches!(code[(x, y)], Color::Dark) {
+                    path.push_str(&format!("M{} {}h1v1h-1z", x + QUIET_ZONE, y + QUIET_ZONE));
+                }
+            }
+        }
+
+        // Atributos con comillas simples: el SVG va dentro de un string de Typst
+        let svg = format!(
+            "<svg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 {0} {0}' shape-rendering='crispEdges'>\
+             <rect width='{0}' height='{0}' fill='#ffffff'/><path d='{1}' fill='#000000'/></svg>",
+            side, path
+        );
+
+        Ok(format!(
+            "image(bytes(\"{}\"), format: \"svg\", width: {}, height: {})",
+            svg, size, size
+        ))
+    }
+
+    /// Código de barras lineal como expresión Typst `image(...)` con el SVG
+    /// embebido, igual que `qr_code_image`. `symbology` es `code128` (texto
+    /// ASCII, p. ej. un NCF; solo dígitos en cantidad par usa el juego C, más
+    /// compacto) o `ean13` (12 dígitos, o 13 con el dígito verificador correcto)
+    pub fn barcode_image(data: &str, symbology: &str, width: &str, height: &str) -> Result<String> {
+        use barcoders::sym::code128::Code128;
+        use barcoders::sym::ean13::EAN13;
 
-                for dy in 0..scale {
-                    for dx in 0..scale {
-                        let px = (x * scale + dx) as u32;
-                        let py = (y * scale + dy) as u32;
-                        image.put_pixel(px, py, color);
-                    }
+        // Margen en blanco a cada lado, en módulos
+        const QUIET_ZONE: usize = 10;
+
+        let bars = match symbology.to_ascii_lowercase().as_str() {
+            "code128" => {
+                if data.is_empty() || !data.chars().all(|c| (' '..='~').contains(&c)) {
+                    anyhow::bail!("Code128 requires printable ASCII text, got {:?}", data);
+                }
+                let charset = if data.len().is_multiple_of(2) && data.bytes().all(|b| b.is_ascii_digit()) { 'Ć' } else { 'Ɓ' };
+                Code128::new(format!("{}{}", charset, data))
+                    .map_err(|e| anyhow::anyhow!("Invalid Code128 data {:?}: {}", data, e))?
+                    .encode()
+            },
+            "ean13" => {
+                if !matches!(data.len(), 12 | 13) || !data.bytes().all(|b| b.is_ascii_digit()) {
+                    anyhow::bail!("EAN-13 requires 12 or 13 digits, got {:?}", data);
+                }
+                let code = EAN13::new(&data[..12])
+                    .map_err(|e| anyhow::anyhow!("Invalid EAN-13 data {:?}: {}", data, e))?;
+                if data.len() == 13 && !data.ends_with(char::from(b'0' + ean13_check_digit(&data[..12]))) {
+                    anyhow::bail!("Invalid EAN-13 check digit in {:?}", data);
+                }
+                code.encode()
+            },
+            other => anyhow::bail!("Unsupported barcode type: {} (expected code128 or ean13)", other),
+        };
+
+        let mut path = String::new();
+        let mut x = 0;
+        while x < bars.len() {
+            if bars[x] == 1 {
+                let start = x;
+                while x < bars.len() && bars[x] == 1 {
+                    x += 1;
                 }
+                path.push_str(&format!("M{} 0h{}v1h-{}z", start + QUIET_ZONE, x - start, x - start));
+            } else {
+                x += 1;
             }
         }
 
-        image.save(output_path)?;
-        Ok(output_path.to_string())
+        // Alto de 1 unidad estirado al `height` pedido
+        let svg = format!(
+            "<svg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 {0} 1' preserveAspectRatio='none' shape-rendering='crispEdges'>\
+             <rect width='{0}' height='1' fill='#ffffff'/><path d='{1}' fill='#000000'/></svg>",
+            bars.len() + 2 * QUIET_ZONE, path
+        );
+
+        Ok(format!(
+            "image(bytes(\"{}\"), format: \"svg\", width: {}, height: {}, fit: \"stretch\")",
+            svg, width, height
+        ))
     }
-}
\ No newline at end of file
+
+    /// Dígito verificador EAN-13 de los primeros 12 dígitos
+    fn ean13_check_digit(digits: &str) -> u8 {
+        let sum: u32 = digits
+            .bytes()
+            .enumerate()
+            .map(|(i, b)| u32::from(b - b'0') * if i % 2 == 0 { 1 } else { 3 })
+            .sum();
+        ((10 - sum % 10) % 10) as u8
+    }
+}
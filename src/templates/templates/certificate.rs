@@ -0,0 +1,159 @@
+use anyhow::{Result, Context};
+use serde_json::Value;
+use crate::models::RenderOptions;
+use crate::templates::template_trait::{TypstTemplate, utils};
+use crate::templates::template_models::{CertificateData, CertificateSignatory};
+
+#[derive(Default)]
+pub struct CertificateTemplate;
+
+impl CertificateTemplate {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Líneas de firma, una columna por firmante
+    fn format_signatories(&self, signatories: &[CertificateSignatory]) -> String {
+        if signatories.is_empty() {
+            return String::new();
+        }
+
+        let columns = vec!["1fr"; signatories.len()].join(", ");
+        let cells = signatories
+            .iter()
+            .map(|signatory| {
+                format!(
+                    r#"  [
+    #line(length: 80%, stroke: 0.5pt)
+    #text(weight: "bold")[{}] \
+    #text(size: 9pt)[{}]
+  ]"#,
+                    utils::escape_typst(&signatory.name),
+                    utils::escape_typst(signatory.title.as_deref().unwrap_or(""))
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        format!("#grid(\n  columns: ({},),\n  gutter: 30pt,\n  align: center,\n{}\n)", columns, cells)
+    }
+
+    /// QR con el enlace de verificación en la esquina inferior (solo el enlace
+    /// si `include_qr` es falso); vacío sin enlace
+    fn format_verification(&self, certificate: &CertificateData, include_qr: bool) -> Result<String> {
+        let Some(url) = &certificate.verification_url else {
+            return Ok(String::new());
+        };
+
+        let qr_image = if include_qr {
+            format!("#{}", utils::qr_code_image(url, "60pt")?)
+        } else {
+            String::new()
+        };
+
+        Ok(format!(r#"#place(bottom + right, dx: -10pt, dy: -10pt)[
+  #align(center)[
+    {}
+    #text(size: 7pt)[Verificar: {}]
+  ]
+]"#,
+            qr_image,
+            utils::escape_typst(url)
+        ))
+    }
+}
+
+impl TypstTemplate for CertificateTemplate {
+    fn generate(&self, data: &Value, options: &RenderOptions) -> Result<String> {
+        let certificate: CertificateData = serde_json::from_value(data.clone())
+            .context("Error deserializando datos de certificado")?;
+
+        let page_setup = utils::page_setup(options, "us-letter", true, "margin: 1.5cm");
+
+        let content = format!(r#"#set document(title: "{} - {}", author: "{}")
+{page_setup}
+#set text(font: "Arial", size: 12pt)
+
+// Borde decorativo
+#place(top + left, dx: -0.5cm, dy: -0.5cm)[
+  #rect(width: 100% + 1cm, height: 100% + 1cm, stroke: 3pt + rgb(31, 56, 100))
+]
+#place(top + left, dx: -0.25cm, dy: -0.25cm)[
+  #rect(width: 100% + 0.5cm, height: 100% + 0.5cm, stroke: 0.75pt + rgb(191, 144, 0))
+]
+
+#v(25pt)
+#align(center)[
+  #text(size: 14pt, fill: rgb(31, 56, 100))[{}]
+
+  #v(15pt)
+  #text(size: 30pt, weight: "bold", fill: rgb(31, 56, 100))[{}]
+
+  #v(20pt)
+  #text(size: 12pt)[Se otorga a]
+
+  #v(10pt)
+  #text(size: 26pt, style: "italic")[{}]
+  #line(length: 60%, stroke: 0.5pt + rgb(191, 144, 0))
+
+  #v(10pt)
+  #block(width: 75%)[#text(size: 12pt)[{}]]
+
+  #v(15pt)
+  #text(size: 10pt)[Emitido el {}{}]
+]
+
+#v(1fr)
+{}
+
+#align(center)[#text(size: 8pt, fill: gray)[Certificado No. {}]]
+{}"#,
+            // Metadata
+            certificate.title,
+            certificate.recipient_name,
+            certificate.issuer_name,
+            // Body
+            utils::escape_typst(&certificate.issuer_name),
+            utils::escape_typst(&certificate.title),
+            utils::escape_typst(&certificate.recipient_name),
+            utils::escape_typst(&certificate.description),
+            certificate.issue_date,
+            match &certificate.expiration_date {
+                Some(date) => format!(" · Válido hasta el {}", date),
+                None => String::new(),
+            },
+            // Signatures
+            self.format_signatories(&certificate.signatories),
+            // Footer
+            utils::escape_typst(&certificate.certificate_number),
+            self.format_verification(&certificate, utils::include_qr(options, true))?
+        );
+
+        Ok(content)
+    }
+
+    fn template_id(&self) -> &str {
+        "certificate"
+    }
+
+    fn validate(&self, data: &Value) -> Result<()> {
+        if !data.is_object() {
+            anyhow::bail!("Los datos deben ser un objeto JSON");
+        }
+
+        let obj = data.as_object().unwrap();
+        let required = vec!["certificateNumber", "title", "recipientName", "description", "issueDate", "issuerName"];
+
+        for field in required {
+            if !obj.contains_key(field) {
+                anyhow::bail!("Campo requerido faltante: {}", field);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn description(&self) -> &str {
+        "Certificado"
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use serde_json::Value;
+use crate::generators::dgii::{ColumnKind, DgiiCell, DgiiFormat, DgiiReport};
+use crate::models::RenderOptions;
+use crate::templates::template_trait::{TypstTemplate, utils};
+
+/// Columnas del listado en PDF (el formato completo tiene 23 y no cabe)
+const LISTING_606: &[&str] = &["rnc_cedula", "ncf", "fecha_comprobante", "total_monto_facturado", "itbis_facturado", "itbis_retenido", "monto_retencion_renta"];
+const LISTING_607: &[&str] = &["rnc_cedula", "ncf", "fecha_comprobante", "monto_facturado", "itbis_facturado", "itbis_retenido_terceros", "retencion_renta_terceros"];
+
+/// Resumen imprimible de un formato 606/607 para revisión antes del envío:
+/// totales y un listado con las columnas principales
+#[derive(Default)]
+pub struct DgiiReportTemplate;
+
+impl DgiiReportTemplate {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn listing_columns(format: DgiiFormat) -> &'static [&'static str] {
+        match format {
+            DgiiFormat::F606 => LISTING_606,
+            DgiiFormat::F607 => LISTING_607,
+        }
+    }
+
+    fn format_rows(&self, report: &DgiiReport) -> String {
+        let columns = report.format.columns();
+        let indexes: Vec<usize> = Self::listing_columns(report.format)
+            .iter()
+            .filter_map(|key| columns.iter().position(|c| c.key == *key))
+            .collect();
+
+        report.rows
+            .iter()
+            .map(|row| {
+                let cells: Vec<String> = indexes
+                    .iter()
+                    .map(|&i| match &row[i] {
+                        DgiiCell::Text(text) => format!("[{}]", utils::escape_typst(text)),
+                        DgiiCell::Amount(amount) => format!("[{:.2}]", amount),
+                        DgiiCell::Empty => "[]".to_string(),
+                    })
+                    .collect();
+                format!("  {}", cells.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join(",\n")
+    }
+
+    fn format_headers(&self, format: DgiiFormat) -> String {
+        let columns = format.columns();
+        Self::listing_columns(format)
+            .iter()
+            .filter_map(|key| columns.iter().find(|c| c.key == *key))
+            .map(|c| format!("[*{}*]", utils::escape_typst(c.header)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn format_totals(&self, report: &DgiiReport) -> String {
+        let columns = report.format.columns();
+        Self::listing_columns(report.format)
+            .iter()
+            .filter(|key| columns.iter().any(|c| c.key == **key && c.kind == ColumnKind::Amount))
+            .map(|key| {
+                let header = columns.iter().find(|c| c.key == *key).map(|c| c.header).unwrap_or(key);
+                format!("    [{}:], [{:.2}],", utils::escape_typst(header), report.total(key))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl TypstTemplate for DgiiReportTemplate {
+    fn generate(&self, data: &Value, options: &RenderOptions) -> Result<String> {
+        let report = DgiiReport::from_data(data)?;
+        let columns = Self::listing_columns(report.format).len();
+
+        let page_setup = utils::page_setup(options, "us-letter", true, "margin: 1.5cm");
+
+        let content = format!(r#"#set document(title: "Formato {} - {} - {}")
+{page_setup}
+#set text(font: "Arial", size: 8pt)
+
+#align(center)[
+  #text(size: 14pt, weight: "bold")[Formato {}] \
+  #text(size: 11pt)[{}]
+]
+
+#v(10pt)
+#grid(
+  columns: (1fr, 1fr, 1fr),
+  [#text(weight: "bold")[RNC:] {}],
+  [#text(weight: "bold")[Período:] {}],
+  [#align(right)[#text(weight: "bold")[Registros:] {}]]
+)
+
+#v(10pt)
+#align(right)[
+  #grid(
+    columns: (auto, 100pt),
+    row-gutter: 3pt,
+    align: (right, right),
+{}
+  )
+]
+
+#v(10pt)
+#table(
+  columns: ({}),
+  stroke: 0.5pt + gray,
+  fill: (x, y) => if y == 0 {{ rgb(230, 230, 230) }} else {{ white }},
+  align: (col, row) => if col < 3 {{ left }} else {{ right }},
+  inset: 5pt,
+  {},
+{}
+)
+"#,
+            report.format.code(), report.rnc, report.period,
+            report.format.code(),
+            report.format.title(),
+            report.rnc,
+            report.period,
+            report.rows.len(),
+            self.format_totals(&report),
+            vec!["1fr"; columns].join(", "),
+            self.format_headers(report.format),
+            self.format_rows(&report)
+        );
+
+        Ok(content)
+    }
+
+    fn template_id(&self) -> &str {
+        "dgii"
+    }
+
+    fn validate(&self, data: &Value) -> Result<()> {
+        if !data.is_object() {
+            anyhow::bail!("Los datos deben ser un objeto JSON");
+        }
+
+        let obj = data.as_object().unwrap();
+        for field in ["format", "rnc", "period"] {
+            if !obj.contains_key(field) {
+                anyhow::bail!("Campo requerido faltante: {}", field);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn description(&self) -> &str {
+        "Formatos DGII 606/607 (resumen)"
+    }
+}
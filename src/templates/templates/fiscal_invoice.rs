@@ -1,7 +1,9 @@
 use anyhow::{Result, Context};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use crate::models::RenderOptions;
 use crate::templates::template_trait::{TypstTemplate, utils};
+use crate::templates::template_labels::Labels;
 use crate::templates::template_models::{InvoiceData, InvoiceItem};
 
 pub struct FiscalInvoiceTemplate;
@@ -28,10 +30,11 @@ impl FiscalInvoiceTemplate {
             .join(",\n")
     }
 
-    fn generate_typst_content(&self, invoice: &InvoiceData) -> Result<String> {
+    fn generate_typst_content(&self, invoice: &InvoiceData, data: &Value, options: &RenderOptions) -> Result<String> {
         let company = &invoice.company_info;
         let client = &invoice.client_info;
         let totals = &invoice.totals;
+        let labels = Labels::for_options(options);
 
         // Generar QR si hay información fiscal
         let qr_section = if let Some(fiscal) = &invoice.fiscal_info {
@@ -43,9 +46,12 @@ impl FiscalInvoiceTemplate {
                 fiscal.security_code
             );
 
-            // Generar QR (en producción, esto debería manejarse mejor)
-            let qr_path = format!("/tmp/qr_{}.png", fiscal.e_ncf);
-            utils::generate_qr_code(&qr_data, &qr_path)?;
+            // Sin QR (`include_qr: false`) quedan el código de seguridad y la fecha de firma
+            let qr_image = if utils::include_qr(options, true) {
+                format!("#{}", utils::qr_code_image(&qr_data, "100pt")?)
+            } else {
+                String::new()
+            };
 
             format!(r#"
 // Código QR y datos fiscales
@@ -53,17 +59,20 @@ impl FiscalInvoiceTemplate {
   columns: (1fr, 250pt),
   gutter: 20pt,
   [
-    #image("{}", width: 100pt, height: 100pt)
+    {}
 
     #v(5pt)
-    #text(size: 8pt, weight: "bold")[Código de Seguridad: {}] \
-    #text(size: 8pt)[Fecha Firma: {}]
+    #text(size: 8pt, weight: "bold")[{}: {}] \
+    #text(size: 8pt)[{}: {}]
   ],
   [
     // Sección de totales se coloca aquí
     TOTALES_PLACEHOLDER
   ]
-)"#, qr_path, fiscal.security_code, fiscal.signature_date)
+)"#,
+                qr_image,
+                labels.get("security_code"), fiscal.security_code,
+                labels.get("signature_date"), fiscal.signature_date)
         } else {
             format!(r#"
 // Sección de totales
@@ -72,18 +81,33 @@ impl FiscalInvoiceTemplate {
 ]"#)
         };
 
+        // Pagada y sin marca de agua pedida: PAGADO
+        let paid = invoice.payment_info.as_ref().map(|p| p.paid).unwrap_or(false);
+        let mut options = options.clone();
+        if paid && options.watermark.is_none() {
+            options.watermark = Some(labels.plain("paid"));
+        }
+
         // Construir el documento completo
-        let content = format!(r#"#set document(title: "Factura Fiscal Electrónica - {}", author: "{}")
-#set page(
-  paper: "us-letter",
-  margin: (left: 20mm, right: 20mm, top: 20mm, bottom: 20mm)
-)
-#set text(font: "Helvetica", size: 10pt, lang: "es", fill: rgb(30, 30, 30))
+        let page_setup = utils::page_setup(
+            &options,
+            "us-letter",
+            false,
+            "margin: (left: 20mm, right: 20mm, top: 20mm, bottom: 20mm)",
+        );
+        // Logo de la empresa o, sin logo, sus iniciales
+        let company_mark = match utils::logo_image(company.logo_path.as_deref(), "width: 56pt, height: 56pt") {
+            Some(logo) => format!("#{}", logo),
+            None => format!(
+                "#text(size: 24pt, weight: \"bold\", fill: rgb(70, 130, 180))[{}]",
+                company.name.chars().filter(|c| c.is_uppercase()).take(2).collect::<String>()
+            ),
+        };
+        let content = format!(r#"#set document(title: "{fiscal_invoice_title} - {}", author: "{}")
+{page_setup}
+#set text(font: "Helvetica", size: 10pt, fill: rgb(30, 30, 30))
 #set align(left)
 
-// Marca de agua si está pagada
-{}
-
 // Header con información de la empresa
 #grid(
   columns: (1fr, 1fr),
@@ -91,7 +115,7 @@ impl FiscalInvoiceTemplate {
     // Logo o inicial de la empresa
     #rect(width: 60pt, height: 60pt, fill: rgb(240, 248, 255), stroke: 1pt + rgb(70, 130, 180), radius: 5pt)[
       #place(center + horizon)[
-        #text(size: 24pt, weight: "bold", fill: rgb(70, 130, 180))[{}]
+        {company_mark}
       ]
     ]
 
@@ -100,20 +124,20 @@ impl FiscalInvoiceTemplate {
     #text(size: 14pt, weight: "bold", fill: rgb(70, 130, 180))[{}]
 
     #text(size: 10pt, weight: "bold")[{}] \
-    #text(size: 9pt)[Sucursal {}] \
-    #text(size: 9pt, weight: "bold")[RNC {}] \
+    #text(size: 9pt)[{branch} {}] \
+    #text(size: 9pt, weight: "bold")[{rnc} {}] \
     #text(size: 8pt)[
-      Dirección: {} \
-      Tel: {} | Email: {} \
-      Fecha Emisión: {}
+      {address}: {} \
+      {phone}: {} | Email: {} \
+      {issue_date}: {}
     ]
   ],
   [
     #align(right)[
-      #text(size: 12pt, weight: "bold", fill: rgb(70, 130, 180))[Factura de Crédito Fiscal Electrónica]
+      #text(size: 12pt, weight: "bold", fill: rgb(70, 130, 180))[{fiscal_invoice}]
       #v(5pt)
       {}
-      #text(size: 9pt)[Fecha Vencimiento: {}]
+      #text(size: 9pt)[{due_date}: {}]
     ]
   ]
 )
@@ -123,8 +147,8 @@ impl FiscalInvoiceTemplate {
 #v(10pt)
 
 // Información del cliente
-#text(size: 10pt, weight: "bold")[Razón Social Cliente: {}] \
-#text(size: 10pt, weight: "bold")[RNC/Cédula Cliente: {}] \
+#text(size: 10pt, weight: "bold")[{client_name}: {}] \
+#text(size: 10pt, weight: "bold")[{client_tax_id}: {}] \
 {}
 
 #v(10pt)
@@ -143,11 +167,11 @@ impl FiscalInvoiceTemplate {
   inset: 8pt,
 
   // Encabezados
-  [#text(weight: "bold")[Descripción]],
-  [#text(weight: "bold")[Cantidad]],
-  [#text(weight: "bold")[Unidad]],
-  [#text(weight: "bold")[Precio]],
-  [#text(weight: "bold")[Total]],
+  [#text(weight: "bold")[{description}]],
+  [#text(weight: "bold")[{quantity}]],
+  [#text(weight: "bold")[{unit}]],
+  [#text(weight: "bold")[{price}]],
+  [#text(weight: "bold")[{total}]],
 
   // Items
 {}
@@ -157,6 +181,10 @@ impl FiscalInvoiceTemplate {
 
 {}
 
+// Monto en letras
+#v(8pt)
+#text(size: 9pt)[#text(weight: "bold")[{amount_label}:] {amount_words}]
+
 // Notas
 {}
 
@@ -173,26 +201,10 @@ impl FiscalInvoiceTemplate {
             // Título del documento
             invoice.invoice_number,
             company.name,
-            // Marca de agua si está pagado
-            if invoice.payment_info.as_ref().map(|p| p.paid).unwrap_or(false) {
-                r#"#place(
-  center + horizon,
-  rotate(45deg)[
-    #text(size: 120pt, fill: rgb(200, 200, 200, 40), weight: "bold")[PAGADO]
-  ]
-)"#
-            } else {
-                ""
-            },
-            // Iniciales de la empresa
-            company.name.chars()
-                .filter(|c| c.is_uppercase())
-                .take(2)
-                .collect::<String>(),
             // Datos de la empresa
             utils::escape_typst(&company.name),
             utils::escape_typst(&company.legal_name.clone().unwrap_or_else(|| company.name.clone())),
-            "Principal", // branch no existe en el modelo actual
+            labels.get("main_branch"), // branch no existe en el modelo actual
             company.tax_id,
             utils::escape_typst(&format!("{}, {}, {}",
                 company.address.street,
@@ -205,14 +217,15 @@ impl FiscalInvoiceTemplate {
             if let Some(fiscal) = &invoice.fiscal_info {
                 format!("#text(size: 10pt, weight: \"bold\")[e-NCF: {}]", fiscal.e_ncf)
             } else {
-                format!("#text(size: 10pt, weight: \"bold\")[Factura No. {}]", invoice.invoice_number)
+                format!("#text(size: 10pt, weight: \"bold\")[{}: {}]", labels.get("invoice_no"), invoice.invoice_number)
             },
             invoice.due_date,
             // Datos del cliente
             utils::escape_typst(&client.name),
             client.tax_id,
             if let Some(address) = &client.address {
-                format!("#text(size: 9pt)[Dirección: {}] \\",
+                format!("#text(size: 9pt)[{}: {}] \\",
+                    labels.get("address"),
                     utils::escape_typst(&format!("{}, {}, {}",
                         address.street,
                         address.city,
@@ -223,54 +236,74 @@ impl FiscalInvoiceTemplate {
             // Items de la factura
             self.format_items(&invoice.items),
             // Sección QR y totales
-            qr_section.replace("TOTALES_PLACEHOLDER", &self.format_totals(&invoice.totals)),
-            // Notas
+            qr_section.replace("TOTALES_PLACEHOLDER", &self.format_totals(&invoice.totals, &labels)),
+            // Notas y campos propios del tenant
             if let Some(notes) = &invoice.notes {
                 format!(r#"
 #v(20pt)
-#text(size: 9pt, weight: "bold")[Notas:]
-#text(size: 9pt)[{}]"#, utils::escape_typst(notes))
+#text(size: 9pt, weight: "bold")[{}:]
+#text(size: 9pt)[{}]"#, labels.get("notes"), utils::escape_typst(notes))
             } else {
                 String::new()
-            },
+            } + &utils::custom_fields_block(data, "9pt"),
             // Información de pago
             if let Some(payment) = &invoice.payment_info {
                 format!(r#"
 #v(10pt)
-#text(size: 9pt)[Método de pago: {} | Términos: {}]"#,
+#text(size: 9pt)[{}: {} | {}: {}]"#,
+                    labels.get("payment_method"),
                     payment.method,
-                    payment.terms.as_deref().unwrap_or("Inmediato"))
+                    labels.get("payment_terms"),
+                    payment.terms.clone().unwrap_or_else(|| labels.get("immediate")))
             } else {
                 String::new()
             },
             // Footer
             if let Some(fiscal) = &invoice.fiscal_info {
-                format!("Esta factura fiscal electrónica es válida hasta: {}",
-                    fiscal.expiration_date.as_deref().unwrap_or("Indefinido"))
+                format!("{}: {}",
+                    labels.get("valid_until"),
+                    fiscal.expiration_date.clone().unwrap_or_else(|| labels.get("indefinite")))
             } else {
-                "Conserve este documento para futuras referencias.".to_string()
-            }
+                labels.get("keep_document")
+            },
+            fiscal_invoice_title = labels.plain("fiscal_invoice_title"),
+            branch = labels.get("branch"),
+            rnc = labels.get("rnc"),
+            address = labels.get("address"),
+            phone = labels.get("phone"),
+            issue_date = labels.get("issue_date"),
+            fiscal_invoice = labels.get("fiscal_invoice"),
+            due_date = labels.get("due_date"),
+            client_name = labels.get("client_name"),
+            client_tax_id = labels.get("client_tax_id"),
+            description = labels.get("description"),
+            quantity = labels.get("quantity"),
+            unit = labels.get("unit"),
+            price = labels.get("price"),
+            total = labels.get("total"),
+            amount_label = labels.get("amount_in_words"),
+            amount_words = labels.amount_in_words(totals.total, &totals.currency),
         );
 
         Ok(content)
     }
 
-    fn format_totals(&self, totals: &crate::templates::template_models::InvoiceTotals) -> String {
+    fn format_totals(&self, totals: &crate::templates::template_models::InvoiceTotals, labels: &Labels) -> String {
         format!(r#"#rect(width: 100%, fill: rgb(245, 245, 245), stroke: 0.5pt + rgb(200, 200, 200), radius: 3pt)[
     #pad(10pt)[
       #grid(
         columns: (150pt, 80pt),
         row-gutter: 5pt,
         align: (right, right),
-        [#text(size: 10pt, weight: "bold")[Subtotal:]],
+        [#text(size: 10pt, weight: "bold")[{subtotal}:]],
         [#text(size: 10pt)[{} {:.2}]],
-        [#text(size: 10pt, weight: "bold")[Descuento:]],
+        [#text(size: 10pt, weight: "bold")[{discount}:]],
         [#text(size: 10pt)[{} {:.2}]],
-        [#text(size: 10pt, weight: "bold")[ITBIS (18%):]],
+        [#text(size: 10pt, weight: "bold")[{itbis}:]],
         [#text(size: 10pt)[{} {:.2}]],
         [#line(length: 100%, stroke: 0.5pt + rgb(150, 150, 150))],
         [#line(length: 100%, stroke: 0.5pt + rgb(150, 150, 150))],
-        [#text(size: 11pt, weight: "bold")[Total:]],
+        [#text(size: 11pt, weight: "bold")[{total}:]],
         [#text(size: 11pt, weight: "bold")[{} {:.2}]]
       )
     ]
@@ -278,19 +311,23 @@ impl FiscalInvoiceTemplate {
             totals.currency, totals.subtotal,
             totals.currency, totals.discount_amount.unwrap_or(0.0),
             totals.currency, totals.tax_amount,
-            totals.currency, totals.total
+            totals.currency, totals.total,
+            subtotal = labels.get("subtotal"),
+            discount = labels.get("discount"),
+            itbis = labels.get("itbis"),
+            total = labels.get("total"),
         )
     }
 }
 
 impl TypstTemplate for FiscalInvoiceTemplate {
-    fn generate(&self, data: &Value) -> Result<String> {
+    fn generate(&self, data: &Value, options: &RenderOptions) -> Result<String> {
         // Deserializar los datos a InvoiceData
         let invoice: InvoiceData = serde_json::from_value(data.clone())
             .context("Error deserializando datos de factura")?;
 
         // Generar contenido Typst
-        self.generate_typst_content(&invoice)
+        self.generate_typst_content(&invoice, data, options)
     }
 
     fn template_id(&self) -> &str {
@@ -307,11 +344,11 @@ impl TypstTemplate for FiscalInvoiceTemplate {
 
         // Campos requeridos
         let required = vec![
-            "invoice_number",
-            "issue_date",
-            "due_date",
-            "company_info",
-            "client_info",
+            "invoiceNumber",
+            "issueDate",
+            "dueDate",
+            "companyInfo",
+            "clientInfo",
             "items",
             "totals"
         ];
@@ -4,8 +4,16 @@ mod fiscal_invoice;
 mod simple_invoice;
 mod receipt;
 mod report;
+mod quote;
+mod statement;
+mod certificate;
+mod dgii_report;
 
 pub use fiscal_invoice::FiscalInvoiceTemplate;
 pub use simple_invoice::SimpleInvoiceTemplate;
 pub use receipt::ReceiptTemplate;
-pub use report::ReportTemplate;
\ No newline at end of file
+pub use report::{cell_width, ReportTemplate};
+pub use quote::QuoteTemplate;
+pub use statement::{aging_buckets, StatementTemplate};
+pub use certificate::CertificateTemplate;
+pub use dgii_report::DgiiReportTemplate;
\ No newline at end of file
@@ -0,0 +1,251 @@
+use anyhow::{Result, Context};
+use serde_json::Value;
+use crate::models::RenderOptions;
+use crate::templates::template_trait::{TypstTemplate, utils};
+use crate::templates::template_models::{QuoteAcceptance, QuoteData, QuoteItem};
+
+#[derive(Default)]
+pub struct QuoteTemplate;
+
+impl QuoteTemplate {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn format_items<'a>(&self, items: impl Iterator<Item = &'a QuoteItem>) -> String {
+        items
+            .map(|item| {
+                format!(
+                    "  [{}], [{}], [{:.2}], [{:.2}]",
+                    utils::escape_typst(&item.description),
+                    item.quantity,
+                    item.unit_price,
+                    item.total
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n")
+    }
+
+    /// Tabla de ítems opcionales; vacía si no hay
+    fn format_optional_items(&self, quote: &QuoteData) -> String {
+        if !quote.items.iter().any(|item| item.optional) {
+            return String::new();
+        }
+
+        format!(r#"
+#v(10pt)
+#text(weight: "bold")[Opcionales] #text(size: 9pt, fill: gray)[(no incluidos en el total)]
+#table(
+  columns: (1fr, 60pt, 80pt, 80pt),
+  stroke: 0.5pt + gray,
+  align: (col, row) => if col == 0 {{ left }} else {{ right }},
+  inset: 8pt,
+  [*Descripción*], [*Cantidad*], [*Precio*], [*Total*],
+{}
+)"#,
+            self.format_items(quote.items.iter().filter(|item| item.optional))
+        )
+    }
+
+    /// Bloque de aceptación; los datos que falten quedan como líneas en blanco
+    fn format_acceptance(&self, acceptance: Option<&QuoteAcceptance>) -> String {
+        let field = |value: Option<&String>| {
+            value
+                .map(|v| utils::escape_typst(v))
+                .unwrap_or_else(|| "#box(width: 1fr, stroke: (bottom: 0.5pt))".to_string())
+        };
+
+        format!(r#"#rect(width: 100%, stroke: 0.5pt + gray, radius: 3pt, inset: 10pt)[
+  #text(weight: "bold")[ACEPTACIÓN DEL CLIENTE]
+  #v(5pt)
+  #text(size: 9pt)[Acepto esta cotización en los términos y precios indicados.]
+  #v(10pt)
+  #grid(
+    columns: (1fr, 1fr),
+    gutter: 20pt,
+    row-gutter: 12pt,
+    [Nombre: {}], [Cargo: {}],
+    [Fecha: {}], [],
+  )
+  #v(25pt)
+  #grid(
+    columns: (1fr, 1fr),
+    gutter: 20pt,
+    [
+      #line(length: 100%, stroke: 0.5pt)
+      #align(center)[#text(size: 9pt)[Firma del Cliente]]
+    ],
+    [
+      #line(length: 100%, stroke: 0.5pt)
+      #align(center)[#text(size: 9pt)[Sello]]
+    ]
+  )
+]"#,
+            field(acceptance.and_then(|a| a.name.as_ref())),
+            field(acceptance.and_then(|a| a.title.as_ref())),
+            field(acceptance.and_then(|a| a.date.as_ref())),
+        )
+    }
+}
+
+impl TypstTemplate for QuoteTemplate {
+    fn generate(&self, data: &Value, options: &RenderOptions) -> Result<String> {
+        let quote: QuoteData = serde_json::from_value(data.clone())
+            .context("Error deserializando datos de cotización")?;
+
+        let company = &quote.company_info;
+        let client = &quote.client_info;
+        let totals = &quote.totals;
+
+        let company_logo = utils::logo_image(company.logo_path.as_deref(), "height: 50pt")
+            .map(|logo| format!("#{}\n", logo))
+            .unwrap_or_default();
+        let page_setup = utils::page_setup(options, "us-letter", false, "margin: 2cm");
+
+        let content = format!(r#"#set document(title: "Cotización - {}", author: "{}")
+{page_setup}
+#set text(font: "Arial", size: 11pt)
+
+// Encabezado
+#align(center)[
+  {company_logo}
+  #text(size: 18pt, weight: "bold")[{}]
+
+  #text(size: 10pt)[
+    {} \
+    Tel: {} | Email: {}
+  ]
+]
+
+#v(10pt)
+#align(center)[
+  #text(size: 14pt, weight: "bold")[COTIZACIÓN]
+]
+
+#v(15pt)
+
+// Información de la cotización
+#grid(
+  columns: (1fr, 1fr),
+  [
+    #text(weight: "bold")[Cotización No:] {} \
+    #text(weight: "bold")[Fecha:] {}
+  ],
+  [
+    #align(right)[
+      #text(weight: "bold")[Válida hasta:] {}
+    ]
+  ]
+)
+
+#v(15pt)
+
+// Información del cliente
+#rect(width: 100%, fill: rgb(245, 245, 245), stroke: 0.5pt + gray, radius: 3pt, inset: 10pt)[
+  #text(weight: "bold")[Cliente:] {} \
+  #text(weight: "bold")[RNC/ID:] {}
+]
+
+#v(15pt)
+
+// Tabla de productos
+#table(
+  columns: (1fr, 60pt, 80pt, 80pt),
+  stroke: 0.5pt + gray,
+  fill: (x, y) => if y == 0 {{ rgb(230, 230, 230) }} else {{ white }},
+  align: (col, row) => if col == 0 {{ left }} else {{ right }},
+  inset: 8pt,
+
+  [*Descripción*], [*Cantidad*], [*Precio*], [*Total*],
+{}
+)
+{}
+
+#v(15pt)
+
+// Totales
+#align(right)[
+  #grid(
+    columns: (100pt, 80pt),
+    row-gutter: 3pt,
+    align: (right, right),
+    [Subtotal:], [{} {:.2}],
+    [Impuestos:], [{} {:.2}],
+    [#text(weight: "bold")[Total:]], [#text(weight: "bold")[{} {:.2}]]
+  )
+]
+{}{}
+
+#v(20pt)
+#text(size: 9pt, style: "italic")[Precios sujetos a cambio después del {}.]
+
+#v(20pt)
+{}"#,
+            // Metadata
+            quote.quote_number,
+            company.name,
+            // Header
+            utils::escape_typst(&company.name),
+            utils::escape_typst(&format!("{}, {}", company.address.city, company.address.country)),
+            company.phone.as_deref().unwrap_or(""),
+            utils::escape_typst(company.email.as_deref().unwrap_or("")),
+            // Quote info
+            quote.quote_number,
+            quote.issue_date,
+            quote.valid_until,
+            // Client info
+            utils::escape_typst(&client.name),
+            client.tax_id,
+            // Items
+            self.format_items(quote.items.iter().filter(|item| !item.optional)),
+            self.format_optional_items(&quote),
+            // Totals
+            totals.currency, totals.subtotal,
+            totals.currency, totals.tax_amount,
+            totals.currency, totals.total,
+            // Terms and notes
+            if let Some(terms) = &quote.terms {
+                format!("\n#v(15pt)\n#text(size: 10pt)[*Condiciones:* {}]", utils::escape_typst(terms))
+            } else {
+                String::new()
+            },
+            if let Some(notes) = &quote.notes {
+                format!("\n#v(10pt)\n#text(size: 10pt)[*Notas:* {}]", utils::escape_typst(notes))
+            } else {
+                String::new()
+            },
+            // Validity
+            quote.valid_until,
+            // Acceptance
+            self.format_acceptance(quote.acceptance.as_ref())
+        );
+
+        Ok(content)
+    }
+
+    fn template_id(&self) -> &str {
+        "quote"
+    }
+
+    fn validate(&self, data: &Value) -> Result<()> {
+        if !data.is_object() {
+            anyhow::bail!("Los datos deben ser un objeto JSON");
+        }
+
+        let obj = data.as_object().unwrap();
+        let required = vec!["quoteNumber", "issueDate", "validUntil", "companyInfo", "clientInfo", "items", "totals"];
+
+        for field in required {
+            if !obj.contains_key(field) {
+                anyhow::bail!("Campo requerido faltante: {}", field);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn description(&self) -> &str {
+        "Cotización"
+    }
+}
@@ -1,6 +1,8 @@
 use anyhow::{Result, Context};
 use serde_json::Value;
+use crate::models::RenderOptions;
 use crate::templates::template_trait::{TypstTemplate, utils};
+use crate::templates::template_labels::Labels;
 use crate::templates::template_models::{ReceiptData, ReceiptItem};
 
 pub struct ReceiptTemplate;
@@ -28,18 +30,25 @@ impl ReceiptTemplate {
 }
 
 impl TypstTemplate for ReceiptTemplate {
-    fn generate(&self, data: &Value) -> Result<String> {
+    fn generate(&self, data: &Value, options: &RenderOptions) -> Result<String> {
         let receipt: ReceiptData = serde_json::from_value(data.clone())
             .context("Error deserializando datos de recibo")?;
 
         let vendor = &receipt.vendor;
 
+        let company_logo = utils::logo_image(vendor.logo_path.as_deref(), "height: 40pt")
+            .map(|logo| format!("#{}\n", logo))
+            .unwrap_or_default();
+        let page_setup = utils::page_setup(options, "a5", false, "margin: 1.5cm");
+        let labels = Labels::for_options(options);
+
         let content = format!(r#"#set document(title: "Recibo #{}", author: "{}")
-#set page(paper: "a5", margin: 1.5cm)
+{page_setup}
 #set text(font: "Arial", size: 10pt)
 
 // Encabezado
 #align(center)[
+  {company_logo}
   #text(size: 16pt, weight: "bold")[{}]
 
   #text(size: 9pt)[
@@ -92,6 +101,7 @@ impl TypstTemplate for ReceiptTemplate {
     #text(size: 12pt, weight: "bold")[Total: {} {:.2}]
   ]
 ]
+#text(size: 9pt)[#text(weight: "bold")[{amount_label}:] {amount_words}]
 
 #v(10pt)
 
@@ -141,7 +151,9 @@ impl TypstTemplate for ReceiptTemplate {
             receipt.currency,
             receipt.total,
             // Payment method
-            utils::escape_typst(&receipt.payment_method)
+            utils::escape_typst(&receipt.payment_method),
+            amount_label = labels.get("amount_in_words"),
+            amount_words = labels.amount_in_words(receipt.total, &receipt.currency),
         );
 
         Ok(content)
@@ -158,12 +170,12 @@ impl TypstTemplate for ReceiptTemplate {
 
         let obj = data.as_object().unwrap();
         let required = vec![
-            "receipt_number",
+            "receiptNumber",
             "date",
             "vendor",
             "items",
             "total",
-            "payment_method",
+            "paymentMethod",
             "currency"
         ];
 
@@ -1,7 +1,10 @@
 use anyhow::{Result, Context};
 use serde_json::Value;
+use crate::models::{Orientation, PageSize, RenderOptions};
 use crate::templates::template_trait::{TypstTemplate, utils};
-use crate::templates::template_models::{ReportData, ChartData};
+use crate::templates::template_models::{ReportChunk, ReportData, ReportSummary, ChartData};
+use crate::generators::report_processor::{compute_summary, filter_and_sort, group_rows, GroupedRow};
+use crate::models::{footnote_markers, CellOverflow, ColumnDefinition, ConditionalFormat, Footnote, OverflowStrategy, ReportSchema};
 
 pub struct ReportTemplate;
 
@@ -48,6 +51,304 @@ impl ReportTemplate {
         format!("{},\n  {}", header_row, data_rows)
     }
 
+    /// Tabla guiada por el esquema: columnas visibles en orden y, si hay
+    /// grupos, encabezado de dos niveles con celdas combinadas
+    fn format_schema_table(
+        &self,
+        schema: &ReportSchema,
+        data: &[HashMap<String, String>],
+        footnotes: &[Footnote],
+        rules: &[ConditionalFormat],
+        chunk: Option<&ReportChunk>,
+        layout: &TableLayout,
+    ) -> String {
+        let columns = schema.visible_columns();
+        let first_row = chunk.map_or(0, |c| c.first_row);
+
+        // Marcadores de notas por columna, en superíndice junto al encabezado
+        let markers = footnote_markers(footnotes);
+        let marker_for = |field: &str| {
+            footnotes
+                .iter()
+                .zip(&markers)
+                .filter(|(note, marker)| note.field.as_deref() == Some(field) && !marker.is_empty())
+                .map(|(_, marker)| utils::escape_typst(marker))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let header_cell = |column: &ColumnDefinition| {
+            let marker = marker_for(&column.field);
+            if marker.is_empty() {
+                format!("[*{}*]", utils::escape_typst(&column.header))
+            } else {
+                format!("[*{}*#super[{}]]", utils::escape_typst(&column.header), marker)
+            }
+        };
+
+        let header = if schema.has_header_groups() {
+            let spans = schema.header_spans();
+            let top = spans
+                .iter()
+                .map(|span| match &span.title {
+                    Some(title) => format!("table.cell(colspan: {}, align: center)[*{}*]", span.len, utils::escape_typst(title)),
+                    None => format!("table.cell(rowspan: 2){}", header_cell(columns[span.start])),
+                })
+                .collect::<Vec<_>>();
+            let bottom = spans
+                .iter()
+                .filter(|span| span.title.is_some())
+                .flat_map(|span| columns[span.start..span.start + span.len].iter().map(|c| header_cell(c)))
+                .collect::<Vec<_>>();
+            format!("table.header({}, {})", top.join(", "), bottom.join(", "))
+        } else {
+            let cells = columns.iter().map(|c| header_cell(c)).collect::<Vec<_>>();
+            format!("table.header({})", cells.join(", "))
+        };
+
+        // Valores recortados con "…": numeración a continuación de las notas
+        let mut next_marker = markers.iter().filter_map(|m| m.parse::<usize>().ok()).max().unwrap_or(0) + 1;
+        let mut overflow_notes = Vec::new();
+        let mut rows = Vec::with_capacity(data.len());
+
+        // Con `grouping`: filas por grupo con encabezado y subtotal; `collapsed`
+        // deja solo encabezados y subtotales
+        let grouped = if schema.grouping.is_some() {
+            let values: Vec<Value> = data.iter().map(|row| serde_json::to_value(row).unwrap_or_default()).collect();
+            group_rows(schema, &values)
+        } else {
+            None
+        };
+        let grouped = grouped.unwrap_or_else(|| (0..data.len()).map(GroupedRow::Data).collect());
+        let show_subtotals = schema.grouping.as_ref().is_some_and(|g| g.show_subtotals);
+        let collapsed = schema.grouping.as_ref().is_some_and(|g| g.collapsed);
+
+        // En un reporte por partes: sumas acumuladas de las partes anteriores
+        let carried = |totals: &HashMap<String, f64>| -> Vec<(String, f64)> {
+            totals.iter().map(|(field, value)| (field.clone(), *value)).collect()
+        };
+        if let Some(chunk) = chunk.filter(|c| !c.is_first() && !c.carried_in.is_empty()) {
+            rows.push(self.format_subtotal_row("Van", &carried(&chunk.carried_in), &columns));
+        }
+
+        for entry in &grouped {
+            let index = match entry {
+                GroupedRow::Header { level, label } => {
+                    rows.push(format!(
+                        "table.cell(colspan: {}, fill: rgb(217, 225, 242))[#h({}pt)*{}*]",
+                        columns.len().max(1),
+                        level * 10,
+                        utils::escape_typst(label)
+                    ));
+                    continue;
+                },
+                GroupedRow::Subtotal { label, values, .. } => {
+                    if show_subtotals {
+                        rows.push(self.format_subtotal_row(label, values, &columns));
+                    }
+                    continue;
+                },
+                GroupedRow::Data(_) if collapsed => continue,
+                GroupedRow::Data(index) => *index,
+            };
+            let row = &data[index];
+            let mut cells = Vec::with_capacity(columns.len());
+            for column in &columns {
+                let value = row.get(&column.field).map(|v| v.as_str()).unwrap_or("-");
+                let cell = match &column.overflow {
+                    Some(overflow) if value.chars().count() > overflow.limit() => {
+                        if matches!(overflow.strategy, OverflowStrategy::Ellipsis) {
+                            overflow_notes.push(format!(
+                                "#super[{}] {}, fila {}: {}",
+                                next_marker,
+                                utils::escape_typst(&column.header),
+                                first_row + index + 1,
+                                utils::escape_typst(value)
+                            ));
+                            next_marker += 1;
+                        }
+                        self.format_overflow_cell(value, overflow, next_marker - 1)
+                    },
+                    _ => utils::escape_typst(value),
+                };
+                cells.push(self.format_conditional_cell(cell, &column.field, value, rules));
+            }
+            rows.push(cells.join(", "));
+        }
+        if let Some(chunk) = chunk.filter(|c| !c.is_last() && !c.carried_out.is_empty()) {
+            rows.push(self.format_subtotal_row("Pasan", &carried(&chunk.carried_out), &columns));
+        }
+        let data_rows = rows.join(",\n  ");
+
+        let header_rows = if schema.has_header_groups() { 2 } else { 1 };
+        let overflow_notes = if overflow_notes.is_empty() {
+            String::new()
+        } else {
+            format!("\n#v(4pt)\n#text(size: 7pt, fill: gray)[\n  {}\n]", overflow_notes.join(" \\\n  "))
+        };
+
+        let table = format!(r#"#table(
+  columns: {},
+  stroke: 0.5pt + gray,
+  fill: (x, y) => if y < {} {{ rgb(240, 240, 240) }} else {{ white }},
+  inset: {}pt,
+  {},
+  {}
+)"#, layout.columns, header_rows, layout.inset, header, data_rows);
+        format!("{}{}", layout.wrap(table), overflow_notes)
+    }
+
+    /// Subtotal de un grupo: la etiqueta en la primera columna (si no lleva una
+    /// agregación) y cada agregación bajo la columna de su campo
+    fn format_subtotal_row(&self, label: &str, values: &[(String, f64)], columns: &[&ColumnDefinition]) -> String {
+        columns
+            .iter()
+            .enumerate()
+            .map(|(col, column)| {
+                let text = match values.iter().find(|(field, _)| *field == column.field) {
+                    Some((_, value)) if value.is_finite() => utils::format_number(*value, 2),
+                    _ if col == 0 => utils::escape_typst(label),
+                    _ => return "table.cell(fill: rgb(242, 242, 242))[]".to_string(),
+                };
+                format!("table.cell(fill: rgb(242, 242, 242))[*{}*]", text)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Celda con los formatos condicionales de su columna que se cumplen; en
+    /// lo que choquen gana la primera regla, como en Excel
+    fn format_conditional_cell(&self, cell: String, field: &str, value: &str, rules: &[ConditionalFormat]) -> String {
+        let value = Value::String(value.to_string());
+        let matching: Vec<&ConditionalFormat> = rules
+            .iter()
+            .filter(|rule| rule.field == field && rule.matches(&value))
+            .collect();
+        if matching.is_empty() {
+            return format!("[{}]", cell);
+        }
+
+        // Colores ya validados en `generate`
+        let fill = matching.iter().find_map(|rule| rule.format.background_rgb().ok().flatten());
+        let color = matching.iter().find_map(|rule| rule.format.font_rgb().ok().flatten());
+        let bold = matching.iter().find_map(|rule| rule.format.bold).unwrap_or(false);
+        let icon = matching.iter().find_map(|rule| rule.format.icon.as_deref());
+
+        let mut text_args = Vec::new();
+        if let Some(rgb) = color {
+            text_args.push(format!("fill: rgb(\"#{:06X}\")", rgb));
+        }
+        if bold {
+            text_args.push("weight: \"bold\"".to_string());
+        }
+        let mut content = match icon {
+            Some(icon) => format!("{} {}", utils::escape_typst(icon), cell),
+            None => cell,
+        };
+        if !text_args.is_empty() {
+            content = format!("#text({})[{}]", text_args.join(", "), content);
+        }
+
+        match fill {
+            Some(rgb) => format!("table.cell(fill: rgb(\"#{:06X}\"))[{}]", rgb, content),
+            None => format!("[{}]", content),
+        }
+    }
+
+    /// Celda con un valor más largo que el límite de su columna
+    fn format_overflow_cell(&self, value: &str, overflow: &CellOverflow, marker: usize) -> String {
+        let limit = overflow.limit();
+        match overflow.strategy {
+            OverflowStrategy::Wrap => utils::escape_typst(&break_long_words(value, limit)),
+            OverflowStrategy::ShrinkToFit => {
+                let scale = (limit as f64 / value.chars().count() as f64).max(0.6);
+                format!("#text(size: {:.2}em)[{}]", scale, utils::escape_typst(&break_long_words(value, limit)))
+            },
+            OverflowStrategy::Ellipsis => {
+                let truncated: String = value.chars().take(limit - 1).collect();
+                format!("{}…#super[{}]", utils::escape_typst(truncated.trim_end()), marker)
+            },
+        }
+    }
+
+    /// Notas al pie debajo de la tabla
+    fn format_footnotes(&self, footnotes: &[Footnote]) -> String {
+        if footnotes.is_empty() {
+            return String::new();
+        }
+
+        let markers = footnote_markers(footnotes);
+        let lines = footnotes
+            .iter()
+            .zip(&markers)
+            .map(|(note, marker)| {
+                if marker.is_empty() {
+                    utils::escape_typst(&note.text)
+                } else {
+                    format!("#super[{}] {}", utils::escape_typst(marker), utils::escape_typst(&note.text))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" \\\n  ");
+
+        format!(r#"
+#v(6pt)
+#text(size: 8pt, fill: gray)[
+  {}
+]"#, lines)
+    }
+
+    /// Gráficos ya dibujados por el motor (`imagePath`); vacío si no hay
+    fn format_charts(&self, charts: &[ChartData]) -> String {
+        let images = charts
+            .iter()
+            .filter_map(|chart| chart.image_path.as_deref().filter(|path| !path.is_empty()))
+            .map(|path| format!("#align(center)[#image({}, width: 100%)]", utils::typst_string(path)))
+            .collect::<Vec<_>>();
+        if images.is_empty() {
+            return String::new();
+        }
+
+        format!(r#"
+#v(15pt)
+#text(size: 14pt, weight: "bold")[Visualizaciones]
+#v(8pt)
+{}"#, images.join("\n#v(10pt)\n"))
+    }
+
+    /// Columnas de la tabla (campo, encabezado, límite de caracteres) medidas
+    /// con su contenido; en un reporte por partes, con los anchos de todas las filas
+    fn table_layout(&self, report: &ReportData, options: &RenderOptions) -> TableLayout {
+        let columns: Vec<(String, String, Option<usize>)> = match report.schema.as_ref().filter(|s| !s.columns.is_empty()) {
+            Some(schema) => schema
+                .visible_columns()
+                .iter()
+                .map(|c| {
+                    let limit = c.width.map(|w| w.round() as usize).or(c.overflow.as_ref().map(CellOverflow::limit));
+                    (c.field.clone(), c.header.clone(), limit)
+                })
+                .collect(),
+            None => report.data
+                .first()
+                .map(|row| row.keys().map(|k| (k.clone(), k.clone(), None)).collect())
+                .unwrap_or_default(),
+        };
+
+        let measured = report.chunk.as_ref().map(|c| &c.column_chars).filter(|c| !c.is_empty());
+        let chars: Vec<usize> = columns
+            .iter()
+            .map(|(field, header, limit)| {
+                let content = match measured {
+                    Some(measured) => measured.get(field).copied().unwrap_or(0),
+                    None => report.data.iter().filter_map(|row| row.get(field)).map(|v| cell_width(v)).max().unwrap_or(0),
+                };
+                let content = limit.map_or(content, |limit| content.min(limit));
+                content.max(cell_width(header)).max(MIN_COLUMN_CHARS)
+            })
+            .collect();
+
+        TableLayout::fit(&chars, options)
+    }
+
     fn format_summary(&self, summary: &crate::templates::template_models::ReportSummary) -> String {
         let mut items = Vec::new();
 
@@ -66,18 +367,161 @@ impl ReportTemplate {
     }
 }
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 
+/// Ancho aproximado de un carácter de Arial a 10pt
+const CHAR_WIDTH_PT: f64 = 5.5;
+/// Relleno horizontal de una celda a 10pt (8pt a cada lado)
+const CELL_PADDING_PT: f64 = 16.0;
+/// Caracteres que cuentan al medir una celda; los textos más largos se parten en líneas
+const MAX_MEASURED_CHARS: usize = 40;
+const MIN_COLUMN_CHARS: usize = 3;
+/// Letra mínima de una tabla que no cabe ni apaisada
+const MIN_FONT_PT: f64 = 7.0;
+/// Márgenes de la página (2cm a cada lado)
+const PAGE_MARGINS_PT: f64 = 2.0 * 56.69;
+
+/// Caracteres de una celda para medir su columna
+pub fn cell_width(text: &str) -> usize {
+    text.chars().count().min(MAX_MEASURED_CHARS)
+}
+
+/// Distribución de la tabla: anchos proporcionales al contenido de cada
+/// columna; si no cabe en vertical la página pasa a horizontal (salvo que
+/// `orientation` lo fije) y, si tampoco cabe, se achica la letra
+struct TableLayout {
+    /// Valor de `columns` en Typst, p. ej. `(12fr, 30fr, 8fr)`
+    columns: String,
+    landscape: bool,
+    font_size: f64,
+    inset: f64,
+}
+
+impl TableLayout {
+    fn fit(chars: &[usize], options: &RenderOptions) -> Self {
+        let natural: f64 = chars.iter().map(|c| *c as f64 * CHAR_WIDTH_PT + CELL_PADDING_PT).sum();
+        let (width, height) = page_dimensions(options);
+        let (portrait, landscape) = (width.min(height) - PAGE_MARGINS_PT, width.max(height) - PAGE_MARGINS_PT);
+
+        let landscape_page = match options.orientation {
+            Some(Orientation::Landscape) => true,
+            Some(Orientation::Portrait) => false,
+            None => natural > portrait,
+        };
+        let available = if landscape_page { landscape } else { portrait };
+        let scale = if natural > available { (available / natural).max(MIN_FONT_PT / 10.0) } else { 1.0 };
+
+        let columns = if chars.is_empty() {
+            "1".to_string()
+        } else {
+            format!("({})", chars.iter().map(|c| format!("{}fr", c)).collect::<Vec<_>>().join(", "))
+        };
+
+        TableLayout {
+            columns,
+            landscape: landscape_page,
+            font_size: (10.0 * scale * 10.0).round() / 10.0,
+            inset: (8.0 * scale * 10.0).round() / 10.0,
+        }
+    }
+
+    /// Tabla con la letra reducida si hizo falta
+    fn wrap(&self, table: String) -> String {
+        if self.font_size < 10.0 {
+            format!("#text(size: {}pt)[{}]", self.font_size, table)
+        } else {
+            table
+        }
+    }
+}
+
+/// Ancho y alto del papel en puntos (carta si no se indica)
+fn page_dimensions(options: &RenderOptions) -> (f64, f64) {
+    match &options.page_size {
+        None | Some(PageSize::Letter) => (612.0, 792.0),
+        Some(PageSize::A4) => (595.3, 841.9),
+        Some(PageSize::Legal) => (612.0, 1008.0),
+        Some(PageSize::A3) => (841.9, 1190.6),
+        Some(PageSize::Custom { width, height }) => (*width as f64 * 2.8346, *height as f64 * 2.8346),
+    }
+}
+
+/// Agrega puntos de corte (espacio de ancho cero) en las palabras más largas
+/// que `max_len`, para que Typst pueda partirlas dentro de la celda
+fn break_long_words(text: &str, max_len: usize) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut run = 0;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            run = 0;
+        } else if run == max_len {
+            result.push('\u{200B}');
+            run = 0;
+        }
+        if !c.is_whitespace() {
+            run += 1;
+        }
+        result.push(c);
+    }
+    result
+}
+
 impl TypstTemplate for ReportTemplate {
-    fn generate(&self, data: &Value) -> Result<String> {
-        let report: ReportData = serde_json::from_value(data.clone())
+    fn generate(&self, data: &Value, options: &RenderOptions) -> Result<String> {
+        let mut report: ReportData = serde_json::from_value(data.clone())
             .context("Error deserializando datos de reporte")?;
 
-        let content = format!(r#"#set document(title: "{}", author: "Sistema de Reportes")
-#set page(paper: "us-letter", margin: 2cm, numbering: "1 / 1")
-#set text(font: "Arial", size: 10pt)
-#set par(justify: true)
+        // Filtros y orden del esquema sobre las filas; luego las agregaciones y
+        // métricas calculadas del esquema al resumen ejecutivo. Las partes de
+        // un reporte por partes ya llegan filtradas, ordenadas y con el resumen
+        if let Some(schema) = report.schema.as_ref().filter(|_| report.chunk.is_none()) {
+            let rows: Vec<Value> = report.data
+                .iter()
+                .map(|row| serde_json::to_value(row).unwrap_or_default())
+                .collect();
+            let rows = filter_and_sort(schema, &rows)?;
+            if let Cow::Owned(selected) = &rows {
+                report.data = selected
+                    .iter()
+                    .map(|row| serde_json::from_value(row.clone()))
+                    .collect::<Result<_, _>>()
+                    .context("Error ordenando filas del reporte")?;
+            }
+            let computed = compute_summary(schema, &rows);
+
+            if !computed.is_empty() {
+                let summary = report.summary.get_or_insert_with(|| ReportSummary {
+                    metrics: HashMap::new(),
+                    highlights: Vec::new(),
+                });
+                summary.metrics.extend(computed);
+            }
+        }
+
+        // Formatos condicionales de los datos o de las opciones del reporte
+        let conditional_formatting = match report.conditional_formatting.take() {
+            Some(rules) => rules,
+            None => match data.pointer("/options/conditional_formatting").filter(|v| !v.is_null()) {
+                Some(rules) => serde_json::from_value(rules.clone()).context("Invalid conditional_formatting")?,
+                None => Vec::new(),
+            },
+        };
+        for rule in &conditional_formatting {
+            rule.operator().map_err(anyhow::Error::msg)?;
+            rule.format.background_rgb().map_err(anyhow::Error::msg)?;
+            rule.format.font_rgb().map_err(anyhow::Error::msg)?;
+        }
+
+        // Por partes: la numeración se estampa al unirlas
+        let chunk = report.chunk.as_ref();
+        let (first, last) = (chunk.is_none_or(ReportChunk::is_first), chunk.is_none_or(ReportChunk::is_last));
+        let page_args = if chunk.is_some() { "margin: 2cm, numbering: none" } else { "margin: 2cm, numbering: \"1 / 1\"" };
+        let layout = self.table_layout(&report, options);
+        let page_setup = utils::page_setup(options, "us-letter", layout.landscape, page_args);
 
+        let header = if first {
+            format!(r#"
 // Encabezado
 #align(center)[
   #text(size: 18pt, weight: "bold")[{}]
@@ -98,30 +542,13 @@ impl TypstTemplate for ReportTemplate {
 #v(15pt)
 #text(size: 14pt, weight: "bold")[Datos del Reporte]
 #v(8pt)
-
-{}
-
-// Charts si existen
-{}
-
-// Footer
-#v(20pt)
-#line(length: 100%, stroke: 0.5pt + gray)
-#v(5pt)
-#text(size: 8pt, fill: gray)[
-  Documento generado automáticamente \
-  Página #counter(page).display() de #context counter(page).final().at(0)
-]"#,
-            // Metadata
-            report.title,
-            // Header
-            utils::escape_typst(&report.title),
-            report.generated_date,
-            report.period.start_date,
-            report.period.end_date,
-            // Summary si existe
-            if let Some(ref summary) = report.summary {
-                format!(r#"
+"#,
+                utils::escape_typst(&report.title),
+                report.generated_date,
+                report.period.start_date,
+                report.period.end_date,
+                match &report.summary {
+                    Some(summary) => format!(r#"
 #v(15pt)
 #rect(width: 100%, fill: rgb(255, 250, 240), stroke: 1pt + rgb(255, 140, 0), radius: 3pt, inset: 10pt)[
   #text(size: 12pt, weight: "bold")[Resumen Ejecutivo]
@@ -131,38 +558,76 @@ impl TypstTemplate for ReportTemplate {
     row-gutter: 3pt,
     {}
   )
-]"#, self.format_summary(summary))
-            } else {
-                String::new()
-            },
-            // Tabla de datos
-            if !report.data.is_empty() {
-                format!(r#"#table(
+]"#, self.format_summary(summary)),
+                    None => String::new(),
+                }
+            )
+        } else {
+            String::new()
+        };
+
+        // Tabla de datos
+        let table = if let Some(schema) = report.schema.as_ref().filter(|s| !s.columns.is_empty()) {
+            self.format_schema_table(
+                schema,
+                &report.data,
+                report.footnotes.as_deref().unwrap_or(&[]),
+                &conditional_formatting,
+                chunk,
+                &layout,
+            )
+        } else if !report.data.is_empty() {
+            layout.wrap(format!(r#"#table(
   columns: {},
   stroke: 0.5pt + gray,
   fill: (x, y) => if y == 0 {{ rgb(240, 240, 240) }} else {{ white }},
-  inset: 8pt,
+  inset: {}pt,
   {}
 )"#,
-                    report.data.first().map(|r| r.len()).unwrap_or(2),
-                    self.format_table_data(&report.data))
-            } else {
+                layout.columns,
+                layout.inset,
+                self.format_table_data(&report.data)))
+        } else {
+            String::new()
+        };
+
+        // Notas al pie, gráficos y pie del documento
+        let closing = if last {
+            let page_line = if chunk.is_some() {
                 String::new()
-            },
-            // Charts placeholder
-            if report.charts.is_some() {
-                r#"
-#v(15pt)
-#text(size: 14pt, weight: "bold")[Visualizaciones]
-#v(8pt)
-#rect(width: 100%, height: 150pt, fill: rgb(250, 250, 250), stroke: 0.5pt + gray)[
-  #align(center + horizon)[
-    #text(fill: gray)[Gráficos disponibles en versión interactiva]
-  ]
-]"#
             } else {
-                ""
-            }
+                " \\\n  Página #counter(page).display() de #context counter(page).final().at(0)".to_string()
+            };
+            format!(r#"{}
+
+// Charts si existen
+{}
+
+// Footer
+#v(20pt)
+#line(length: 100%, stroke: 0.5pt + gray)
+#v(5pt)
+#text(size: 8pt, fill: gray)[
+  Documento generado automáticamente{}
+]"#,
+                self.format_footnotes(report.footnotes.as_deref().unwrap_or(&[])),
+                self.format_charts(report.charts.as_deref().unwrap_or(&[])),
+                page_line)
+        } else {
+            String::new()
+        };
+
+        let content = format!(r#"#set document(title: "{}", author: "Sistema de Reportes")
+{page_setup}
+#set text(font: "Arial", size: 10pt)
+#set par(justify: true)
+{}
+{}
+{}"#,
+            report.title,
+            header,
+            table,
+            closing
         );
 
         Ok(content)
@@ -183,8 +648,8 @@ impl TypstTemplate for ReportTemplate {
             anyhow::bail!("Campo requerido faltante: title");
         }
 
-        if !obj.contains_key("generated_date") {
-            anyhow::bail!("Campo requerido faltante: generated_date");
+        if !obj.contains_key("generatedDate") {
+            anyhow::bail!("Campo requerido faltante: generatedDate");
         }
 
         if !obj.contains_key("period") {
@@ -1,6 +1,8 @@
 use anyhow::{Result, Context};
 use serde_json::Value;
+use crate::models::RenderOptions;
 use crate::templates::template_trait::{TypstTemplate, utils};
+use crate::templates::template_labels::Labels;
 use crate::templates::template_models::{InvoiceData, InvoiceItem};
 
 pub struct SimpleInvoiceTemplate;
@@ -29,7 +31,7 @@ impl SimpleInvoiceTemplate {
 }
 
 impl TypstTemplate for SimpleInvoiceTemplate {
-    fn generate(&self, data: &Value) -> Result<String> {
+    fn generate(&self, data: &Value, options: &RenderOptions) -> Result<String> {
         let invoice: InvoiceData = serde_json::from_value(data.clone())
             .context("Error deserializando datos de factura simple")?;
 
@@ -37,23 +39,30 @@ impl TypstTemplate for SimpleInvoiceTemplate {
         let client = &invoice.client_info;
         let totals = &invoice.totals;
 
-        let content = format!(r#"#set document(title: "Factura - {}", author: "{}")
-#set page(paper: "us-letter", margin: 2cm)
+        let company_logo = utils::logo_image(company.logo_path.as_deref(), "height: 50pt")
+            .map(|logo| format!("#{}\n", logo))
+            .unwrap_or_default();
+        let page_setup = utils::page_setup(options, "us-letter", false, "margin: 2cm");
+        let labels = Labels::for_options(options);
+
+        let content = format!(r#"#set document(title: "{invoice_title} - {}", author: "{}")
+{page_setup}
 #set text(font: "Arial", size: 11pt)
 
 // Encabezado
 #align(center)[
+  {company_logo}
   #text(size: 18pt, weight: "bold")[{}]
 
   #text(size: 10pt)[
     {} \
-    Tel: {} | Email: {}
+    {phone}: {} | Email: {}
   ]
 ]
 
 #v(10pt)
 #align(center)[
-  #text(size: 14pt, weight: "bold")[FACTURA]
+  #text(size: 14pt, weight: "bold")[{invoice}]
 ]
 
 #v(15pt)
@@ -62,12 +71,12 @@ impl TypstTemplate for SimpleInvoiceTemplate {
 #grid(
   columns: (1fr, 1fr),
   [
-    #text(weight: "bold")[Factura No:] {} \
-    #text(weight: "bold")[Fecha:] {}
+    #text(weight: "bold")[{invoice_no}:] {} \
+    #text(weight: "bold")[{date}:] {}
   ],
   [
     #align(right)[
-      #text(weight: "bold")[Vencimiento:] {}
+      #text(weight: "bold")[{due_date}:] {}
     ]
   ]
 )
@@ -76,8 +85,8 @@ impl TypstTemplate for SimpleInvoiceTemplate {
 
 // Información del cliente
 #rect(width: 100%, fill: rgb(245, 245, 245), stroke: 0.5pt + gray, radius: 3pt, inset: 10pt)[
-  #text(weight: "bold")[Cliente:] {} \
-  #text(weight: "bold")[RNC/ID:] {} \
+  #text(weight: "bold")[{client}:] {} \
+  #text(weight: "bold")[{tax_id}:] {} \
   {}
 ]
 
@@ -91,7 +100,7 @@ impl TypstTemplate for SimpleInvoiceTemplate {
   align: (col, row) => if col == 0 {{ left }} else {{ right }},
   inset: 8pt,
 
-  [*Descripción*], [*Cantidad*], [*Precio*], [*Total*],
+  [*{description}*], [*{quantity}*], [*{price}*], [*{total}*],
 {}
 )
 
@@ -103,9 +112,9 @@ impl TypstTemplate for SimpleInvoiceTemplate {
     columns: (100pt, 80pt),
     row-gutter: 3pt,
     align: (right, right),
-    [Subtotal:], [{} {:.2}],
-    [Impuestos:], [{} {:.2}],
-    [#text(weight: "bold")[Total:]], [#text(weight: "bold")[{} {:.2}]]
+    [{subtotal}:], [{} {:.2}],
+    [{taxes}:], [{} {:.2}],
+    [#text(weight: "bold")[{total}:]], [#text(weight: "bold")[{} {:.2}]]
   )
 ]
 
@@ -113,7 +122,7 @@ impl TypstTemplate for SimpleInvoiceTemplate {
 
 #v(30pt)
 #align(center)[
-  #text(size: 9pt, fill: gray)[¡Gracias por su compra!]
+  #text(size: 9pt, fill: gray)[{thanks}]
 ]"#,
             // Metadata
             invoice.invoice_number,
@@ -131,7 +140,7 @@ impl TypstTemplate for SimpleInvoiceTemplate {
             utils::escape_typst(&client.name),
             client.tax_id,
             if let Some(address) = &client.address {
-                format!("Dirección: {}", utils::escape_typst(&format!("{}, {}",
+                format!("{}: {}", labels.get("address"), utils::escape_typst(&format!("{}, {}",
                     address.street, address.city)))
             } else {
                 String::new()
@@ -142,12 +151,27 @@ impl TypstTemplate for SimpleInvoiceTemplate {
             totals.currency, totals.subtotal,
             totals.currency, totals.tax_amount,
             totals.currency, totals.total,
-            // Notes
+            // Notes y campos propios del tenant
             if let Some(notes) = &invoice.notes {
-                format!("\n#v(15pt)\n#text(size: 10pt)[*Notas:* {}]", utils::escape_typst(notes))
+                format!("\n#v(15pt)\n#text(size: 10pt)[*{}:* {}]", labels.get("notes"), utils::escape_typst(notes))
             } else {
                 String::new()
-            }
+            } + &utils::custom_fields_block(data, "10pt"),
+            invoice_title = labels.plain("invoice_title"),
+            phone = labels.get("phone"),
+            invoice = labels.get("invoice"),
+            invoice_no = labels.get("invoice_no"),
+            date = labels.get("date"),
+            due_date = labels.get("due_date"),
+            client = labels.get("client"),
+            tax_id = labels.get("tax_id"),
+            description = labels.get("description"),
+            quantity = labels.get("quantity"),
+            price = labels.get("price"),
+            total = labels.get("total"),
+            subtotal = labels.get("subtotal"),
+            taxes = labels.get("taxes"),
+            thanks = labels.get("thanks"),
         );
 
         Ok(content)
@@ -163,7 +187,7 @@ impl TypstTemplate for SimpleInvoiceTemplate {
         }
 
         let obj = data.as_object().unwrap();
-        let required = vec!["invoice_number", "company_info", "client_info", "items", "totals"];
+        let required = vec!["invoiceNumber", "companyInfo", "clientInfo", "items", "totals"];
 
         for field in required {
             if !obj.contains_key(field) {
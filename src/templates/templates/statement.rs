@@ -0,0 +1,264 @@
+use anyhow::{Result, Context};
+use chrono::NaiveDate;
+use serde_json::Value;
+use crate::models::RenderOptions;
+use crate::templates::template_trait::{TypstTemplate, utils};
+use crate::templates::template_models::{AgingBuckets, StatementData, StatementInvoice, StatementPayment};
+
+#[derive(Default)]
+pub struct StatementTemplate;
+
+fn parse_date(value: &str, field: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("Fecha inválida en {}: '{}' (se espera YYYY-MM-DD)", field, value))
+}
+
+/// Antigüedad de saldos a la fecha de corte: usa `aging` si viene en los datos,
+/// si no reparte el saldo de cada factura según sus días de vencida
+pub fn aging_buckets(statement: &StatementData) -> Result<AgingBuckets> {
+    if let Some(aging) = &statement.aging {
+        return Ok(aging.clone());
+    }
+
+    let cutoff = parse_date(&statement.statement_date, "statementDate")?;
+    let mut buckets = AgingBuckets::default();
+    for invoice in &statement.invoices {
+        let due = parse_date(&invoice.due_date, &format!("dueDate de {}", invoice.invoice_number))?;
+        match (cutoff - due).num_days() {
+            ..=30 => buckets.current += invoice.balance,
+            31..=60 => buckets.days_31_60 += invoice.balance,
+            61..=90 => buckets.days_61_90 += invoice.balance,
+            _ => buckets.over_90 += invoice.balance,
+        }
+    }
+    Ok(buckets)
+}
+
+impl StatementTemplate {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn format_invoices(&self, invoices: &[StatementInvoice]) -> String {
+        invoices
+            .iter()
+            .map(|invoice| {
+                format!(
+                    "  [{}], [{}], [{}], [{:.2}], [{:.2}]",
+                    utils::escape_typst(&invoice.invoice_number),
+                    invoice.issue_date,
+                    invoice.due_date,
+                    invoice.amount,
+                    invoice.balance
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n")
+    }
+
+    /// Tabla de pagos del período; vacía si no hay
+    fn format_payments(&self, payments: &[StatementPayment], currency: &str) -> String {
+        if payments.is_empty() {
+            return String::new();
+        }
+
+        let rows = payments
+            .iter()
+            .map(|payment| {
+                format!(
+                    "  [{}], [{}], [{}], [{:.2}]",
+                    payment.date,
+                    utils::escape_typst(&payment.reference),
+                    utils::escape_typst(payment.method.as_deref().unwrap_or("")),
+                    payment.amount
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        format!(r#"
+#v(15pt)
+#text(weight: "bold")[Pagos recibidos]
+#table(
+  columns: (80pt, 1fr, 100pt, 90pt),
+  stroke: 0.5pt + gray,
+  fill: (x, y) => if y == 0 {{ rgb(230, 230, 230) }} else {{ white }},
+  align: (col, row) => if col == 3 {{ right }} else {{ left }},
+  inset: 6pt,
+  [*Fecha*], [*Referencia*], [*Método*], [*Monto ({})*],
+{}
+)"#,
+            currency, rows
+        )
+    }
+
+    fn format_aging(&self, aging: &AgingBuckets, currency: &str) -> String {
+        format!(r#"#table(
+  columns: (1fr, 1fr, 1fr, 1fr, 1fr),
+  stroke: 0.5pt + gray,
+  fill: (x, y) => if y == 0 {{ rgb(230, 230, 230) }} else {{ white }},
+  align: center,
+  inset: 8pt,
+  [*0-30 días*], [*31-60 días*], [*61-90 días*], [*Más de 90*], [*Total*],
+  [{} {:.2}], [{} {:.2}], [{} {:.2}], [{} {:.2}], [#text(weight: "bold")[{} {:.2}]]
+)"#,
+            currency, aging.current,
+            currency, aging.days_31_60,
+            currency, aging.days_61_90,
+            currency, aging.over_90,
+            currency, aging.total()
+        )
+    }
+}
+
+impl TypstTemplate for StatementTemplate {
+    fn generate(&self, data: &Value, options: &RenderOptions) -> Result<String> {
+        let statement: StatementData = serde_json::from_value(data.clone())
+            .context("Error deserializando datos de estado de cuenta")?;
+
+        let company = &statement.company_info;
+        let client = &statement.client_info;
+        let currency = utils::escape_typst(&statement.currency);
+        let aging = aging_buckets(&statement)?;
+        let balance_due: f64 = statement.invoices.iter().map(|invoice| invoice.balance).sum();
+        let paid: f64 = statement.payments.iter().map(|payment| payment.amount).sum();
+
+        let company_logo = utils::logo_image(company.logo_path.as_deref(), "height: 50pt")
+            .map(|logo| format!("#{}\n", logo))
+            .unwrap_or_default();
+        let page_setup = utils::page_setup(options, "us-letter", false, "margin: 2cm");
+
+        let content = format!(r#"#set document(title: "Estado de Cuenta - {}", author: "{}")
+{page_setup}
+#set text(font: "Arial", size: 10pt)
+
+// Encabezado
+#align(center)[
+  {company_logo}
+  #text(size: 18pt, weight: "bold")[{}]
+
+  #text(size: 10pt)[
+    {} \
+    RNC: {} | Tel: {}
+  ]
+]
+
+#v(10pt)
+#align(center)[
+  #text(size: 14pt, weight: "bold")[ESTADO DE CUENTA]
+]
+
+#v(15pt)
+
+// Información del estado
+#grid(
+  columns: (1fr, 1fr),
+  [
+    #text(weight: "bold")[Cliente:] {} \
+    #text(weight: "bold")[RNC/ID:] {}
+  ],
+  [
+    #align(right)[
+      #text(weight: "bold")[No.:] {} \
+      #text(weight: "bold")[Fecha de corte:] {}{}
+    ]
+  ]
+)
+
+#v(15pt)
+
+// Facturas abiertas
+#text(weight: "bold")[Facturas pendientes]
+#table(
+  columns: (1fr, 80pt, 80pt, 90pt, 90pt),
+  stroke: 0.5pt + gray,
+  fill: (x, y) => if y == 0 {{ rgb(230, 230, 230) }} else {{ white }},
+  align: (col, row) => if col < 3 {{ left }} else {{ right }},
+  inset: 6pt,
+  [*Factura*], [*Emisión*], [*Vence*], [*Monto*], [*Saldo*],
+{}
+)
+{}
+
+#v(15pt)
+
+// Antigüedad de saldos
+#text(weight: "bold")[Antigüedad de saldos]
+{}
+
+#v(15pt)
+
+// Resumen
+#align(right)[
+  #grid(
+    columns: (120pt, 100pt),
+    row-gutter: 3pt,
+    align: (right, right),
+    [Saldo anterior:], [{} {:.2}],
+    [Pagos del período:], [{} {:.2}],
+    [#text(weight: "bold")[Saldo pendiente:]], [#text(weight: "bold")[{} {:.2}]]
+  )
+]
+{}"#,
+            // Metadata
+            statement.statement_number,
+            company.name,
+            // Header
+            utils::escape_typst(&company.name),
+            utils::escape_typst(&format!("{}, {}", company.address.city, company.address.country)),
+            company.tax_id,
+            company.phone.as_deref().unwrap_or(""),
+            // Statement info
+            utils::escape_typst(&client.name),
+            client.tax_id,
+            statement.statement_number,
+            statement.statement_date,
+            match &statement.period {
+                Some(period) => format!(" \\\n      #text(weight: \"bold\")[Período:] {} al {}", period.start_date, period.end_date),
+                None => String::new(),
+            },
+            // Invoices and payments
+            self.format_invoices(&statement.invoices),
+            self.format_payments(&statement.payments, &currency),
+            // Aging
+            self.format_aging(&aging, &currency),
+            // Summary
+            currency, statement.opening_balance,
+            currency, paid,
+            currency, balance_due,
+            // Notes
+            if let Some(notes) = &statement.notes {
+                format!("\n#v(15pt)\n#text(size: 9pt)[*Notas:* {}]", utils::escape_typst(notes))
+            } else {
+                String::new()
+            }
+        );
+
+        Ok(content)
+    }
+
+    fn template_id(&self) -> &str {
+        "statement"
+    }
+
+    fn validate(&self, data: &Value) -> Result<()> {
+        if !data.is_object() {
+            anyhow::bail!("Los datos deben ser un objeto JSON");
+        }
+
+        let obj = data.as_object().unwrap();
+        let required = vec!["statementNumber", "statementDate", "companyInfo", "clientInfo", "currency", "invoices"];
+
+        for field in required {
+            if !obj.contains_key(field) {
+                anyhow::bail!("Campo requerido faltante: {}", field);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn description(&self) -> &str {
+        "Estado de cuenta"
+    }
+}
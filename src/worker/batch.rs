@@ -0,0 +1,322 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+use crate::models::DocumentStatus;
+
+/// Documentos por evento de progreso si la suscripción no lo indica
+pub const DEFAULT_EVENT_EVERY: usize = 10;
+
+/// Lotes terminados que se conservan para consulta (los más viejos se descartan)
+const MAX_FINISHED_BATCHES: usize = 1_000;
+
+/// Suscripción a los eventos de un lote: en vez de un callback por documento
+/// se envía un evento cada `every` documentos terminados y un resumen final
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchSubscription {
+    pub url: String,
+    #[serde(default = "default_event_every")]
+    pub every: usize,
+}
+
+fn default_event_every() -> usize {
+    DEFAULT_EVENT_EVERY
+}
+
+/// Resultado de un documento del lote, tal como va en los eventos
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchItem {
+    pub id: Uuid,
+    pub status: DocumentStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Fila del lote rechazada al recibirlo (JSON o validación inválidos); no se genera
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchRowError {
+    /// Posición en `documents` (desde 0)
+    pub index: usize,
+    pub document_id: Option<Uuid>,
+    pub error: String,
+}
+
+/// Estado del lote: `partial_success` si terminó con filas rechazadas o
+/// documentos fallidos pero al menos uno generado
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Processing,
+    Completed,
+    PartialSuccess,
+    Failed,
+}
+
+/// Zip con los documentos del lote (`archive: true`), se arma al terminar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveStatus {
+    Pending,
+    Building,
+    Ready,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchSummary {
+    pub id: Uuid,
+    pub status: BatchStatus,
+    /// Documentos encolados (sin contar los rechazados)
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub rejected: usize,
+    pub finished: bool,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub subscription: Option<BatchSubscription>,
+    pub document_ids: Vec<Uuid>,
+    pub errors: Vec<BatchRowError>,
+    /// Clave de `errors.xlsx` en el bucket de documentos
+    #[serde(skip)]
+    pub errors_key: Option<String>,
+    /// `None` si el lote no pidió zip
+    pub archive: Option<ArchiveStatus>,
+    /// Clave de `documents.zip` en el bucket de documentos
+    #[serde(skip)]
+    pub archive_key: Option<String>,
+}
+
+struct Batch {
+    tenant_id: i64,
+    document_ids: Vec<Uuid>,
+    subscription: Option<BatchSubscription>,
+    /// Resultados aún no enviados en un evento
+    pending: Vec<BatchItem>,
+    completed: usize,
+    failed: usize,
+    rejected: Vec<BatchRowError>,
+    errors_key: Option<String>,
+    archive: Option<ArchiveStatus>,
+    archive_key: Option<String>,
+    sequence: u32,
+    created_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    /// Serializa las entregas del lote para que los eventos lleguen en orden
+    delivery: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Batch {
+    fn status(&self) -> BatchStatus {
+        if self.finished_at.is_none() {
+            BatchStatus::Processing
+        } else if self.completed == 0 {
+            BatchStatus::Failed
+        } else if self.failed == 0 && self.rejected.is_empty() {
+            BatchStatus::Completed
+        } else {
+            BatchStatus::PartialSuccess
+        }
+    }
+}
+
+/// Evento listo para entregar a la suscripción del lote
+pub struct BatchEvent {
+    pub batch_id: Uuid,
+    pub tenant_id: i64,
+    pub url: String,
+    pub payload: Value,
+}
+
+/// Lotes de generación asíncrona en memoria y el lote de cada documento
+#[derive(Default)]
+pub struct BatchStore {
+    inner: RwLock<BatchInner>,
+}
+
+#[derive(Default)]
+struct BatchInner {
+    batches: HashMap<Uuid, Batch>,
+    by_document: HashMap<Uuid, Uuid>,
+    finished: VecDeque<Uuid>,
+}
+
+impl BatchStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create(
+        &self,
+        tenant_id: i64,
+        document_ids: Vec<Uuid>,
+        rejected: Vec<BatchRowError>,
+        subscription: Option<BatchSubscription>,
+        archive: bool,
+    ) -> Uuid {
+        let id = Uuid::new_v4();
+        let mut inner = self.inner.write().unwrap();
+
+        for document_id in &document_ids {
+            inner.by_document.insert(*document_id, id);
+        }
+        inner.batches.insert(id, Batch {
+            tenant_id,
+            document_ids,
+            subscription,
+            pending: Vec::new(),
+            completed: 0,
+            failed: 0,
+            rejected,
+            errors_key: None,
+            archive: archive.then_some(ArchiveStatus::Pending),
+            archive_key: None,
+            sequence: 0,
+            created_at: Utc::now(),
+            finished_at: None,
+            delivery: Arc::new(tokio::sync::Mutex::new(())),
+        });
+
+        id
+    }
+
+    pub fn get(&self, id: &Uuid, tenant_id: i64) -> Option<BatchSummary> {
+        let inner = self.inner.read().unwrap();
+        let batch = inner.batches.get(id).filter(|b| b.tenant_id == tenant_id)?;
+
+        Some(BatchSummary {
+            id: *id,
+            status: batch.status(),
+            total: batch.document_ids.len(),
+            completed: batch.completed,
+            failed: batch.failed,
+            rejected: batch.rejected.len(),
+            finished: batch.finished_at.is_some(),
+            created_at: batch.created_at,
+            finished_at: batch.finished_at,
+            subscription: batch.subscription.clone(),
+            document_ids: batch.document_ids.clone(),
+            errors: batch.rejected.clone(),
+            errors_key: batch.errors_key.clone(),
+            archive: batch.archive,
+            archive_key: batch.archive_key.clone(),
+        })
+    }
+
+    /// Registra la clave del `errors.xlsx` subido para el lote
+    pub fn set_errors_key(&self, id: &Uuid, key: String) {
+        if let Some(batch) = self.inner.write().unwrap().batches.get_mut(id) {
+            batch.errors_key = Some(key);
+        }
+    }
+
+    /// Si el lote del documento terminó y pidió zip, lo marca en construcción
+    /// y retorna su id; así solo se arma una vez
+    pub fn start_archive(&self, document_id: &Uuid) -> Option<Uuid> {
+        let mut inner = self.inner.write().unwrap();
+        let batch_id = inner.by_document.get(document_id).copied()?;
+        let batch = inner.batches.get_mut(&batch_id)?;
+        if batch.finished_at.is_none() || batch.archive != Some(ArchiveStatus::Pending) {
+            return None;
+        }
+        batch.archive = Some(ArchiveStatus::Building);
+        Some(batch_id)
+    }
+
+    /// Registra el zip subido (`None` si falló) y retorna el evento
+    /// `batch.archived` para la suscripción del lote, si tiene
+    pub fn finish_archive(&self, id: &Uuid, key: Option<String>) -> Option<BatchEvent> {
+        let mut inner = self.inner.write().unwrap();
+        let batch = inner.batches.get_mut(id)?;
+        let status = if key.is_some() { ArchiveStatus::Ready } else { ArchiveStatus::Failed };
+        batch.archive = Some(status);
+        batch.archive_key = key;
+
+        let subscription = batch.subscription.clone()?;
+        batch.sequence += 1;
+        Some(BatchEvent {
+            batch_id: *id,
+            tenant_id: batch.tenant_id,
+            url: subscription.url,
+            payload: json!({
+                "event": "batch.archived",
+                "batch_id": id,
+                "sequence": batch.sequence,
+                "archive": status
+            }),
+        })
+    }
+
+    /// Candado de entrega del lote del documento; se toma antes de `record`
+    /// y se suelta después de entregar sus eventos
+    pub fn delivery_lock(&self, document_id: &Uuid) -> Option<Arc<tokio::sync::Mutex<()>>> {
+        let inner = self.inner.read().unwrap();
+        let batch_id = inner.by_document.get(document_id)?;
+        inner.batches.get(batch_id).map(|b| b.delivery.clone())
+    }
+
+    /// Registra el resultado final de un documento y retorna los eventos que
+    /// corresponde enviar: progreso cada `every` documentos y el resumen al terminar.
+    /// `None` si el documento no pertenece a un lote
+    pub fn record(&self, item: BatchItem) -> Option<Vec<BatchEvent>> {
+        let mut inner = self.inner.write().unwrap();
+        let batch_id = inner.by_document.get(&item.id).copied()?;
+        let batch = inner.batches.get_mut(&batch_id)?;
+        if batch.finished_at.is_some() {
+            return Some(Vec::new());
+        }
+
+        match item.status {
+            DocumentStatus::Completed => batch.completed += 1,
+            _ => batch.failed += 1,
+        }
+        batch.pending.push(item);
+
+        let total = batch.document_ids.len();
+        let done = batch.completed + batch.failed >= total;
+        if done {
+            batch.finished_at = Some(Utc::now());
+        }
+
+        let mut events = Vec::new();
+        if let Some(subscription) = batch.subscription.clone() {
+            if done || batch.pending.len() >= subscription.every.max(1) {
+                batch.sequence += 1;
+                let documents = std::mem::take(&mut batch.pending);
+                events.push(BatchEvent {
+                    batch_id,
+                    tenant_id: batch.tenant_id,
+                    url: subscription.url,
+                    payload: json!({
+                        "event": if done { "batch.completed" } else { "batch.progress" },
+                        "batch_id": batch_id,
+                        "sequence": batch.sequence,
+                        "status": batch.status(),
+                        "total": total,
+                        "completed": batch.completed,
+                        "failed": batch.failed,
+                        "rejected": batch.rejected.len(),
+                        "documents": documents
+                    }),
+                });
+            }
+        }
+
+        if done {
+            inner.finished.push_back(batch_id);
+            while inner.finished.len() > MAX_FINISHED_BATCHES {
+                let Some(oldest) = inner.finished.pop_front() else { break };
+                if let Some(old) = inner.batches.remove(&oldest) {
+                    for document_id in old.document_ids {
+                        inner.by_document.remove(&document_id);
+                    }
+                }
+            }
+        }
+
+        Some(events)
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde_json::Value;
+use std::io::Write;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::storage::storage_trait::Storage;
+
+/// Tamaño de cada parte del upload (S3 pide al menos 5 MB salvo la última)
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Documento del lote que va dentro del zip
+#[derive(Debug, Clone)]
+pub struct ArchiveFile {
+    /// Nombre dentro del zip
+    pub name: String,
+    pub bucket: String,
+    pub key: String,
+}
+
+/// Arma el zip de un lote mientras lo sube: cada documento se lee del
+/// almacenamiento y se escribe de a uno, y el zip sale en partes de
+/// `PART_SIZE`, así la memoria no crece con el tamaño del lote. `manifest.json`
+/// va al final
+pub async fn write_archive(
+    storage: Arc<dyn Storage>,
+    bucket: &str,
+    key: &str,
+    files: Vec<ArchiveFile>,
+    manifest: &Value,
+) -> Result<()> {
+    let manifest = serde_json::to_vec_pretty(manifest)?;
+    let (tx, rx) = mpsc::channel::<Result<Bytes>>(2);
+    let runtime = tokio::runtime::Handle::current();
+    let source = storage.clone();
+
+    let writer = tokio::task::spawn_blocking(move || {
+        let failed = tx.clone();
+        let result = (|| -> Result<()> {
+            let mut zip = ZipWriter::new_stream(PartSink { buffer: Vec::with_capacity(PART_SIZE), parts: tx });
+            let options = SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .large_file(true);
+
+            for file in &files {
+                let bytes = runtime
+                    .block_on(source.get(&file.bucket, &file.key))
+                    .with_context(|| format!("Failed to read {} for the archive", file.key))?;
+                zip.start_file(file.name.as_str(), options)?;
+                zip.write_all(&bytes)?;
+            }
+            zip.start_file("manifest.json", options)?;
+            zip.write_all(&manifest)?;
+            zip.finish()?;
+            Ok(())
+        })();
+
+        // El error corta el upload antes de completarlo
+        if let Err(e) = &result {
+            let _ = failed.blocking_send(Err(anyhow::anyhow!("{:#}", e)));
+        }
+        result
+    });
+
+    let parts = futures::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|part| (part, rx)) });
+    let uploaded = storage.put_stream(bucket, key, parts.boxed(), "application/zip").await;
+    let written = writer.await?;
+    written.and(uploaded)
+}
+
+/// Destino del zip: junta lo escrito y lo envía en partes de `PART_SIZE`; la
+/// última sale al soltarlo
+struct PartSink {
+    buffer: Vec<u8>,
+    parts: mpsc::Sender<Result<Bytes>>,
+}
+
+impl PartSink {
+    fn send(&mut self) -> std::io::Result<()> {
+        let part = std::mem::replace(&mut self.buffer, Vec::with_capacity(PART_SIZE));
+        self.parts
+            .blocking_send(Ok(Bytes::from(part)))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Archive upload stopped"))
+    }
+}
+
+impl Write for PartSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= PART_SIZE {
+            self.send()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for PartSink {
+    fn drop(&mut self) {
+        if !self.buffer.is_empty() {
+            let _ = self.send();
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::io::{Cursor, Write};
+use std::sync::{Mutex, RwLock};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::FormatFields;
+use tracing_subscriber::layer::{Context, Layer};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::api::redaction::{redact_json, redact_text, RedactedFields};
+use crate::models::DocumentRequest;
+use crate::storage::document_store::DocumentRecord;
+use crate::worker::webhook::DeliveryAttempt;
+use crate::templates::template_engine::CompileError;
+
+/// Documentos fallidos cuyo diagnóstico se conserva (los más viejos se descartan)
+const MAX_FAILURES: usize = 1_000;
+
+/// Líneas de log recientes que se conservan en memoria
+const MAX_LOG_LINES: usize = 20_000;
+
+/// Últimas líneas de log del proceso, ya redactadas, para los bundles de soporte
+pub static RECENT_LOGS: Lazy<LogBuffer> = Lazy::new(|| LogBuffer::new(MAX_LOG_LINES));
+
+/// Lo que se sabe de la generación fallida de un documento
+#[derive(Debug, Clone, Serialize)]
+pub struct FailureDiagnostics {
+    pub document_id: Uuid,
+    pub captured_at: DateTime<Utc>,
+    /// Request con los valores enmascarados (se conserva la estructura)
+    pub request: Value,
+    pub error: String,
+    /// Fuente Typst generado, si la falla fue al compilar
+    pub typst_source: Option<String>,
+    pub compiler_stderr: Option<String>,
+}
+
+impl FailureDiagnostics {
+    pub fn new(request: &DocumentRequest, error: &anyhow::Error) -> Self {
+        let compile = error.downcast_ref::<CompileError>();
+
+        FailureDiagnostics {
+            document_id: request.id,
+            captured_at: Utc::now(),
+            request: serde_json::to_value(request).map(|v| redact_json(&v)).unwrap_or(Value::Null),
+            error: redact_text(&format!("{:#}", error)),
+            // Typst escapa `@`; sin quitar el escape los emails no se detectan
+            typst_source: compile.map(|e| redact_text(&e.typst_source.replace("\\@", "@"))),
+            compiler_stderr: compile.map(|e| redact_text(&e.stderr)),
+        }
+    }
+}
+
+/// Diagnósticos de los últimos documentos fallidos, por id
+#[derive(Default)]
+pub struct DiagnosticsStore {
+    inner: RwLock<DiagnosticsInner>,
+}
+
+#[derive(Default)]
+struct DiagnosticsInner {
+    failures: HashMap<Uuid, FailureDiagnostics>,
+    order: VecDeque<Uuid>,
+}
+
+impl DiagnosticsStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registra (o reemplaza) el diagnóstico de un documento
+    pub fn record(&self, diagnostics: FailureDiagnostics) {
+        let mut inner = self.inner.write().unwrap();
+        let id = diagnostics.document_id;
+
+        if inner.failures.insert(id, diagnostics).is_none() {
+            inner.order.push_back(id);
+        }
+        while inner.order.len() > MAX_FAILURES {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.failures.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn get(&self, document_id: &Uuid) -> Option<FailureDiagnostics> {
+        self.inner.read().unwrap().failures.get(document_id).cloned()
+    }
+}
+
+/// Buffer circular de líneas de log
+pub struct LogBuffer {
+    lines: Mutex<VecDeque<String>>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        LogBuffer {
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    pub fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// Líneas que mencionan `needle`, en orden
+    pub fn matching(&self, needle: &str) -> Vec<String> {
+        self.lines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.contains(needle))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Capa de `tracing_subscriber` que copia cada evento (redactado, aunque
+/// `LOG_REDACTION=false`) a `RECENT_LOGS`
+pub struct LogCaptureLayer;
+
+impl<S: Subscriber> Layer<S> for LogCaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = String::new();
+        if RedactedFields.format_fields(Writer::new(&mut fields), event).is_err() {
+            return;
+        }
+
+        let metadata = event.metadata();
+        RECENT_LOGS.push(format!(
+            "{} {:>5} {}: {}",
+            Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            metadata.level(),
+            metadata.target(),
+            fields
+        ));
+    }
+}
+
+/// Arma el zip de soporte de un documento fallido: `document.json` (estado y
+/// tiempos por etapa), `request.json`, `error.txt`, `source.typ` y
+/// `compiler_stderr.txt` (si falló al compilar), `worker.log` y `webhooks.json`
+pub fn support_bundle(
+    record: &DocumentRecord,
+    diagnostics: Option<&FailureDiagnostics>,
+    log_lines: &[String],
+    deliveries: &[DeliveryAttempt],
+) -> anyhow::Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut add = |name: &str, contents: &[u8]| -> anyhow::Result<()> {
+        zip.start_file(name, options)?;
+        zip.write_all(contents)?;
+        Ok(())
+    };
+
+    add("document.json", &serde_json::to_vec_pretty(record)?)?;
+    match diagnostics {
+        Some(diagnostics) => {
+            add("request.json", &serde_json::to_vec_pretty(&diagnostics.request)?)?;
+            add("error.txt", diagnostics.error.as_bytes())?;
+            if let Some(source) = &diagnostics.typst_source {
+                add("source.typ", source.as_bytes())?;
+            }
+            if let Some(stderr) = &diagnostics.compiler_stderr {
+                add("compiler_stderr.txt", stderr.as_bytes())?;
+            }
+        },
+        // Falló antes del último reinicio o ya salió del buffer
+        None => add("error.txt", record.error.as_deref().unwrap_or_default().as_bytes())?,
+    }
+    add("worker.log", log_lines.join("\n").as_bytes())?;
+    add("webhooks.json", &serde_json::to_vec_pretty(deliveries)?)?;
+
+    Ok(zip.finish()?.into_inner())
+}
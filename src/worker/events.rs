@@ -0,0 +1,214 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::storage::database::Database;
+use crate::storage::document_store::DocumentRecord;
+use crate::storage::redis_pool::RedisPool;
+
+/// Versión del esquema de `DocumentEvent`; cambia solo con cambios incompatibles
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// Eventos que se conservan en memoria para replay (los más viejos se descartan)
+const MAX_EVENTS: usize = 50_000;
+
+/// Espera entre reintentos de escritura en Postgres
+const SINK_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+const INSERT_EVENT: &str = "INSERT INTO document_events
+    (event_id, event_type, schema_version, document_id, tenant_id, organization_id, template_id, occurred_at, data)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+    ON CONFLICT (event_id) DO NOTHING";
+
+/// Etapa del ciclo de vida de un documento
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventType {
+    Created,
+    Queued,
+    Started,
+    Completed,
+    Failed,
+    Downloaded,
+}
+
+impl EventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventType::Created => "created",
+            EventType::Queued => "queued",
+            EventType::Started => "started",
+            EventType::Completed => "completed",
+            EventType::Failed => "failed",
+            EventType::Downloaded => "downloaded",
+        }
+    }
+}
+
+/// Evento del ciclo de vida; `data` depende del tipo (intento, tamaño, error, usuario)
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentEvent {
+    /// Posición en el log de este proceso (cursor de `GET /api/v1/events`)
+    pub sequence: u64,
+    pub event_id: Uuid,
+    pub event_type: EventType,
+    pub schema_version: u32,
+    pub document_id: Uuid,
+    pub tenant_id: i64,
+    pub organization_id: String,
+    pub template_id: String,
+    pub occurred_at: DateTime<Utc>,
+    pub data: Value,
+}
+
+/// Log de eventos de documentos: los últimos en memoria para replay y, con
+/// `DATABASE_URL`, copia en la tabla `document_events` de Postgres.
+/// Con Redis además se publican en `documents:events:{tenant_id}` para
+/// seguir el progreso desde otras réplicas
+pub struct EventLog {
+    inner: RwLock<EventsInner>,
+    sink: Option<mpsc::UnboundedSender<DocumentEvent>>,
+    publisher: Option<mpsc::UnboundedSender<DocumentEvent>>,
+}
+
+#[derive(Default)]
+struct EventsInner {
+    events: VecDeque<DocumentEvent>,
+    next_sequence: u64,
+}
+
+impl EventLog {
+    /// Con el pool compartido los eventos se copian a Postgres; sin él solo en memoria
+    pub fn new(database: Option<Database>) -> Self {
+        EventLog {
+            inner: RwLock::new(EventsInner { events: VecDeque::new(), next_sequence: 1 }),
+            sink: database.map(spawn_postgres_sink),
+            publisher: None,
+        }
+    }
+
+    /// Publica cada evento en Redis, en orden; si Redis falla el evento se
+    /// pierde solo para los suscriptores (queda en memoria y en Postgres)
+    pub fn with_publisher(mut self, redis: Arc<RedisPool>) -> Self {
+        self.publisher = Some(spawn_redis_publisher(redis));
+        self
+    }
+
+    pub fn emit(&self, event_type: EventType, record: &DocumentRecord, data: Value) {
+        let event = {
+            let mut inner = self.inner.write().unwrap();
+            let event = DocumentEvent {
+                sequence: inner.next_sequence,
+                event_id: Uuid::new_v4(),
+                event_type,
+                schema_version: EVENT_SCHEMA_VERSION,
+                document_id: record.id,
+                tenant_id: record.tenant_id,
+                organization_id: record.organization_id.clone(),
+                template_id: record.template_id.clone(),
+                occurred_at: Utc::now(),
+                data,
+            };
+            inner.next_sequence += 1;
+            if inner.events.len() == MAX_EVENTS {
+                inner.events.pop_front();
+            }
+            inner.events.push_back(event.clone());
+            event
+        };
+
+        if let Some(sink) = &self.sink {
+            if sink.send(event.clone()).is_err() {
+                tracing::error!("Document event sink is closed; event kept in memory only");
+            }
+        }
+        if let Some(publisher) = &self.publisher {
+            let _ = publisher.send(event);
+        }
+    }
+
+    /// Eventos del tenant posteriores a `after`, en orden, hasta `limit`
+    pub fn since(&self, tenant_id: i64, after: u64, limit: usize) -> Vec<DocumentEvent> {
+        self.inner
+            .read()
+            .unwrap()
+            .events
+            .iter()
+            .filter(|e| e.sequence > after && e.tenant_id == tenant_id)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Escribe los eventos en Postgres en orden; ante errores reintenta el mismo
+/// evento con otra conexión del pool (el insert es idempotente por `event_id`)
+fn spawn_postgres_sink(database: Database) -> mpsc::UnboundedSender<DocumentEvent> {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<DocumentEvent>();
+
+    tokio::spawn(async move {
+        while let Some(event) = receiver.recv().await {
+            loop {
+                let db = match database.get().await {
+                    Ok(db) => db,
+                    Err(e) => {
+                        tracing::warn!("Failed to connect to the events database: {}", e);
+                        tokio::time::sleep(SINK_RETRY_DELAY).await;
+                        continue;
+                    },
+                };
+
+                let result = db.execute(INSERT_EVENT, &[
+                    &event.event_id,
+                    &event.event_type.as_str(),
+                    &(event.schema_version as i32),
+                    &event.document_id,
+                    &event.tenant_id,
+                    &event.organization_id,
+                    &event.template_id,
+                    &event.occurred_at,
+                    &event.data,
+                ]).await;
+
+                match result {
+                    Ok(_) => break,
+                    Err(e) => {
+                        tracing::warn!("Failed to write document event {}: {}", event.event_id, e);
+                        tokio::time::sleep(SINK_RETRY_DELAY).await;
+                    },
+                }
+            }
+        }
+    });
+
+    sender
+}
+
+fn spawn_redis_publisher(redis: Arc<RedisPool>) -> mpsc::UnboundedSender<DocumentEvent> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<DocumentEvent>();
+
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            let payload = match serde_json::to_string(&event) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    tracing::warn!("Failed to serialize document event {}: {}", event.event_id, e);
+                    continue;
+                },
+            };
+            let channel = format!("documents:events:{}", event.tenant_id);
+            let publish = deadpool_redis::redis::cmd("PUBLISH").arg(&channel).arg(payload).to_owned();
+            if let Err(e) = redis.query::<i64>(&publish).await {
+                tracing::warn!("Failed to publish document event {}: {:#}", event.event_id, e);
+            }
+        }
+    });
+
+    tx
+}
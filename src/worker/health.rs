@@ -0,0 +1,251 @@
+use actix_web::{web, App, HttpResponse, HttpServer};
+use serde::Serialize;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::api::state::MaintenanceState;
+use crate::storage::redis_pool::RedisPool;
+use crate::storage::storage_trait::Storage;
+
+/// Intervalo del latido del runtime principal
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Salud del procesamiento en segundo plano: latido del runtime que ejecuta
+/// los trabajos, estado del storage y trabajos en curso. Se sirve desde un
+/// listener con runtime propio para seguir respondiendo si el principal se traba
+pub struct WorkerHealth {
+    started_at: Instant,
+    last_heartbeat_ms: AtomicU64,
+    max_stall: Duration,
+    storage_ok: AtomicBool,
+    storage_error: RwLock<Option<String>>,
+    /// Error del último sondeo a Redis; `None` si respondió o no está configurado
+    redis_error: RwLock<Option<String>>,
+    in_flight: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+    completed: AtomicU64,
+}
+
+/// Estado de concurrencia de los trabajos
+#[derive(Debug, Clone, Serialize)]
+pub struct ConcurrencyStats {
+    pub in_flight: usize,
+    pub peak_in_flight: usize,
+    pub completed: u64,
+}
+
+/// Lugar ocupado por un trabajo en curso; se libera al descartarse
+pub struct JobSlot {
+    health: Arc<WorkerHealth>,
+}
+
+impl Drop for JobSlot {
+    fn drop(&mut self) {
+        self.health.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.health.completed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl WorkerHealth {
+    pub fn new(max_stall: Duration) -> Self {
+        WorkerHealth {
+            started_at: Instant::now(),
+            last_heartbeat_ms: AtomicU64::new(0),
+            max_stall,
+            storage_ok: AtomicBool::new(false),
+            storage_error: RwLock::new(Some("storage not probed yet".to_string())),
+            redis_error: RwLock::new(None),
+            in_flight: AtomicUsize::new(0),
+            peak_in_flight: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+        }
+    }
+
+    /// `WORKER_LIVENESS_MAX_STALL_SECS` (por defecto 10)
+    pub fn from_env() -> Self {
+        let max_stall = std::env::var("WORKER_LIVENESS_MAX_STALL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10);
+
+        Self::new(Duration::from_secs(max_stall))
+    }
+
+    /// Registra un trabajo en curso mientras viva el `JobSlot`
+    pub fn job_started(self: &Arc<Self>) -> JobSlot {
+        let current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak_in_flight.fetch_max(current, Ordering::SeqCst);
+        JobSlot { health: self.clone() }
+    }
+
+    pub fn concurrency(&self) -> ConcurrencyStats {
+        ConcurrencyStats {
+            in_flight: self.in_flight.load(Ordering::SeqCst),
+            peak_in_flight: self.peak_in_flight.load(Ordering::SeqCst),
+            completed: self.completed.load(Ordering::SeqCst),
+        }
+    }
+
+    fn heartbeat(&self) {
+        self.last_heartbeat_ms.store(self.started_at.elapsed().as_millis() as u64, Ordering::SeqCst);
+    }
+
+    /// Tiempo desde el último latido del runtime principal
+    pub fn stalled_for(&self) -> Duration {
+        let now = self.started_at.elapsed().as_millis() as u64;
+        Duration::from_millis(now.saturating_sub(self.last_heartbeat_ms.load(Ordering::SeqCst)))
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.stalled_for() <= self.max_stall
+    }
+
+    /// Error del último sondeo al storage, `None` si respondió
+    pub fn storage_error(&self) -> Option<String> {
+        if self.storage_ok.load(Ordering::SeqCst) {
+            None
+        } else {
+            self.storage_error.read().unwrap().clone()
+        }
+    }
+
+    pub fn redis_error(&self) -> Option<String> {
+        self.redis_error.read().unwrap().clone()
+    }
+
+    fn record_probe(&self, result: anyhow::Result<()>) {
+        match result {
+            Ok(()) => {
+                self.storage_ok.store(true, Ordering::SeqCst);
+                *self.storage_error.write().unwrap() = None;
+            },
+            Err(e) => {
+                if self.storage_ok.swap(false, Ordering::SeqCst) {
+                    tracing::warn!("Storage health probe failed: {}", e);
+                }
+                *self.storage_error.write().unwrap() = Some(e.to_string());
+            },
+        }
+    }
+
+    /// Latido en el runtime principal y sondeo periódico del storage
+    pub fn spawn_monitor(self: &Arc<Self>, storage: Arc<dyn Storage>, bucket: String, probe_interval_secs: u64) {
+        let health = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                interval.tick().await;
+                health.heartbeat();
+            }
+        });
+
+        let health = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(probe_interval_secs.max(1)));
+            loop {
+                interval.tick().await;
+                let probe = tokio::time::timeout(Duration::from_secs(5), storage.list(&bucket, Some("health/")))
+                    .await
+                    .map_err(anyhow::Error::new)
+                    .and_then(|r| r.map(|_| ()));
+                health.record_probe(probe);
+            }
+        });
+    }
+
+    /// Sondeo periódico del pool de Redis (`PING`)
+    pub fn spawn_redis_probe(self: &Arc<Self>, redis: Arc<RedisPool>, probe_interval_secs: u64) {
+        let health = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(probe_interval_secs.max(1)));
+            loop {
+                interval.tick().await;
+                let error = redis.ping().await.err().map(|e| format!("{:#}", e));
+                let mut current = health.redis_error.write().unwrap();
+                if error.is_some() && current.is_none() {
+                    tracing::warn!("Redis health probe failed: {}", error.as_deref().unwrap_or_default());
+                }
+                *current = error;
+            }
+        });
+    }
+}
+
+#[derive(Clone)]
+struct HealthState {
+    health: Arc<WorkerHealth>,
+    maintenance: Arc<MaintenanceState>,
+}
+
+/// Levanta el listener de salud (`/live`, `/ready`, `/concurrency`) en un hilo
+/// con su propio runtime
+pub fn serve(health: Arc<WorkerHealth>, maintenance: Arc<MaintenanceState>, port: u16) -> std::io::Result<()> {
+    let state = HealthState { health, maintenance };
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(state.clone()))
+            .route("/live", web::get().to(live))
+            .route("/ready", web::get().to(ready))
+            .route("/concurrency", web::get().to(concurrency))
+    })
+    .workers(1)
+    .disable_signals()
+    .bind(("0.0.0.0", port))?
+    .run();
+
+    std::thread::Builder::new()
+        .name("worker-health".to_string())
+        .spawn(move || {
+            if let Err(e) = actix_web::rt::System::new().block_on(server) {
+                tracing::error!("Worker health listener stopped: {}", e);
+            }
+        })?;
+
+    tracing::info!("Worker health listener on port {}", port);
+    Ok(())
+}
+
+async fn live(state: web::Data<HealthState>) -> HttpResponse {
+    let stalled_ms = state.health.stalled_for().as_millis() as u64;
+
+    if state.health.is_live() {
+        HttpResponse::Ok().json(json!({ "status": "live", "heartbeat_age_ms": stalled_ms }))
+    } else {
+        HttpResponse::ServiceUnavailable().json(json!({
+            "status": "stalled",
+            "heartbeat_age_ms": stalled_ms,
+            "max_stall_ms": state.health.max_stall.as_millis() as u64
+        }))
+    }
+}
+
+async fn ready(state: web::Data<HealthState>) -> HttpResponse {
+    let live = state.health.is_live();
+    let storage_error = state.health.storage_error();
+    let redis_error = state.health.redis_error();
+    let maintenance = state.maintenance.is_enabled();
+    let ready = live && storage_error.is_none() && redis_error.is_none() && !maintenance;
+
+    let body = json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "checks": {
+            "event_loop": if live { "ok" } else { "stalled" },
+            "storage": storage_error.as_deref().unwrap_or("ok"),
+            "redis": redis_error.as_deref().unwrap_or("ok"),
+            "accepting_jobs": !maintenance
+        },
+        "concurrency": state.health.concurrency()
+    });
+
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+async fn concurrency(state: web::Data<HealthState>) -> HttpResponse {
+    HttpResponse::Ok().json(state.health.concurrency())
+}
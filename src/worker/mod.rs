@@ -0,0 +1,8 @@
+pub mod retry;
+pub mod webhook;
+pub mod health;
+pub mod queue;
+pub mod diagnostics;
+pub mod batch;
+pub mod batch_archive;
+pub mod events;
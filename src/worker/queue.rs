@@ -0,0 +1,332 @@
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+
+use crate::models::{DocumentRequest, Priority};
+
+/// Ventana del límite por minuto de las plantillas
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Cola en proceso de los trabajos asíncronos: un carril por prioridad y un
+/// límite de trabajos simultáneos. Los de mayor prioridad salen primero, salvo
+/// que su plantilla esté en su propio límite: entonces pasa el siguiente
+pub struct JobQueue {
+    lanes: Mutex<[VecDeque<DocumentRequest>; 3]>,
+    available: Arc<Notify>,
+    slots: Arc<Semaphore>,
+    concurrency: usize,
+    template_limits: HashMap<String, TemplateLimit>,
+    template_usage: Arc<Mutex<HashMap<String, TemplateUsage>>>,
+}
+
+/// Límites de una plantilla costosa, aparte del límite global del pool
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateLimit {
+    /// Trabajos simultáneos de la plantilla
+    pub concurrency: Option<usize>,
+    /// Trabajos que pueden empezar por minuto
+    pub per_minute: Option<usize>,
+}
+
+#[derive(Default)]
+struct TemplateUsage {
+    running: usize,
+    started: VecDeque<Instant>,
+}
+
+/// Lugar ocupado por un trabajo de una plantilla con límites; se libera al descartarlo
+pub struct TemplatePermit {
+    template_id: Option<String>,
+    usage: Arc<Mutex<HashMap<String, TemplateUsage>>>,
+    available: Arc<Notify>,
+}
+
+impl Drop for TemplatePermit {
+    fn drop(&mut self) {
+        let Some(template_id) = &self.template_id else { return };
+        if let Some(usage) = self.usage.lock().unwrap().get_mut(template_id) {
+            usage.running = usage.running.saturating_sub(1);
+        }
+        // Puede haber trabajos de la plantilla esperando este lugar
+        self.available.notify_one();
+    }
+}
+
+/// Resultado de cambiar la prioridad de un trabajo en cola
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reprioritized {
+    /// Se sacó de su carril y se volvió a encolar con la nueva prioridad
+    Moved { previous: String },
+    /// Ya tenía esa prioridad
+    Unchanged,
+    /// No está en cola (en proceso, terminado o inexistente)
+    NotQueued,
+}
+
+impl JobQueue {
+    pub fn new(concurrency: usize) -> Self {
+        let concurrency = concurrency.max(1);
+        JobQueue {
+            lanes: Mutex::new(Default::default()),
+            available: Arc::new(Notify::new()),
+            slots: Arc::new(Semaphore::new(concurrency)),
+            concurrency,
+            template_limits: HashMap::new(),
+            template_usage: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn with_template_limits(mut self, limits: HashMap<String, TemplateLimit>) -> Self {
+        self.template_limits = limits;
+        self
+    }
+
+    /// `WORKER_CONCURRENCY` (por defecto 8) y `TEMPLATE_LIMITS`
+    /// (JSON `{"report": {"concurrency": 2, "per_minute": 30}}`)
+    pub fn from_env() -> anyhow::Result<Self> {
+        let concurrency = std::env::var("WORKER_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(8);
+
+        let limits = match std::env::var("TEMPLATE_LIMITS") {
+            Ok(json) if !json.trim().is_empty() => serde_json::from_str(&json)
+                .map_err(|e| anyhow::anyhow!("Invalid TEMPLATE_LIMITS: {}", e))?,
+            _ => HashMap::new(),
+        };
+
+        Ok(Self::new(concurrency).with_template_limits(limits))
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    pub fn push(&self, request: DocumentRequest) {
+        let lane = lane_of(&request.priority);
+        self.lanes.lock().unwrap()[lane].push_back(request);
+        self.available.notify_one();
+    }
+
+    /// Espera el siguiente trabajo, el más antiguo del carril más prioritario
+    /// cuya plantilla no esté en su límite
+    pub async fn next(&self) -> (DocumentRequest, TemplatePermit) {
+        loop {
+            let (popped, retry_in) = self.pop_eligible();
+            if let Some(next) = popped {
+                return next;
+            }
+
+            match retry_in {
+                // Solo quedan trabajos frenados por el límite por minuto
+                Some(delay) => {
+                    tokio::select! {
+                        _ = self.available.notified() => {},
+                        _ = tokio::time::sleep(delay) => {},
+                    }
+                },
+                None => self.available.notified().await,
+            }
+        }
+    }
+
+    /// Saca el primer trabajo elegible; si no hay, cuánto falta para que el
+    /// límite por minuto de alguna plantilla deje pasar uno
+    fn pop_eligible(&self) -> (Option<(DocumentRequest, TemplatePermit)>, Option<Duration>) {
+        let mut lanes = self.lanes.lock().unwrap();
+        let mut usage = self.template_usage.lock().unwrap();
+        let now = Instant::now();
+        let mut retry_in: Option<Duration> = None;
+
+        for lane in lanes.iter_mut() {
+            let found = lane.iter().position(|request| {
+                let Some(limit) = self.template_limits.get(&request.template_id) else {
+                    return true;
+                };
+                let usage = usage.entry(request.template_id.clone()).or_default();
+                while usage.started.front().is_some_and(|at| now.duration_since(*at) >= RATE_WINDOW) {
+                    usage.started.pop_front();
+                }
+
+                if limit.concurrency.is_some_and(|max| usage.running >= max.max(1)) {
+                    return false;
+                }
+                if limit.per_minute.is_some_and(|max| usage.started.len() >= max.max(1)) {
+                    let wait = usage.started.front().map(|at| RATE_WINDOW - now.duration_since(*at)).unwrap_or_default();
+                    retry_in = Some(retry_in.map_or(wait, |current| current.min(wait)));
+                    return false;
+                }
+                true
+            });
+
+            let Some(idx) = found else { continue };
+            let request = lane.remove(idx).expect("position found above");
+
+            let template_id = self.template_limits.contains_key(&request.template_id).then(|| {
+                let entry = usage.entry(request.template_id.clone()).or_default();
+                entry.running += 1;
+                entry.started.push_back(now);
+                request.template_id.clone()
+            });
+            let permit = TemplatePermit {
+                template_id,
+                usage: self.template_usage.clone(),
+                available: self.available.clone(),
+            };
+            return (Some((request, permit)), None);
+        }
+
+        (None, retry_in)
+    }
+
+    /// Espera un lugar libre; se libera al descartar el permiso
+    pub async fn acquire_slot(&self) -> OwnedSemaphorePermit {
+        self.slots.clone().acquire_owned().await.expect("job queue semaphore is never closed")
+    }
+
+    /// Mueve un trabajo en cola a otra prioridad: se retira de su carril y
+    /// se encola al final del nuevo
+    pub fn reprioritize(&self, id: &Uuid, priority: Priority) -> Reprioritized {
+        let mut lanes = self.lanes.lock().unwrap();
+
+        let found = lanes.iter().enumerate().find_map(|(lane, queue)| {
+            queue.iter().position(|r| r.id == *id).map(|idx| (lane, idx))
+        });
+        let Some((lane, idx)) = found else {
+            return Reprioritized::NotQueued;
+        };
+
+        let target = lane_of(&priority);
+        if lane == target {
+            return Reprioritized::Unchanged;
+        }
+
+        let mut request = lanes[lane].remove(idx).expect("position found above");
+        let previous = priority_name(&request.priority);
+        request.priority = priority;
+        lanes[target].push_back(request);
+
+        Reprioritized::Moved { previous }
+    }
+
+    /// Trabajos en cola por prioridad
+    pub fn depths(&self) -> [(&'static str, usize); 3] {
+        let lanes = self.lanes.lock().unwrap();
+        [("high", lanes[0].len()), ("normal", lanes[1].len()), ("low", lanes[2].len())]
+    }
+}
+
+fn lane_of(priority: &Priority) -> usize {
+    match priority {
+        Priority::High => 0,
+        Priority::Normal => 1,
+        Priority::Low => 2,
+    }
+}
+
+fn priority_name(priority: &Priority) -> String {
+    serde_json::to_value(priority)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(template_id: &str, priority: &str) -> DocumentRequest {
+        serde_json::from_value(json!({
+            "template_id": template_id,
+            "document_type": "report",
+            "format": "pdf",
+            "priority": priority,
+            "metadata": {},
+            "data": {}
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn higher_priorities_go_first_in_arrival_order() {
+        let queue = JobQueue::new(2);
+        let low = request("report", "low");
+        let normal = request("report", "normal");
+        let high_first = request("report", "high");
+        let high_second = request("report", "high");
+        let expected = [high_first.id, high_second.id, normal.id, low.id];
+        for job in [low, normal, high_first, high_second] {
+            queue.push(job);
+        }
+        assert_eq!(queue.depths(), [("high", 2), ("normal", 1), ("low", 1)]);
+
+        let mut order = Vec::new();
+        for _ in 0..expected.len() {
+            order.push(queue.next().await.0.id);
+        }
+        assert_eq!(order, expected);
+    }
+
+    #[tokio::test]
+    async fn a_template_at_its_limit_lets_other_jobs_through() {
+        let limits = HashMap::from([("heavy".to_string(), TemplateLimit { concurrency: Some(1), per_minute: None })]);
+        let queue = JobQueue::new(4).with_template_limits(limits);
+        let (heavy_first, heavy_second, light) = (request("heavy", "high"), request("heavy", "high"), request("light", "low"));
+        let (second_id, light_id) = (heavy_second.id, light.id);
+        for job in [heavy_first, heavy_second, light] {
+            queue.push(job);
+        }
+
+        let (_, permit) = queue.next().await;
+        // El segundo `heavy` espera su lugar aunque sea más prioritario
+        assert_eq!(queue.next().await.0.id, light_id);
+
+        let waiting = tokio::time::timeout(Duration::from_millis(50), queue.next()).await;
+        assert!(waiting.is_err(), "the template limit was not applied");
+
+        drop(permit);
+        let (next, _) = tokio::time::timeout(Duration::from_secs(1), queue.next()).await.unwrap();
+        assert_eq!(next.id, second_id);
+    }
+
+    #[tokio::test]
+    async fn per_minute_limit_holds_jobs_back() {
+        let limits = HashMap::from([("heavy".to_string(), TemplateLimit { concurrency: None, per_minute: Some(1) })]);
+        let queue = JobQueue::new(4).with_template_limits(limits);
+        queue.push(request("heavy", "normal"));
+        queue.push(request("heavy", "normal"));
+
+        drop(queue.next().await);
+        let (popped, retry_in) = queue.pop_eligible();
+        assert!(popped.is_none());
+        assert!(retry_in.is_some_and(|wait| wait <= RATE_WINDOW));
+    }
+
+    #[test]
+    fn reprioritize_moves_queued_jobs() {
+        let queue = JobQueue::new(1);
+        let job = request("report", "low");
+        let id = job.id;
+        queue.push(job);
+
+        assert_eq!(queue.reprioritize(&id, Priority::Low), Reprioritized::Unchanged);
+        assert_eq!(queue.reprioritize(&id, Priority::High), Reprioritized::Moved { previous: "low".to_string() });
+        assert_eq!(queue.depths(), [("high", 1), ("normal", 0), ("low", 0)]);
+        assert_eq!(queue.reprioritize(&Uuid::new_v4(), Priority::High), Reprioritized::NotQueued);
+    }
+
+    #[tokio::test]
+    async fn concurrency_limits_running_jobs() {
+        let queue = JobQueue::new(0);
+        assert_eq!(queue.concurrency(), 1);
+
+        let slot = queue.acquire_slot().await;
+        assert!(tokio::time::timeout(Duration::from_millis(50), queue.acquire_slot()).await.is_err());
+        drop(slot);
+        assert!(tokio::time::timeout(Duration::from_secs(1), queue.acquire_slot()).await.is_ok());
+    }
+}
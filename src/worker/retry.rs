@@ -0,0 +1,93 @@
+use anyhow::Result;
+use std::future::Future;
+use std::time::Duration;
+
+/// Política de reintentos con backoff exponencial para trabajos de generación
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay_ms: 1000,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Espera antes del reintento siguiente al intento `attempt` (1-based):
+    /// base * 2^(attempt-1), acotado por `max_delay_ms`
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_millis(self.base_delay_ms.saturating_mul(factor).min(self.max_delay_ms))
+    }
+}
+
+/// Fallas que vale la pena reintentar: timeouts, errores de red y
+/// respuestas de throttling/indisponibilidad del almacenamiento
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    for cause in error.chain() {
+        if cause.is::<tokio::time::error::Elapsed>() {
+            return true;
+        }
+
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            use std::io::ErrorKind::*;
+            if matches!(io.kind(), TimedOut | ConnectionReset | ConnectionAborted | ConnectionRefused | Interrupted) {
+                return true;
+            }
+        }
+    }
+
+    let message = format!("{:#}", error).to_lowercase();
+    [
+        "timeout",
+        "timed out",
+        "dispatch failure",
+        "connection",
+        "service unavailable",
+        "slowdown",
+        "throttl",
+        "internalerror",
+    ]
+    .iter()
+    .any(|needle| message.contains(needle))
+}
+
+/// Ejecuta `operation` reintentando las fallas transitorias según la política.
+/// `operation` recibe el número de intento (1-based); el último error se retorna
+/// cuando se agotan los intentos o la falla no es transitoria.
+pub async fn retry_with_backoff<T, F, Fut>(policy: &RetryPolicy, label: &str, mut operation: F) -> Result<T>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match operation(attempt).await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < policy.max_attempts && is_transient(&e) => {
+                let delay = policy.delay_for(attempt);
+                tracing::warn!(
+                    "{} failed on attempt {}/{} (transient), retrying in {}ms: {}",
+                    label, attempt, policy.max_attempts, delay.as_millis(), e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            },
+            Err(e) => {
+                if attempt >= policy.max_attempts {
+                    tracing::error!("{} failed after {} attempts: {}", label, attempt, e);
+                }
+                return Err(e);
+            },
+        }
+    }
+}
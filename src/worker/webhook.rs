@@ -0,0 +1,249 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+use uuid::Uuid;
+
+use anyhow::{Context, Result};
+
+use super::retry::RetryPolicy;
+use crate::storage::database::Database;
+use crate::storage::s3::to_hex;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Máximo de intentos registrados por documento cuando la bitácora vive en memoria
+const MAX_ATTEMPTS_PER_DOCUMENT: usize = 50;
+
+/// Intento de entrega de un webhook (para que el cliente depure entregas perdidas)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryAttempt {
+    pub document_id: Uuid,
+    pub tenant_id: i64,
+    pub url: String,
+    pub attempt: u32,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub delivered: bool,
+    pub attempted_at: DateTime<Utc>,
+}
+
+/// Bitácora de entregas de webhooks por documento. Con `DATABASE_URL` cada
+/// intento se escribe en la tabla `webhook_deliveries` de Postgres (sobrevive
+/// reinicios y se ve desde cualquier réplica); sin ella queda en memoria
+#[derive(Default)]
+pub struct DeliveryLog {
+    attempts: RwLock<HashMap<Uuid, Vec<DeliveryAttempt>>>,
+    database: Option<Database>,
+}
+
+impl DeliveryLog {
+    pub fn new(database: Option<Database>) -> Self {
+        if database.is_none() {
+            tracing::warn!("DATABASE_URL not set: webhook deliveries are kept in memory only");
+        }
+        DeliveryLog { attempts: RwLock::default(), database }
+    }
+
+    pub async fn record(&self, attempt: DeliveryAttempt) -> Result<()> {
+        if let Some(database) = &self.database {
+            let db = database.get().await.context("Failed to connect to the webhook deliveries database")?;
+            db.execute(
+                "INSERT INTO webhook_deliveries
+                     (document_id, tenant_id, url, attempt, status_code, error, duration_ms, delivered, attempted_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                &[
+                    &attempt.document_id,
+                    &attempt.tenant_id,
+                    &attempt.url,
+                    &(attempt.attempt as i32),
+                    &attempt.status_code.map(i32::from),
+                    &attempt.error,
+                    &(attempt.duration_ms as i64),
+                    &attempt.delivered,
+                    &attempt.attempted_at,
+                ],
+            ).await.context("Failed to record the webhook delivery")?;
+            return Ok(());
+        }
+
+        let mut attempts = self.attempts.write().unwrap();
+        let log = attempts.entry(attempt.document_id).or_default();
+        if log.len() >= MAX_ATTEMPTS_PER_DOCUMENT {
+            log.remove(0);
+        }
+        log.push(attempt);
+        Ok(())
+    }
+
+    /// Intentos de un documento visibles para el tenant dado, del más antiguo al más reciente
+    pub async fn for_document(&self, document_id: &Uuid, tenant_id: i64) -> Result<Vec<DeliveryAttempt>> {
+        if let Some(database) = &self.database {
+            let db = database.get().await.context("Failed to connect to the webhook deliveries database")?;
+            let rows = db.query(
+                "SELECT url, attempt, status_code, error, duration_ms, delivered, attempted_at FROM webhook_deliveries
+                 WHERE document_id = $1 AND tenant_id = $2 ORDER BY attempted_at, id",
+                &[document_id, &tenant_id],
+            ).await?;
+            return Ok(rows
+                .iter()
+                .map(|row| DeliveryAttempt {
+                    document_id: *document_id,
+                    tenant_id,
+                    url: row.get(0),
+                    attempt: row.get::<_, i32>(1) as u32,
+                    status_code: row.get::<_, Option<i32>>(2).map(|code| code as u16),
+                    error: row.get(3),
+                    duration_ms: row.get::<_, i64>(4) as u64,
+                    delivered: row.get(5),
+                    attempted_at: row.get(6),
+                })
+                .collect());
+        }
+
+        Ok(self.attempts
+            .read()
+            .unwrap()
+            .get(document_id)
+            .map(|log| log.iter().filter(|a| a.tenant_id == tenant_id).cloned().collect())
+            .unwrap_or_default())
+    }
+}
+
+/// Envía los callbacks de documentos firmados con HMAC-SHA256
+/// (`X-Signature: t={timestamp},v1={hex}` sobre `{timestamp}.{body}`)
+pub struct WebhookSender {
+    client: reqwest::Client,
+    default_secret: Option<String>,
+    tenant_secrets: HashMap<i64, String>,
+    policy: RetryPolicy,
+    log: DeliveryLog,
+}
+
+impl WebhookSender {
+    pub fn new(
+        default_secret: Option<String>,
+        tenant_secrets: HashMap<i64, String>,
+        policy: RetryPolicy,
+        log: DeliveryLog,
+    ) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+
+        WebhookSender {
+            client,
+            default_secret,
+            tenant_secrets,
+            policy,
+            log,
+        }
+    }
+
+    /// `WEBHOOK_SECRET` (por defecto) y `WEBHOOK_TENANT_SECRETS` (JSON `{"tenant_id": "secreto"}`);
+    /// los intentos van a la bitácora del pool compartido
+    pub fn from_env(policy: RetryPolicy, database: Option<Database>) -> anyhow::Result<Self> {
+        let default_secret = std::env::var("WEBHOOK_SECRET").ok().filter(|s| !s.is_empty());
+
+        let tenant_secrets = match std::env::var("WEBHOOK_TENANT_SECRETS") {
+            Ok(json) if !json.trim().is_empty() => {
+                let raw: HashMap<String, String> = serde_json::from_str(&json)?;
+                raw.into_iter()
+                    .map(|(tenant, secret)| Ok((tenant.parse::<i64>()?, secret)))
+                    .collect::<anyhow::Result<_>>()?
+            },
+            _ => HashMap::new(),
+        };
+
+        if default_secret.is_none() && tenant_secrets.is_empty() {
+            tracing::warn!("No webhook secrets configured: callbacks will be sent unsigned");
+        }
+
+        Ok(Self::new(default_secret, tenant_secrets, policy, DeliveryLog::new(database)))
+    }
+
+    pub async fn deliveries(&self, document_id: &Uuid, tenant_id: i64) -> Result<Vec<DeliveryAttempt>> {
+        self.log.for_document(document_id, tenant_id).await
+    }
+
+    /// Firma `{timestamp}.{body}` con el secreto del tenant
+    pub fn sign(&self, tenant_id: i64, timestamp: i64, body: &[u8]) -> Option<String> {
+        let secret = self.tenant_secrets.get(&tenant_id).or(self.default_secret.as_ref())?;
+
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC acepta llaves de cualquier tamaño");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+
+        Some(format!("t={},v1={}", timestamp, to_hex(&mac.finalize().into_bytes())))
+    }
+
+    /// Entrega el payload reintentando con backoff ante errores de red o respuestas
+    /// no exitosas; retorna true si algún intento fue aceptado (2xx)
+    pub async fn deliver(&self, document_id: Uuid, tenant_id: i64, url: &str, payload: &serde_json::Value) -> bool {
+        let body = match serde_json::to_vec(payload) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!("Failed to serialize webhook payload for {}: {}", document_id, e);
+                return false;
+            }
+        };
+
+        for attempt in 1..=self.policy.max_attempts {
+            let timestamp = Utc::now().timestamp();
+            let started = std::time::Instant::now();
+
+            let mut request = self.client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .header("X-Document-Id", document_id.to_string())
+                .header("X-Delivery-Attempt", attempt.to_string());
+            if let Some(signature) = self.sign(tenant_id, timestamp, &body) {
+                request = request.header("X-Signature", signature);
+            }
+
+            let (status_code, error) = match request.body(body.clone()).send().await {
+                Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+                Ok(response) => (Some(response.status().as_u16()), Some(format!("HTTP {}", response.status()))),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            let delivered = error.is_none();
+
+            let recorded = self.log.record(DeliveryAttempt {
+                document_id,
+                tenant_id,
+                url: url.to_string(),
+                attempt,
+                status_code,
+                error: error.clone(),
+                duration_ms: started.elapsed().as_millis() as u64,
+                delivered,
+                attempted_at: Utc::now(),
+            }).await;
+            if let Err(e) = recorded {
+                tracing::warn!("Failed to record webhook attempt {} for document {}: {:#}", attempt, document_id, e);
+            }
+
+            if delivered {
+                tracing::info!("Webhook for document {} delivered on attempt {}", document_id, attempt);
+                return true;
+            }
+
+            tracing::warn!(
+                "Webhook for document {} failed on attempt {}/{}: {}",
+                document_id, attempt, self.policy.max_attempts, error.unwrap_or_default()
+            );
+
+            if attempt < self.policy.max_attempts {
+                tokio::time::sleep(self.policy.delay_for(attempt)).await;
+            }
+        }
+
+        false
+    }
+}